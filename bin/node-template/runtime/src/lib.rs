@@ -536,6 +536,7 @@ impl_runtime_apis! {
 	}
 
 	#[cfg(feature = "try-runtime")]
+	#[api_version(2)]
 	impl frame_try_runtime::TryRuntime<Block> for Runtime {
 		fn on_runtime_upgrade() -> (Weight, Weight) {
 			// NOTE: intentional unwrap: we don't want to propagate the error backwards, and want to
@@ -545,6 +546,12 @@ impl_runtime_apis! {
 			(weight, BlockWeights::get().max_block)
 		}
 
+		fn on_runtime_upgrade_of(pallets: Vec<Vec<u8>>) -> (Weight, Weight) {
+			// NOTE: intentional unwrap, see `on_runtime_upgrade`.
+			let weight = Executive::try_runtime_upgrade_of(pallets).unwrap();
+			(weight, BlockWeights::get().max_block)
+		}
+
 		fn execute_block(
 			block: Block,
 			state_root_check: bool,
@@ -554,6 +561,25 @@ impl_runtime_apis! {
 			// have a backtrace here.
 			Executive::try_execute_block(block, state_root_check, select).expect("execute-block failed")
 		}
+
+		fn try_state(
+			select: frame_try_runtime::TryStateSelect,
+		) -> Result<Vec<(Vec<u8>, Option<Vec<u8>>)>, Vec<u8>> {
+			let outcomes = Executive::try_state_of_each(select).map_err(|e| e.as_bytes().to_vec())?;
+			Ok(outcomes
+				.into_iter()
+				.map(|(pallet, result)| {
+					(pallet.as_bytes().to_vec(), result.err().map(|e| e.as_bytes().to_vec()))
+				})
+				.collect())
+		}
+
+		fn dispatch_call(
+			call: Vec<u8>,
+			signer: Option<Vec<u8>>,
+		) -> Result<(Weight, Weight, sp_runtime::DispatchResult), Vec<u8>> {
+			Executive::try_dispatch_call(call, signer).map_err(|e| e.as_bytes().to_vec())
+		}
 	}
 }
 
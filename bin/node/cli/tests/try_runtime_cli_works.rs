@@ -0,0 +1,203 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+#![cfg(feature = "try-runtime")]
+
+use assert_cmd::cargo::cargo_bin;
+use jsonrpsee::{ws_server::WsServerBuilder, RpcModule};
+use std::process::{Command, Output};
+
+/// A block hash that the mock node knows nothing about.
+const UNKNOWN_HASH: &str = "0x0000000000000000000000000000000000000000000000000000000000000001";
+
+/// Run `substrate try-runtime --chain dev` with `args`.
+fn try_runtime(args: &[&str]) -> Output {
+	Command::new(cargo_bin("substrate"))
+		.args(&["try-runtime", "--chain", "dev"])
+		.args(args)
+		.output()
+		.unwrap()
+}
+
+/// Assert that `output` is that of a command which failed with `code`, without panicking, and
+/// printed `message`.
+fn assert_fails_with(output: &Output, code: i32, message: &str) {
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	assert_eq!(output.status.code(), Some(code), "stderr: {}", stderr);
+	assert!(stderr.contains(message), "expected {:?} in stderr: {}", message, stderr);
+	assert!(!stderr.contains("panicked"), "stderr: {}", stderr);
+}
+
+#[test]
+fn invalid_arguments_are_rejected() {
+	// clap exits with 2 on invalid arguments.
+	assert_fails_with(
+		&try_runtime(&["on-runtime-upgrade", "live", "--uri", "foo://bar"]),
+		2,
+		"not a valid url",
+	);
+	assert_fails_with(
+		&try_runtime(&["follow-chain", "--uri", "http://localhost:9933"]),
+		2,
+		"not a valid WS(S) url",
+	);
+	assert_fails_with(
+		&try_runtime(&["--state-version", "2", "on-runtime-upgrade", "snap", "-s", "snap"]),
+		2,
+		"Invalid state version.",
+	);
+	assert_fails_with(
+		&try_runtime(&["--assert", "System::Number", "on-runtime-upgrade", "snap", "-s", "snap"]),
+		2,
+		"expected `Pallet::Item <op> <value>`",
+	);
+	assert_fails_with(
+		&try_runtime(&["execute-block", "--from", "1", "live", "--uri", "ws://localhost:9944"]),
+		2,
+		"--to <TO>",
+	);
+	assert_fails_with(
+		&try_runtime(&[
+			"execute-block",
+			"--from",
+			"1",
+			"--to",
+			"2",
+			"--hook-timings",
+			"live",
+			"--uri",
+			"ws://localhost:9944",
+		]),
+		2,
+		"cannot be used with",
+	);
+	assert_fails_with(
+		&try_runtime(&["on-runtime-upgrade", "live", "--uri", "ws://localhost:9944", "--at", "x"]),
+		2,
+		"or a block number",
+	);
+}
+
+#[test]
+fn missing_inputs_are_errors() {
+	assert_fails_with(
+		&try_runtime(&["execute-block", "snap", "-s", "snap"]),
+		1,
+		"either `--block-uri` must be provided, or state must be `live`",
+	);
+	assert_fails_with(
+		&try_runtime(&["offchain-worker", "snap", "-s", "snap"]),
+		1,
+		"either `--header-at` must be provided",
+	);
+	assert_fails_with(
+		&try_runtime(&["on-runtime-upgrade", "snap", "-s", "/does/not/exist"]),
+		1,
+		"fs::read failed.",
+	);
+}
+
+#[test]
+fn unsupported_flags_are_errors() {
+	assert_fails_with(
+		&try_runtime(&["--check-issuance", "check-state", "snap", "-s", "/does/not/exist"]),
+		1,
+		"check-state does not support --check-issuance",
+	);
+	assert_fails_with(
+		&try_runtime(&[
+			"--print-storage-diff",
+			"--assert",
+			"System::Number == 1",
+			"scenario",
+			"--file",
+			"/does/not/exist",
+			"snap",
+			"-s",
+			"/does/not/exist",
+		]),
+		1,
+		"scenario does not support --assert, --print-storage-diff",
+	);
+	assert_fails_with(
+		&try_runtime(&["--storage-trace", "trace", "execute-block", "snap", "-s", "snap"]),
+		1,
+		"execute-block does not support --storage-trace",
+	);
+}
+
+#[test]
+fn executor_settings_can_follow_the_command() {
+	// the arguments are accepted, hence the missing snapshot is the first error.
+	assert_fails_with(
+		&try_runtime(&[
+			"on-runtime-upgrade",
+			"--wasm-execution",
+			"compiled",
+			"--heap-pages",
+			"64",
+			"snap",
+			"-s",
+			"/does/not/exist",
+		]),
+		1,
+		"fs::read failed.",
+	);
+}
+
+#[test]
+fn list_commands_is_valid_json() {
+	let output = try_runtime(&["list-commands", "--json"]);
+	assert!(output.status.success());
+
+	let listing: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+	let subcommands = listing["subcommands"].as_array().unwrap();
+	for name in
+		["on-runtime-upgrade", "execute-block", "offchain-worker", "follow-chain", "check-state"]
+	{
+		assert!(subcommands.iter().any(|c| c["name"] == name), "{} is not listed", name);
+	}
+}
+
+#[tokio::test]
+async fn unknown_blocks_of_the_node_are_errors() {
+	// a node that knows no block at all.
+	let server = WsServerBuilder::default().build("127.0.0.1:0").await.unwrap();
+	let uri = format!("ws://{}", server.local_addr().unwrap());
+	let mut module = RpcModule::new(());
+	module.register_method("chain_getHeader", |_, _| Ok(None::<()>)).unwrap();
+	module.register_method("chain_getBlock", |_, _| Ok(None::<()>)).unwrap();
+	let _handle = server.start(module).unwrap();
+
+	let output = tokio::task::spawn_blocking(move || {
+		try_runtime(&[
+			"--no-cache",
+			"offchain-worker",
+			"--header-at",
+			UNKNOWN_HASH,
+			"live",
+			"--uri",
+			&uri,
+			"--at",
+			UNKNOWN_HASH,
+		])
+	})
+	.await
+	.unwrap();
+	assert_fails_with(&output, 1, "not found");
+}
@@ -788,6 +788,12 @@ impl pallet_nomination_pools::Config for Runtime {
 	type PostUnbondingPoolsWindow = PostUnbondPoolsWindow;
 	type MaxMetadataLen = ConstU32<256>;
 	type MaxUnbonding = ConstU32<8>;
+	type MaxWithdrawBatch = ConstU32<32>;
+	type MinPayout = ConstU128<{ CENTS }>;
+	type JoinRequestTimeout = ConstU32<{ 7 * DAYS }>;
+	type MaxJoinRequests = ConstU32<64>;
+	type MaxAliasLen = ConstU32<32>;
+	type AliasDeposit = ConstU128<{ 10 * DOLLARS }>;
 	type PalletId = NominationPoolsPalletId;
 	type MaxPointsToBalance = MaxPointsToBalance;
 }
@@ -1893,10 +1899,27 @@ impl_runtime_apis! {
 		}
 	}
 
+	#[api_version(2)]
 	impl pallet_nomination_pools_runtime_api::NominationPoolsApi<Block, AccountId, Balance> for Runtime {
 		fn pending_rewards(member_account: AccountId) -> Balance {
 			NominationPools::pending_rewards(member_account).unwrap_or_default()
 		}
+
+		fn member_cap(pool_id: pallet_nomination_pools::PoolId) -> Option<u32> {
+			NominationPools::member_cap(pool_id)
+		}
+
+		fn pool_by_alias(alias: Vec<u8>) -> Option<pallet_nomination_pools::PoolId> {
+			pallet_nomination_pools::PoolAliasOf::<Runtime>::try_from(alias)
+				.ok()
+				.and_then(NominationPools::pool_by_alias)
+		}
+
+		fn pool_nominations(
+			pool_id: pallet_nomination_pools::PoolId,
+		) -> Option<(Vec<(AccountId, bool)>, sp_staking::EraIndex)> {
+			NominationPools::pool_nominations(pool_id)
+		}
 	}
 
 	impl sp_consensus_babe::BabeApi<Block> for Runtime {
@@ -2114,6 +2137,7 @@ impl_runtime_apis! {
 	}
 
 	#[cfg(feature = "try-runtime")]
+	#[api_version(2)]
 	impl frame_try_runtime::TryRuntime<Block> for Runtime {
 		fn on_runtime_upgrade() -> (Weight, Weight) {
 			// NOTE: intentional unwrap: we don't want to propagate the error backwards, and want to
@@ -2123,6 +2147,12 @@ impl_runtime_apis! {
 			(weight, RuntimeBlockWeights::get().max_block)
 		}
 
+		fn on_runtime_upgrade_of(pallets: Vec<Vec<u8>>) -> (Weight, Weight) {
+			// NOTE: intentional unwrap, see `on_runtime_upgrade`.
+			let weight = Executive::try_runtime_upgrade_of(pallets).unwrap();
+			(weight, RuntimeBlockWeights::get().max_block)
+		}
+
 		fn execute_block(
 			block: Block,
 			state_root_check: bool,
@@ -2139,6 +2169,25 @@ impl_runtime_apis! {
 			// have a backtrace here.
 			Executive::try_execute_block(block, state_root_check, select).unwrap()
 		}
+
+		fn try_state(
+			select: frame_try_runtime::TryStateSelect,
+		) -> Result<Vec<(Vec<u8>, Option<Vec<u8>>)>, Vec<u8>> {
+			let outcomes = Executive::try_state_of_each(select).map_err(|e| e.as_bytes().to_vec())?;
+			Ok(outcomes
+				.into_iter()
+				.map(|(pallet, result)| {
+					(pallet.as_bytes().to_vec(), result.err().map(|e| e.as_bytes().to_vec()))
+				})
+				.collect())
+		}
+
+		fn dispatch_call(
+			call: Vec<u8>,
+			signer: Option<Vec<u8>>,
+		) -> Result<(Weight, Weight, sp_runtime::DispatchResult), Vec<u8>> {
+			Executive::try_dispatch_call(call, signer).map_err(|e| e.as_bytes().to_vec())
+		}
 	}
 
 	#[cfg(feature = "runtime-benchmarks")]
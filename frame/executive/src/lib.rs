@@ -213,7 +213,9 @@ impl<
 			+ OnIdle<System::BlockNumber>
 			+ OnFinalize<System::BlockNumber>
 			+ OffchainWorker<System::BlockNumber>
-			+ frame_support::traits::TryState<System::BlockNumber>,
+			+ frame_support::traits::TryState<System::BlockNumber>
+			+ frame_support::traits::TryStateOfEach<System::BlockNumber>
+			+ frame_support::traits::TryOnRuntimeUpgradeOf,
 		COnRuntimeUpgrade: OnRuntimeUpgrade,
 	> Executive<System, Block, Context, UnsignedValidator, AllPalletsWithSystem, COnRuntimeUpgrade>
 where
@@ -290,6 +292,75 @@ where
 		let weight = Self::execute_on_runtime_upgrade();
 		Ok(weight)
 	}
+
+	/// Execute the `OnRuntimeUpgrade` of only the pallets whose name is in `pallets`, including
+	/// their pre and post migration checks.
+	///
+	/// `COnRuntimeUpgrade` is not executed, as it belongs to no pallet.
+	///
+	/// This should only be used for testing.
+	pub fn try_runtime_upgrade_of(
+		pallets: Vec<Vec<u8>>,
+	) -> Result<frame_support::weights::Weight, &'static str> {
+		use frame_support::traits::TryOnRuntimeUpgradeOf;
+
+		<AllPalletsWithSystem as TryOnRuntimeUpgradeOf>::try_on_runtime_upgrade_of(&pallets)
+			.map_err(|e| {
+				frame_support::log::error!(target: "runtime::executive", "failure: {:?}", e);
+				e
+			})
+	}
+
+	/// Execute the try-state checks of each pallet that `select` selects on top of the current
+	/// state, at the current block number, and return the outcome of each pallet's checks.
+	///
+	/// Fails if `select` names a pallet that does not exist.
+	///
+	/// This should only be used for testing.
+	pub fn try_state_of_each(
+		select: frame_try_runtime::TryStateSelect,
+	) -> Result<Vec<(&'static str, Result<(), &'static str>)>, &'static str> {
+		use frame_support::traits::TryStateOfEach;
+
+		<AllPalletsWithSystem as TryStateOfEach<System::BlockNumber>>::try_state_of_each(
+			frame_system::Pallet::<System>::block_number(),
+			select,
+		)
+	}
+
+	/// Dispatch the given encoded `call` directly on top of the current state, as signed by the
+	/// encoded account `signer`, or as root if `None`.
+	///
+	/// None of the checks and payments of a real extrinsic are performed. Returns the declared
+	/// weight of the call, the actual weight that it reported after dispatch, and its outcome.
+	///
+	/// This should only be used for testing.
+	pub fn try_dispatch_call(
+		call: Vec<u8>,
+		signer: Option<Vec<u8>>,
+	) -> Result<(Weight, Weight, sp_runtime::DispatchResult), &'static str>
+	where
+		CallOf<Block::Extrinsic, Context>: codec::Decode + GetDispatchInfo,
+		OriginOf<Block::Extrinsic, Context>: From<frame_system::RawOrigin<System::AccountId>>,
+	{
+		let call = <CallOf<Block::Extrinsic, Context> as codec::Decode>::decode(&mut &*call)
+			.map_err(|_| "failed to decode the call")?;
+		let origin = match signer {
+			Some(signer) => frame_system::RawOrigin::Signed(
+				<System::AccountId as codec::Decode>::decode(&mut &*signer)
+					.map_err(|_| "failed to decode the signer")?,
+			),
+			None => frame_system::RawOrigin::Root,
+		};
+
+		let info = call.get_dispatch_info();
+		let (post_info, result) = match call.dispatch(origin.into()) {
+			Ok(post_info) => (post_info, Ok(())),
+			Err(e) => (e.post_info, Err(e.error)),
+		};
+
+		Ok((info.weight, post_info.calc_actual_weight(&info), result))
+	}
 }
 
 impl<
@@ -25,12 +25,13 @@ mod mock;
 
 use frame_benchmarking::{account, frame_support::traits::Currency, vec, whitelist_account, Vec};
 use frame_election_provider_support::SortedListProvider;
-use frame_support::{assert_ok, ensure, traits::Get};
+use frame_support::{assert_ok, ensure, traits::Get, BoundedVec};
 use frame_system::RawOrigin as RuntimeOrigin;
 use pallet_nomination_pools::{
-	BalanceOf, BondExtra, BondedPoolInner, BondedPools, ConfigOp, MaxPoolMembers,
-	MaxPoolMembersPerPool, MaxPools, Metadata, MinCreateBond, MinJoinBond, Pallet as Pools,
-	PoolMembers, PoolRoles, PoolState, RewardPools, SubPoolsStorage,
+	BalanceOf, BondExtra, BondedPoolInner, BondedPools, ConfigOp, DustPayout, DustPayouts,
+	JoinRequests, LiquidatedPools, LiquidationThreshold, MaxPoolMembers, MaxPoolMembersPerPool,
+	MaxPools, Metadata, MinCreateBond, MinJoinBond, Pallet as Pools, PoolAliasOf, PoolAliases,
+	PoolIdByAlias, PoolMemberCap, PoolMembers, PoolRoles, PoolState, RewardPools, SubPoolsStorage,
 };
 use sp_runtime::traits::{Bounded, StaticLookup, Zero};
 use sp_staking::{EraIndex, StakingInterface};
@@ -62,6 +63,11 @@ fn create_funded_user_with_balance<T: pallet_nomination_pools::Config>(
 	user
 }
 
+// An alias of `len` times `c`.
+fn alias_of<T: pallet_nomination_pools::Config>(c: u8, len: u32) -> PoolAliasOf<T> {
+	vec![c; len as usize].try_into().unwrap()
+}
+
 // Create a bonded pool account, bonding `balance` and giving the account `balance * 2` free
 // balance.
 fn create_pool_account<T: pallet_nomination_pools::Config>(
@@ -296,6 +302,16 @@ frame_benchmarking::benchmarks! {
 		);
 	}
 
+	claim_payout_nothing {
+		let origin_weight = Pools::<T>::depositor_min_bond() * 2u32.into();
+		let (depositor, _) = create_pool_account::<T>(0, origin_weight);
+
+		whitelist_account!(depositor);
+	}:claim_payout(RuntimeOrigin::Signed(depositor.clone()))
+	verify {
+		assert_eq!(CurrencyOf::<T>::free_balance(&depositor), origin_weight);
+	}
+
 	unbond {
 		// The weight the nominator will start at. The value used here is expected to be
 		// significantly higher than the first position in a list (e.g. the first bag threshold).
@@ -422,6 +438,13 @@ frame_benchmarking::benchmarks! {
 		let (depositor, pool_account) = create_pool_account::<T>(0, min_create_bond);
 		let depositor_lookup = T::Lookup::unlookup(depositor.clone());
 
+		// Fill the join requests of the pool, which are all refunded once it is dissolved.
+		let amount = MinJoinBond::<T>::get().max(CurrencyOf::<T>::minimum_balance());
+		for i in 0..T::MaxJoinRequests::get() {
+			let joiner = create_funded_user_with_balance::<T>("joiner", i, amount * 2u32.into());
+			Pools::<T>::join_permissioned(RuntimeOrigin::Signed(joiner).into(), amount, 1).unwrap();
+		}
+
 		// We set the pool to the destroying state so the depositor can leave
 		BondedPools::<T>::try_mutate(&1, |maybe_bonded_pool| {
 			maybe_bonded_pool.as_mut().ok_or(()).map(|bonded_pool| {
@@ -473,6 +496,7 @@ frame_benchmarking::benchmarks! {
 		assert!(!PoolMembers::<T>::contains_key(&depositor));
 		assert!(!frame_system::Account::<T>::contains_key(&pool_account));
 		assert!(!frame_system::Account::<T>::contains_key(&reward_account));
+		assert_eq!(JoinRequests::<T>::iter_prefix(1).count(), 0);
 
 		// Funds where transferred back correctly
 		assert_eq!(
@@ -482,6 +506,50 @@ frame_benchmarking::benchmarks! {
 		);
 	}
 
+	withdraw_unbonded_many {
+		let n in 1 .. T::MaxWithdrawBatch::get();
+		let s in 0 .. MAX_SPANS;
+
+		// The worst case is that each member is the last one of its pool, whose withdrawal thus
+		// dissolves the pool. Only the depositor can be the last member, hence `n` pools.
+		MaxPools::<T>::kill();
+		MaxPoolMembers::<T>::kill();
+		pallet_staking::CurrentEra::<T>::put(0);
+		let min_create_bond = Pools::<T>::depositor_min_bond();
+		let mut depositors = Vec::new();
+		for i in 0 .. n {
+			let (depositor, pool_account) = create_pool_account::<T>(i, min_create_bond);
+			// pools are created with consecutive ids.
+			BondedPools::<T>::try_mutate(&(i + 1), |maybe_bonded_pool| {
+				maybe_bonded_pool.as_mut().ok_or(()).map(|bonded_pool| {
+					bonded_pool.state = PoolState::Destroying;
+				})
+			})
+			.unwrap();
+			Pools::<T>::fully_unbond(RuntimeOrigin::Signed(depositor.clone()).into(), depositor.clone()).unwrap();
+			pallet_staking::benchmarking::add_slashing_spans::<T>(&pool_account, s);
+			depositors.push(depositor);
+		}
+
+		// Set the current era to ensure we can withdraw unbonded funds
+		pallet_staking::CurrentEra::<T>::put(EraIndex::max_value());
+
+		// anyone can withdraw the members of a destroying pool.
+		let caller: T::AccountId = account("caller", 0, USER_SEED);
+		let members: BoundedVec<_, T::MaxWithdrawBatch> = depositors
+			.iter()
+			.cloned()
+			.map(T::Lookup::unlookup)
+			.collect::<Vec<_>>()
+			.try_into()
+			.unwrap();
+		whitelist_account!(caller);
+	}: _(RuntimeOrigin::Signed(caller), members, s)
+	verify {
+		assert_eq!(BondedPools::<T>::count(), 0);
+		assert!(depositors.iter().all(|depositor| !PoolMembers::<T>::contains_key(depositor)));
+	}
+
 	create {
 		let min_create_bond = Pools::<T>::depositor_min_bond();
 		let depositor: T::AccountId = account("depositor", USER_SEED, 0);
@@ -524,6 +592,10 @@ frame_benchmarking::benchmarks! {
 			T::Staking::active_stake(&Pools::<T>::create_bonded_account(1)),
 			Ok(min_create_bond)
 		);
+		assert_eq!(
+			T::Staking::payee(&Pools::<T>::create_bonded_account(1)),
+			Some(Pools::<T>::create_reward_account(1))
+		);
 	}
 
 	nominate {
@@ -533,11 +605,15 @@ frame_benchmarking::benchmarks! {
 		let min_create_bond = Pools::<T>::depositor_min_bond() * 2u32.into();
 		let (depositor, pool_account) = create_pool_account::<T>(0, min_create_bond);
 
-		// Create some accounts to nominate. For the sake of benchmarking they don't need to be
-		// actual validators
-		 let validators: Vec<_> = (0..n)
+		// Create some accounts to nominate. For the sake of benchmarking they only need to be
+		// registered as validators, not actually bonded. Each of them is still checked to be a
+		// validator, hence the weight accounts for `n` reads of `Validators`.
+		let validators: Vec<_> = (0..n)
 			.map(|i| account("stash", USER_SEED, i))
 			.collect();
+		for validator in &validators {
+			pallet_staking::Validators::<T>::insert(validator, pallet_staking::ValidatorPrefs::default());
+		}
 
 		whitelist_account!(depositor);
 	}:_(RuntimeOrigin::Signed(depositor.clone()), 1, validators)
@@ -603,13 +679,15 @@ frame_benchmarking::benchmarks! {
 		ConfigOp::Set(BalanceOf::<T>::max_value()),
 		ConfigOp::Set(u32::MAX),
 		ConfigOp::Set(u32::MAX),
-		ConfigOp::Set(u32::MAX)
+		ConfigOp::Set(u32::MAX),
+		ConfigOp::Set(BalanceOf::<T>::max_value())
 	) verify {
 		assert_eq!(MinJoinBond::<T>::get(), BalanceOf::<T>::max_value());
 		assert_eq!(MinCreateBond::<T>::get(), BalanceOf::<T>::max_value());
 		assert_eq!(MaxPools::<T>::get(), Some(u32::MAX));
 		assert_eq!(MaxPoolMembers::<T>::get(), Some(u32::MAX));
 		assert_eq!(MaxPoolMembersPerPool::<T>::get(), Some(u32::MAX));
+		assert_eq!(LiquidationThreshold::<T>::get(), Some(BalanceOf::<T>::max_value()));
 	}
 
 	update_roles {
@@ -652,6 +730,172 @@ frame_benchmarking::benchmarks! {
 		assert!(T::Staking::nominations(Pools::<T>::create_bonded_account(1)).is_none());
 	}
 
+	liquidate {
+		// Create a pool
+		let min_create_bond = Pools::<T>::depositor_min_bond();
+		let (depositor, pool_account) = create_pool_account::<T>(0, min_create_bond);
+
+		// Nominate with the pool, so that it needs to be chilled as well.
+		let validators: Vec<_> = (0..T::MaxNominations::get())
+			.map(|i| account("stash", USER_SEED, i))
+			.collect();
+		assert_ok!(T::Staking::nominate(&pool_account, validators));
+
+		// Make the pool liquidatable.
+		LiquidationThreshold::<T>::put(min_create_bond * 2u32.into());
+
+		let caller = account("caller", 0, USER_SEED);
+		whitelist_account!(caller);
+	}:_(RuntimeOrigin::Signed(caller), 1)
+	verify {
+		assert_eq!(BondedPools::<T>::get(1).unwrap().state, PoolState::Destroying);
+		assert!(LiquidatedPools::<T>::contains_key(1));
+		assert_eq!(T::Staking::active_stake(&pool_account), Ok(Zero::zero()));
+	}
+
+	set_member_cap {
+		// Create a pool
+		let (depositor, _) = create_pool_account::<T>(0, Pools::<T>::depositor_min_bond() * 2u32.into());
+		whitelist_account!(depositor);
+	}:_(RuntimeOrigin::Signed(depositor), 1, Some(1))
+	verify {
+		assert_eq!(PoolMemberCap::<T>::get(1), Some(1));
+	}
+
+	fix_payee {
+		// Create a pool, whose rewards are not forwarded to its reward account.
+		let (_, pool_account) = create_pool_account::<T>(0, Pools::<T>::depositor_min_bond() * 2u32.into());
+		pallet_staking::Payee::<T>::insert(&pool_account, pallet_staking::RewardDestination::Staked);
+
+		let caller: T::AccountId = account("caller", 0, USER_SEED);
+		whitelist_account!(caller);
+	}:_(RuntimeOrigin::Signed(caller), 1)
+	verify {
+		assert_eq!(
+			pallet_staking::Payee::<T>::get(&pool_account),
+			pallet_staking::RewardDestination::Account(Pools::<T>::create_reward_account(1))
+		);
+	}
+
+	set_dust_payout {
+		// Create a pool
+		let (depositor, _) = create_pool_account::<T>(0, Pools::<T>::depositor_min_bond() * 2u32.into());
+		whitelist_account!(depositor);
+	}:_(RuntimeOrigin::Signed(depositor.clone()), DustPayout::Compound)
+	verify {
+		assert_eq!(DustPayouts::<T>::get(&depositor), DustPayout::Compound);
+	}
+
+	join_permissioned {
+		let origin_weight = Pools::<T>::depositor_min_bond() * 2u32.into();
+		let scenario = ListScenario::<T>::new(origin_weight, true)?;
+
+		let amount = scenario.dest_weight - origin_weight;
+		let joiner_free = CurrencyOf::<T>::minimum_balance() + amount;
+		let joiner: T::AccountId = create_funded_user_with_balance::<T>("joiner", 0, joiner_free);
+		whitelist_account!(joiner);
+	}: _(RuntimeOrigin::Signed(joiner.clone()), amount, 1)
+	verify {
+		assert!(JoinRequests::<T>::contains_key(1, &joiner));
+		assert_eq!(CurrencyOf::<T>::free_balance(&joiner), joiner_free - amount);
+	}
+
+	approve_join {
+		let origin_weight = Pools::<T>::depositor_min_bond() * 2u32.into();
+
+		// setup the worst case list scenario.
+		let scenario = ListScenario::<T>::new(origin_weight, true)?;
+
+		let amount = scenario.dest_weight - origin_weight;
+		let joiner_free = CurrencyOf::<T>::minimum_balance() + amount;
+		let joiner: T::AccountId = create_funded_user_with_balance::<T>("joiner", 0, joiner_free);
+		Pools::<T>::join_permissioned(RuntimeOrigin::Signed(joiner.clone()).into(), amount, 1)
+			.unwrap();
+
+		let joiner_lookup = T::Lookup::unlookup(joiner.clone());
+		whitelist_account!(scenario.creator1);
+	}: _(RuntimeOrigin::Signed(scenario.creator1.clone()), 1, joiner_lookup)
+	verify {
+		assert!(PoolMembers::<T>::contains_key(&joiner));
+		assert_eq!(
+			T::Staking::active_stake(&scenario.origin1).unwrap(),
+			scenario.dest_weight
+		);
+	}
+
+	reject_join {
+		let origin_weight = Pools::<T>::depositor_min_bond() * 2u32.into();
+		let (depositor, _) = create_pool_account::<T>(0, origin_weight);
+
+		let amount = Pools::<T>::depositor_min_bond();
+		let joiner_free = CurrencyOf::<T>::minimum_balance() + amount;
+		let joiner: T::AccountId = create_funded_user_with_balance::<T>("joiner", 0, joiner_free);
+		Pools::<T>::join_permissioned(RuntimeOrigin::Signed(joiner.clone()).into(), amount, 1)
+			.unwrap();
+
+		let joiner_lookup = T::Lookup::unlookup(joiner.clone());
+		whitelist_account!(depositor);
+	}: _(RuntimeOrigin::Signed(depositor), 1, joiner_lookup)
+	verify {
+		assert!(!JoinRequests::<T>::contains_key(1, &joiner));
+		assert_eq!(CurrencyOf::<T>::free_balance(&joiner), joiner_free);
+	}
+
+	set_alias {
+		let origin_weight = Pools::<T>::depositor_min_bond() * 2u32.into();
+		let (depositor, _) = create_pool_account::<T>(0, origin_weight);
+		CurrencyOf::<T>::deposit_creating(&depositor, T::AliasDeposit::get() * 2u32.into());
+
+		// worst case is replacing an existing alias, which refunds its deposit.
+		Pools::<T>::set_alias(RuntimeOrigin::Signed(depositor.clone()).into(), 1, alias_of::<T>(b'a', 1))
+			.unwrap();
+		let alias = alias_of::<T>(b'b', T::MaxAliasLen::get());
+
+		whitelist_account!(depositor);
+	}: _(RuntimeOrigin::Signed(depositor), 1, alias.clone())
+	verify {
+		assert_eq!(Pools::<T>::pool_by_alias(alias), Some(1));
+		assert_eq!(PoolIdByAlias::<T>::iter().count(), 1);
+	}
+
+	clear_alias {
+		let origin_weight = Pools::<T>::depositor_min_bond() * 2u32.into();
+		let (depositor, _) = create_pool_account::<T>(0, origin_weight);
+		CurrencyOf::<T>::deposit_creating(&depositor, T::AliasDeposit::get());
+
+		let alias = alias_of::<T>(b'a', T::MaxAliasLen::get());
+		Pools::<T>::set_alias(RuntimeOrigin::Signed(depositor.clone()).into(), 1, alias).unwrap();
+
+		whitelist_account!(depositor);
+	}: _(RuntimeOrigin::Signed(depositor), 1)
+	verify {
+		assert!(!PoolAliases::<T>::contains_key(1));
+		assert_eq!(PoolIdByAlias::<T>::iter().count(), 0);
+	}
+
+	unbond_balance {
+		// Same setup as `unbond`, but denominated in balance.
+		let origin_weight = Pools::<T>::depositor_min_bond() * 200u32.into();
+		let scenario = ListScenario::<T>::new(origin_weight, false)?;
+		let amount = origin_weight - scenario.dest_weight;
+
+		let scenario = scenario.add_joiner(amount);
+		let member_id = scenario.origin1_member.unwrap().clone();
+		let member_id_lookup = T::Lookup::unlookup(member_id.clone());
+		let all_points = PoolMembers::<T>::get(&member_id).unwrap().points;
+		whitelist_account!(member_id);
+	}: _(RuntimeOrigin::Signed(member_id.clone()), member_id_lookup, amount)
+	verify {
+		let bonded_after = T::Staking::active_stake(&scenario.origin1).unwrap();
+		assert!(bonded_after <= scenario.dest_weight);
+		let member = PoolMembers::<T>::get(&member_id).unwrap();
+		assert_eq!(member.points, Zero::zero());
+		assert_eq!(
+			member.unbonding_eras.values().cloned().collect::<Vec<_>>(),
+			vec![all_points]
+		);
+	}
+
 	impl_benchmark_test_suite!(
 		Pallet,
 		crate::mock::new_test_ext(),
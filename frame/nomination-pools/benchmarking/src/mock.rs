@@ -164,6 +164,12 @@ impl pallet_nomination_pools::Config for Runtime {
 	type PostUnbondingPoolsWindow = PostUnbondingPoolsWindow;
 	type MaxMetadataLen = ConstU32<256>;
 	type MaxUnbonding = ConstU32<8>;
+	type MaxWithdrawBatch = ConstU32<32>;
+	type MinPayout = frame_support::traits::ConstU128<0>;
+	type JoinRequestTimeout = frame_support::traits::ConstU64<10>;
+	type MaxJoinRequests = frame_support::traits::ConstU32<8>;
+	type MaxAliasLen = ConstU32<32>;
+	type AliasDeposit = frame_support::traits::ConstU128<10>;
 	type PalletId = PoolsPalletId;
 	type MaxPointsToBalance = MaxPointsToBalance;
 }
@@ -21,20 +21,20 @@
 //!
 //! # Debugging a panic
 //! Once a panic is found, it can be debugged with
-//! `cargo hfuzz run-debug per_thing_rational hfuzz_workspace/call/*.fuzz`.
+//! `cargo hfuzz run-debug call hfuzz_workspace/call/*.fuzz`.
 
 use frame_support::{
 	assert_ok,
-	traits::{Currency, GetCallName, UnfilteredDispatchable},
+	traits::{Currency, Get, GetCallName, UnfilteredDispatchable},
 };
 use honggfuzz::fuzz;
 use pallet_nomination_pools::{
 	log,
 	mock::*,
 	pallet as pools,
-	pallet::{BondedPools, Call as PoolsCall, Event as PoolsEvents, PoolMembers},
-	BondExtra, BondedPool, LastPoolId, MaxPoolMembers, MaxPoolMembersPerPool, MaxPools,
-	MinCreateBond, MinJoinBond, PoolId,
+	pallet::{BondedPools, Call as PoolsCall, Event as PoolsEvents, JoinRequests, PoolMembers},
+	BondExtra, BondedPool, DustPayout, LastPoolId, LiquidationThreshold, MaxPoolMembers,
+	MaxPoolMembersPerPool, MaxPools, MinCreateBond, MinJoinBond, PoolAliasOf, PoolId,
 };
 use rand::{seq::SliceRandom, Rng};
 use sp_runtime::{assert_eq_error_rate, Perquintill};
@@ -85,11 +85,25 @@ fn random_existing_pool<R: Rng>(mut rng: &mut R) -> Option<PoolId> {
 	BondedPools::<T>::iter_keys().collect::<Vec<_>>().choose(&mut rng).map(|x| *x)
 }
 
+fn random_join_request<R: Rng>(mut rng: &mut R) -> Option<(PoolId, AccountId)> {
+	JoinRequests::<T>::iter_keys().collect::<Vec<_>>().choose(&mut rng).cloned()
+}
+
+/// The origin of the state toggler of `pool_id`, if the pool and its state toggler exist, or a
+/// random one otherwise.
+fn state_toggler_origin<R: Rng>(rng: &mut R, pool_id: PoolId) -> RuntimeOrigin {
+	match BondedPool::<T>::get(pool_id).and_then(|p| p.roles.state_toggler.clone()) {
+		Some(state_toggler) if rng.gen::<bool>() => RuntimeOrigin::signed(state_toggler),
+		_ => random_signed_origin(rng).0,
+	}
+}
+
 fn random_call<R: Rng>(mut rng: &mut R) -> (pools::Call<T>, RuntimeOrigin) {
 	let op = rng.gen::<usize>();
 	let mut op_count = <pools::Call<T> as GetCallName>::get_call_names().len();
-	// Exclude set_state, set_metadata, set_configs, update_roles and chill.
-	op_count -= 5;
+	// Exclude create_with_pool_id, set_state, set_metadata, set_configs, update_roles, chill and
+	// set_member_cap.
+	op_count -= 7;
 
 	match op % op_count {
 		0 => {
@@ -151,13 +165,113 @@ fn random_call<R: Rng>(mut rng: &mut R) -> (pools::Call<T>, RuntimeOrigin) {
 			// nominate
 			let (origin, _) = random_signed_origin(&mut rng);
 			let pool_id = random_existing_pool(&mut rng).unwrap_or_default();
-			let validators = Default::default();
+			let all_validators = Validators::get();
+			let count = rng.gen_range(0..=all_validators.len());
+			let validators =
+				all_validators.choose_multiple(&mut rng, count).cloned().collect::<Vec<_>>();
 			(PoolsCall::<T>::nominate { pool_id, validators }, origin)
 		},
+		8 => {
+			// liquidate
+			let (origin, _) = random_signed_origin(&mut rng);
+			let pool_id = random_existing_pool(&mut rng).unwrap_or_default();
+			(PoolsCall::<T>::liquidate { pool_id }, origin)
+		},
+		9 => {
+			// withdraw_unbonded_many
+			let (origin, _) = random_signed_origin(&mut rng);
+			let count = rng.gen_range(1..=<T as pools::Config>::MaxWithdrawBatch::get() as usize);
+			let members = (0..count).map(|_| random_signed_origin(&mut rng).1).collect::<Vec<_>>();
+			(
+				PoolsCall::<T>::withdraw_unbonded_many {
+					members: members.try_into().unwrap(),
+					num_slashing_spans: 0,
+				},
+				origin,
+			)
+		},
+		10 => {
+			// fix_payee
+			let (origin, _) = random_signed_origin(&mut rng);
+			let pool_id = random_existing_pool(&mut rng).unwrap_or_default();
+			(PoolsCall::<T>::fix_payee { pool_id }, origin)
+		},
+		11 => {
+			// set_dust_payout
+			let (origin, _) = random_signed_origin(&mut rng);
+			let dust_payout =
+				if rng.gen::<bool>() { DustPayout::Accumulate } else { DustPayout::Compound };
+			(PoolsCall::<T>::set_dust_payout { dust_payout }, origin)
+		},
+		12 => {
+			// join_permissioned
+			let pool_id = random_existing_pool(&mut rng).unwrap_or_default();
+			let (origin, who) = random_signed_origin(&mut rng);
+			fund_account(&mut rng, &who);
+			let amount = random_ed_multiple(&mut rng);
+			(PoolsCall::<T>::join_permissioned { amount, pool_id }, origin)
+		},
+		13 => {
+			// approve_join
+			let (pool_id, member) = random_join_request(&mut rng)
+				.unwrap_or_else(|| (Default::default(), rng.gen::<AccountId>()));
+			let origin = state_toggler_origin(&mut rng, pool_id);
+			(PoolsCall::<T>::approve_join { pool_id, member }, origin)
+		},
+		14 => {
+			// reject_join
+			let (pool_id, member) = random_join_request(&mut rng)
+				.unwrap_or_else(|| (Default::default(), rng.gen::<AccountId>()));
+			let origin = state_toggler_origin(&mut rng, pool_id);
+			(PoolsCall::<T>::reject_join { pool_id, member }, origin)
+		},
+		15 => {
+			// set_alias
+			let pool_id = random_existing_pool(&mut rng).unwrap_or_default();
+			let origin = match BondedPool::<T>::get(pool_id).and_then(|p| p.roles.root.clone()) {
+				Some(root) if rng.gen::<bool>() => {
+					fund_account(&mut rng, &root);
+					RuntimeOrigin::signed(root)
+				},
+				_ => random_signed_origin(&mut rng).0,
+			};
+			let len = rng.gen_range(1..=<T as pools::Config>::MaxAliasLen::get() as usize);
+			let alias: PoolAliasOf<T> = (0..len)
+				.map(|_| rng.gen_range(b'a'..=b'c'))
+				.collect::<Vec<_>>()
+				.try_into()
+				.unwrap();
+			(PoolsCall::<T>::set_alias { pool_id, alias }, origin)
+		},
+		16 => {
+			// clear_alias
+			let pool_id = random_existing_pool(&mut rng).unwrap_or_default();
+			let origin = if rng.gen::<bool>() {
+				RuntimeOrigin::root()
+			} else {
+				random_signed_origin(&mut rng).0
+			};
+			(PoolsCall::<T>::clear_alias { pool_id }, origin)
+		},
+		17 => {
+			// unbond_balance
+			let (origin, who) = random_signed_origin(&mut rng);
+			let amount = random_ed_multiple(&mut rng);
+			(PoolsCall::<T>::unbond_balance { member_account: who, amount }, origin)
+		},
 		_ => unreachable!(),
 	}
 }
 
+/// Ensure that no balance was created or destroyed out of thin air, i.e. that the total issuance
+/// is exactly the sum of the balances of all accounts.
+fn assert_balance_conservation() {
+	let total = frame_system::Account::<T>::iter_values()
+		.map(|a| a.data.free + a.data.reserved)
+		.sum::<Balance>();
+	assert_eq!(total, Balances::total_issuance(), "balance conservation violated");
+}
+
 #[derive(Default)]
 struct RewardAgent {
 	who: AccountId,
@@ -227,6 +341,7 @@ fn main() {
 
 		MinCreateBond::<T>::set(10 * ExistentialDeposit::get());
 		MinJoinBond::<T>::set(5 * ExistentialDeposit::get());
+		LiquidationThreshold::<T>::set(Some(5 * ExistentialDeposit::get()));
 		System::set_block_number(1);
 	});
 
@@ -274,6 +389,7 @@ fn main() {
 				{
 					log!(info, "running sanity checks at {}", iteration);
 					Pools::do_try_state(u8::MAX).unwrap();
+					assert_balance_conservation();
 				}
 
 				// collect and reset events.
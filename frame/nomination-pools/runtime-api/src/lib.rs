@@ -16,11 +16,11 @@
 // limitations under the License.
 
 //! Runtime API definition for nomination-pools pallet.
-//! Currently supports only one rpc endpoint.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
 use codec::Codec;
+use sp_std::vec::Vec;
 
 sp_api::decl_runtime_apis! {
 	/// Runtime api for accessing information about nomination pools.
@@ -29,5 +29,23 @@ sp_api::decl_runtime_apis! {
 	{
 		/// Returns the pending rewards for the member that the AccountId was given for.
 		fn pending_rewards(member: AccountId) -> Balance;
+
+		/// Returns the maximum number of members that the given pool can have, if any.
+		#[api_version(2)]
+		fn member_cap(pool_id: u32) -> Option<u32>;
+
+		/// Returns the pool that the given alias refers to, if any.
+		///
+		/// An alias longer than the runtime allows refers to no pool.
+		#[api_version(2)]
+		fn pool_by_alias(alias: Vec<u8>) -> Option<u32>;
+
+		/// Returns the targets that the given pool nominates, and the era in which it submitted
+		/// the nomination, if the pool exists and nominates.
+		///
+		/// Each target comes with whether the nomination of it is stale, i.e. was submitted
+		/// before the target was last slashed, and is hence disregarded.
+		#[api_version(2)]
+		fn pool_nominations(pool_id: u32) -> Option<(Vec<(AccountId, bool)>, u32)>;
 	}
 }
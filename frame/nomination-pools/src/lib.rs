@@ -136,6 +136,12 @@
 //! > account's stake might not be able to ge below a certain threshold as a nominator. At this
 //! > point, the pool should `chill` itself to allow the depositor to leave. See [`Call::chill`].
 //!
+//! A pool whose active stake has dropped below [`LiquidationThreshold`] (e.g. after a slash) is too
+//! small to operate, yet still has members that must go through the above. Such a pool can be
+//! liquidated by anyone via [`Call::liquidate`]: its entire stake is unbonded at once and it is set
+//! to `Destroying`. The members then only need to unbond (which no longer touches the staking
+//! system) and withdraw once the bonding duration has passed.
+//!
 //! ## Implementor's Guide
 //!
 //! Some notes and common mistakes that wallets/apps wishing to implement this pallet should be
@@ -237,6 +243,9 @@
 //! See [this link](https://hackmd.io/PFGn6wI5TbCmBYoEA_f2Uw) for an in-depth explanation of the
 //! reward pool mechanism.
 //!
+//! Other pallets can reward a pool through [`PoolRewardCredit`], which accounts for the reward
+//! right away, rather than relying on the pool to notice a transfer into its reward account.
+//!
 //! **Relevant extrinsics:**
 //!
 //! * [`Call::claim_payout`]
@@ -323,7 +332,7 @@ use codec::Codec;
 use frame_support::{
 	defensive, ensure,
 	pallet_prelude::{MaxEncodedLen, *},
-	storage::bounded_btree_map::BoundedBTreeMap,
+	storage::{bounded_btree_map::BoundedBTreeMap, with_storage_layer},
 	traits::{
 		Currency, Defensive, DefensiveOption, DefensiveResult, DefensiveSaturating,
 		ExistenceRequirement, Get,
@@ -334,11 +343,12 @@ use scale_info::TypeInfo;
 use sp_core::U256;
 use sp_runtime::{
 	traits::{
-		AccountIdConversion, CheckedAdd, CheckedSub, Convert, Saturating, StaticLookup, Zero,
+		AccountIdConversion, CheckedAdd, CheckedSub, Convert, SaturatedConversion, Saturating,
+		StaticLookup, Zero,
 	},
 	FixedPointNumber,
 };
-use sp_staking::{EraIndex, OnStakerSlash, StakingInterface};
+use sp_staking::{EraIndex, OnStakerSlash, PoolRewardCredit, StakingInterface};
 use sp_std::{collections::btree_map::BTreeMap, fmt::Debug, ops::Div, vec::Vec};
 
 /// The log target of this pallet.
@@ -404,11 +414,55 @@ pub enum BondExtra<Balance> {
 	Rewards,
 }
 
+/// What to do with the rewards of a member when they claim less than [`Config::MinPayout`].
+#[derive(Encode, Decode, MaxEncodedLen, Clone, Copy, Debug, PartialEq, Eq, TypeInfo)]
+pub enum DustPayout {
+	/// Leave the rewards in the pool, until enough is accumulated to be paid out.
+	Accumulate,
+	/// Bond the rewards back into the pool.
+	Compound,
+}
+
+impl Default for DustPayout {
+	fn default() -> Self {
+		DustPayout::Accumulate
+	}
+}
+
+/// A pending request to join a pool, see [`Call::join_permissioned`].
+#[derive(Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebugNoBound, CloneNoBound)]
+#[cfg_attr(feature = "std", derive(frame_support::PartialEqNoBound))]
+#[codec(mel_bound(T: Config))]
+#[scale_info(skip_type_params(T))]
+pub struct JoinRequest<T: Config> {
+	/// The amount held in the escrow account of the pool, to be bonded once approved.
+	pub amount: BalanceOf<T>,
+	/// The block from which on the request can no longer be approved, and is refunded.
+	pub expires_at: T::BlockNumber,
+}
+
+/// A short, unique name of a pool, see [`Call::set_alias`].
+pub type PoolAliasOf<T> = BoundedVec<u8, <T as Config>::MaxAliasLen>;
+
+/// The alias of a pool.
+#[derive(Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebugNoBound, CloneNoBound)]
+#[cfg_attr(feature = "std", derive(frame_support::PartialEqNoBound))]
+#[codec(mel_bound(T: Config))]
+#[scale_info(skip_type_params(T))]
+pub struct PoolAlias<T: Config> {
+	/// The alias itself.
+	pub alias: PoolAliasOf<T>,
+	/// The account that registered the alias, along with the deposit it paid, unless it was
+	/// registered by root.
+	pub deposit: Option<(T::AccountId, BalanceOf<T>)>,
+}
+
 /// The type of account being created.
 #[derive(Encode, Decode)]
 enum AccountType {
 	Bonded,
 	Reward,
+	Escrow,
 }
 
 /// A member in a pool.
@@ -652,6 +706,11 @@ impl<T: Config> BondedPool<T> {
 		Pallet::<T>::create_reward_account(self.id)
 	}
 
+	/// Get the escrow account id of this pool.
+	fn escrow_account(&self) -> T::AccountId {
+		Pallet::<T>::create_escrow_account(self.id)
+	}
+
 	/// Consume self and put into storage.
 	fn put(self) {
 		BondedPools::<T>::insert(self.id, BondedPoolInner { ..self.inner });
@@ -709,6 +768,10 @@ impl<T: Config> BondedPool<T> {
 				.map_or(true, |max_per_pool| self.member_counter < max_per_pool),
 			Error::<T>::MaxPoolMembers
 		);
+		ensure!(
+			PoolMemberCap::<T>::get(self.id).map_or(true, |cap| self.member_counter < cap),
+			Error::<T>::MaxPoolMembers
+		);
 		ensure!(
 			MaxPoolMembers::<T>::get().map_or(true, |max| PoolMembers::<T>::count() < max),
 			Error::<T>::MaxPoolMembers
@@ -838,6 +901,19 @@ impl<T: Config> BondedPool<T> {
 			Error::<T>::PartialUnbondNotAllowedPermissionlessly
 		);
 
+		// the depositor of an open pool with other members must keep their minimum bond, as the
+		// pool cannot be destroyed otherwise. This is checked upfront, for a clearer error.
+		if is_permissioned &&
+			is_depositor &&
+			self.state == PoolState::Open &&
+			self.member_counter > 1
+		{
+			ensure!(
+				!is_full_unbond && balance_after_unbond >= Pallet::<T>::depositor_min_bond(),
+				Error::<T>::MinimumBondNotMetWhileOpen
+			);
+		}
+
 		// any unbond must comply with the balance condition:
 		ensure!(
 			is_full_unbond ||
@@ -898,6 +974,20 @@ impl<T: Config> BondedPool<T> {
 		Ok(())
 	}
 
+	/// Make sure that the staking rewards of this pool are forwarded to its reward account.
+	///
+	/// Returns `true` if they were not, and thus had to be fixed.
+	fn fix_payee(&self) -> Result<bool, DispatchError> {
+		let bonded_account = self.bonded_account();
+		let reward_account = self.reward_account();
+		if T::Staking::payee(&bonded_account).as_ref() == Some(&reward_account) {
+			return Ok(false)
+		}
+
+		T::Staking::set_payee(&bonded_account, &reward_account)?;
+		Ok(true)
+	}
+
 	/// Bond exactly `amount` from `who`'s funds into this pool.
 	///
 	/// If the bond type is `Create`, `Staking::bond` is called, and `who`
@@ -927,7 +1017,18 @@ impl<T: Config> BondedPool<T> {
 		let points_issued = self.issue(amount);
 
 		match ty {
-			BondType::Create => T::Staking::bond(&bonded_account, amount, &self.reward_account())?,
+			BondType::Create => {
+				T::Staking::bond(&bonded_account, amount, &self.reward_account())?;
+				// the rewards are asked to be forwarded to the reward account while bonding, but
+				// make sure that the staking system actually does.
+				if self.fix_payee()? {
+					log!(
+						warn,
+						"payee of pool {} was not its reward account after bonding",
+						self.id
+					);
+				}
+			},
 			// The pool should always be created in such a way its in a state to bond extra, but if
 			// the active balance is slashed below the minimum bonded or the account cannot be
 			// found, we exit early.
@@ -1055,6 +1156,38 @@ impl<T: Config> RewardPool<T> {
 	}
 }
 
+/// The record of a liquidated pool, see [`Call::liquidate`].
+///
+/// The entire active stake of the pool has been moved into the unbonding pool of `era`. The
+/// remaining `bonded_points` of the bonded pool are worth `unbonding_points` of that unbonding
+/// pool, and are converted as the members unbond.
+#[derive(Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebugNoBound)]
+#[cfg_attr(feature = "std", derive(Clone, PartialEq, Eq))]
+#[codec(mel_bound(T: Config))]
+#[scale_info(skip_type_params(T))]
+pub struct Liquidation<T: Config> {
+	/// The era in which the liquidated stake is unlocked.
+	pub era: EraIndex,
+	/// The points of the bonded pool that are yet to be unbonded by their members.
+	pub bonded_points: BalanceOf<T>,
+	/// The points of the unbonding pool of `era` that `bonded_points` are worth.
+	pub unbonding_points: BalanceOf<T>,
+}
+
+impl<T: Config> Liquidation<T> {
+	/// Convert `points` of the bonded pool into their share of the unbonding pool, and remove them
+	/// from the record.
+	///
+	/// Returns the unbonding pool points that `points` are worth.
+	fn convert(&mut self, points: BalanceOf<T>) -> BalanceOf<T> {
+		let unbonding_points =
+			Pallet::<T>::point_to_balance(self.unbonding_points, self.bonded_points, points);
+		self.bonded_points = self.bonded_points.saturating_sub(points);
+		self.unbonding_points = self.unbonding_points.saturating_sub(unbonding_points);
+		unbonding_points
+	}
+}
+
 /// An unbonding pool. This is always mapped with an era.
 #[derive(Encode, Decode, MaxEncodedLen, TypeInfo, DefaultNoBound, RuntimeDebugNoBound)]
 #[cfg_attr(feature = "std", derive(Clone, PartialEq, Eq))]
@@ -1245,6 +1378,33 @@ pub mod pallet {
 
 		/// The maximum number of simultaneous unbonding chunks that can exist per member.
 		type MaxUnbonding: Get<u32>;
+
+		/// The maximum number of members that can be withdrawn in a single call to
+		/// [`Call::withdraw_unbonded_many`].
+		#[pallet::constant]
+		type MaxWithdrawBatch: Get<u32>;
+
+		/// The minimum amount of rewards that [`Call::claim_payout`] pays out.
+		///
+		/// Smaller rewards are treated as per the [`DustPayout`] of the member.
+		#[pallet::constant]
+		type MinPayout: Get<BalanceOf<Self>>;
+
+		/// The number of blocks for which a [`Call::join_permissioned`] request can be approved.
+		#[pallet::constant]
+		type JoinRequestTimeout: Get<Self::BlockNumber>;
+
+		/// The maximum number of pending [`Call::join_permissioned`] requests of a pool.
+		#[pallet::constant]
+		type MaxJoinRequests: Get<u32>;
+
+		/// The maximum length, in bytes, of the alias of a pool.
+		#[pallet::constant]
+		type MaxAliasLen: Get<u32>;
+
+		/// The deposit that the root role of a pool pays to register an alias for it.
+		#[pallet::constant]
+		type AliasDeposit: Get<BalanceOf<Self>>;
 	}
 
 	/// Minimum amount to bond to join a pool.
@@ -1276,6 +1436,51 @@ pub mod pallet {
 	#[pallet::storage]
 	pub type MaxPoolMembersPerPool<T: Config> = StorageValue<_, u32, OptionQuery>;
 
+	/// An optional cap on the number of members of a pool, set by its root, see
+	/// [`Call::set_member_cap`].
+	///
+	/// This can only be lower than [`MaxPoolMembersPerPool`], which keeps applying as well.
+	#[pallet::storage]
+	pub type PoolMemberCap<T: Config> = StorageMap<_, Twox64Concat, PoolId, u32, OptionQuery>;
+
+	/// What to do with the rewards of a member below [`Config::MinPayout`], as set by
+	/// [`Call::set_dust_payout`].
+	///
+	/// Members who never set it accumulate them.
+	#[pallet::storage]
+	pub type DustPayouts<T: Config> =
+		StorageMap<_, Twox64Concat, T::AccountId, DustPayout, ValueQuery>;
+
+	/// Pending requests to join a pool, by pool and applicant, see [`Call::join_permissioned`].
+	#[pallet::storage]
+	pub type JoinRequests<T: Config> =
+		StorageDoubleMap<_, Twox64Concat, PoolId, Twox64Concat, T::AccountId, JoinRequest<T>>;
+
+	/// The number of pending requests to join each pool, at most [`Config::MaxJoinRequests`].
+	#[pallet::storage]
+	pub type JoinRequestCount<T: Config> = StorageMap<_, Twox64Concat, PoolId, u32, ValueQuery>;
+
+	/// The last join request that was visited by the sweep of expired requests, if it is still
+	/// in progress, see [`Pallet::sweep_join_requests`].
+	#[pallet::storage]
+	pub type JoinRequestSweepCursor<T: Config> = StorageValue<_, (PoolId, T::AccountId)>;
+
+	/// The pool that each alias refers to, see [`Call::set_alias`].
+	#[pallet::storage]
+	pub type PoolIdByAlias<T: Config> = StorageMap<_, Blake2_128Concat, PoolAliasOf<T>, PoolId>;
+
+	/// The alias of each pool, if any, see [`Call::set_alias`].
+	#[pallet::storage]
+	pub type PoolAliases<T: Config> = StorageMap<_, Twox64Concat, PoolId, PoolAlias<T>>;
+
+	/// The active stake below which a pool can be liquidated by anyone. If `None`, pools cannot be
+	/// liquidated.
+	///
+	/// A sensible value is the existential deposit plus the minimum nominator bond of the staking
+	/// system, below which a pool can neither nominate nor be kept around much longer.
+	#[pallet::storage]
+	pub type LiquidationThreshold<T: Config> = StorageValue<_, BalanceOf<T>, OptionQuery>;
+
 	/// Active members.
 	#[pallet::storage]
 	pub type PoolMembers<T: Config> =
@@ -1314,6 +1519,12 @@ pub mod pallet {
 	pub type ReversePoolIdLookup<T: Config> =
 		CountedStorageMap<_, Twox64Concat, T::AccountId, PoolId, OptionQuery>;
 
+	/// Pools that have been liquidated, see [`Call::liquidate`].
+	///
+	/// The record is kept until the pool is destroyed.
+	#[pallet::storage]
+	pub type LiquidatedPools<T: Config> = StorageMap<_, Twox64Concat, PoolId, Liquidation<T>>;
+
 	#[pallet::genesis_config]
 	pub struct GenesisConfig<T: Config> {
 		pub min_join_bond: BalanceOf<T>,
@@ -1412,6 +1623,67 @@ pub mod pallet {
 		PoolSlashed { pool_id: PoolId, balance: BalanceOf<T> },
 		/// The unbond pool at `era` of pool `pool_id` has been slashed to `balance`.
 		UnbondingPoolSlashed { pool_id: PoolId, era: EraIndex, balance: BalanceOf<T> },
+		/// A slash of pool `pool_id` has been applied, deducting `bonded` from its active balance,
+		/// and the amounts in `unbonding` from its unbond pools, by era.
+		///
+		/// Emitted after [`Event::PoolSlashed`] and [`Event::UnbondingPoolSlashed`], which report
+		/// the balances that remain.
+		SlashApplied {
+			pool_id: PoolId,
+			bonded: BalanceOf<T>,
+			unbonding: BTreeMap<EraIndex, BalanceOf<T>>,
+		},
+		/// The pool `pool_id` has been liquidated. All of its active stake, `balance`, is unbonded
+		/// and can be withdrawn by the members at `era`.
+		Liquidated { pool_id: PoolId, balance: BalanceOf<T>, era: EraIndex },
+		/// `caller` has acted on behalf of `member`, e.g. by permissionlessly unbonding or
+		/// withdrawing them.
+		///
+		/// This is always deposited right after the event of the action itself (e.g.
+		/// [`Event::Unbonded`] or [`Event::Withdrawn`]), and only if `caller` is not `member`.
+		ActedOnBehalf { pool_id: PoolId, caller: T::AccountId, member: T::AccountId },
+		/// The member cap of pool `pool_id` has been set to `cap`, or removed if `None`.
+		MemberCapUpdated { pool_id: PoolId, cap: Option<u32> },
+		/// `member` has been skipped by [`Call::withdraw_unbonded_many`] because they could not
+		/// be withdrawn, with `error`.
+		WithdrawSkipped { member: T::AccountId, error: DispatchError },
+		/// The staking rewards of pool `pool_id` are now forwarded to its reward account.
+		PayeeFixed { pool_id: PoolId },
+		/// `member` now treats rewards below [`Config::MinPayout`] as per `dust_payout`.
+		DustPayoutUpdated { member: T::AccountId, dust_payout: DustPayout },
+		/// `member` has requested to join pool `pool_id` with `amount`, which is held in escrow
+		/// until the request is approved, rejected or expires at `expires_at`.
+		JoinRequested {
+			member: T::AccountId,
+			pool_id: PoolId,
+			amount: BalanceOf<T>,
+			expires_at: T::BlockNumber,
+		},
+		/// The request of `member` to join pool `pool_id` has been approved.
+		///
+		/// This is always followed by [`Event::Bonded`].
+		JoinApproved { member: T::AccountId, pool_id: PoolId },
+		/// The request of `member` to join pool `pool_id` has been rejected, or refunded after it
+		/// `expired`. Once a pool is dissolved, its pending requests are rejected as well.
+		JoinRejected { member: T::AccountId, pool_id: PoolId, expired: bool },
+		/// Pool `pool_id` can now be referred to as `alias`.
+		AliasSet { pool_id: PoolId, alias: PoolAliasOf<T> },
+		/// The alias of pool `pool_id` has been removed.
+		AliasCleared { pool_id: PoolId },
+		/// `member` has requested to unbond `amount` from pool `pool_id`, which is worth `points`
+		/// of the pool, see [`Call::unbond_balance`].
+		///
+		/// This is always followed by [`Event::Unbonded`], which reports the balance that is
+		/// actually unbonded.
+		UnbondRequested {
+			member: T::AccountId,
+			pool_id: PoolId,
+			amount: BalanceOf<T>,
+			points: BalanceOf<T>,
+		},
+		/// `source` has credited `amount` to the rewards of pool `pool_id`, see
+		/// [`PoolRewardCredit`].
+		RewardCredited { pool_id: PoolId, source: T::AccountId, amount: BalanceOf<T> },
 	}
 
 	#[pallet::error]
@@ -1471,6 +1743,47 @@ pub mod pallet {
 		PoolIdInUse,
 		/// Pool id provided is not correct/usable.
 		InvalidPoolId,
+		/// The active stake of the pool is not below [`LiquidationThreshold`], or liquidation is
+		/// disabled.
+		NotLiquidatable,
+		/// The pool has already been liquidated.
+		AlreadyLiquidated,
+		/// More targets than allowed by the staking system have been provided, after removing
+		/// duplicates.
+		TooManyTargets,
+		/// Some of the targets are not validators.
+		///
+		/// `first` is the index of the first target of the sorted and deduplicated target list
+		/// that is not a validator, and `count` the number of such targets, saturating. An error
+		/// cannot carry more than [`frame_support::MAX_MODULE_ERROR_ENCODED_SIZE`] bytes, hence
+		/// all invalid targets are logged.
+		InvalidTargets { first: u16, count: u8 },
+		/// The member cap of a pool cannot be higher than [`MaxPoolMembersPerPool`].
+		MemberCapTooHigh,
+		/// The staking rewards of the pool are already forwarded to its reward account.
+		PayeeAlreadySet,
+		/// The account has already requested to join the pool.
+		JoinRequestExists,
+		/// The account has not requested to join the pool.
+		JoinRequestNotFound,
+		/// The request to join the pool has expired, and can only be refunded.
+		JoinRequestExpired,
+		/// The pool already has [`Config::MaxJoinRequests`] pending join requests.
+		TooManyJoinRequests,
+		/// An alias must be non-empty, and only consist of lowercase letters, digits and `-`.
+		InvalidAlias,
+		/// The alias already refers to a pool.
+		AliasTaken,
+		/// The pool has no alias.
+		AliasNotFound,
+		/// The amount to unbond is not worth a single point of the pool.
+		UnbondAmountTooLow,
+		/// The pool has no points, thus a reward can not be shared among its members.
+		NoPointsToReward,
+		/// The depositor cannot unbond below `Pallet::depositor_min_bond` while the pool is open
+		/// and has other members. The pool must first be set to [`PoolState::Destroying`], and
+		/// all the other members must leave.
+		MinimumBondNotMetWhileOpen,
 	}
 
 	#[derive(Encode, Decode, PartialEq, TypeInfo, frame_support::PalletError, RuntimeDebug)]
@@ -1518,40 +1831,10 @@ pub mod pallet {
 			// If a member already exists that means they already belong to a pool
 			ensure!(!PoolMembers::<T>::contains_key(&who), Error::<T>::AccountBelongsToOtherPool);
 
-			let mut bonded_pool = BondedPool::<T>::get(pool_id).ok_or(Error::<T>::PoolNotFound)?;
+			let bonded_pool = BondedPool::<T>::get(pool_id).ok_or(Error::<T>::PoolNotFound)?;
 			bonded_pool.ok_to_join()?;
 
-			let mut reward_pool = RewardPools::<T>::get(pool_id)
-				.defensive_ok_or::<Error<T>>(DefensiveError::RewardPoolNotFound.into())?;
-			// IMPORTANT: reward pool records must be updated with the old points.
-			reward_pool.update_records(pool_id, bonded_pool.points)?;
-
-			bonded_pool.try_inc_members()?;
-			let points_issued = bonded_pool.try_bond_funds(&who, amount, BondType::Later)?;
-
-			PoolMembers::insert(
-				who.clone(),
-				PoolMember::<T> {
-					pool_id,
-					points: points_issued,
-					// we just updated `last_known_reward_counter` to the current one in
-					// `update_recorded`.
-					last_recorded_reward_counter: reward_pool.last_recorded_reward_counter(),
-					unbonding_eras: Default::default(),
-				},
-			);
-
-			Self::deposit_event(Event::<T>::Bonded {
-				member: who,
-				pool_id,
-				bonded: amount,
-				joined: true,
-			});
-
-			bonded_pool.put();
-			RewardPools::<T>::insert(pool_id, reward_pool);
-
-			Ok(())
+			Self::do_join(who, amount, bonded_pool)
 		}
 
 		/// Bond `extra` more funds from `origin` into the pool to which they already belong.
@@ -1569,34 +1852,7 @@ pub mod pallet {
 		)]
 		pub fn bond_extra(origin: OriginFor<T>, extra: BondExtra<BalanceOf<T>>) -> DispatchResult {
 			let who = ensure_signed(origin)?;
-			let (mut member, mut bonded_pool, mut reward_pool) = Self::get_member_with_pools(&who)?;
-
-			// payout related stuff: we must claim the payouts, and updated recorded payout data
-			// before updating the bonded pool points, similar to that of `join` transaction.
-			reward_pool.update_records(bonded_pool.id, bonded_pool.points)?;
-			let claimed =
-				Self::do_reward_payout(&who, &mut member, &mut bonded_pool, &mut reward_pool)?;
-
-			let (points_issued, bonded) = match extra {
-				BondExtra::FreeBalance(amount) =>
-					(bonded_pool.try_bond_funds(&who, amount, BondType::Later)?, amount),
-				BondExtra::Rewards =>
-					(bonded_pool.try_bond_funds(&who, claimed, BondType::Later)?, claimed),
-			};
-
-			bonded_pool.ok_to_be_open()?;
-			member.points =
-				member.points.checked_add(&points_issued).ok_or(Error::<T>::OverflowRisk)?;
-
-			Self::deposit_event(Event::<T>::Bonded {
-				member: who.clone(),
-				pool_id: member.pool_id,
-				bonded,
-				joined: false,
-			});
-			Self::put_member_with_pools(&who, member, bonded_pool, reward_pool);
-
-			Ok(())
+			Self::do_bond_extra(who, extra)
 		}
 
 		/// A bonded member can use this to claim their payout based on the rewards that the pool
@@ -1605,15 +1861,48 @@ pub mod pallet {
 		///
 		/// The member will earn rewards pro rata based on the members stake vs the sum of the
 		/// members in the pools stake. Rewards do not "expire".
-		#[pallet::weight(T::WeightInfo::claim_payout())]
-		pub fn claim_payout(origin: OriginFor<T>) -> DispatchResult {
+		///
+		/// If there is nothing to pay out, nothing is written, the call is free and the weight is
+		/// refunded down to that of the reads that were needed to find out. Thus, this can be
+		/// called optimistically.
+		///
+		/// Rewards below [`Config::MinPayout`] are not paid out, but treated as per the
+		/// [`DustPayout`] of the member: either they keep accumulating, in which case the call is
+		/// free and the weight is refunded as if there was nothing to pay out, or they are bonded
+		/// back into the pool like [`Call::bond_extra`] does.
+		#[pallet::weight(
+			T::WeightInfo::claim_payout().max(T::WeightInfo::bond_extra_reward())
+		)]
+		pub fn claim_payout(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
 			let who = ensure_signed(origin)?;
 			let (mut member, mut bonded_pool, mut reward_pool) = Self::get_member_with_pools(&who)?;
 
-			let _ = Self::do_reward_payout(&who, &mut member, &mut bonded_pool, &mut reward_pool)?;
+			let pending = member.pending_rewards(
+				reward_pool.current_reward_counter(bonded_pool.id, bonded_pool.points)?,
+			)?;
+			if !pending.is_zero() &&
+				pending < T::MinPayout::get() &&
+				!member.active_points().is_zero()
+			{
+				return match DustPayouts::<T>::get(&who) {
+					DustPayout::Accumulate =>
+						Ok((Some(T::WeightInfo::claim_payout_nothing()), Pays::No).into()),
+					DustPayout::Compound => {
+						Self::do_bond_extra(who, BondExtra::Rewards)?;
+						Ok(Some(T::WeightInfo::bond_extra_reward()).into())
+					},
+				}
+			}
+
+			let payout =
+				Self::do_reward_payout(&who, &mut member, &mut bonded_pool, &mut reward_pool)?;
+			if payout.is_zero() {
+				// nothing has been altered, so there is nothing to write back either.
+				return Ok((Some(T::WeightInfo::claim_payout_nothing()), Pays::No).into())
+			}
 
 			Self::put_member_with_pools(&who, member, bonded_pool, reward_pool);
-			Ok(())
+			Ok(Some(T::WeightInfo::claim_payout()).into())
 		}
 
 		/// Unbond up to `unbonding_points` of the `member_account`'s funds from the pool. It
@@ -1638,6 +1927,10 @@ pub mod pallet {
 		/// * The caller is the depositor, the pool is destroying and no other members are in the
 		///   pool.
 		///
+		/// The depositor can otherwise only unbond for as long as they keep
+		/// `Pallet::depositor_min_bond`. If the pool is open and has other members, failing to do
+		/// so is a [`Error::MinimumBondNotMetWhileOpen`].
+		///
 		/// # Note
 		///
 		/// If there are too many unlocking chunks to unbond with the pool account,
@@ -1652,65 +1945,9 @@ pub mod pallet {
 		) -> DispatchResult {
 			let who = ensure_signed(origin)?;
 			let member_account = T::Lookup::lookup(member_account)?;
-			let (mut member, mut bonded_pool, mut reward_pool) =
-				Self::get_member_with_pools(&member_account)?;
-
-			bonded_pool.ok_to_unbond_with(&who, &member_account, &member, unbonding_points)?;
-
-			// Claim the the payout prior to unbonding. Once the user is unbonding their points no
-			// longer exist in the bonded pool and thus they can no longer claim their payouts. It
-			// is not strictly necessary to claim the rewards, but we do it here for UX.
-			let _ = reward_pool.update_records(bonded_pool.id, bonded_pool.points)?;
-			let _ = Self::do_reward_payout(&who, &mut member, &mut bonded_pool, &mut reward_pool)?;
-
-			let current_era = T::Staking::current_era();
-			let unbond_era = T::Staking::bonding_duration().saturating_add(current_era);
-
-			// Unbond in the actual underlying nominator.
-			let unbonding_balance = bonded_pool.dissolve(unbonding_points);
-			T::Staking::unbond(&bonded_pool.bonded_account(), unbonding_balance)?;
-
-			// Note that we lazily create the unbonding pools here if they don't already exist
-			let mut sub_pools = SubPoolsStorage::<T>::get(member.pool_id)
-				.unwrap_or_default()
-				.maybe_merge_pools(current_era);
-
-			// Update the unbond pool associated with the current era with the unbonded funds. Note
-			// that we lazily create the unbond pool if it does not yet exist.
-			if !sub_pools.with_era.contains_key(&unbond_era) {
-				sub_pools
-					.with_era
-					.try_insert(unbond_era, UnbondPool::default())
-					// The above call to `maybe_merge_pools` should ensure there is
-					// always enough space to insert.
-					.defensive_map_err::<Error<T>, _>(|_| {
-						DefensiveError::NotEnoughSpaceInUnbondPool.into()
-					})?;
-			}
-
-			let points_unbonded = sub_pools
-				.with_era
-				.get_mut(&unbond_era)
-				// The above check ensures the pool exists.
-				.defensive_ok_or::<Error<T>>(DefensiveError::PoolNotFound.into())?
-				.issue(unbonding_balance);
-
-			// Try and unbond in the member map.
-			member.try_unbond(unbonding_points, points_unbonded, unbond_era)?;
-
-			Self::deposit_event(Event::<T>::Unbonded {
-				member: member_account.clone(),
-				pool_id: member.pool_id,
-				points: points_unbonded,
-				balance: unbonding_balance,
-				era: unbond_era,
-			});
-
-			// Now that we know everything has worked write the items to storage.
-			SubPoolsStorage::insert(&member.pool_id, sub_pools);
-			Self::put_member_with_pools(&member_account, member, bonded_pool, reward_pool);
+			let (member, bonded_pool, reward_pool) = Self::get_member_with_pools(&member_account)?;
 
-			Ok(())
+			Self::do_unbond(who, member_account, member, bonded_pool, reward_pool, unbonding_points)
 		}
 
 		/// Call `withdraw_unbonded` for the pools account. This call can be made by any account.
@@ -1763,98 +2000,7 @@ pub mod pallet {
 		) -> DispatchResultWithPostInfo {
 			let caller = ensure_signed(origin)?;
 			let member_account = T::Lookup::lookup(member_account)?;
-			let mut member =
-				PoolMembers::<T>::get(&member_account).ok_or(Error::<T>::PoolMemberNotFound)?;
-			let current_era = T::Staking::current_era();
-
-			let bonded_pool = BondedPool::<T>::get(member.pool_id)
-				.defensive_ok_or::<Error<T>>(DefensiveError::PoolNotFound.into())?;
-			let mut sub_pools =
-				SubPoolsStorage::<T>::get(member.pool_id).ok_or(Error::<T>::SubPoolsNotFound)?;
-
-			bonded_pool.ok_to_withdraw_unbonded_with(&caller, &member_account)?;
-
-			// NOTE: must do this after we have done the `ok_to_withdraw_unbonded_other_with` check.
-			let withdrawn_points = member.withdraw_unlocked(current_era);
-			ensure!(!withdrawn_points.is_empty(), Error::<T>::CannotWithdrawAny);
-
-			// Before calculating the `balance_to_unbond`, we call withdraw unbonded to ensure the
-			// `transferrable_balance` is correct.
-			let stash_killed =
-				T::Staking::withdraw_unbonded(bonded_pool.bonded_account(), num_slashing_spans)?;
-
-			// defensive-only: the depositor puts enough funds into the stash so that it will only
-			// be destroyed when they are leaving.
-			ensure!(
-				!stash_killed || caller == bonded_pool.roles.depositor,
-				Error::<T>::Defensive(DefensiveError::BondedStashKilledPrematurely)
-			);
-
-			let mut sum_unlocked_points: BalanceOf<T> = Zero::zero();
-			let balance_to_unbond = withdrawn_points
-				.iter()
-				.fold(BalanceOf::<T>::zero(), |accumulator, (era, unlocked_points)| {
-					sum_unlocked_points = sum_unlocked_points.saturating_add(*unlocked_points);
-					if let Some(era_pool) = sub_pools.with_era.get_mut(&era) {
-						let balance_to_unbond = era_pool.dissolve(*unlocked_points);
-						if era_pool.points.is_zero() {
-							sub_pools.with_era.remove(&era);
-						}
-						accumulator.saturating_add(balance_to_unbond)
-					} else {
-						// A pool does not belong to this era, so it must have been merged to the
-						// era-less pool.
-						accumulator.saturating_add(sub_pools.no_era.dissolve(*unlocked_points))
-					}
-				})
-				// A call to this transaction may cause the pool's stash to get dusted. If this
-				// happens before the last member has withdrawn, then all subsequent withdraws will
-				// be 0. However the unbond pools do no get updated to reflect this. In the
-				// aforementioned scenario, this check ensures we don't try to withdraw funds that
-				// don't exist. This check is also defensive in cases where the unbond pool does not
-				// update its balance (e.g. a bug in the slashing hook.) We gracefully proceed in
-				// order to ensure members can leave the pool and it can be destroyed.
-				.min(bonded_pool.transferrable_balance());
-
-			T::Currency::transfer(
-				&bonded_pool.bonded_account(),
-				&member_account,
-				balance_to_unbond,
-				ExistenceRequirement::AllowDeath,
-			)
-			.defensive()?;
-
-			Self::deposit_event(Event::<T>::Withdrawn {
-				member: member_account.clone(),
-				pool_id: member.pool_id,
-				points: sum_unlocked_points,
-				balance: balance_to_unbond,
-			});
-
-			let post_info_weight = if member.total_points().is_zero() {
-				// member being reaped.
-				PoolMembers::<T>::remove(&member_account);
-				Self::deposit_event(Event::<T>::MemberRemoved {
-					pool_id: member.pool_id,
-					member: member_account.clone(),
-				});
-
-				if member_account == bonded_pool.roles.depositor {
-					Pallet::<T>::dissolve_pool(bonded_pool);
-					None
-				} else {
-					bonded_pool.dec_members().put();
-					SubPoolsStorage::<T>::insert(&member.pool_id, sub_pools);
-					Some(T::WeightInfo::withdraw_unbonded_update(num_slashing_spans))
-				}
-			} else {
-				// we certainly don't need to delete any pools, because no one is being removed.
-				SubPoolsStorage::<T>::insert(&member.pool_id, sub_pools);
-				PoolMembers::<T>::insert(&member_account, member);
-				Some(T::WeightInfo::withdraw_unbonded_update(num_slashing_spans))
-			};
-
-			Ok(post_info_weight.into())
+			Ok(Self::do_withdraw_unbonded(caller, member_account, num_slashing_spans)?.into())
 		}
 
 		/// Create a new delegation pool.
@@ -1920,8 +2066,9 @@ pub mod pallet {
 		/// The dispatch origin of this call must be signed by the pool nominator or the pool
 		/// root role.
 		///
-		/// This directly forward the call to the staking pallet, on behalf of the pool bonded
-		/// account.
+		/// The targets are sorted and deduplicated, and must then all be validators and no more
+		/// than the maximum number of nominations allowed by the staking system. The call is then
+		/// directly forwarded to the staking pallet, on behalf of the pool bonded account.
 		#[pallet::weight(T::WeightInfo::nominate(validators.len() as u32))]
 		pub fn nominate(
 			origin: OriginFor<T>,
@@ -1931,6 +2078,30 @@ pub mod pallet {
 			let who = ensure_signed(origin)?;
 			let bonded_pool = BondedPool::<T>::get(pool_id).ok_or(Error::<T>::PoolNotFound)?;
 			ensure!(bonded_pool.can_nominate(&who), Error::<T>::NotNominator);
+
+			let mut validators = validators;
+			validators.sort();
+			validators.dedup();
+			ensure!(
+				validators.len() <= T::Staking::max_nominations() as usize,
+				Error::<T>::TooManyTargets
+			);
+
+			let mut invalid = Vec::new();
+			for (i, target) in
+				validators.iter().enumerate().filter(|(_, t)| !T::Staking::is_validator(t))
+			{
+				log!(debug, "target #{} ({:?}) of pool {} is not a validator", i, target, pool_id);
+				invalid.push(i);
+			}
+			if let Some(first) = invalid.first() {
+				return Err(Error::<T>::InvalidTargets {
+					first: (*first).saturated_into(),
+					count: invalid.len().saturated_into(),
+				}
+				.into())
+			}
+
 			T::Staking::nominate(&bonded_pool.bonded_account(), validators)
 		}
 
@@ -2003,6 +2174,7 @@ pub mod pallet {
 		/// * `max_pools` - Set [`MaxPools`].
 		/// * `max_members` - Set [`MaxPoolMembers`].
 		/// * `max_members_per_pool` - Set [`MaxPoolMembersPerPool`].
+		/// * `liquidation_threshold` - Set [`LiquidationThreshold`].
 		#[pallet::weight(T::WeightInfo::set_configs())]
 		pub fn set_configs(
 			origin: OriginFor<T>,
@@ -2011,6 +2183,7 @@ pub mod pallet {
 			max_pools: ConfigOp<u32>,
 			max_members: ConfigOp<u32>,
 			max_members_per_pool: ConfigOp<u32>,
+			liquidation_threshold: ConfigOp<BalanceOf<T>>,
 		) -> DispatchResult {
 			ensure_root(origin)?;
 
@@ -2029,6 +2202,7 @@ pub mod pallet {
 			config_op_exp!(MaxPools::<T>, max_pools);
 			config_op_exp!(MaxPoolMembers::<T>, max_members);
 			config_op_exp!(MaxPoolMembersPerPool::<T>, max_members_per_pool);
+			config_op_exp!(LiquidationThreshold::<T>, liquidation_threshold);
 			Ok(())
 		}
 
@@ -2098,45 +2272,830 @@ pub mod pallet {
 			ensure!(bonded_pool.can_nominate(&who), Error::<T>::NotNominator);
 			T::Staking::chill(&bonded_pool.bonded_account())
 		}
-	}
 
-	#[pallet::hooks]
-	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
-		#[cfg(feature = "try-runtime")]
-		fn try_state(_n: BlockNumberFor<T>) -> Result<(), &'static str> {
-			Self::do_try_state(u8::MAX)
-		}
+		/// Liquidate a pool whose active stake has fallen below [`LiquidationThreshold`].
+		///
+		/// The pool is chilled, all of its active stake is unbonded at once, and its state is set
+		/// to [`PoolState::Destroying`]. Afterwards, the members (who can then be unbonded
+		/// permissionlessly) merely claim their share of the unbonding stake, and can withdraw it
+		/// once the bonding duration has passed.
+		///
+		/// This call can be made by any account.
+		#[pallet::weight(T::WeightInfo::liquidate())]
+		pub fn liquidate(origin: OriginFor<T>, pool_id: PoolId) -> DispatchResult {
+			let _ = ensure_signed(origin)?;
+			let mut bonded_pool = BondedPool::<T>::get(pool_id).ok_or(Error::<T>::PoolNotFound)?;
+			ensure!(!LiquidatedPools::<T>::contains_key(pool_id), Error::<T>::AlreadyLiquidated);
+			ensure!(!bonded_pool.points.is_zero(), Error::<T>::NotLiquidatable);
 
-		fn integrity_test() {
-			assert!(
-				T::MaxPointsToBalance::get() > 0,
-				"Minimum points to balance ratio must be greater than 0"
-			);
-			assert!(
-				T::Staking::bonding_duration() < TotalUnbondingPools::<T>::get(),
-				"There must be more unbonding pools then the bonding duration /
-				so a slash can be applied to relevant unboding pools. (We assume /
-				the bonding duration > slash deffer duration.",
+			let bonded_account = bonded_pool.bonded_account();
+			let active_stake = T::Staking::active_stake(&bonded_account)?;
+			ensure!(
+				LiquidationThreshold::<T>::get()
+					.map_or(false, |threshold| active_stake < threshold),
+				Error::<T>::NotLiquidatable
 			);
-		}
-	}
-}
 
-impl<T: Config> Pallet<T> {
-	/// Returns the pending rewards for the specified `member_account`.
-	///
-	/// In the case of error, `None` is returned.
-	pub fn pending_rewards(member_account: T::AccountId) -> Option<BalanceOf<T>> {
-		if let Some(pool_member) = PoolMembers::<T>::get(member_account) {
-			if let Some((reward_pool, bonded_pool)) = RewardPools::<T>::get(pool_member.pool_id)
-				.zip(BondedPools::<T>::get(pool_member.pool_id))
-			{
-				let current_reward_counter = reward_pool
-					.current_reward_counter(pool_member.pool_id, bonded_pool.points)
-					.ok()?;
-				return pool_member.pending_rewards(current_reward_counter).ok()
-			}
-		}
+			// A nominator cannot unbond below the minimum nominator bond, hence chill first.
+			T::Staking::chill(&bonded_account)?;
+			T::Staking::unbond(&bonded_account, active_stake)?;
+
+			let current_era = T::Staking::current_era();
+			let unbond_era = T::Staking::bonding_duration().saturating_add(current_era);
+			let mut sub_pools = SubPoolsStorage::<T>::get(pool_id)
+				.unwrap_or_default()
+				.maybe_merge_pools(current_era);
+			if !sub_pools.with_era.contains_key(&unbond_era) {
+				sub_pools
+					.with_era
+					.try_insert(unbond_era, UnbondPool::default())
+					// The above call to `maybe_merge_pools` should ensure there is
+					// always enough space to insert.
+					.defensive_map_err::<Error<T>, _>(|_| {
+						DefensiveError::NotEnoughSpaceInUnbondPool.into()
+					})?;
+			}
+			let unbonding_points = sub_pools
+				.with_era
+				.get_mut(&unbond_era)
+				// The above check ensures the pool exists.
+				.defensive_ok_or::<Error<T>>(DefensiveError::PoolNotFound.into())?
+				.issue(active_stake);
+
+			LiquidatedPools::<T>::insert(
+				pool_id,
+				Liquidation {
+					era: unbond_era,
+					bonded_points: bonded_pool.points,
+					unbonding_points,
+				},
+			);
+			SubPoolsStorage::<T>::insert(pool_id, sub_pools);
+			bonded_pool.set_state(PoolState::Destroying);
+			bonded_pool.put();
+			Self::deposit_event(Event::<T>::Liquidated {
+				pool_id,
+				balance: active_stake,
+				era: unbond_era,
+			});
+
+			Ok(())
+		}
+
+		/// Set a cap on the number of members of a pool, or remove it if `None`.
+		///
+		/// The cap can never be higher than [`MaxPoolMembersPerPool`]. It only applies to members
+		/// joining from now on, existing members are never removed.
+		///
+		/// The dispatch origin of this call must be signed by the root role of the pool.
+		#[pallet::weight(T::WeightInfo::set_member_cap())]
+		pub fn set_member_cap(
+			origin: OriginFor<T>,
+			pool_id: PoolId,
+			cap: Option<u32>,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let bonded_pool = BondedPool::<T>::get(pool_id).ok_or(Error::<T>::PoolNotFound)?;
+			ensure!(bonded_pool.is_root(&who), Error::<T>::DoesNotHavePermission);
+			if let Some(cap) = cap {
+				ensure!(
+					MaxPoolMembersPerPool::<T>::get().map_or(true, |max| cap <= max),
+					Error::<T>::MemberCapTooHigh
+				);
+			}
+
+			PoolMemberCap::<T>::set(pool_id, cap);
+			Self::deposit_event(Event::<T>::MemberCapUpdated { pool_id, cap });
+
+			Ok(())
+		}
+
+		/// Withdraw the unbonded funds of all of `members`, as per [`Call::withdraw_unbonded`].
+		///
+		/// Members that cannot be withdrawn (e.g. because they have nothing to withdraw yet, or the
+		/// caller is not allowed to withdraw them) are skipped, and reported with
+		/// [`Event::WithdrawSkipped`]. The changes of a skipped member are reverted, while those of
+		/// all other members are kept. If any of `members` cannot be looked up, the whole batch is
+		/// rejected upfront, since there is no account to report.
+		///
+		/// This is meant to be used by bots that maintain pools, to withdraw many members in one
+		/// transaction. Only the weight of the members actually processed is charged.
+		#[pallet::weight(
+			T::WeightInfo::withdraw_unbonded_many(members.len() as u32, *num_slashing_spans)
+		)]
+		pub fn withdraw_unbonded_many(
+			origin: OriginFor<T>,
+			members: BoundedVec<AccountIdLookupOf<T>, T::MaxWithdrawBatch>,
+			num_slashing_spans: u32,
+		) -> DispatchResultWithPostInfo {
+			let caller = ensure_signed(origin)?;
+			let members =
+				members.into_iter().map(T::Lookup::lookup).collect::<Result<Vec<_>, _>>()?;
+
+			let mut weight = Weight::zero();
+			for member_account in members {
+				let result = with_storage_layer(|| {
+					Self::do_withdraw_unbonded(
+						caller.clone(),
+						member_account.clone(),
+						num_slashing_spans,
+					)
+				});
+				weight.saturating_accrue(match result {
+					Ok(actual) => actual.unwrap_or_else(|| {
+						T::WeightInfo::withdraw_unbonded_kill(num_slashing_spans)
+					}),
+					Err(error) => {
+						Self::deposit_event(Event::<T>::WithdrawSkipped {
+							member: member_account,
+							error,
+						});
+						T::WeightInfo::withdraw_unbonded_update(num_slashing_spans)
+					},
+				});
+			}
+
+			Ok(Some(weight).into())
+		}
+
+		/// Forward the staking rewards of pool `pool_id` to its reward account, if they are not
+		/// already.
+		///
+		/// This is always done when a pool is created, and thus is only needed to repair pools that
+		/// were created before this was enforced.
+		///
+		/// The dispatch origin of this call must be signed, by any account.
+		#[pallet::weight(T::WeightInfo::fix_payee())]
+		pub fn fix_payee(origin: OriginFor<T>, pool_id: PoolId) -> DispatchResult {
+			let _ = ensure_signed(origin)?;
+			let bonded_pool = BondedPool::<T>::get(pool_id).ok_or(Error::<T>::PoolNotFound)?;
+			ensure!(bonded_pool.fix_payee()?, Error::<T>::PayeeAlreadySet);
+
+			Self::deposit_event(Event::<T>::PayeeFixed { pool_id });
+			Ok(())
+		}
+
+		/// Set what to do with the rewards of the caller when they claim less than
+		/// [`Config::MinPayout`].
+		///
+		/// The dispatch origin of this call must be signed by a pool member.
+		#[pallet::weight(T::WeightInfo::set_dust_payout())]
+		pub fn set_dust_payout(origin: OriginFor<T>, dust_payout: DustPayout) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(PoolMembers::<T>::contains_key(&who), Error::<T>::PoolMemberNotFound);
+
+			if dust_payout == DustPayout::default() {
+				DustPayouts::<T>::remove(&who);
+			} else {
+				DustPayouts::<T>::insert(&who, dust_payout);
+			}
+			Self::deposit_event(Event::<T>::DustPayoutUpdated { member: who, dust_payout });
+
+			Ok(())
+		}
+
+		/// Request to join pool `pool_id` with `amount`, which is transferred into the escrow
+		/// account of the pool until the request is either approved or rejected.
+		///
+		/// Unlike [`Call::join`], this does not require the pool to be [`PoolState::Open`], which
+		/// allows pools to be blocked to everyone but the members approved by their root or state
+		/// toggler, see [`Call::approve_join`].
+		///
+		/// If the request is not approved within [`Config::JoinRequestTimeout`] blocks, it expires
+		/// and is refunded automatically, in the idle time of the following blocks. Until then,
+		/// anyone can refund it, see [`Call::reject_join`]. Requests are also refunded once their
+		/// pool is dissolved.
+		///
+		/// A pool can have at most [`Config::MaxJoinRequests`] pending requests.
+		#[pallet::weight(T::WeightInfo::join_permissioned())]
+		pub fn join_permissioned(
+			origin: OriginFor<T>,
+			#[pallet::compact] amount: BalanceOf<T>,
+			pool_id: PoolId,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			ensure!(amount >= MinJoinBond::<T>::get(), Error::<T>::MinimumBondNotMet);
+			ensure!(!PoolMembers::<T>::contains_key(&who), Error::<T>::AccountBelongsToOtherPool);
+			ensure!(!JoinRequests::<T>::contains_key(pool_id, &who), Error::<T>::JoinRequestExists);
+
+			let bonded_pool = BondedPool::<T>::get(pool_id).ok_or(Error::<T>::PoolNotFound)?;
+			bonded_pool.ok_to_be_open()?;
+			ensure!(
+				JoinRequestCount::<T>::get(pool_id) < T::MaxJoinRequests::get(),
+				Error::<T>::TooManyJoinRequests
+			);
+
+			T::Currency::transfer(
+				&who,
+				&bonded_pool.escrow_account(),
+				amount,
+				ExistenceRequirement::KeepAlive,
+			)?;
+			let expires_at = frame_system::Pallet::<T>::block_number()
+				.saturating_add(T::JoinRequestTimeout::get());
+			JoinRequests::<T>::insert(pool_id, &who, JoinRequest { amount, expires_at });
+			JoinRequestCount::<T>::mutate(pool_id, |count| *count += 1);
+
+			Self::deposit_event(Event::<T>::JoinRequested {
+				member: who,
+				pool_id,
+				amount,
+				expires_at,
+			});
+			Ok(())
+		}
+
+		/// Approve the request of `member` to join pool `pool_id`, bonding the amount held in
+		/// escrow.
+		///
+		/// The dispatch origin of this call must be signed by the root or the state toggler of the
+		/// pool, and the request must not have expired.
+		#[pallet::weight(T::WeightInfo::approve_join())]
+		pub fn approve_join(
+			origin: OriginFor<T>,
+			pool_id: PoolId,
+			member: AccountIdLookupOf<T>,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let member = T::Lookup::lookup(member)?;
+			let bonded_pool = BondedPool::<T>::get(pool_id).ok_or(Error::<T>::PoolNotFound)?;
+			ensure!(bonded_pool.can_toggle_state(&who), Error::<T>::DoesNotHavePermission);
+			ensure!(
+				!PoolMembers::<T>::contains_key(&member),
+				Error::<T>::AccountBelongsToOtherPool
+			);
+			bonded_pool.ok_to_be_open()?;
+
+			let request =
+				JoinRequests::<T>::get(pool_id, &member).ok_or(Error::<T>::JoinRequestNotFound)?;
+			ensure!(
+				frame_system::Pallet::<T>::block_number() < request.expires_at,
+				Error::<T>::JoinRequestExpired
+			);
+			// the funds go back to `member`, from where they are bonded like any other join.
+			Self::refund_join_request(pool_id, &member, &request)?;
+
+			Self::deposit_event(Event::<T>::JoinApproved { member: member.clone(), pool_id });
+			Self::do_join(member, request.amount, bonded_pool)
+		}
+
+		/// Reject the request of `member` to join pool `pool_id`, refunding the amount held in
+		/// escrow.
+		///
+		/// The dispatch origin of this call must be signed by the root or the state toggler of the
+		/// pool. Once the request has expired, or if the pool does not exist anymore, it can be
+		/// signed by any account.
+		#[pallet::weight(T::WeightInfo::reject_join())]
+		pub fn reject_join(
+			origin: OriginFor<T>,
+			pool_id: PoolId,
+			member: AccountIdLookupOf<T>,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let member = T::Lookup::lookup(member)?;
+
+			let request =
+				JoinRequests::<T>::get(pool_id, &member).ok_or(Error::<T>::JoinRequestNotFound)?;
+			let expired = frame_system::Pallet::<T>::block_number() >= request.expires_at;
+			ensure!(
+				expired ||
+					BondedPool::<T>::get(pool_id).map_or(true, |bonded_pool| {
+						bonded_pool.is_root(&who) || bonded_pool.is_state_toggler(&who)
+					}),
+				Error::<T>::DoesNotHavePermission
+			);
+			Self::refund_join_request(pool_id, &member, &request)?;
+
+			Self::deposit_event(Event::<T>::JoinRejected { member, pool_id, expired });
+			Ok(())
+		}
+
+		/// Register `alias` as a short, unique name of pool `pool_id`, replacing its previous
+		/// alias, if any.
+		///
+		/// An alias must be non-empty, and only consist of lowercase letters, digits and `-`. The
+		/// pool can then be looked up by its alias, see [`Pallet::pool_by_alias`], while all calls
+		/// keep referring to it by its id.
+		///
+		/// The dispatch origin of this call must be either root, or signed by the root role of the
+		/// pool, who then pays [`Config::AliasDeposit`]. The deposit is refunded once the alias is
+		/// removed.
+		#[pallet::weight(T::WeightInfo::set_alias())]
+		pub fn set_alias(
+			origin: OriginFor<T>,
+			pool_id: PoolId,
+			alias: PoolAliasOf<T>,
+		) -> DispatchResult {
+			let who = match ensure_root(origin.clone()) {
+				Ok(()) => None,
+				Err(frame_support::error::BadOrigin) => Some(ensure_signed(origin)?),
+			};
+			let bonded_pool = BondedPool::<T>::get(pool_id).ok_or(Error::<T>::PoolNotFound)?;
+			if let Some(who) = &who {
+				ensure!(bonded_pool.is_root(who), Error::<T>::DoesNotHavePermission);
+			}
+
+			ensure!(
+				!alias.is_empty() &&
+					alias.iter().all(|c| matches!(c, b'a'..=b'z' | b'0'..=b'9' | b'-')),
+				Error::<T>::InvalidAlias
+			);
+			ensure!(!PoolIdByAlias::<T>::contains_key(&alias), Error::<T>::AliasTaken);
+
+			Self::remove_alias(pool_id)?;
+			let deposit = match who {
+				Some(who) => {
+					let deposit = T::AliasDeposit::get();
+					T::Currency::transfer(
+						&who,
+						&bonded_pool.escrow_account(),
+						deposit,
+						ExistenceRequirement::KeepAlive,
+					)?;
+					Some((who, deposit))
+				},
+				None => None,
+			};
+
+			PoolIdByAlias::<T>::insert(&alias, pool_id);
+			PoolAliases::<T>::insert(pool_id, PoolAlias { alias: alias.clone(), deposit });
+			Self::deposit_event(Event::<T>::AliasSet { pool_id, alias });
+
+			Ok(())
+		}
+
+		/// Remove the alias of pool `pool_id`, refunding its deposit, if any.
+		///
+		/// The dispatch origin of this call must be either root, or signed by the root role of the
+		/// pool.
+		#[pallet::weight(T::WeightInfo::clear_alias())]
+		pub fn clear_alias(origin: OriginFor<T>, pool_id: PoolId) -> DispatchResult {
+			if let Err(frame_support::error::BadOrigin) = ensure_root(origin.clone()) {
+				let who = ensure_signed(origin)?;
+				let bonded_pool = BondedPool::<T>::get(pool_id).ok_or(Error::<T>::PoolNotFound)?;
+				ensure!(bonded_pool.is_root(&who), Error::<T>::DoesNotHavePermission);
+			}
+
+			ensure!(Self::remove_alias(pool_id)?, Error::<T>::AliasNotFound);
+			Self::deposit_event(Event::<T>::AliasCleared { pool_id });
+
+			Ok(())
+		}
+
+		/// Unbond `amount` of the `member_account`'s active balance from the pool. This is
+		/// [`Call::unbond`], denominated in balance rather than points, and can be dispatched
+		/// under the same conditions.
+		///
+		/// `amount` is converted into points at the current rate of the pool, rounding down, such
+		/// that never more than `amount` is unbonded. If `amount` is at least the active balance of
+		/// the member, all of their points are unbonded. The conversion is reported by
+		/// [`Event::UnbondRequested`].
+		///
+		/// Fails with [`Error::MinimumBondNotMet`] if the balance that remains bonded would fall
+		/// below the minimum bond of the member, and with [`Error::UnbondAmountTooLow`] if `amount`
+		/// is not worth a single point.
+		#[pallet::weight(T::WeightInfo::unbond_balance())]
+		pub fn unbond_balance(
+			origin: OriginFor<T>,
+			member_account: AccountIdLookupOf<T>,
+			#[pallet::compact] amount: BalanceOf<T>,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let member_account = T::Lookup::lookup(member_account)?;
+			let (member, bonded_pool, reward_pool) = Self::get_member_with_pools(&member_account)?;
+			// the points of a liquidated pool are no longer backed by any active balance.
+			ensure!(
+				!LiquidatedPools::<T>::contains_key(member.pool_id),
+				Error::<T>::AlreadyLiquidated
+			);
+			ensure!(!member.active_points().is_zero(), Error::<T>::FullyUnbonding);
+
+			let points = if amount >= bonded_pool.points_to_balance(member.active_points()) {
+				member.active_points()
+			} else {
+				bonded_pool.balance_to_point(amount).min(member.active_points())
+			};
+			ensure!(!points.is_zero(), Error::<T>::UnbondAmountTooLow);
+
+			Self::deposit_event(Event::<T>::UnbondRequested {
+				member: member_account.clone(),
+				pool_id: member.pool_id,
+				amount,
+				points,
+			});
+			Self::do_unbond(who, member_account, member, bonded_pool, reward_pool, points)
+		}
+	}
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn on_idle(now: BlockNumberFor<T>, remaining_weight: Weight) -> Weight {
+			Self::sweep_join_requests(now, remaining_weight)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn try_state(_n: BlockNumberFor<T>) -> Result<(), &'static str> {
+			Self::do_try_state(u8::MAX)
+		}
+
+		fn integrity_test() {
+			assert!(
+				T::MaxPointsToBalance::get() > 0,
+				"Minimum points to balance ratio must be greater than 0"
+			);
+			assert!(
+				T::Staking::bonding_duration() < TotalUnbondingPools::<T>::get(),
+				"There must be more unbonding pools then the bonding duration /
+				so a slash can be applied to relevant unboding pools. (We assume /
+				the bonding duration > slash deffer duration.",
+			);
+		}
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// Bond `amount` of `who` into `bonded_pool`, as a new member.
+	///
+	/// Whether `who` may join `bonded_pool` must have been checked already, see [`Call::join`].
+	fn do_join(
+		who: T::AccountId,
+		amount: BalanceOf<T>,
+		mut bonded_pool: BondedPool<T>,
+	) -> DispatchResult {
+		let pool_id = bonded_pool.id;
+		let mut reward_pool = RewardPools::<T>::get(pool_id)
+			.defensive_ok_or::<Error<T>>(DefensiveError::RewardPoolNotFound.into())?;
+		// IMPORTANT: reward pool records must be updated with the old points.
+		reward_pool.update_records(pool_id, bonded_pool.points)?;
+
+		bonded_pool.try_inc_members()?;
+		let points_issued = bonded_pool.try_bond_funds(&who, amount, BondType::Later)?;
+
+		PoolMembers::insert(
+			who.clone(),
+			PoolMember::<T> {
+				pool_id,
+				points: points_issued,
+				// we just updated `last_known_reward_counter` to the current one in
+				// `update_recorded`.
+				last_recorded_reward_counter: reward_pool.last_recorded_reward_counter(),
+				unbonding_eras: Default::default(),
+			},
+		);
+
+		Self::deposit_event(Event::<T>::Bonded {
+			member: who,
+			pool_id,
+			bonded: amount,
+			joined: true,
+		});
+
+		bonded_pool.put();
+		RewardPools::<T>::insert(pool_id, reward_pool);
+
+		Ok(())
+	}
+
+	/// Unbond `unbonding_points` of `member_account`, on behalf of `who`.
+	///
+	/// Whether the unbond is allowed is checked here, see [`Call::unbond`].
+	fn do_unbond(
+		who: T::AccountId,
+		member_account: T::AccountId,
+		mut member: PoolMember<T>,
+		mut bonded_pool: BondedPool<T>,
+		mut reward_pool: RewardPool<T>,
+		unbonding_points: BalanceOf<T>,
+	) -> DispatchResult {
+		bonded_pool.ok_to_unbond_with(&who, &member_account, &member, unbonding_points)?;
+
+		// Claim the the payout prior to unbonding. Once the user is unbonding their points no
+		// longer exist in the bonded pool and thus they can no longer claim their payouts. It
+		// is not strictly necessary to claim the rewards, but we do it here for UX.
+		let _ = reward_pool.update_records(bonded_pool.id, bonded_pool.points)?;
+		let _ = Self::do_reward_payout(&who, &mut member, &mut bonded_pool, &mut reward_pool)?;
+
+		let current_era = T::Staking::current_era();
+
+		// Note that we lazily create the unbonding pools here if they don't already exist
+		let mut sub_pools = SubPoolsStorage::<T>::get(member.pool_id)
+			.unwrap_or_default()
+			.maybe_merge_pools(current_era);
+
+		if let Some(mut liquidation) = LiquidatedPools::<T>::get(member.pool_id) {
+			// The entire stake of a liquidated pool is already unbonding, the points of the
+			// member only need to be converted into their share of it.
+			let _ = bonded_pool.dissolve(unbonding_points);
+			let points_unbonded = liquidation.convert(unbonding_points);
+			let unbonding_balance = sub_pools
+				.with_era
+				.get(&liquidation.era)
+				.unwrap_or(&sub_pools.no_era)
+				.point_to_balance(points_unbonded);
+
+			member.try_unbond(unbonding_points, points_unbonded, liquidation.era)?;
+
+			Self::deposit_event(Event::<T>::Unbonded {
+				member: member_account.clone(),
+				pool_id: member.pool_id,
+				points: points_unbonded,
+				balance: unbonding_balance,
+				era: liquidation.era,
+			});
+			Self::deposit_on_behalf(member.pool_id, &who, &member_account);
+
+			LiquidatedPools::<T>::insert(member.pool_id, liquidation);
+			SubPoolsStorage::insert(&member.pool_id, sub_pools);
+			Self::put_member_with_pools(&member_account, member, bonded_pool, reward_pool);
+
+			return Ok(())
+		}
+
+		let unbond_era = T::Staking::bonding_duration().saturating_add(current_era);
+
+		// Unbond in the actual underlying nominator.
+		let unbonding_balance = bonded_pool.dissolve(unbonding_points);
+		T::Staking::unbond(&bonded_pool.bonded_account(), unbonding_balance)?;
+
+		// Update the unbond pool associated with the current era with the unbonded funds. Note
+		// that we lazily create the unbond pool if it does not yet exist.
+		if !sub_pools.with_era.contains_key(&unbond_era) {
+			sub_pools
+				.with_era
+				.try_insert(unbond_era, UnbondPool::default())
+				// The above call to `maybe_merge_pools` should ensure there is
+				// always enough space to insert.
+				.defensive_map_err::<Error<T>, _>(|_| {
+					DefensiveError::NotEnoughSpaceInUnbondPool.into()
+				})?;
+		}
+
+		let points_unbonded = sub_pools
+			.with_era
+			.get_mut(&unbond_era)
+			// The above check ensures the pool exists.
+			.defensive_ok_or::<Error<T>>(DefensiveError::PoolNotFound.into())?
+			.issue(unbonding_balance);
+
+		// Try and unbond in the member map.
+		member.try_unbond(unbonding_points, points_unbonded, unbond_era)?;
+
+		Self::deposit_event(Event::<T>::Unbonded {
+			member: member_account.clone(),
+			pool_id: member.pool_id,
+			points: points_unbonded,
+			balance: unbonding_balance,
+			era: unbond_era,
+		});
+		Self::deposit_on_behalf(member.pool_id, &who, &member_account);
+
+		// Now that we know everything has worked write the items to storage.
+		SubPoolsStorage::insert(&member.pool_id, sub_pools);
+		Self::put_member_with_pools(&member_account, member, bonded_pool, reward_pool);
+
+		Ok(())
+	}
+
+	/// Remove the `request` of `who` to join pool `pool_id`, and refund its amount from escrow.
+	fn refund_join_request(
+		pool_id: PoolId,
+		who: &T::AccountId,
+		request: &JoinRequest<T>,
+	) -> DispatchResult {
+		T::Currency::transfer(
+			&Self::create_escrow_account(pool_id),
+			who,
+			request.amount,
+			ExistenceRequirement::AllowDeath,
+		)?;
+		JoinRequests::<T>::remove(pool_id, who);
+		JoinRequestCount::<T>::mutate_exists(pool_id, |count| {
+			*count = count.map(|count| count.saturating_sub(1)).filter(|count| *count > 0)
+		});
+		Ok(())
+	}
+
+	/// Refund the expired join requests, as many as fit into `limit`.
+	///
+	/// Each sweep continues where the previous one has stopped, as per [`JoinRequestSweepCursor`],
+	/// and starts over once all requests have been visited. Returns the consumed weight.
+	pub(crate) fn sweep_join_requests(now: T::BlockNumber, limit: Weight) -> Weight {
+		// the cursor is read and written, each visited request read, and each refund is a
+		// rejection.
+		let mut weight = T::DbWeight::get().reads_writes(1, 1);
+		let visit = T::DbWeight::get().reads(1);
+		let refund = T::WeightInfo::reject_join();
+		if limit.any_lt(weight.saturating_add(visit).saturating_add(refund)) {
+			return Weight::zero()
+		}
+
+		let mut requests = match JoinRequestSweepCursor::<T>::get() {
+			Some((pool_id, who)) =>
+				JoinRequests::<T>::iter_from(JoinRequests::<T>::hashed_key_for(pool_id, who)),
+			None => JoinRequests::<T>::iter(),
+		};
+		let mut expired = Vec::new();
+		let mut cursor = None;
+		while limit.all_gte(weight.saturating_add(visit).saturating_add(refund)) {
+			match requests.next() {
+				Some((pool_id, who, request)) => {
+					weight.saturating_accrue(visit);
+					if now >= request.expires_at {
+						weight.saturating_accrue(refund);
+						expired.push((pool_id, who.clone(), request));
+					}
+					cursor = Some((pool_id, who));
+				},
+				None => {
+					cursor = None;
+					break
+				},
+			}
+		}
+		JoinRequestSweepCursor::<T>::set(cursor);
+
+		for (pool_id, member, request) in expired {
+			if Self::refund_join_request(pool_id, &member, &request).defensive().is_ok() {
+				Self::deposit_event(Event::<T>::JoinRejected { member, pool_id, expired: true });
+			}
+		}
+		weight
+	}
+
+	/// Remove the alias of pool `pool_id`, refunding its deposit from escrow, if any.
+	///
+	/// Returns whether the pool had an alias. If the deposit cannot be refunded, the alias is kept.
+	fn remove_alias(pool_id: PoolId) -> Result<bool, DispatchError> {
+		let PoolAlias { alias, deposit } = match PoolAliases::<T>::get(pool_id) {
+			Some(pool_alias) => pool_alias,
+			None => return Ok(false),
+		};
+		if let Some((who, deposit)) = deposit {
+			T::Currency::transfer(
+				&Self::create_escrow_account(pool_id),
+				&who,
+				deposit,
+				ExistenceRequirement::AllowDeath,
+			)?;
+		}
+		PoolAliases::<T>::remove(pool_id);
+		PoolIdByAlias::<T>::remove(&alias);
+		Ok(true)
+	}
+
+	/// Withdraw the unlocked funds of `member_account`, on behalf of `caller`.
+	///
+	/// See [`Call::withdraw_unbonded`]. Returns the actual weight, or `None` if the pool got
+	/// dissolved.
+	fn do_withdraw_unbonded(
+		caller: T::AccountId,
+		member_account: T::AccountId,
+		num_slashing_spans: u32,
+	) -> Result<Option<Weight>, DispatchError> {
+		let mut member =
+			PoolMembers::<T>::get(&member_account).ok_or(Error::<T>::PoolMemberNotFound)?;
+		let current_era = T::Staking::current_era();
+
+		let bonded_pool = BondedPool::<T>::get(member.pool_id)
+			.defensive_ok_or::<Error<T>>(DefensiveError::PoolNotFound.into())?;
+		let mut sub_pools =
+			SubPoolsStorage::<T>::get(member.pool_id).ok_or(Error::<T>::SubPoolsNotFound)?;
+
+		bonded_pool.ok_to_withdraw_unbonded_with(&caller, &member_account)?;
+
+		// NOTE: must do this after we have done the `ok_to_withdraw_unbonded_other_with` check.
+		let withdrawn_points = member.withdraw_unlocked(current_era);
+		ensure!(!withdrawn_points.is_empty(), Error::<T>::CannotWithdrawAny);
+
+		// Before calculating the `balance_to_unbond`, we call withdraw unbonded to ensure the
+		// `transferrable_balance` is correct.
+		//
+		// The stash of a liquidated pool has no active stake left, so it is killed by the first
+		// member to withdraw, after which there is nothing left to withdraw from staking.
+		let is_liquidated = LiquidatedPools::<T>::contains_key(member.pool_id);
+		let stash_killed =
+			if is_liquidated && T::Staking::stake(&bonded_pool.bonded_account()).is_err() {
+				false
+			} else {
+				T::Staking::withdraw_unbonded(bonded_pool.bonded_account(), num_slashing_spans)?
+			};
+
+		// defensive-only: the depositor puts enough funds into the stash so that it will only
+		// be destroyed when they are leaving.
+		ensure!(
+			!stash_killed || is_liquidated || caller == bonded_pool.roles.depositor,
+			Error::<T>::Defensive(DefensiveError::BondedStashKilledPrematurely)
+		);
+
+		let mut sum_unlocked_points: BalanceOf<T> = Zero::zero();
+		let balance_to_unbond = withdrawn_points
+			.iter()
+			.fold(BalanceOf::<T>::zero(), |accumulator, (era, unlocked_points)| {
+				sum_unlocked_points = sum_unlocked_points.saturating_add(*unlocked_points);
+				if let Some(era_pool) = sub_pools.with_era.get_mut(&era) {
+					let balance_to_unbond = era_pool.dissolve(*unlocked_points);
+					if era_pool.points.is_zero() {
+						sub_pools.with_era.remove(&era);
+					}
+					accumulator.saturating_add(balance_to_unbond)
+				} else {
+					// A pool does not belong to this era, so it must have been merged to the
+					// era-less pool.
+					accumulator.saturating_add(sub_pools.no_era.dissolve(*unlocked_points))
+				}
+			})
+			// A call to this transaction may cause the pool's stash to get dusted. If this
+			// happens before the last member has withdrawn, then all subsequent withdraws will
+			// be 0. However the unbond pools do no get updated to reflect this. In the
+			// aforementioned scenario, this check ensures we don't try to withdraw funds that
+			// don't exist. This check is also defensive in cases where the unbond pool does not
+			// update its balance (e.g. a bug in the slashing hook.) We gracefully proceed in
+			// order to ensure members can leave the pool and it can be destroyed.
+			.min(bonded_pool.transferrable_balance());
+
+		T::Currency::transfer(
+			&bonded_pool.bonded_account(),
+			&member_account,
+			balance_to_unbond,
+			ExistenceRequirement::AllowDeath,
+		)
+		.defensive()?;
+
+		Self::deposit_event(Event::<T>::Withdrawn {
+			member: member_account.clone(),
+			pool_id: member.pool_id,
+			points: sum_unlocked_points,
+			balance: balance_to_unbond,
+		});
+		Self::deposit_on_behalf(member.pool_id, &caller, &member_account);
+
+		let post_info_weight = if member.total_points().is_zero() {
+			// member being reaped.
+			PoolMembers::<T>::remove(&member_account);
+			DustPayouts::<T>::remove(&member_account);
+			Self::deposit_event(Event::<T>::MemberRemoved {
+				pool_id: member.pool_id,
+				member: member_account.clone(),
+			});
+
+			if member_account == bonded_pool.roles.depositor {
+				Pallet::<T>::dissolve_pool(bonded_pool);
+				None
+			} else {
+				bonded_pool.dec_members().put();
+				SubPoolsStorage::<T>::insert(&member.pool_id, sub_pools);
+				Some(T::WeightInfo::withdraw_unbonded_update(num_slashing_spans))
+			}
+		} else {
+			// we certainly don't need to delete any pools, because no one is being removed.
+			SubPoolsStorage::<T>::insert(&member.pool_id, sub_pools);
+			PoolMembers::<T>::insert(&member_account, member);
+			Some(T::WeightInfo::withdraw_unbonded_update(num_slashing_spans))
+		};
+
+		Ok(post_info_weight)
+	}
+
+	/// Returns the maximum number of members that the given pool can have, if any.
+	///
+	/// This is the lower of the member cap of the pool and [`MaxPoolMembersPerPool`].
+	pub fn member_cap(pool_id: PoolId) -> Option<u32> {
+		match (PoolMemberCap::<T>::get(pool_id), MaxPoolMembersPerPool::<T>::get()) {
+			(Some(cap), Some(max)) => Some(cap.min(max)),
+			(cap, max) => cap.or(max),
+		}
+	}
+
+	/// Returns the targets that the pool with `pool_id` nominates, each along with whether the
+	/// nomination of it is stale, and the era in which the pool submitted the nomination, if the
+	/// pool exists and nominates.
+	///
+	/// See [`StakingInterface::nomination_targets`].
+	pub fn pool_nominations(pool_id: PoolId) -> Option<(Vec<(T::AccountId, bool)>, EraIndex)> {
+		let bonded_pool = BondedPool::<T>::get(pool_id)?;
+		T::Staking::nomination_targets(&bonded_pool.bonded_account())
+	}
+
+	/// Returns the pool that `alias` refers to, if any.
+	pub fn pool_by_alias(alias: PoolAliasOf<T>) -> Option<PoolId> {
+		PoolIdByAlias::<T>::get(alias)
+	}
+
+	/// Returns the pending rewards for the specified `member_account`.
+	///
+	/// In the case of error, `None` is returned.
+	pub fn pending_rewards(member_account: T::AccountId) -> Option<BalanceOf<T>> {
+		if let Some(pool_member) = PoolMembers::<T>::get(member_account) {
+			if let Some((reward_pool, bonded_pool)) = RewardPools::<T>::get(pool_member.pool_id)
+				.zip(BondedPools::<T>::get(pool_member.pool_id))
+			{
+				let current_reward_counter = reward_pool
+					.current_reward_counter(pool_member.pool_id, bonded_pool.points)
+					.ok()?;
+				return pool_member.pending_rewards(current_reward_counter).ok()
+			}
+		}
 
 		None
 	}
@@ -2165,6 +3124,21 @@ impl<T: Config> Pallet<T> {
 		ReversePoolIdLookup::<T>::remove(&bonded_account);
 		RewardPools::<T>::remove(bonded_pool.id);
 		SubPoolsStorage::<T>::remove(bonded_pool.id);
+		LiquidatedPools::<T>::remove(bonded_pool.id);
+		PoolMemberCap::<T>::remove(bonded_pool.id);
+		// This shouldn't fail, since the escrow account holds the alias deposit and all join
+		// requests.
+		let _ = Self::remove_alias(bonded_pool.id).defensive();
+		let requests = JoinRequests::<T>::iter_prefix(bonded_pool.id).collect::<Vec<_>>();
+		for (member, request) in requests {
+			if Self::refund_join_request(bonded_pool.id, &member, &request).defensive().is_ok() {
+				Self::deposit_event(Event::<T>::JoinRejected {
+					member,
+					pool_id: bonded_pool.id,
+					expired: false,
+				});
+			}
+		}
 
 		// Kill accounts from storage by making their balance go below ED. We assume that the
 		// accounts have no references that would prevent destruction once we get to this point. We
@@ -2212,6 +3186,22 @@ impl<T: Config> Pallet<T> {
 		T::PalletId::get().into_sub_account_truncating((AccountType::Reward, id))
 	}
 
+	/// Create the escrow account of a pool with the given id, see [`Call::join_permissioned`].
+	pub fn create_escrow_account(id: PoolId) -> T::AccountId {
+		T::PalletId::get().into_sub_account_truncating((AccountType::Escrow, id))
+	}
+
+	/// Deposit [`Event::ActedOnBehalf`], if `caller` is not `member`.
+	fn deposit_on_behalf(pool_id: PoolId, caller: &T::AccountId, member: &T::AccountId) {
+		if caller != member {
+			Self::deposit_event(Event::<T>::ActedOnBehalf {
+				pool_id,
+				caller: caller.clone(),
+				member: member.clone(),
+			});
+		}
+	}
+
 	/// Get the member with their associated bonded and reward pool.
 	fn get_member_with_pools(
 		who: &T::AccountId,
@@ -2330,6 +3320,38 @@ impl<T: Config> Pallet<T> {
 		Ok(pending_rewards)
 	}
 
+	/// Bond `extra` into the pool of `who`, see [`Call::bond_extra`].
+	fn do_bond_extra(who: T::AccountId, extra: BondExtra<BalanceOf<T>>) -> DispatchResult {
+		let (mut member, mut bonded_pool, mut reward_pool) = Self::get_member_with_pools(&who)?;
+
+		// payout related stuff: we must claim the payouts, and updated recorded payout data
+		// before updating the bonded pool points, similar to that of `join` transaction.
+		reward_pool.update_records(bonded_pool.id, bonded_pool.points)?;
+		let claimed =
+			Self::do_reward_payout(&who, &mut member, &mut bonded_pool, &mut reward_pool)?;
+
+		let (points_issued, bonded) = match extra {
+			BondExtra::FreeBalance(amount) =>
+				(bonded_pool.try_bond_funds(&who, amount, BondType::Later)?, amount),
+			BondExtra::Rewards =>
+				(bonded_pool.try_bond_funds(&who, claimed, BondType::Later)?, claimed),
+		};
+
+		bonded_pool.ok_to_be_open()?;
+		member.points =
+			member.points.checked_add(&points_issued).ok_or(Error::<T>::OverflowRisk)?;
+
+		Self::deposit_event(Event::<T>::Bonded {
+			member: who.clone(),
+			pool_id: member.pool_id,
+			bonded,
+			joined: false,
+		});
+		Self::put_member_with_pools(&who, member, bonded_pool, reward_pool);
+
+		Ok(())
+	}
+
 	fn do_create(
 		who: T::AccountId,
 		amount: BalanceOf<T>,
@@ -2448,6 +3470,44 @@ impl<T: Config> Pallet<T> {
 
 		assert!(Metadata::<T>::iter_keys().all(|k| bonded_pools.contains(&k)));
 		assert!(SubPoolsStorage::<T>::iter_keys().all(|k| bonded_pools.contains(&k)));
+		assert!(PoolMemberCap::<T>::iter_keys().all(|k| bonded_pools.contains(&k)));
+		assert!(DustPayouts::<T>::iter_keys().all(|k| PoolMembers::<T>::contains_key(&k)));
+
+		// every alias refers to exactly one existing pool, and the other way around.
+		assert_eq!(PoolIdByAlias::<T>::iter().count(), PoolAliases::<T>::iter().count());
+		for (id, pool_alias) in PoolAliases::<T>::iter() {
+			assert!(BondedPools::<T>::contains_key(id));
+			assert_eq!(PoolIdByAlias::<T>::get(&pool_alias.alias), Some(id));
+		}
+
+		// join requests are counted per pool, and only exist for pools that do.
+		let mut join_request_count = BTreeMap::<PoolId, u32>::new();
+		for (id, _, _) in JoinRequests::<T>::iter() {
+			assert!(BondedPools::<T>::contains_key(id));
+			*join_request_count.entry(id).or_default() += 1;
+		}
+		assert_eq!(join_request_count, JoinRequestCount::<T>::iter().collect::<BTreeMap<_, _>>());
+		assert!(join_request_count.values().all(|count| *count <= T::MaxJoinRequests::get()));
+
+		// the escrow account of a pool holds at least the funds of all of its join requests, and
+		// the deposit of its alias.
+		let mut escrowed = BTreeMap::<PoolId, BalanceOf<T>>::new();
+		let join_requests = JoinRequests::<T>::iter().map(|(id, _, request)| (id, request.amount));
+		let alias_deposits = PoolAliases::<T>::iter()
+			.filter_map(|(id, pool_alias)| pool_alias.deposit.map(|(_, deposit)| (id, deposit)));
+		for (id, amount) in join_requests.chain(alias_deposits) {
+			let total = escrowed.entry(id).or_default();
+			*total = total.saturating_add(amount);
+		}
+		for (id, amount) in escrowed {
+			assert!(T::Currency::free_balance(&Self::create_escrow_account(id)) >= amount);
+		}
+
+		LiquidatedPools::<T>::iter().for_each(|(id, liquidation)| {
+			let bonded_pool = BondedPools::<T>::get(id).unwrap();
+			assert_eq!(bonded_pool.state, PoolState::Destroying);
+			assert_eq!(liquidation.bonded_points, bonded_pool.points);
+		});
 
 		assert!(MaxPools::<T>::get().map_or(true, |max| bonded_pools.len() <= (max as usize)));
 
@@ -2504,6 +3564,16 @@ impl<T: Config> Pallet<T> {
 			assert!(MaxPoolMembersPerPool::<T>::get()
 				.map_or(true, |max| bonded_pool.member_counter <= max));
 
+			// the staking rewards of a bonded pool must be forwarded to its reward account.
+			if T::Staking::stake(&bonded_pool.bonded_account()).is_ok() {
+				assert_eq!(
+					T::Staking::payee(&bonded_pool.bonded_account()),
+					Some(bonded_pool.reward_account()),
+					"staking rewards of pool {} are not forwarded to its reward account",
+					id,
+				);
+			}
+
 			let depositor = PoolMembers::<T>::get(&bonded_pool.roles.depositor).unwrap();
 			assert!(
 				bonded_pool.is_destroying_and_only_depositor(depositor.active_points()) ||
@@ -2568,8 +3638,10 @@ impl<T: Config> OnStakerSlash<T::AccountId, BalanceOf<T>> for Pallet<T> {
 				Some(sub_pools) => sub_pools,
 				None => return,
 			};
+			let mut unbonding = BTreeMap::new();
 			for (era, slashed_balance) in slashed_unlocking.iter() {
 				if let Some(pool) = sub_pools.with_era.get_mut(era) {
+					unbonding.insert(*era, pool.balance.saturating_sub(*slashed_balance));
 					pool.balance = *slashed_balance;
 					Self::deposit_event(Event::<T>::UnbondingPoolSlashed {
 						era: *era,
@@ -2579,8 +3651,48 @@ impl<T: Config> OnStakerSlash<T::AccountId, BalanceOf<T>> for Pallet<T> {
 				}
 			}
 
+			// the ledger of the pool is only updated after this hook, hence this is the active
+			// balance before the slash.
+			let bonded = T::Staking::active_stake(pool_account)
+				.unwrap_or_default()
+				.saturating_sub(slashed_bonded);
 			Self::deposit_event(Event::<T>::PoolSlashed { pool_id, balance: slashed_bonded });
+			Self::deposit_event(Event::<T>::SlashApplied { pool_id, bonded, unbonding });
 			SubPoolsStorage::<T>::insert(pool_id, sub_pools);
 		}
 	}
 }
+
+impl<T: Config> PoolRewardCredit<T::AccountId, BalanceOf<T>> for Pallet<T> {
+	type PoolId = PoolId;
+
+	fn credit_reward(
+		source: &T::AccountId,
+		pool_id: PoolId,
+		amount: BalanceOf<T>,
+	) -> DispatchResult {
+		let bonded_pool = BondedPool::<T>::get(pool_id).ok_or(Error::<T>::PoolNotFound)?;
+		ensure!(!bonded_pool.points.is_zero(), Error::<T>::NoPointsToReward);
+		let mut reward_pool = RewardPools::<T>::get(pool_id)
+			.defensive_ok_or::<Error<T>>(DefensiveError::RewardPoolNotFound.into())?;
+
+		with_storage_layer(|| -> DispatchResult {
+			T::Currency::transfer(
+				source,
+				&bonded_pool.reward_account(),
+				amount,
+				ExistenceRequirement::KeepAlive,
+			)?;
+			// record the reward right away, rather than once the pool is next interacted with.
+			reward_pool.update_records(pool_id, bonded_pool.points)?;
+			RewardPools::<T>::insert(pool_id, reward_pool);
+
+			Self::deposit_event(Event::<T>::RewardCredited {
+				pool_id,
+				source: source.clone(),
+				amount,
+			});
+			Ok(())
+		})
+	}
+}
@@ -25,14 +25,21 @@ pub fn default_reward_account() -> AccountId {
 
 parameter_types! {
 	pub static MinJoinBondConfig: Balance = 2;
+	pub static MinPayout: Balance = 0;
+	pub static AliasDeposit: Balance = 10;
 	pub static CurrentEra: EraIndex = 0;
 	pub static BondingDuration: EraIndex = 3;
 	pub storage BondedBalanceMap: BTreeMap<AccountId, Balance> = Default::default();
 	pub storage UnbondingBalanceMap: BTreeMap<AccountId, Balance> = Default::default();
+	pub storage PayeeMap: BTreeMap<AccountId, AccountId> = Default::default();
 	#[derive(Clone, PartialEq)]
 	pub static MaxUnbonding: u32 = 8;
 	pub static StakingMinBond: Balance = 10;
 	pub storage Nominations: Option<Vec<AccountId>> = None;
+	pub storage NominationsSubmittedIn: EraIndex = 0;
+	pub static StaleTargets: Vec<AccountId> = vec![];
+	pub static MaxNominations: u32 = 16;
+	pub static Validators: Vec<AccountId> = vec![21, 22, 23, 31];
 }
 
 pub struct StakingMock;
@@ -97,16 +104,48 @@ impl sp_staking::StakingInterface for StakingMock {
 		Ok(UnbondingBalanceMap::get().is_empty() && BondedBalanceMap::get().is_empty())
 	}
 
-	fn bond(stash: &Self::AccountId, value: Self::Balance, _: &Self::AccountId) -> DispatchResult {
+	fn bond(
+		stash: &Self::AccountId,
+		value: Self::Balance,
+		payee: &Self::AccountId,
+	) -> DispatchResult {
 		StakingMock::set_bonded_balance(*stash, value);
+		Self::set_payee(stash, payee)
+	}
+
+	fn payee(who: &Self::AccountId) -> Option<Self::AccountId> {
+		PayeeMap::get().get(who).copied()
+	}
+
+	fn set_payee(who: &Self::AccountId, payee: &Self::AccountId) -> DispatchResult {
+		let mut x = PayeeMap::get();
+		x.insert(*who, *payee);
+		PayeeMap::set(&x);
 		Ok(())
 	}
 
+	fn max_nominations() -> u32 {
+		MaxNominations::get()
+	}
+
+	fn is_validator(who: &Self::AccountId) -> bool {
+		Validators::get().contains(who)
+	}
+
 	fn nominate(_: &Self::AccountId, nominations: Vec<Self::AccountId>) -> DispatchResult {
 		Nominations::set(&Some(nominations));
+		NominationsSubmittedIn::set(&CurrentEra::get());
 		Ok(())
 	}
 
+	fn nomination_targets(_: &Self::AccountId) -> Option<(Vec<(Self::AccountId, bool)>, EraIndex)> {
+		let stale = StaleTargets::get();
+		Nominations::get().map(|targets| {
+			let targets = targets.into_iter().map(|t| (t, stale.contains(&t))).collect();
+			(targets, NominationsSubmittedIn::get())
+		})
+	}
+
 	#[cfg(feature = "runtime-benchmarks")]
 	fn nominations(_: Self::AccountId) -> Option<Vec<Self::AccountId>> {
 		Nominations::get()
@@ -230,6 +269,12 @@ impl pools::Config for Runtime {
 	type PalletId = PoolsPalletId;
 	type MaxMetadataLen = MaxMetadataLen;
 	type MaxUnbonding = MaxUnbonding;
+	type MaxWithdrawBatch = frame_support::traits::ConstU32<4>;
+	type MinPayout = MinPayout;
+	type JoinRequestTimeout = frame_support::traits::ConstU64<10>;
+	type MaxJoinRequests = frame_support::traits::ConstU32<8>;
+	type MaxAliasLen = frame_support::traits::ConstU32<16>;
+	type AliasDeposit = AliasDeposit;
 	type MaxPointsToBalance = frame_support::traits::ConstU8<10>;
 }
 
@@ -249,13 +294,26 @@ frame_support::construct_runtime!(
 
 pub struct ExtBuilder {
 	members: Vec<(AccountId, Balance)>,
+	// the depositor, bond and members of each pool after the default one.
+	pools: Vec<(AccountId, Balance, Vec<(AccountId, Balance)>)>,
+	rewards: Vec<(PoolId, Balance)>,
+	unbonding: Vec<(EraIndex, AccountId, Balance)>,
+	era: Option<EraIndex>,
 	max_members: Option<u32>,
 	max_members_per_pool: Option<u32>,
 }
 
 impl Default for ExtBuilder {
 	fn default() -> Self {
-		Self { members: Default::default(), max_members: Some(4), max_members_per_pool: Some(3) }
+		Self {
+			members: Default::default(),
+			pools: Default::default(),
+			rewards: Default::default(),
+			unbonding: Default::default(),
+			era: None,
+			max_members: Some(4),
+			max_members_per_pool: Some(3),
+		}
 	}
 }
 
@@ -287,6 +345,39 @@ impl ExtBuilder {
 		self
 	}
 
+	// Create another pool, with the next pool id, bonded by `depositor` who also has all roles of
+	// it, and add `members` to it. At the fresh points to balance ratio of 1, the bond of each
+	// member is also its number of points.
+	pub fn add_pool(
+		mut self,
+		depositor: AccountId,
+		bond: Balance,
+		members: Vec<(AccountId, Balance)>,
+	) -> Self {
+		self.pools.push((depositor, bond, members));
+		self
+	}
+
+	// Have `member` unbond `points` in `era`, i.e. leave it with an unbonding chunk that can be
+	// withdrawn `BondingDuration` eras later.
+	pub fn add_unbonding(mut self, era: EraIndex, member: AccountId, points: Balance) -> Self {
+		self.unbonding.push((era, member, points));
+		self
+	}
+
+	// Pay `amount` to the reward account of `pool_id` after all members joined and unbonded, i.e.
+	// leave it as pending rewards of the members of the pool.
+	pub fn add_rewards(mut self, pool_id: PoolId, amount: Balance) -> Self {
+		self.rewards.push((pool_id, amount));
+		self
+	}
+
+	// The current era of staking once the ext is built. Must not be before any unbonding era.
+	pub fn era(mut self, era: EraIndex) -> Self {
+		self.era = Some(era);
+		self
+	}
+
 	pub fn max_members(mut self, max: Option<u32>) -> Self {
 		self.max_members = max;
 		self
@@ -305,7 +396,7 @@ impl ExtBuilder {
 		let _ = crate::GenesisConfig::<Runtime> {
 			min_join_bond: MinJoinBondConfig::get(),
 			min_create_bond: 2,
-			max_pools: Some(2),
+			max_pools: Some(2.max(1 + self.pools.len() as u32)),
 			max_members_per_pool: self.max_members_per_pool,
 			max_members: self.max_members,
 		}
@@ -327,6 +418,39 @@ impl ExtBuilder {
 				Balances::make_free_balance_be(&account_id, bonded * 2);
 				assert_ok!(Pools::join(RawOrigin::Signed(account_id).into(), bonded, last_pool));
 			}
+
+			for (depositor, bond, members) in self.pools {
+				Balances::make_free_balance_be(&depositor, bond * 2);
+				assert_ok!(Pools::create(
+					RawOrigin::Signed(depositor).into(),
+					bond,
+					depositor,
+					depositor,
+					depositor
+				));
+				let pool_id = LastPoolId::<Runtime>::get();
+				for (account_id, bonded) in members {
+					Balances::make_free_balance_be(&account_id, bonded * 2);
+					assert_ok!(Pools::join(RawOrigin::Signed(account_id).into(), bonded, pool_id));
+				}
+			}
+
+			let mut unbonding = self.unbonding;
+			unbonding.sort_by_key(|(era, _, _)| *era);
+			for (era, member, points) in unbonding {
+				CurrentEra::set(era);
+				assert_ok!(Pools::unbond(RawOrigin::Signed(member).into(), member, points));
+			}
+			if let Some(era) = self.era {
+				assert!(era >= CurrentEra::get(), "the era must not be before any unbonding");
+				CurrentEra::set(era);
+			}
+
+			for (pool_id, amount) in self.rewards {
+				assert_ok!(Balances::mutate_account(&Pools::create_reward_account(pool_id), |a| {
+					a.free += amount
+				}));
+			}
 		});
 
 		ext
@@ -340,6 +464,11 @@ impl ExtBuilder {
 	}
 }
 
+/// Move staking `eras` eras ahead.
+pub fn advance_era(eras: EraIndex) {
+	CurrentEra::set(CurrentEra::get() + eras);
+}
+
 pub fn unsafe_set_state(pool_id: PoolId, state: PoolState) {
 	BondedPools::<Runtime>::try_mutate(pool_id, |maybe_bonded_pool| {
 		maybe_bonded_pool.as_mut().ok_or(()).map(|bonded_pool| {
@@ -890,6 +890,39 @@ mod claim_payout {
 		});
 	}
 
+	#[test]
+	fn claim_payout_refunds_weight_if_there_is_nothing_to_pay_out() {
+		ExtBuilder::default().build_and_execute(|| {
+			// nothing to claim yet.
+			let post_info = Pools::claim_payout(RuntimeOrigin::signed(10)).unwrap();
+			assert_eq!(post_info.pays_fee, Pays::No);
+			assert_eq!(
+				post_info.actual_weight,
+				Some(<<Runtime as Config>::WeightInfo as WeightInfo>::claim_payout_nothing())
+			);
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Created { depositor: 10, pool_id: 1 },
+					Event::Bonded { member: 10, pool_id: 1, bonded: 10, joined: true },
+				]
+			);
+
+			// once there are rewards, the full weight of a claim is used.
+			assert_ok!(Balances::mutate_account(&default_reward_account(), |a| a.free += 10));
+			let post_info = Pools::claim_payout(RuntimeOrigin::signed(10)).unwrap();
+			assert_eq!(post_info.pays_fee, Pays::Yes);
+			assert_eq!(
+				post_info.actual_weight,
+				Some(<<Runtime as Config>::WeightInfo as WeightInfo>::claim_payout())
+			);
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![Event::PaidOut { member: 10, pool_id: 1, payout: 10 }]
+			);
+		});
+	}
+
 	#[test]
 	fn do_reward_payout_works_with_a_pool_of_1() {
 		let del = |last_recorded_reward_counter| del_float(10, last_recorded_reward_counter);
@@ -1832,7 +1865,7 @@ mod claim_payout {
 				assert_eq!(reward_pool.total_rewards_claimed, 30);
 				// since points change, these two are updated.
 				assert_eq!(reward_pool.last_recorded_total_payouts, 30);
-				assert_eq!(reward_pool.last_recorded_reward_counter, 1.into());
+				assert_eq!(reward_pool.last_recorded_reward_counter, 1u128.into());
 
 				assert_eq!(
 					pool_events_since_last_call(),
@@ -2200,6 +2233,64 @@ mod unbond {
 		})
 	}
 
+	#[test]
+	fn depositor_unbond_open_with_members() {
+		// depositor in pool with other members, pool state open
+		//   - depositor can unbond to above limit.
+		//   - depositor cannot unbond to below limit or 0, with a dedicated error.
+		//   - the dedicated error is specific to an open pool.
+		ExtBuilder::default()
+			.min_join_bond(10)
+			.add_members(vec![(20, 20)])
+			.build_and_execute(|| {
+				// give the depositor some extra funds.
+				assert_ok!(Pools::bond_extra(
+					RuntimeOrigin::signed(10),
+					BondExtra::FreeBalance(10)
+				));
+				assert_eq!(PoolMembers::<T>::get(10).unwrap().points, 20);
+
+				// can unbond to above the limit.
+				assert_ok!(Pools::unbond(RuntimeOrigin::signed(10), 10, 5));
+				assert_eq!(PoolMembers::<Runtime>::get(10).unwrap().active_points(), 15);
+				assert_eq!(PoolMembers::<Runtime>::get(10).unwrap().unbonding_points(), 5);
+
+				// cannot go to below 10:
+				assert_noop!(
+					Pools::unbond(RuntimeOrigin::signed(10), 10, 10),
+					Error::<T>::MinimumBondNotMetWhileOpen
+				);
+				assert_noop!(
+					Pools::unbond_balance(RuntimeOrigin::signed(10), 10, 10),
+					Error::<T>::MinimumBondNotMetWhileOpen
+				);
+
+				// cannot go to 0 either.
+				assert_noop!(
+					Pools::unbond(RuntimeOrigin::signed(10), 10, 15),
+					Error::<T>::MinimumBondNotMetWhileOpen
+				);
+
+				// other members still cannot unbond the depositor.
+				assert_noop!(
+					Pools::unbond(RuntimeOrigin::signed(20), 10, 15),
+					Error::<T>::DoesNotHavePermission
+				);
+
+				// once the pool is not open, the generic error applies.
+				unsafe_set_state(1, PoolState::Blocked);
+				assert_noop!(
+					Pools::unbond(RuntimeOrigin::signed(10), 10, 10),
+					Error::<T>::MinimumBondNotMet
+				);
+				unsafe_set_state(1, PoolState::Destroying);
+				assert_noop!(
+					Pools::unbond(RuntimeOrigin::signed(10), 10, 15),
+					Error::<T>::MinimumBondNotMet
+				);
+			})
+	}
+
 	#[test]
 	fn depositor_kick() {
 		// depositor in pool, pool state blocked
@@ -2492,8 +2583,10 @@ mod unbond {
 					pool_events_since_last_call(),
 					vec![
 						Event::Withdrawn { member: 40, pool_id: 1, points: 6, balance: 6 },
+						Event::ActedOnBehalf { pool_id: 1, caller: 10, member: 40 },
 						Event::MemberRemoved { pool_id: 1, member: 40 },
 						Event::Withdrawn { member: 550, pool_id: 1, points: 92, balance: 92 },
+						Event::ActedOnBehalf { pool_id: 1, caller: 10, member: 550 },
 						Event::MemberRemoved { pool_id: 1, member: 550 },
 						Event::PaidOut { member: 10, pool_id: 1, payout: 10 },
 						Event::Unbonded { member: 10, pool_id: 1, points: 2, balance: 2, era: 6 }
@@ -2548,6 +2641,47 @@ mod unbond {
 		});
 	}
 
+	#[test]
+	fn sub_pools_stay_bounded_regardless_of_chain_age() {
+		ExtBuilder::default()
+			.add_members(vec![(20, 200), (30, 30)])
+			.build_and_execute(|| {
+				let bonding_duration = StakingMock::bonding_duration();
+
+				// 30 unbonds right away, but only withdraws much later.
+				assert_ok!(fully_unbond_permissioned(30));
+
+				for era in 1..100 {
+					CurrentEra::set(era);
+
+					// 20 unbonds a bit in every era, and withdraws whatever got unlocked.
+					assert_ok!(Pools::unbond(RuntimeOrigin::signed(20), 20, 1));
+					if era > bonding_duration {
+						assert_ok!(Pools::withdraw_unbonded(RuntimeOrigin::signed(20), 20, 0));
+					}
+
+					// no matter how old the chain gets, the sub pools never outgrow their bound.
+					let sub_pools = SubPoolsStorage::<Runtime>::get(1).unwrap();
+					assert!(
+						sub_pools.with_era.len() as u32 <= TotalUnbondingPools::<Runtime>::get()
+					);
+					assert!(
+						PoolMembers::<Runtime>::get(20).unwrap().unbonding_eras.len() as u32 <=
+							bonding_duration + 1
+					);
+				}
+
+				// the funds of 30 have long been merged into the no era pool, and are still intact.
+				assert_eq!(
+					SubPoolsStorage::<Runtime>::get(1).unwrap().no_era,
+					UnbondPool { points: 30, balance: 30 }
+				);
+				assert_ok!(Pools::withdraw_unbonded(RuntimeOrigin::signed(30), 30, 0));
+				assert_eq!(Balances::free_balance(30), 30 * 2);
+				assert!(!PoolMembers::<Runtime>::contains_key(30));
+			});
+	}
+
 	#[test]
 	fn unbond_kick_works() {
 		// Kick: the pool is blocked and the caller is either the root or state-toggler.
@@ -2584,6 +2718,7 @@ mod unbond {
 							balance: 100,
 							era: 3
 						},
+						Event::ActedOnBehalf { pool_id: 1, caller: 900, member: 100 },
 					]
 				);
 
@@ -2592,13 +2727,16 @@ mod unbond {
 
 				assert_eq!(
 					pool_events_since_last_call(),
-					vec![Event::Unbonded {
-						member: 200,
-						pool_id: 1,
-						points: 200,
-						balance: 200,
-						era: 3
-					}]
+					vec![
+						Event::Unbonded {
+							member: 200,
+							pool_id: 1,
+							points: 200,
+							balance: 200,
+							era: 3
+						},
+						Event::ActedOnBehalf { pool_id: 1, caller: 902, member: 200 },
+					]
 				);
 
 				assert_eq!(
@@ -2667,7 +2805,8 @@ mod unbond {
 					Event::Created { depositor: 10, pool_id: 1 },
 					Event::Bonded { member: 10, pool_id: 1, bonded: 10, joined: true },
 					Event::Bonded { member: 100, pool_id: 1, bonded: 100, joined: true },
-					Event::Unbonded { member: 100, pool_id: 1, points: 100, balance: 100, era: 3 }
+					Event::Unbonded { member: 100, pool_id: 1, points: 100, balance: 100, era: 3 },
+					Event::ActedOnBehalf { pool_id: 1, caller: 420, member: 100 }
 				]
 			);
 
@@ -3084,7 +3223,7 @@ mod pool_withdraw_unbonded {
 			// Given 10 unbond'ed directly against the pool account
 			assert_ok!(StakingMock::unbond(&default_bonded_account(), 5));
 			// and the pool account only has 10 balance
-			assert_eq!(StakingMock::active_stake(&default_bonded_account()), Ok(5));
+			assert_eq!(StakingMock::active_stake(&default_bonded_account()).unwrap(), 5);
 			assert_eq!(StakingMock::total_stake(&default_bonded_account()), Ok(10));
 			assert_eq!(Balances::free_balance(&default_bonded_account()), 10);
 
@@ -3092,7 +3231,7 @@ mod pool_withdraw_unbonded {
 			assert_ok!(Pools::pool_withdraw_unbonded(RuntimeOrigin::signed(10), 1, 0));
 
 			// Then there unbonding balance is no longer locked
-			assert_eq!(StakingMock::active_stake(&default_bonded_account()), Ok(5));
+			assert_eq!(StakingMock::active_stake(&default_bonded_account()).unwrap(), 5);
 			assert_eq!(StakingMock::total_stake(&default_bonded_account()), Ok(5));
 			assert_eq!(Balances::free_balance(&default_bonded_account()), 10);
 		});
@@ -3530,8 +3669,10 @@ mod withdraw_unbonded {
 					pool_events_since_last_call(),
 					vec![
 						Event::Withdrawn { member: 100, pool_id: 1, points: 100, balance: 100 },
+						Event::ActedOnBehalf { pool_id: 1, caller: 900, member: 100 },
 						Event::MemberRemoved { pool_id: 1, member: 100 },
 						Event::Withdrawn { member: 200, pool_id: 1, points: 200, balance: 200 },
+						Event::ActedOnBehalf { pool_id: 1, caller: 900, member: 200 },
 						Event::MemberRemoved { pool_id: 1, member: 200 }
 					]
 				);
@@ -3581,6 +3722,7 @@ mod withdraw_unbonded {
 					Event::Bonded { member: 100, pool_id: 1, bonded: 100, joined: true },
 					Event::Unbonded { member: 100, pool_id: 1, points: 100, balance: 100, era: 3 },
 					Event::Withdrawn { member: 100, pool_id: 1, points: 100, balance: 100 },
+					Event::ActedOnBehalf { pool_id: 1, caller: 420, member: 100 },
 					Event::MemberRemoved { pool_id: 1, member: 100 }
 				]
 			);
@@ -4273,6 +4415,68 @@ mod nominate {
 			);
 		});
 	}
+
+	#[test]
+	fn nominate_normalizes_targets() {
+		ExtBuilder::default().build_and_execute(|| {
+			// targets are sorted and deduplicated
+			assert_ok!(Pools::nominate(RuntimeOrigin::signed(901), 1, vec![31, 21, 23, 21, 31]));
+			assert_eq!(Nominations::get().unwrap(), vec![21, 23, 31]);
+
+			// the bound applies after deduplication
+			MaxNominations::set(2);
+			assert_ok!(Pools::nominate(RuntimeOrigin::signed(901), 1, vec![22, 21, 22, 21]));
+			assert_eq!(Nominations::get().unwrap(), vec![21, 22]);
+			assert_noop!(
+				Pools::nominate(RuntimeOrigin::signed(901), 1, vec![21, 22, 23]),
+				Error::<Runtime>::TooManyTargets
+			);
+		});
+	}
+
+	#[test]
+	fn pool_nominations_works() {
+		ExtBuilder::default().build_and_execute(|| {
+			// the pool does not nominate yet.
+			assert_eq!(Pools::pool_nominations(1), None);
+
+			CurrentEra::set(2);
+			assert_ok!(Pools::nominate(RuntimeOrigin::signed(901), 1, vec![31, 21]));
+			assert_eq!(Pools::pool_nominations(1), Some((vec![(21, false), (31, false)], 2)));
+
+			// the era of the nomination stays as the eras pass.
+			CurrentEra::set(5);
+			assert_eq!(Pools::pool_nominations(1), Some((vec![(21, false), (31, false)], 2)));
+
+			// the staking system reports which of the targets are stale.
+			StaleTargets::set(vec![31]);
+			assert_eq!(Pools::pool_nominations(1), Some((vec![(21, false), (31, true)], 2)));
+
+			// no nominations for a pool that doesn't exist.
+			assert_eq!(Pools::pool_nominations(123), None);
+		});
+	}
+
+	#[test]
+	fn nominate_reports_invalid_targets() {
+		ExtBuilder::default().build_and_execute(|| {
+			// sorted, the targets are [3, 21, 30, 31], so #0 and #2 are not validators.
+			assert_noop!(
+				Pools::nominate(RuntimeOrigin::signed(901), 1, vec![31, 30, 21, 3]),
+				Error::<Runtime>::InvalidTargets { first: 0, count: 2 }
+			);
+
+			// targets beyond the first 24 are reported as well.
+			MaxNominations::set(40);
+			let mut targets = (100..130).collect::<Vec<_>>();
+			Validators::set(targets.clone());
+			targets.extend([200, 201]);
+			assert_noop!(
+				Pools::nominate(RuntimeOrigin::signed(901), 1, targets),
+				Error::<Runtime>::InvalidTargets { first: 30, count: 2 }
+			);
+		});
+	}
 }
 
 mod set_state {
@@ -4411,12 +4615,14 @@ mod set_configs {
 				ConfigOp::Set(3u32),
 				ConfigOp::Set(4u32),
 				ConfigOp::Set(5u32),
+				ConfigOp::Set(6 as Balance),
 			));
 			assert_eq!(MinJoinBond::<Runtime>::get(), 1);
 			assert_eq!(MinCreateBond::<Runtime>::get(), 2);
 			assert_eq!(MaxPools::<Runtime>::get(), Some(3));
 			assert_eq!(MaxPoolMembers::<Runtime>::get(), Some(4));
 			assert_eq!(MaxPoolMembersPerPool::<Runtime>::get(), Some(5));
+			assert_eq!(LiquidationThreshold::<Runtime>::get(), Some(6));
 
 			// Noop does nothing
 			assert_storage_noop!(assert_ok!(Pools::set_configs(
@@ -4426,6 +4632,7 @@ mod set_configs {
 				ConfigOp::Noop,
 				ConfigOp::Noop,
 				ConfigOp::Noop,
+				ConfigOp::Noop,
 			)));
 
 			// Removing works
@@ -4436,12 +4643,14 @@ mod set_configs {
 				ConfigOp::Remove,
 				ConfigOp::Remove,
 				ConfigOp::Remove,
+				ConfigOp::Remove,
 			));
 			assert_eq!(MinJoinBond::<Runtime>::get(), 0);
 			assert_eq!(MinCreateBond::<Runtime>::get(), 0);
 			assert_eq!(MaxPools::<Runtime>::get(), None);
 			assert_eq!(MaxPoolMembers::<Runtime>::get(), None);
 			assert_eq!(MaxPoolMembersPerPool::<Runtime>::get(), None);
+			assert_eq!(LiquidationThreshold::<Runtime>::get(), None);
 		});
 	}
 }
@@ -4756,141 +4965,1028 @@ mod update_roles {
 	}
 }
 
-mod reward_counter_precision {
-	use sp_runtime::FixedU128;
-
+mod set_member_cap {
 	use super::*;
 
-	const DOT: Balance = 10u128.pow(10u32);
-	const POLKADOT_TOTAL_ISSUANCE_GENESIS: Balance = DOT * 10u128.pow(9u32);
+	#[test]
+	fn set_member_cap_works() {
+		ExtBuilder::default().build_and_execute(|| {
+			assert_eq!(Pools::member_cap(1), Some(3));
 
-	const fn inflation(years: u128) -> u128 {
-		let mut i = 0;
-		let mut start = POLKADOT_TOTAL_ISSUANCE_GENESIS;
-		while i < years {
-			start = start + start / 10;
-			i += 1
-		}
-		start
-	}
+			// only the root can set the cap.
+			assert_noop!(
+				Pools::set_member_cap(RuntimeOrigin::signed(902), 1, Some(2)),
+				Error::<Runtime>::DoesNotHavePermission
+			);
+			assert_noop!(
+				Pools::set_member_cap(RuntimeOrigin::signed(900), 2, Some(2)),
+				Error::<Runtime>::PoolNotFound
+			);
 
-	fn default_pool_reward_counter() -> FixedU128 {
-		RewardPools::<T>::get(1)
-			.unwrap()
-			.current_reward_counter(1, BondedPools::<T>::get(1).unwrap().points)
-			.unwrap()
-	}
+			// the cap can never be higher than `MaxPoolMembersPerPool`.
+			assert_noop!(
+				Pools::set_member_cap(RuntimeOrigin::signed(900), 1, Some(4)),
+				Error::<Runtime>::MemberCapTooHigh
+			);
 
-	fn pending_rewards(of: AccountId) -> Option<BalanceOf<T>> {
-		let member = PoolMembers::<T>::get(of).unwrap();
-		assert_eq!(member.pool_id, 1);
-		let rc = default_pool_reward_counter();
-		member.pending_rewards(rc).ok()
-	}
+			assert_ok!(Pools::set_member_cap(RuntimeOrigin::signed(900), 1, Some(2)));
+			assert_eq!(PoolMemberCap::<Runtime>::get(1), Some(2));
+			assert_eq!(Pools::member_cap(1), Some(2));
+
+			assert_ok!(Pools::set_member_cap(RuntimeOrigin::signed(900), 1, None));
+			assert_eq!(PoolMemberCap::<Runtime>::get(1), None);
+			assert_eq!(Pools::member_cap(1), Some(3));
 
-	#[test]
-	fn smallest_claimable_reward() {
-		// create a pool that has all of the polkadot issuance in 50 years.
-		let pool_bond = inflation(50);
-		ExtBuilder::default().ed(DOT).min_bond(pool_bond).build_and_execute(|| {
 			assert_eq!(
 				pool_events_since_last_call(),
 				vec![
 					Event::Created { depositor: 10, pool_id: 1 },
-					Event::Bonded {
-						member: 10,
-						pool_id: 1,
-						bonded: 1173908528796953165005,
-						joined: true,
-					}
+					Event::Bonded { member: 10, pool_id: 1, bonded: 10, joined: true },
+					Event::MemberCapUpdated { pool_id: 1, cap: Some(2) },
+					Event::MemberCapUpdated { pool_id: 1, cap: None },
 				]
 			);
-
-			// the smallest reward that this pool can handle is
-			let expected_smallest_reward = inflation(50) / 10u128.pow(18);
-
-			// tad bit less. cannot be paid out.
-			assert_ok!(Balances::mutate_account(&default_reward_account(), |a| a.free +=
-				expected_smallest_reward - 1));
-			assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(10)));
-			assert_eq!(pool_events_since_last_call(), vec![]);
-			// revert it.
-
-			assert_ok!(Balances::mutate_account(&default_reward_account(), |a| a.free -=
-				expected_smallest_reward - 1));
-
-			// tad bit more. can be claimed.
-			assert_ok!(Balances::mutate_account(&default_reward_account(), |a| a.free +=
-				expected_smallest_reward + 1));
-			assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(10)));
-			assert_eq!(
-				pool_events_since_last_call(),
-				vec![Event::PaidOut { member: 10, pool_id: 1, payout: 1173 }]
-			);
-		})
+		});
 	}
 
 	#[test]
-	fn massive_reward_in_small_pool() {
-		let tiny_bond = 1000 * DOT;
-		ExtBuilder::default().ed(DOT).min_bond(tiny_bond).build_and_execute(|| {
-			assert_eq!(
-				pool_events_since_last_call(),
-				vec![
-					Event::Created { depositor: 10, pool_id: 1 },
-					Event::Bonded { member: 10, pool_id: 1, bonded: 10000000000000, joined: true }
-				]
-			);
+	fn member_cap_is_enforced_on_join() {
+		ExtBuilder::default().build_and_execute(|| {
+			assert_ok!(Pools::set_member_cap(RuntimeOrigin::signed(900), 1, Some(2)));
 
-			Balances::make_free_balance_be(&20, tiny_bond);
-			assert_ok!(Pools::join(RuntimeOrigin::signed(20), tiny_bond / 2, 1));
+			Balances::make_free_balance_be(&11, ExistentialDeposit::get() + 2);
+			assert_ok!(Pools::join(RuntimeOrigin::signed(11), 2, 1));
 
-			// Suddenly, add a shit ton of rewards.
-			assert_ok!(
-				Balances::mutate_account(&default_reward_account(), |a| a.free += inflation(1))
+			// the pool is now full, even though `MaxPoolMembersPerPool` is not reached.
+			Balances::make_free_balance_be(&12, ExistentialDeposit::get() + 2);
+			assert_noop!(
+				Pools::join(RuntimeOrigin::signed(12), 2, 1),
+				Error::<Runtime>::MaxPoolMembers
 			);
 
-			// now claim.
-			assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(10)));
-			assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(20)));
+			// lowering the cap below the current members is allowed, and removes no one.
+			assert_ok!(Pools::set_member_cap(RuntimeOrigin::signed(900), 1, Some(1)));
+			assert_eq!(BondedPool::<Runtime>::get(1).unwrap().member_counter, 2);
 
-			assert_eq!(
-				pool_events_since_last_call(),
-				vec![
-					Event::Bonded { member: 20, pool_id: 1, bonded: 5000000000000, joined: true },
-					Event::PaidOut { member: 10, pool_id: 1, payout: 7333333333333333333 },
-					Event::PaidOut { member: 20, pool_id: 1, payout: 3666666666666666666 }
-				]
-			);
-		})
+			// removing the cap makes room again.
+			assert_ok!(Pools::set_member_cap(RuntimeOrigin::signed(900), 1, None));
+			assert_ok!(Pools::join(RuntimeOrigin::signed(12), 2, 1));
+		});
 	}
 
 	#[test]
-	fn reward_counter_calc_wont_fail_in_normal_polkadot_future() {
-		// create a pool that has roughly half of the polkadot issuance in 10 years.
-		let pool_bond = inflation(10) / 2;
-		ExtBuilder::default().ed(DOT).min_bond(pool_bond).build_and_execute(|| {
-			assert_eq!(
-				pool_events_since_last_call(),
-				vec![
-					Event::Created { depositor: 10, pool_id: 1 },
-					Event::Bonded {
-						member: 10,
-						pool_id: 1,
-						bonded: 12_968_712_300_500_000_000,
-						joined: true,
-					}
-				]
-			);
-
-			// in 10 years, the total claimed rewards are large values as well. assuming that a pool
-			// is earning all of the inflation per year (which is really unrealistic, but worse
-			// case), that will be:
-			let pool_total_earnings_10_years = inflation(10) - POLKADOT_TOTAL_ISSUANCE_GENESIS;
-			assert_ok!(Balances::mutate_account(&default_reward_account(), |a| a.free +=
-				pool_total_earnings_10_years));
+	fn member_cap_is_removed_with_the_pool() {
+		ExtBuilder::default().build_and_execute(|| {
+			assert_ok!(Pools::set_member_cap(RuntimeOrigin::signed(900), 1, Some(2)));
 
-			// some whale now joins with the other half ot the total issuance. This will bloat all
+			// the depositor leaves, and the pool is destroyed.
+			assert_ok!(Pools::set_state(RuntimeOrigin::signed(902), 1, PoolState::Destroying));
+			assert_ok!(fully_unbond_permissioned(10));
+			CurrentEra::set(3);
+			assert_ok!(Pools::withdraw_unbonded(RuntimeOrigin::signed(10), 10, 10));
+
+			assert!(!BondedPools::<Runtime>::contains_key(1));
+			assert!(!PoolMemberCap::<Runtime>::contains_key(1));
+		});
+	}
+}
+
+mod withdraw_unbonded_many {
+	use super::*;
+
+	#[test]
+	fn withdraw_unbonded_many_works() {
+		ExtBuilder::default()
+			.add_members(vec![(100, 100), (200, 200)])
+			.build_and_execute(|| {
+				// 100 unbonds at era 0, 200 at era 1.
+				assert_ok!(Pools::fully_unbond(RuntimeOrigin::signed(100), 100));
+				CurrentEra::set(1);
+				assert_ok!(Pools::fully_unbond(RuntimeOrigin::signed(200), 200));
+				CurrentEra::set(StakingMock::bonding_duration());
+				unsafe_set_state(1, PoolState::Destroying);
+
+				// When
+				let members = vec![100, 200, 999].try_into().unwrap();
+				let post_info =
+					Pools::withdraw_unbonded_many(RuntimeOrigin::signed(420), members, 0).unwrap();
+
+				// Then only 100 is withdrawn, the others are skipped.
+				assert!(!PoolMembers::<Runtime>::contains_key(100));
+				assert_eq!(Balances::free_balance(100), 100 + 100);
+				assert_eq!(PoolMembers::<Runtime>::get(200).unwrap().unbonding_points(), 200);
+				assert_eq!(Balances::free_balance(200), 200);
+				assert_eq!(BondedPool::<Runtime>::get(1).unwrap().member_counter, 2);
+				assert_eq!(
+					post_info.actual_weight,
+					Some(<() as WeightInfo>::withdraw_unbonded_update(0).saturating_mul(3))
+				);
+				assert_eq!(
+					pool_events_since_last_call(),
+					vec![
+						Event::Created { depositor: 10, pool_id: 1 },
+						Event::Bonded { member: 10, pool_id: 1, bonded: 10, joined: true },
+						Event::Bonded { member: 100, pool_id: 1, bonded: 100, joined: true },
+						Event::Bonded { member: 200, pool_id: 1, bonded: 200, joined: true },
+						Event::Unbonded {
+							member: 100,
+							pool_id: 1,
+							points: 100,
+							balance: 100,
+							era: 3
+						},
+						Event::Unbonded {
+							member: 200,
+							pool_id: 1,
+							points: 200,
+							balance: 200,
+							era: 4
+						},
+						Event::Withdrawn { member: 100, pool_id: 1, points: 100, balance: 100 },
+						Event::ActedOnBehalf { pool_id: 1, caller: 420, member: 100 },
+						Event::MemberRemoved { pool_id: 1, member: 100 },
+						Event::WithdrawSkipped {
+							member: 200,
+							error: Error::<Runtime>::CannotWithdrawAny.into()
+						},
+						Event::WithdrawSkipped {
+							member: 999,
+							error: Error::<Runtime>::PoolMemberNotFound.into()
+						},
+					]
+				);
+
+				// 200 can be withdrawn once their era is reached.
+				CurrentEra::set(4);
+				let members = vec![200].try_into().unwrap();
+				assert_ok!(Pools::withdraw_unbonded_many(RuntimeOrigin::signed(420), members, 0));
+				assert!(!PoolMembers::<Runtime>::contains_key(200));
+				assert_eq!(Balances::free_balance(200), 200 + 200);
+			});
+	}
+
+	#[test]
+	fn withdraw_unbonded_many_respects_permissions() {
+		ExtBuilder::default().add_members(vec![(100, 100)]).build_and_execute(|| {
+			assert_ok!(Pools::fully_unbond(RuntimeOrigin::signed(100), 100));
+			CurrentEra::set(StakingMock::bonding_duration());
+
+			// the pool is open, thus only 100 can withdraw themselves.
+			let members = vec![100].try_into().unwrap();
+			assert_ok!(Pools::withdraw_unbonded_many(RuntimeOrigin::signed(420), members, 0));
+			assert!(PoolMembers::<Runtime>::contains_key(100));
+			assert_eq!(
+				pool_events_since_last_call().last(),
+				Some(&Event::WithdrawSkipped {
+					member: 100,
+					error: Error::<Runtime>::NotKickerOrDestroying.into()
+				})
+			);
+
+			let members = vec![100].try_into().unwrap();
+			assert_ok!(Pools::withdraw_unbonded_many(RuntimeOrigin::signed(100), members, 0));
+			assert!(!PoolMembers::<Runtime>::contains_key(100));
+		});
+	}
+}
+
+mod fix_payee {
+	use super::*;
+
+	#[test]
+	fn fix_payee_works() {
+		ExtBuilder::default().build_and_execute(|| {
+			// the rewards of a new pool are forwarded to its reward account.
+			assert_eq!(
+				StakingMock::payee(&default_bonded_account()),
+				Some(default_reward_account())
+			);
+			assert_noop!(
+				Pools::fix_payee(RuntimeOrigin::signed(420), 1),
+				Error::<Runtime>::PayeeAlreadySet
+			);
+			assert_noop!(
+				Pools::fix_payee(RuntimeOrigin::signed(420), 2),
+				Error::<Runtime>::PoolNotFound
+			);
+
+			// Given a pool whose rewards are forwarded elsewhere.
+			assert_ok!(StakingMock::set_payee(&default_bonded_account(), &42));
+
+			// When
+			assert_ok!(Pools::fix_payee(RuntimeOrigin::signed(420), 1));
+
+			// Then
+			assert_eq!(
+				StakingMock::payee(&default_bonded_account()),
+				Some(default_reward_account())
+			);
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Created { depositor: 10, pool_id: 1 },
+					Event::Bonded { member: 10, pool_id: 1, bonded: 10, joined: true },
+					Event::PayeeFixed { pool_id: 1 },
+				]
+			);
+		});
+	}
+}
+
+mod dust_payout {
+	use super::*;
+
+	#[test]
+	fn dust_rewards_accumulate_by_default() {
+		ExtBuilder::default().build_and_execute(|| {
+			MinPayout::set(5);
+			let _ = pool_events_since_last_call();
+
+			// Given rewards below `MinPayout`.
+			assert_ok!(Balances::mutate_account(&default_reward_account(), |a| a.free += 4));
+
+			// When
+			let post_info = Pools::claim_payout(RuntimeOrigin::signed(10)).unwrap();
+
+			// Then nothing is paid out, and the call is free.
+			assert_eq!(post_info.pays_fee, Pays::No);
+			assert_eq!(
+				post_info.actual_weight,
+				Some(<<Runtime as Config>::WeightInfo as WeightInfo>::claim_payout_nothing())
+			);
+			assert_eq!(
+				PoolMembers::<Runtime>::get(10).unwrap().last_recorded_reward_counter,
+				0.into()
+			);
+			assert_eq!(pool_events_since_last_call(), vec![]);
+
+			// Given enough accumulated rewards.
+			assert_ok!(Balances::mutate_account(&default_reward_account(), |a| a.free += 2));
+
+			// When
+			let post_info = Pools::claim_payout(RuntimeOrigin::signed(10)).unwrap();
+
+			// Then all of them are paid out.
+			assert_eq!(post_info.pays_fee, Pays::Yes);
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![Event::PaidOut { member: 10, pool_id: 1, payout: 6 }]
+			);
+		});
+	}
+
+	#[test]
+	fn dust_rewards_can_be_compounded() {
+		ExtBuilder::default().build_and_execute(|| {
+			MinPayout::set(5);
+			assert_noop!(
+				Pools::set_dust_payout(RuntimeOrigin::signed(11), DustPayout::Compound),
+				Error::<Runtime>::PoolMemberNotFound
+			);
+			assert_ok!(Pools::set_dust_payout(RuntimeOrigin::signed(10), DustPayout::Compound));
+			assert_eq!(DustPayouts::<Runtime>::get(10), DustPayout::Compound);
+
+			// Given rewards below `MinPayout`.
+			assert_ok!(Balances::mutate_account(&default_reward_account(), |a| a.free += 4));
+
+			// When
+			assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(10)));
+
+			// Then they are bonded into the pool.
+			assert_eq!(PoolMembers::<Runtime>::get(10).unwrap().active_points(), 14);
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Created { depositor: 10, pool_id: 1 },
+					Event::Bonded { member: 10, pool_id: 1, bonded: 10, joined: true },
+					Event::DustPayoutUpdated { member: 10, dust_payout: DustPayout::Compound },
+					Event::PaidOut { member: 10, pool_id: 1, payout: 4 },
+					Event::Bonded { member: 10, pool_id: 1, bonded: 4, joined: false },
+				]
+			);
+
+			// going back to the default removes the preference.
+			assert_ok!(Pools::set_dust_payout(RuntimeOrigin::signed(10), DustPayout::Accumulate));
+			assert!(!DustPayouts::<Runtime>::contains_key(10));
+		});
+	}
+}
+
+mod join_permissioned {
+	use super::*;
+
+	#[test]
+	fn approved_request_joins_blocked_pool() {
+		ExtBuilder::default().build_and_execute(|| {
+			let escrow = Pools::create_escrow_account(1);
+			unsafe_set_state(1, PoolState::Blocked);
+			Balances::make_free_balance_be(&11, ExistentialDeposit::get() + 20);
+			assert_noop!(Pools::join(RuntimeOrigin::signed(11), 20, 1), Error::<Runtime>::NotOpen);
+
+			// Given
+			assert_ok!(Pools::join_permissioned(RuntimeOrigin::signed(11), 20, 1));
+			assert_eq!(Balances::free_balance(&11), ExistentialDeposit::get());
+			assert_eq!(Balances::free_balance(&escrow), 20);
+			assert_eq!(
+				JoinRequests::<Runtime>::get(1, 11),
+				Some(JoinRequest { amount: 20, expires_at: 11 })
+			);
+			assert_noop!(
+				Pools::join_permissioned(RuntimeOrigin::signed(11), 20, 1),
+				Error::<Runtime>::JoinRequestExists
+			);
+
+			// only the root and the state toggler can approve.
+			assert_noop!(
+				Pools::approve_join(RuntimeOrigin::signed(901), 1, 11),
+				Error::<Runtime>::DoesNotHavePermission
+			);
+
+			// When
+			assert_ok!(Pools::approve_join(RuntimeOrigin::signed(902), 1, 11));
+
+			// Then
+			assert_eq!(PoolMembers::<Runtime>::get(11).unwrap().active_points(), 20);
+			assert!(!JoinRequests::<Runtime>::contains_key(1, 11));
+			assert_eq!(Balances::free_balance(&11), ExistentialDeposit::get());
+			assert_eq!(Balances::free_balance(&escrow), 0);
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Created { depositor: 10, pool_id: 1 },
+					Event::Bonded { member: 10, pool_id: 1, bonded: 10, joined: true },
+					Event::JoinRequested { member: 11, pool_id: 1, amount: 20, expires_at: 11 },
+					Event::JoinApproved { member: 11, pool_id: 1 },
+					Event::Bonded { member: 11, pool_id: 1, bonded: 20, joined: true },
+				]
+			);
+		});
+	}
+
+	#[test]
+	fn rejected_or_expired_requests_are_refunded() {
+		ExtBuilder::default().build_and_execute(|| {
+			let escrow = Pools::create_escrow_account(1);
+			Balances::make_free_balance_be(&11, ExistentialDeposit::get() + 20);
+			Balances::make_free_balance_be(&12, ExistentialDeposit::get() + 20);
+			assert_ok!(Pools::join_permissioned(RuntimeOrigin::signed(11), 20, 1));
+			assert_ok!(Pools::join_permissioned(RuntimeOrigin::signed(12), 20, 1));
+			let _ = pool_events_since_last_call();
+
+			// a request can only be rejected by the root or the state toggler before it expires.
+			assert_noop!(
+				Pools::reject_join(RuntimeOrigin::signed(11), 1, 11),
+				Error::<Runtime>::DoesNotHavePermission
+			);
+			assert_ok!(Pools::reject_join(RuntimeOrigin::signed(900), 1, 11));
+			assert_eq!(Balances::free_balance(&11), ExistentialDeposit::get() + 20);
+			assert_eq!(Balances::free_balance(&escrow), 20);
+
+			// once expired, it can no longer be approved, but refunded by anyone.
+			System::set_block_number(11);
+			assert_noop!(
+				Pools::approve_join(RuntimeOrigin::signed(902), 1, 12),
+				Error::<Runtime>::JoinRequestExpired
+			);
+			assert_ok!(Pools::reject_join(RuntimeOrigin::signed(13), 1, 12));
+			assert_eq!(Balances::free_balance(&12), ExistentialDeposit::get() + 20);
+			assert_eq!(Balances::free_balance(&escrow), 0);
+			assert_noop!(
+				Pools::reject_join(RuntimeOrigin::signed(13), 1, 12),
+				Error::<Runtime>::JoinRequestNotFound
+			);
+
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::JoinRejected { member: 11, pool_id: 1, expired: false },
+					Event::JoinRejected { member: 12, pool_id: 1, expired: true },
+				]
+			);
+		});
+	}
+
+	#[test]
+	fn expired_requests_are_swept_on_idle() {
+		ExtBuilder::default().build_and_execute(|| {
+			let escrow = Pools::create_escrow_account(1);
+			for who in 11..14 {
+				Balances::make_free_balance_be(&who, ExistentialDeposit::get() + 20);
+				assert_ok!(Pools::join_permissioned(RuntimeOrigin::signed(who), 20, 1));
+			}
+			let _ = pool_events_since_last_call();
+
+			// nothing has expired yet.
+			Pools::on_idle(10, Weight::MAX);
+			assert_eq!(JoinRequestCount::<Runtime>::get(1), 3);
+			assert_eq!(JoinRequestSweepCursor::<Runtime>::get(), None);
+
+			// with room for a single refund, the sweep resumes in the next block.
+			let refund = <<Runtime as Config>::WeightInfo as WeightInfo>::reject_join();
+			assert_eq!(Pools::sweep_join_requests(11, Weight::zero()), Weight::zero());
+			assert_eq!(Pools::sweep_join_requests(11, refund), refund);
+			assert_eq!(JoinRequestCount::<Runtime>::get(1), 2);
+			assert!(JoinRequestSweepCursor::<Runtime>::get().is_some());
+			assert_eq!(
+				Pools::sweep_join_requests(12, refund.saturating_mul(10)),
+				refund.saturating_mul(2)
+			);
+			assert_eq!(JoinRequestSweepCursor::<Runtime>::get(), None);
+
+			assert_eq!(JoinRequests::<Runtime>::iter().count(), 0);
+			assert!(!JoinRequestCount::<Runtime>::contains_key(1));
+			assert_eq!(Balances::free_balance(&escrow), 0);
+			for who in 11..14 {
+				assert_eq!(Balances::free_balance(&who), ExistentialDeposit::get() + 20);
+			}
+			assert_eq!(pool_events_since_last_call().len(), 3);
+		});
+	}
+
+	#[test]
+	fn requests_are_refunded_when_the_pool_is_dissolved() {
+		ExtBuilder::default().build_and_execute(|| {
+			Balances::make_free_balance_be(&11, ExistentialDeposit::get() + 20);
+			assert_ok!(Pools::join_permissioned(RuntimeOrigin::signed(11), 20, 1));
+			let _ = pool_events_since_last_call();
+
+			// When
+			unsafe_set_state(1, PoolState::Destroying);
+			assert_ok!(fully_unbond_permissioned(10));
+			CurrentEra::set(CurrentEra::get() + 3);
+			assert_ok!(Pools::withdraw_unbonded(RuntimeOrigin::signed(10), 10, 0));
+
+			// Then
+			assert!(!JoinRequests::<Runtime>::contains_key(1, 11));
+			assert!(!JoinRequestCount::<Runtime>::contains_key(1));
+			assert_eq!(Balances::free_balance(&11), ExistentialDeposit::get() + 20);
+			assert_eq!(Balances::free_balance(&Pools::create_escrow_account(1)), 0);
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Unbonded { member: 10, pool_id: 1, points: 10, balance: 10, era: 3 },
+					Event::Withdrawn { member: 10, pool_id: 1, points: 10, balance: 10 },
+					Event::MemberRemoved { pool_id: 1, member: 10 },
+					Event::JoinRejected { member: 11, pool_id: 1, expired: false },
+					Event::Destroyed { pool_id: 1 },
+				]
+			);
+		});
+	}
+
+	#[test]
+	fn join_requests_are_bounded() {
+		ExtBuilder::default().build_and_execute(|| {
+			let max = <<Runtime as Config>::MaxJoinRequests as Get<u32>>::get();
+			for who in 100..100 + max as AccountId {
+				Balances::make_free_balance_be(&who, ExistentialDeposit::get() + 20);
+				assert_ok!(Pools::join_permissioned(RuntimeOrigin::signed(who), 20, 1));
+			}
+			assert_eq!(JoinRequestCount::<Runtime>::get(1), max);
+
+			Balances::make_free_balance_be(&11, ExistentialDeposit::get() + 20);
+			assert_noop!(
+				Pools::join_permissioned(RuntimeOrigin::signed(11), 20, 1),
+				Error::<Runtime>::TooManyJoinRequests
+			);
+
+			// a rejection makes room for another request.
+			assert_ok!(Pools::reject_join(RuntimeOrigin::signed(900), 1, 100));
+			assert_ok!(Pools::join_permissioned(RuntimeOrigin::signed(11), 20, 1));
+		});
+	}
+
+	#[test]
+	fn join_permissioned_errors_correctly() {
+		ExtBuilder::default().build_and_execute(|| {
+			Balances::make_free_balance_be(&11, ExistentialDeposit::get() + 20);
+
+			assert_noop!(
+				Pools::join_permissioned(RuntimeOrigin::signed(10), 20, 1),
+				Error::<Runtime>::AccountBelongsToOtherPool
+			);
+			assert_noop!(
+				Pools::join_permissioned(RuntimeOrigin::signed(11), 1, 1),
+				Error::<Runtime>::MinimumBondNotMet
+			);
+			assert_noop!(
+				Pools::join_permissioned(RuntimeOrigin::signed(11), 20, 123),
+				Error::<Runtime>::PoolNotFound
+			);
+
+			unsafe_set_state(1, PoolState::Destroying);
+			assert_noop!(
+				Pools::join_permissioned(RuntimeOrigin::signed(11), 20, 1),
+				Error::<Runtime>::CanNotChangeState
+			);
+		});
+	}
+}
+
+mod alias {
+	use super::*;
+
+	fn alias(alias: &[u8]) -> PoolAliasOf<Runtime> {
+		alias.to_vec().try_into().unwrap()
+	}
+
+	#[test]
+	fn alias_can_be_set_by_root_or_pool_root() {
+		ExtBuilder::default().build_and_execute(|| {
+			let escrow = Pools::create_escrow_account(1);
+			Balances::make_free_balance_be(&900, ExistentialDeposit::get() + AliasDeposit::get());
+
+			// the pool root pays a deposit.
+			assert_ok!(Pools::set_alias(RuntimeOrigin::signed(900), 1, alias(b"pool-1")));
+			assert_eq!(Pools::pool_by_alias(alias(b"pool-1")), Some(1));
+			assert_eq!(Balances::free_balance(&900), ExistentialDeposit::get());
+			assert_eq!(Balances::free_balance(&escrow), AliasDeposit::get());
+
+			// root does not, and replacing the alias refunds the previous deposit.
+			assert_ok!(Pools::set_alias(RuntimeOrigin::root(), 1, alias(b"one")));
+			assert_eq!(Pools::pool_by_alias(alias(b"pool-1")), None);
+			assert_eq!(Pools::pool_by_alias(alias(b"one")), Some(1));
+			assert_eq!(
+				Balances::free_balance(&900),
+				ExistentialDeposit::get() + AliasDeposit::get()
+			);
+			assert_eq!(Balances::free_balance(&escrow), 0);
+
+			assert_ok!(Pools::clear_alias(RuntimeOrigin::signed(900), 1));
+			assert_eq!(Pools::pool_by_alias(alias(b"one")), None);
+			assert!(!PoolAliases::<Runtime>::contains_key(1));
+
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Created { depositor: 10, pool_id: 1 },
+					Event::Bonded { member: 10, pool_id: 1, bonded: 10, joined: true },
+					Event::AliasSet { pool_id: 1, alias: alias(b"pool-1") },
+					Event::AliasSet { pool_id: 1, alias: alias(b"one") },
+					Event::AliasCleared { pool_id: 1 },
+				]
+			);
+		});
+	}
+
+	#[test]
+	fn alias_is_removed_with_the_pool() {
+		ExtBuilder::default().build_and_execute(|| {
+			Balances::make_free_balance_be(&900, ExistentialDeposit::get() + AliasDeposit::get());
+			assert_ok!(Pools::set_alias(RuntimeOrigin::signed(900), 1, alias(b"pool-1")));
+
+			// when the pool is destroyed.
+			unsafe_set_state(1, PoolState::Destroying);
+			assert_ok!(fully_unbond_permissioned(10));
+			CurrentEra::set(3);
+			assert_ok!(Pools::withdraw_unbonded(RuntimeOrigin::signed(10), 10, 0));
+			assert!(!BondedPools::<Runtime>::contains_key(1));
+
+			// then its alias is free again, and the deposit is refunded.
+			assert_eq!(Pools::pool_by_alias(alias(b"pool-1")), None);
+			assert!(!PoolAliases::<Runtime>::contains_key(1));
+			assert_eq!(
+				Balances::free_balance(&900),
+				ExistentialDeposit::get() + AliasDeposit::get()
+			);
+		});
+	}
+
+	#[test]
+	fn alias_is_kept_if_its_deposit_cannot_be_refunded() {
+		ExtBuilder::default().build_and_execute(|| {
+			Balances::make_free_balance_be(&900, ExistentialDeposit::get() + AliasDeposit::get());
+			assert_ok!(Pools::set_alias(RuntimeOrigin::signed(900), 1, alias(b"pool-1")));
+
+			// Given an escrow account that lost its funds.
+			let escrow = Pools::create_escrow_account(1);
+			Balances::make_free_balance_be(&escrow, 0);
+
+			// Then the alias cannot be cleared, and both of its entries remain.
+			assert_noop!(
+				Pools::clear_alias(RuntimeOrigin::signed(900), 1),
+				pallet_balances::Error::<Runtime>::InsufficientBalance
+			);
+			assert_eq!(Pools::pool_by_alias(alias(b"pool-1")), Some(1));
+
+			// Until the funds are back.
+			Balances::make_free_balance_be(&escrow, AliasDeposit::get());
+			assert_ok!(Pools::clear_alias(RuntimeOrigin::signed(900), 1));
+			assert_eq!(Pools::pool_by_alias(alias(b"pool-1")), None);
+			assert_eq!(
+				Balances::free_balance(&900),
+				ExistentialDeposit::get() + AliasDeposit::get()
+			);
+		});
+	}
+
+	#[test]
+	fn set_alias_errors_correctly() {
+		ExtBuilder::default().build_and_execute(|| {
+			assert_noop!(
+				Pools::set_alias(RuntimeOrigin::signed(901), 1, alias(b"pool-1")),
+				Error::<Runtime>::DoesNotHavePermission
+			);
+			assert_noop!(
+				Pools::set_alias(RuntimeOrigin::root(), 123, alias(b"pool-1")),
+				Error::<Runtime>::PoolNotFound
+			);
+			for invalid in [&b""[..], b"Pool-1", b"pool 1", b"pool_1"] {
+				assert_noop!(
+					Pools::set_alias(RuntimeOrigin::root(), 1, alias(invalid)),
+					Error::<Runtime>::InvalidAlias
+				);
+			}
+			// longer aliases cannot even be decoded.
+			assert!(PoolAliasOf::<Runtime>::try_from(vec![b'a'; 17]).is_err());
+
+			assert_ok!(Pools::set_alias(RuntimeOrigin::root(), 1, alias(&[b'a'; 16])));
+			assert_noop!(
+				Pools::set_alias(RuntimeOrigin::root(), 1, alias(&[b'a'; 16])),
+				Error::<Runtime>::AliasTaken
+			);
+
+			assert_noop!(
+				Pools::clear_alias(RuntimeOrigin::signed(901), 1),
+				Error::<Runtime>::DoesNotHavePermission
+			);
+			assert_ok!(Pools::clear_alias(RuntimeOrigin::root(), 1));
+			assert_noop!(
+				Pools::clear_alias(RuntimeOrigin::root(), 1),
+				Error::<Runtime>::AliasNotFound
+			);
+		});
+	}
+}
+
+mod unbond_balance {
+	use super::*;
+
+	#[test]
+	fn unbond_balance_converts_at_the_pool_rate() {
+		ExtBuilder::default().add_members(vec![(20, 20)]).build_and_execute(|| {
+			// 30 points are now backed by 20 balance.
+			StakingMock::set_bonded_balance(default_bonded_account(), 20);
+
+			// 3 balance is worth 4.5 points, which is rounded down.
+			assert_ok!(Pools::unbond_balance(RuntimeOrigin::signed(20), 20, 3));
+			assert_eq!(PoolMembers::<Runtime>::get(20).unwrap().active_points(), 16);
+			assert_eq!(StakingMock::active_stake(&default_bonded_account()).unwrap(), 18);
+
+			// more than the active balance unbonds all points.
+			assert_ok!(Pools::unbond_balance(RuntimeOrigin::signed(20), 20, 100));
+			assert_eq!(PoolMembers::<Runtime>::get(20).unwrap().active_points(), 0);
+			assert_eq!(StakingMock::active_stake(&default_bonded_account()).unwrap(), 7);
+
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Created { depositor: 10, pool_id: 1 },
+					Event::Bonded { member: 10, pool_id: 1, bonded: 10, joined: true },
+					Event::Bonded { member: 20, pool_id: 1, bonded: 20, joined: true },
+					Event::UnbondRequested { member: 20, pool_id: 1, amount: 3, points: 4 },
+					Event::Unbonded { member: 20, pool_id: 1, points: 2, balance: 2, era: 3 },
+					Event::UnbondRequested { member: 20, pool_id: 1, amount: 100, points: 16 },
+					Event::Unbonded { member: 20, pool_id: 1, points: 11, balance: 11, era: 3 },
+				]
+			);
+		});
+	}
+
+	#[test]
+	fn unbond_balance_errors_correctly() {
+		ExtBuilder::default()
+			.min_join_bond(10)
+			.add_members(vec![(20, 20)])
+			.build_and_execute(|| {
+				assert_noop!(
+					Pools::unbond_balance(RuntimeOrigin::signed(21), 21, 5),
+					Error::<Runtime>::PoolMemberNotFound
+				);
+				assert_noop!(
+					Pools::unbond_balance(RuntimeOrigin::signed(20), 20, 0),
+					Error::<Runtime>::UnbondAmountTooLow
+				);
+				// the remaining 5 would be below the minimum join bond.
+				assert_noop!(
+					Pools::unbond_balance(RuntimeOrigin::signed(20), 20, 15),
+					Error::<Runtime>::MinimumBondNotMet
+				);
+				assert_noop!(
+					Pools::unbond_balance(RuntimeOrigin::signed(123), 20, 5),
+					Error::<Runtime>::PartialUnbondNotAllowedPermissionlessly
+				);
+
+				assert_ok!(Pools::unbond_balance(RuntimeOrigin::signed(20), 20, 20));
+				assert_noop!(
+					Pools::unbond_balance(RuntimeOrigin::signed(20), 20, 5),
+					Error::<Runtime>::FullyUnbonding
+				);
+			});
+	}
+}
+
+mod credit_reward {
+	use super::*;
+
+	#[test]
+	fn credited_reward_is_shared_among_current_members() {
+		ExtBuilder::default().add_members(vec![(20, 20)]).build_and_execute(|| {
+			Balances::make_free_balance_be(&100, 100);
+
+			assert_ok!(<Pools as PoolRewardCredit<_, _>>::credit_reward(&100, 1, 30));
+			assert_eq!(Balances::free_balance(&100), 70);
+			// the reward is recorded right away.
+			let reward_pool = RewardPools::<Runtime>::get(1).unwrap();
+			assert_eq!(reward_pool.last_recorded_reward_counter, 1u128.into());
+			assert_eq!(reward_pool.last_recorded_total_payouts, 30);
+
+			// a member that joins afterwards does not get any of it.
+			Balances::make_free_balance_be(&30, 100);
+			assert_ok!(Pools::join(RuntimeOrigin::signed(30), 30, 1));
+			assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(10)));
+			assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(20)));
+			assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(30)));
+
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Created { depositor: 10, pool_id: 1 },
+					Event::Bonded { member: 10, pool_id: 1, bonded: 10, joined: true },
+					Event::Bonded { member: 20, pool_id: 1, bonded: 20, joined: true },
+					Event::RewardCredited { pool_id: 1, source: 100, amount: 30 },
+					Event::Bonded { member: 30, pool_id: 1, bonded: 30, joined: true },
+					Event::PaidOut { member: 10, pool_id: 1, payout: 10 },
+					Event::PaidOut { member: 20, pool_id: 1, payout: 20 },
+				]
+			);
+		});
+	}
+
+	#[test]
+	fn credit_reward_errors_correctly() {
+		ExtBuilder::default().build_and_execute(|| {
+			Balances::make_free_balance_be(&100, 100);
+
+			assert_noop!(
+				<Pools as PoolRewardCredit<_, _>>::credit_reward(&100, 123, 30),
+				Error::<Runtime>::PoolNotFound
+			);
+			assert_noop!(
+				<Pools as PoolRewardCredit<_, _>>::credit_reward(&100, 1, 300),
+				pallet_balances::Error::<Runtime>::InsufficientBalance
+			);
+
+			// once the pool has no points left, nobody could ever claim the reward.
+			unsafe_set_state(1, PoolState::Destroying);
+			assert_ok!(fully_unbond_permissioned(10));
+			assert_noop!(
+				<Pools as PoolRewardCredit<_, _>>::credit_reward(&100, 1, 30),
+				Error::<Runtime>::NoPointsToReward
+			);
+		});
+	}
+}
+
+mod liquidate {
+	use super::*;
+
+	#[test]
+	fn liquidate_requires_stake_below_threshold() {
+		ExtBuilder::default().build_and_execute(|| {
+			// liquidation is disabled by default.
+			assert_noop!(
+				Pools::liquidate(RuntimeOrigin::signed(550), 1),
+				Error::<Runtime>::NotLiquidatable
+			);
+
+			// the pool has 10 active stake, which is not below the threshold.
+			LiquidationThreshold::<Runtime>::put(10);
+			assert_noop!(
+				Pools::liquidate(RuntimeOrigin::signed(550), 1),
+				Error::<Runtime>::NotLiquidatable
+			);
+
+			assert_noop!(
+				Pools::liquidate(RuntimeOrigin::signed(550), 2),
+				Error::<Runtime>::PoolNotFound
+			);
+
+			// once the threshold is above the active stake, anyone can liquidate.
+			LiquidationThreshold::<Runtime>::put(11);
+			assert_ok!(Pools::liquidate(RuntimeOrigin::signed(550), 1));
+			assert_noop!(
+				Pools::liquidate(RuntimeOrigin::signed(550), 1),
+				Error::<Runtime>::AlreadyLiquidated
+			);
+		})
+	}
+
+	#[test]
+	fn liquidate_works() {
+		ExtBuilder::default().add_members(vec![(20, 20)]).build_and_execute(|| {
+			// given a pool that got slashed from 30 to 6.
+			StakingMock::set_bonded_balance(default_bonded_account(), 6);
+			LiquidationThreshold::<Runtime>::put(
+				ExistentialDeposit::get() + StakingMock::minimum_nominator_bond(),
+			);
+
+			// when
+			assert_ok!(Pools::liquidate(RuntimeOrigin::signed(550), 1));
+
+			// then
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Created { depositor: 10, pool_id: 1 },
+					Event::Bonded { member: 10, pool_id: 1, bonded: 10, joined: true },
+					Event::Bonded { member: 20, pool_id: 1, bonded: 20, joined: true },
+					Event::StateChanged { pool_id: 1, new_state: PoolState::Destroying },
+					Event::Liquidated { pool_id: 1, balance: 6, era: 3 },
+				]
+			);
+			assert_eq!(StakingMock::active_stake(&default_bonded_account()).unwrap(), 0);
+			assert_eq!(
+				LiquidatedPools::<Runtime>::get(1).unwrap(),
+				Liquidation { era: 3, bonded_points: 30, unbonding_points: 6 }
+			);
+			assert_eq!(
+				SubPoolsStorage::<Runtime>::get(1).unwrap().with_era,
+				unbonding_pools_with_era! { 3 => UnbondPool { points: 6, balance: 6 } }
+			);
+
+			// members can be unbonded permissionlessly, and receive their share of the
+			// liquidated stake in the era of the liquidation, even if they unbond later.
+			CurrentEra::set(1);
+			assert_ok!(Pools::fully_unbond(RuntimeOrigin::signed(550), 20));
+			assert_eq!(
+				PoolMembers::<Runtime>::get(20).unwrap().unbonding_eras,
+				member_unbonding_eras!(3 => 4)
+			);
+			assert_eq!(
+				LiquidatedPools::<Runtime>::get(1).unwrap(),
+				Liquidation { era: 3, bonded_points: 10, unbonding_points: 2 }
+			);
+
+			CurrentEra::set(3);
+			assert_ok!(Pools::withdraw_unbonded(RuntimeOrigin::signed(550), 20, 0));
+			assert_ok!(fully_unbond_permissioned(10));
+			assert_ok!(Pools::withdraw_unbonded(RuntimeOrigin::signed(10), 10, 0));
+
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Unbonded { member: 20, pool_id: 1, balance: 4, points: 4, era: 3 },
+					Event::ActedOnBehalf { pool_id: 1, caller: 550, member: 20 },
+					Event::Withdrawn { member: 20, pool_id: 1, balance: 4, points: 4 },
+					Event::ActedOnBehalf { pool_id: 1, caller: 550, member: 20 },
+					Event::MemberRemoved { pool_id: 1, member: 20 },
+					Event::Unbonded { member: 10, pool_id: 1, balance: 2, points: 2, era: 3 },
+					Event::Withdrawn { member: 10, pool_id: 1, balance: 2, points: 2 },
+					Event::MemberRemoved { pool_id: 1, member: 10 },
+					Event::Destroyed { pool_id: 1 }
+				]
+			);
+			assert!(!LiquidatedPools::<Runtime>::contains_key(1));
+		})
+	}
+}
+
+mod reward_counter_precision {
+	use sp_runtime::FixedU128;
+
+	use super::*;
+
+	const DOT: Balance = 10u128.pow(10u32);
+	const POLKADOT_TOTAL_ISSUANCE_GENESIS: Balance = DOT * 10u128.pow(9u32);
+
+	const fn inflation(years: u128) -> u128 {
+		let mut i = 0;
+		let mut start = POLKADOT_TOTAL_ISSUANCE_GENESIS;
+		while i < years {
+			start = start + start / 10;
+			i += 1
+		}
+		start
+	}
+
+	fn default_pool_reward_counter() -> FixedU128 {
+		RewardPools::<T>::get(1)
+			.unwrap()
+			.current_reward_counter(1, BondedPools::<T>::get(1).unwrap().points)
+			.unwrap()
+	}
+
+	fn pending_rewards(of: AccountId) -> Option<BalanceOf<T>> {
+		let member = PoolMembers::<T>::get(of).unwrap();
+		assert_eq!(member.pool_id, 1);
+		let rc = default_pool_reward_counter();
+		member.pending_rewards(rc).ok()
+	}
+
+	#[test]
+	fn smallest_claimable_reward() {
+		// create a pool that has all of the polkadot issuance in 50 years.
+		let pool_bond = inflation(50);
+		ExtBuilder::default().ed(DOT).min_bond(pool_bond).build_and_execute(|| {
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Created { depositor: 10, pool_id: 1 },
+					Event::Bonded {
+						member: 10,
+						pool_id: 1,
+						bonded: 1173908528796953165005,
+						joined: true,
+					}
+				]
+			);
+
+			// the smallest reward that this pool can handle is
+			let expected_smallest_reward = inflation(50) / 10u128.pow(18);
+
+			// tad bit less. cannot be paid out.
+			assert_ok!(Balances::mutate_account(&default_reward_account(), |a| a.free +=
+				expected_smallest_reward - 1));
+			assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(10)));
+			assert_eq!(pool_events_since_last_call(), vec![]);
+			// revert it.
+
+			assert_ok!(Balances::mutate_account(&default_reward_account(), |a| a.free -=
+				expected_smallest_reward - 1));
+
+			// tad bit more. can be claimed.
+			assert_ok!(Balances::mutate_account(&default_reward_account(), |a| a.free +=
+				expected_smallest_reward + 1));
+			assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(10)));
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![Event::PaidOut { member: 10, pool_id: 1, payout: 1173 }]
+			);
+		})
+	}
+
+	#[test]
+	fn massive_reward_in_small_pool() {
+		let tiny_bond = 1000 * DOT;
+		ExtBuilder::default().ed(DOT).min_bond(tiny_bond).build_and_execute(|| {
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Created { depositor: 10, pool_id: 1 },
+					Event::Bonded { member: 10, pool_id: 1, bonded: 10000000000000, joined: true }
+				]
+			);
+
+			Balances::make_free_balance_be(&20, tiny_bond);
+			assert_ok!(Pools::join(RuntimeOrigin::signed(20), tiny_bond / 2, 1));
+
+			// Suddenly, add a shit ton of rewards.
+			assert_ok!(
+				Balances::mutate_account(&default_reward_account(), |a| a.free += inflation(1))
+			);
+
+			// now claim.
+			assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(10)));
+			assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(20)));
+
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Bonded { member: 20, pool_id: 1, bonded: 5000000000000, joined: true },
+					Event::PaidOut { member: 10, pool_id: 1, payout: 7333333333333333333 },
+					Event::PaidOut { member: 20, pool_id: 1, payout: 3666666666666666666 }
+				]
+			);
+		})
+	}
+
+	#[test]
+	fn reward_counter_calc_wont_fail_in_normal_polkadot_future() {
+		// create a pool that has roughly half of the polkadot issuance in 10 years.
+		let pool_bond = inflation(10) / 2;
+		ExtBuilder::default().ed(DOT).min_bond(pool_bond).build_and_execute(|| {
+			assert_eq!(
+				pool_events_since_last_call(),
+				vec![
+					Event::Created { depositor: 10, pool_id: 1 },
+					Event::Bonded {
+						member: 10,
+						pool_id: 1,
+						bonded: 12_968_712_300_500_000_000,
+						joined: true,
+					}
+				]
+			);
+
+			// in 10 years, the total claimed rewards are large values as well. assuming that a pool
+			// is earning all of the inflation per year (which is really unrealistic, but worse
+			// case), that will be:
+			let pool_total_earnings_10_years = inflation(10) - POLKADOT_TOTAL_ISSUANCE_GENESIS;
+			assert_ok!(Balances::mutate_account(&default_reward_account(), |a| a.free +=
+				pool_total_earnings_10_years));
+
+			// some whale now joins with the other half ot the total issuance. This will bloat all
 			// the calculation regarding current reward counter.
 			Balances::make_free_balance_be(&20, pool_bond * 2);
 			assert_ok!(Pools::join(RuntimeOrigin::signed(20), pool_bond, 1));
@@ -5100,3 +6196,71 @@ mod reward_counter_precision {
 			});
 	}
 }
+
+mod ext_builder {
+	use super::*;
+
+	#[test]
+	fn pools_members_unbonding_and_rewards_are_set_up() {
+		ExtBuilder::default()
+			.max_members(None)
+			.max_members_per_pool(None)
+			.add_members(vec![(20, 20)])
+			.add_pool(30, 30, vec![(40, 40)])
+			.add_unbonding(2, 40, 10)
+			.add_unbonding(1, 20, 10)
+			.add_rewards(1, 40)
+			.add_rewards(2, 60)
+			.era(3)
+			.build_and_execute(|| {
+				assert_eq!(
+					pool_events_since_last_call(),
+					vec![
+						Event::Created { depositor: 10, pool_id: 1 },
+						Event::Bonded { member: 10, pool_id: 1, bonded: 10, joined: true },
+						Event::Bonded { member: 20, pool_id: 1, bonded: 20, joined: true },
+						Event::Created { depositor: 30, pool_id: 2 },
+						Event::Bonded { member: 30, pool_id: 2, bonded: 30, joined: true },
+						Event::Bonded { member: 40, pool_id: 2, bonded: 40, joined: true },
+						Event::Unbonded { member: 20, pool_id: 1, balance: 10, points: 10, era: 4 },
+						Event::Unbonded { member: 40, pool_id: 2, balance: 10, points: 10, era: 5 },
+					]
+				);
+				assert_eq!(CurrentEra::get(), 3);
+
+				assert_eq!(BondedPool::<Runtime>::get(2).unwrap().points, 60);
+				let member = PoolMembers::<Runtime>::get(40).unwrap();
+				assert_eq!(member.points, 30);
+				assert_eq!(member.unbonding_eras, bounded_btree_map!(5 => 10));
+
+				// the rewards are pending, pro rata of the bonded points.
+				assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(20)));
+				assert_ok!(Pools::claim_payout(RuntimeOrigin::signed(40)));
+				assert_eq!(
+					pool_events_since_last_call(),
+					vec![
+						Event::PaidOut { member: 20, pool_id: 1, payout: 20 },
+						Event::PaidOut { member: 40, pool_id: 2, payout: 30 },
+					]
+				);
+			});
+	}
+
+	#[test]
+	fn unbonding_chunks_are_withdrawn_once_the_era_is_advanced() {
+		ExtBuilder::default()
+			.add_members(vec![(20, 20)])
+			.add_unbonding(0, 20, 20)
+			.build_and_execute(|| {
+				assert_noop!(
+					Pools::withdraw_unbonded(RuntimeOrigin::signed(20), 20, 0),
+					Error::<Runtime>::CannotWithdrawAny
+				);
+
+				advance_era(BondingDuration::get());
+				assert_ok!(Pools::withdraw_unbonded(RuntimeOrigin::signed(20), 20, 0));
+				assert!(!PoolMembers::<Runtime>::contains_key(20));
+				assert_eq!(Balances::free_balance(&20), 40);
+			});
+	}
+}
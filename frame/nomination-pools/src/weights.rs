@@ -51,10 +51,12 @@ pub trait WeightInfo {
 	fn bond_extra_transfer() -> Weight;
 	fn bond_extra_reward() -> Weight;
 	fn claim_payout() -> Weight;
+	fn claim_payout_nothing() -> Weight;
 	fn unbond() -> Weight;
 	fn pool_withdraw_unbonded(s: u32, ) -> Weight;
 	fn withdraw_unbonded_update(s: u32, ) -> Weight;
 	fn withdraw_unbonded_kill(s: u32, ) -> Weight;
+	fn withdraw_unbonded_many(n: u32, s: u32, ) -> Weight;
 	fn create() -> Weight;
 	fn nominate(n: u32, ) -> Weight;
 	fn set_state() -> Weight;
@@ -62,6 +64,16 @@ pub trait WeightInfo {
 	fn set_configs() -> Weight;
 	fn update_roles() -> Weight;
 	fn chill() -> Weight;
+	fn liquidate() -> Weight;
+	fn set_member_cap() -> Weight;
+	fn fix_payee() -> Weight;
+	fn set_dust_payout() -> Weight;
+	fn join_permissioned() -> Weight;
+	fn approve_join() -> Weight;
+	fn reject_join() -> Weight;
+	fn set_alias() -> Weight;
+	fn clear_alias() -> Weight;
+	fn unbond_balance() -> Weight;
 }
 
 /// Weights for pallet_nomination_pools using the Substrate node and recommended hardware.
@@ -76,14 +88,16 @@ impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
 	// Storage: System Account (r:2 w:1)
 	// Storage: NominationPools MaxPoolMembersPerPool (r:1 w:0)
 	// Storage: NominationPools MaxPoolMembers (r:1 w:0)
+	// Storage: NominationPools PoolMemberCap (r:1 w:0)
 	// Storage: NominationPools CounterForPoolMembers (r:1 w:1)
 	// Storage: Balances Locks (r:1 w:1)
 	// Storage: VoterList ListNodes (r:3 w:3)
 	// Storage: VoterList ListBags (r:2 w:2)
 	fn join() -> Weight {
+		// Not benchmarked yet, the measured weight plus the read of `PoolMemberCap`; regenerate with `benchmark pallet`.
 		// Minimum execution time: 159_948 nanoseconds.
 		Weight::from_ref_time(161_133_000 as u64)
-			.saturating_add(T::DbWeight::get().reads(17 as u64))
+			.saturating_add(T::DbWeight::get().reads(18 as u64))
 			.saturating_add(T::DbWeight::get().writes(12 as u64))
 	}
 	// Storage: NominationPools PoolMembers (r:1 w:1)
@@ -126,6 +140,15 @@ impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
 			.saturating_add(T::DbWeight::get().reads(4 as u64))
 			.saturating_add(T::DbWeight::get().writes(4 as u64))
 	}
+	// Storage: NominationPools PoolMembers (r:1 w:0)
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: NominationPools RewardPools (r:1 w:0)
+	// Storage: System Account (r:1 w:0)
+	fn claim_payout_nothing() -> Weight {
+		// Not benchmarked yet, the time is that of `claim_payout`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(64_950_000 as u64)
+			.saturating_add(T::DbWeight::get().reads(4 as u64))
+	}
 	// Storage: NominationPools PoolMembers (r:1 w:1)
 	// Storage: NominationPools BondedPools (r:1 w:1)
 	// Storage: NominationPools RewardPools (r:1 w:1)
@@ -207,6 +230,12 @@ impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
 			.saturating_add(T::DbWeight::get().reads(20 as u64))
 			.saturating_add(T::DbWeight::get().writes(17 as u64))
 	}
+	/// The range of component `n` is `[1, 32]`.
+	/// The range of component `s` is `[0, 100]`.
+	fn withdraw_unbonded_many(n: u32, s: u32, ) -> Weight {
+		// Not benchmarked yet, `n` times `withdraw_unbonded_kill`; regenerate with `benchmark pallet`.
+		Self::withdraw_unbonded_kill(s).saturating_mul(n as u64)
+	}
 	// Storage: NominationPools LastPoolId (r:1 w:1)
 	// Storage: Staking MinNominatorBond (r:1 w:0)
 	// Storage: NominationPools MinCreateBond (r:1 w:0)
@@ -282,10 +311,12 @@ impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
 	// Storage: NominationPools MaxPoolMembersPerPool (r:0 w:1)
 	// Storage: NominationPools MinCreateBond (r:0 w:1)
 	// Storage: NominationPools MaxPools (r:0 w:1)
+	// Storage: NominationPools LiquidationThreshold (r:0 w:1)
 	fn set_configs() -> Weight {
+		// Not benchmarked yet, the measured weight plus the write of `LiquidationThreshold`; regenerate with `benchmark pallet`.
 		// Minimum execution time: 9_231 nanoseconds.
 		Weight::from_ref_time(9_526_000 as u64)
-			.saturating_add(T::DbWeight::get().writes(5 as u64))
+			.saturating_add(T::DbWeight::get().writes(6 as u64))
 	}
 	// Storage: NominationPools BondedPools (r:1 w:1)
 	fn update_roles() -> Weight {
@@ -309,6 +340,137 @@ impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
 			.saturating_add(T::DbWeight::get().reads(9 as u64))
 			.saturating_add(T::DbWeight::get().writes(5 as u64))
 	}
+	// Storage: NominationPools BondedPools (r:1 w:1)
+	// Storage: NominationPools LiquidatedPools (r:1 w:1)
+	// Storage: NominationPools LiquidationThreshold (r:1 w:0)
+	// Storage: Staking Bonded (r:1 w:0)
+	// Storage: Staking Ledger (r:1 w:1)
+	// Storage: Staking Validators (r:1 w:0)
+	// Storage: Staking Nominators (r:1 w:1)
+	// Storage: Staking CounterForNominators (r:1 w:1)
+	// Storage: Staking CurrentEra (r:1 w:0)
+	// Storage: Balances Locks (r:1 w:1)
+	// Storage: VoterList ListNodes (r:1 w:1)
+	// Storage: VoterList ListBags (r:1 w:1)
+	// Storage: VoterList CounterForListNodes (r:1 w:1)
+	// Storage: NominationPools SubPoolsStorage (r:1 w:1)
+	// Storage: NominationPools CounterForSubPoolsStorage (r:1 w:1)
+	fn liquidate() -> Weight {
+		// Not benchmarked yet, the time is that of `unbond`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(162_991_000 as u64)
+			.saturating_add(T::DbWeight::get().reads(15 as u64))
+			.saturating_add(T::DbWeight::get().writes(12 as u64))
+	}
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: NominationPools MaxPoolMembersPerPool (r:1 w:0)
+	// Storage: NominationPools PoolMemberCap (r:0 w:1)
+	fn set_member_cap() -> Weight {
+		// Not benchmarked yet, the time is that of `set_state`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(46_689_000 as u64)
+			.saturating_add(T::DbWeight::get().reads(2 as u64))
+			.saturating_add(T::DbWeight::get().writes(1 as u64))
+	}
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: Staking Bonded (r:1 w:0)
+	// Storage: Staking Payee (r:1 w:1)
+	// Storage: Staking Ledger (r:1 w:0)
+	fn fix_payee() -> Weight {
+		// Not benchmarked yet, the time is that of `set_state`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(46_689_000 as u64)
+			.saturating_add(T::DbWeight::get().reads(4 as u64))
+			.saturating_add(T::DbWeight::get().writes(1 as u64))
+	}
+	// Storage: NominationPools PoolMembers (r:1 w:0)
+	// Storage: NominationPools DustPayouts (r:0 w:1)
+	fn set_dust_payout() -> Weight {
+		// Not benchmarked yet, the time is that of `update_roles`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(31_762_000 as u64)
+			.saturating_add(T::DbWeight::get().reads(1 as u64))
+			.saturating_add(T::DbWeight::get().writes(1 as u64))
+	}
+	// Storage: NominationPools MinJoinBond (r:1 w:0)
+	// Storage: NominationPools PoolMembers (r:1 w:0)
+	// Storage: NominationPools JoinRequests (r:1 w:1)
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: Staking Bonded (r:1 w:0)
+	// Storage: Staking Ledger (r:1 w:0)
+	// Storage: System Account (r:2 w:2)
+	fn join_permissioned() -> Weight {
+		// Not benchmarked yet, the time is that of `claim_payout`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(64_950_000 as u64)
+			.saturating_add(T::DbWeight::get().reads(8 as u64))
+			.saturating_add(T::DbWeight::get().writes(3 as u64))
+	}
+	// Storage: NominationPools BondedPools (r:1 w:1)
+	// Storage: NominationPools PoolMembers (r:1 w:1)
+	// Storage: Staking Bonded (r:1 w:0)
+	// Storage: Staking Ledger (r:1 w:1)
+	// Storage: NominationPools JoinRequests (r:1 w:1)
+	// Storage: System Account (r:3 w:3)
+	// Storage: NominationPools RewardPools (r:1 w:1)
+	// Storage: NominationPools MaxPoolMembersPerPool (r:1 w:0)
+	// Storage: NominationPools MaxPoolMembers (r:1 w:0)
+	// Storage: NominationPools PoolMemberCap (r:1 w:0)
+	// Storage: NominationPools CounterForPoolMembers (r:1 w:1)
+	// Storage: Balances Locks (r:1 w:1)
+	// Storage: VoterList ListNodes (r:3 w:3)
+	// Storage: VoterList ListBags (r:2 w:2)
+	fn approve_join() -> Weight {
+		// Not benchmarked yet, the time is that of `join` plus `claim_payout`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(226_083_000 as u64)
+			.saturating_add(T::DbWeight::get().reads(19 as u64))
+			.saturating_add(T::DbWeight::get().writes(15 as u64))
+	}
+	// Storage: NominationPools JoinRequests (r:1 w:1)
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: System Account (r:2 w:2)
+	fn reject_join() -> Weight {
+		// Not benchmarked yet, the time is that of `claim_payout`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(64_950_000 as u64)
+			.saturating_add(T::DbWeight::get().reads(4 as u64))
+			.saturating_add(T::DbWeight::get().writes(3 as u64))
+	}
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: NominationPools PoolIdByAlias (r:2 w:2)
+	// Storage: NominationPools PoolAliases (r:1 w:1)
+	// Storage: System Account (r:3 w:3)
+	fn set_alias() -> Weight {
+		// Not benchmarked yet, the time is that of `claim_payout`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(64_950_000 as u64)
+			.saturating_add(T::DbWeight::get().reads(7 as u64))
+			.saturating_add(T::DbWeight::get().writes(6 as u64))
+	}
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: NominationPools PoolAliases (r:1 w:1)
+	// Storage: NominationPools PoolIdByAlias (r:0 w:1)
+	// Storage: System Account (r:2 w:2)
+	fn clear_alias() -> Weight {
+		// Not benchmarked yet, the time is that of `claim_payout`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(64_950_000 as u64)
+			.saturating_add(T::DbWeight::get().reads(4 as u64))
+			.saturating_add(T::DbWeight::get().writes(4 as u64))
+	}
+	// Storage: NominationPools PoolMembers (r:1 w:1)
+	// Storage: NominationPools BondedPools (r:1 w:1)
+	// Storage: NominationPools RewardPools (r:1 w:1)
+	// Storage: NominationPools LiquidatedPools (r:1 w:0)
+	// Storage: Staking Bonded (r:1 w:0)
+	// Storage: Staking Ledger (r:1 w:1)
+	// Storage: System Account (r:2 w:1)
+	// Storage: Staking CurrentEra (r:1 w:0)
+	// Storage: Staking Nominators (r:1 w:0)
+	// Storage: Staking MinNominatorBond (r:1 w:0)
+	// Storage: Balances Locks (r:1 w:1)
+	// Storage: VoterList ListNodes (r:3 w:3)
+	// Storage: VoterList ListBags (r:2 w:2)
+	// Storage: NominationPools SubPoolsStorage (r:1 w:1)
+	// Storage: NominationPools CounterForSubPoolsStorage (r:1 w:1)
+	fn unbond_balance() -> Weight {
+		// Not benchmarked yet, the time is that of `unbond`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(162_991_000 as u64)
+			.saturating_add(T::DbWeight::get().reads(19 as u64))
+			.saturating_add(T::DbWeight::get().writes(13 as u64))
+	}
 }
 
 // For backwards compatibility and tests
@@ -322,14 +484,16 @@ impl WeightInfo for () {
 	// Storage: System Account (r:2 w:1)
 	// Storage: NominationPools MaxPoolMembersPerPool (r:1 w:0)
 	// Storage: NominationPools MaxPoolMembers (r:1 w:0)
+	// Storage: NominationPools PoolMemberCap (r:1 w:0)
 	// Storage: NominationPools CounterForPoolMembers (r:1 w:1)
 	// Storage: Balances Locks (r:1 w:1)
 	// Storage: VoterList ListNodes (r:3 w:3)
 	// Storage: VoterList ListBags (r:2 w:2)
 	fn join() -> Weight {
+		// Not benchmarked yet, the measured weight plus the read of `PoolMemberCap`; regenerate with `benchmark pallet`.
 		// Minimum execution time: 159_948 nanoseconds.
 		Weight::from_ref_time(161_133_000 as u64)
-			.saturating_add(RocksDbWeight::get().reads(17 as u64))
+			.saturating_add(RocksDbWeight::get().reads(18 as u64))
 			.saturating_add(RocksDbWeight::get().writes(12 as u64))
 	}
 	// Storage: NominationPools PoolMembers (r:1 w:1)
@@ -372,6 +536,15 @@ impl WeightInfo for () {
 			.saturating_add(RocksDbWeight::get().reads(4 as u64))
 			.saturating_add(RocksDbWeight::get().writes(4 as u64))
 	}
+	// Storage: NominationPools PoolMembers (r:1 w:0)
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: NominationPools RewardPools (r:1 w:0)
+	// Storage: System Account (r:1 w:0)
+	fn claim_payout_nothing() -> Weight {
+		// Not benchmarked yet, the time is that of `claim_payout`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(64_950_000 as u64)
+			.saturating_add(RocksDbWeight::get().reads(4 as u64))
+	}
 	// Storage: NominationPools PoolMembers (r:1 w:1)
 	// Storage: NominationPools BondedPools (r:1 w:1)
 	// Storage: NominationPools RewardPools (r:1 w:1)
@@ -453,6 +626,12 @@ impl WeightInfo for () {
 			.saturating_add(RocksDbWeight::get().reads(20 as u64))
 			.saturating_add(RocksDbWeight::get().writes(17 as u64))
 	}
+	/// The range of component `n` is `[1, 32]`.
+	/// The range of component `s` is `[0, 100]`.
+	fn withdraw_unbonded_many(n: u32, s: u32, ) -> Weight {
+		// Not benchmarked yet, `n` times `withdraw_unbonded_kill`; regenerate with `benchmark pallet`.
+		Self::withdraw_unbonded_kill(s).saturating_mul(n as u64)
+	}
 	// Storage: NominationPools LastPoolId (r:1 w:1)
 	// Storage: Staking MinNominatorBond (r:1 w:0)
 	// Storage: NominationPools MinCreateBond (r:1 w:0)
@@ -528,10 +707,12 @@ impl WeightInfo for () {
 	// Storage: NominationPools MaxPoolMembersPerPool (r:0 w:1)
 	// Storage: NominationPools MinCreateBond (r:0 w:1)
 	// Storage: NominationPools MaxPools (r:0 w:1)
+	// Storage: NominationPools LiquidationThreshold (r:0 w:1)
 	fn set_configs() -> Weight {
+		// Not benchmarked yet, the measured weight plus the write of `LiquidationThreshold`; regenerate with `benchmark pallet`.
 		// Minimum execution time: 9_231 nanoseconds.
 		Weight::from_ref_time(9_526_000 as u64)
-			.saturating_add(RocksDbWeight::get().writes(5 as u64))
+			.saturating_add(RocksDbWeight::get().writes(6 as u64))
 	}
 	// Storage: NominationPools BondedPools (r:1 w:1)
 	fn update_roles() -> Weight {
@@ -555,4 +736,135 @@ impl WeightInfo for () {
 			.saturating_add(RocksDbWeight::get().reads(9 as u64))
 			.saturating_add(RocksDbWeight::get().writes(5 as u64))
 	}
+	// Storage: NominationPools BondedPools (r:1 w:1)
+	// Storage: NominationPools LiquidatedPools (r:1 w:1)
+	// Storage: NominationPools LiquidationThreshold (r:1 w:0)
+	// Storage: Staking Bonded (r:1 w:0)
+	// Storage: Staking Ledger (r:1 w:1)
+	// Storage: Staking Validators (r:1 w:0)
+	// Storage: Staking Nominators (r:1 w:1)
+	// Storage: Staking CounterForNominators (r:1 w:1)
+	// Storage: Staking CurrentEra (r:1 w:0)
+	// Storage: Balances Locks (r:1 w:1)
+	// Storage: VoterList ListNodes (r:1 w:1)
+	// Storage: VoterList ListBags (r:1 w:1)
+	// Storage: VoterList CounterForListNodes (r:1 w:1)
+	// Storage: NominationPools SubPoolsStorage (r:1 w:1)
+	// Storage: NominationPools CounterForSubPoolsStorage (r:1 w:1)
+	fn liquidate() -> Weight {
+		// Not benchmarked yet, the time is that of `unbond`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(162_991_000 as u64)
+			.saturating_add(RocksDbWeight::get().reads(15 as u64))
+			.saturating_add(RocksDbWeight::get().writes(12 as u64))
+	}
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: NominationPools MaxPoolMembersPerPool (r:1 w:0)
+	// Storage: NominationPools PoolMemberCap (r:0 w:1)
+	fn set_member_cap() -> Weight {
+		// Not benchmarked yet, the time is that of `set_state`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(46_689_000 as u64)
+			.saturating_add(RocksDbWeight::get().reads(2 as u64))
+			.saturating_add(RocksDbWeight::get().writes(1 as u64))
+	}
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: Staking Bonded (r:1 w:0)
+	// Storage: Staking Payee (r:1 w:1)
+	// Storage: Staking Ledger (r:1 w:0)
+	fn fix_payee() -> Weight {
+		// Not benchmarked yet, the time is that of `set_state`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(46_689_000 as u64)
+			.saturating_add(RocksDbWeight::get().reads(4 as u64))
+			.saturating_add(RocksDbWeight::get().writes(1 as u64))
+	}
+	// Storage: NominationPools PoolMembers (r:1 w:0)
+	// Storage: NominationPools DustPayouts (r:0 w:1)
+	fn set_dust_payout() -> Weight {
+		// Not benchmarked yet, the time is that of `update_roles`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(31_762_000 as u64)
+			.saturating_add(RocksDbWeight::get().reads(1 as u64))
+			.saturating_add(RocksDbWeight::get().writes(1 as u64))
+	}
+	// Storage: NominationPools MinJoinBond (r:1 w:0)
+	// Storage: NominationPools PoolMembers (r:1 w:0)
+	// Storage: NominationPools JoinRequests (r:1 w:1)
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: Staking Bonded (r:1 w:0)
+	// Storage: Staking Ledger (r:1 w:0)
+	// Storage: System Account (r:2 w:2)
+	fn join_permissioned() -> Weight {
+		// Not benchmarked yet, the time is that of `claim_payout`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(64_950_000 as u64)
+			.saturating_add(RocksDbWeight::get().reads(8 as u64))
+			.saturating_add(RocksDbWeight::get().writes(3 as u64))
+	}
+	// Storage: NominationPools BondedPools (r:1 w:1)
+	// Storage: NominationPools PoolMembers (r:1 w:1)
+	// Storage: Staking Bonded (r:1 w:0)
+	// Storage: Staking Ledger (r:1 w:1)
+	// Storage: NominationPools JoinRequests (r:1 w:1)
+	// Storage: System Account (r:3 w:3)
+	// Storage: NominationPools RewardPools (r:1 w:1)
+	// Storage: NominationPools MaxPoolMembersPerPool (r:1 w:0)
+	// Storage: NominationPools MaxPoolMembers (r:1 w:0)
+	// Storage: NominationPools PoolMemberCap (r:1 w:0)
+	// Storage: NominationPools CounterForPoolMembers (r:1 w:1)
+	// Storage: Balances Locks (r:1 w:1)
+	// Storage: VoterList ListNodes (r:3 w:3)
+	// Storage: VoterList ListBags (r:2 w:2)
+	fn approve_join() -> Weight {
+		// Not benchmarked yet, the time is that of `join` plus `claim_payout`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(226_083_000 as u64)
+			.saturating_add(RocksDbWeight::get().reads(19 as u64))
+			.saturating_add(RocksDbWeight::get().writes(15 as u64))
+	}
+	// Storage: NominationPools JoinRequests (r:1 w:1)
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: System Account (r:2 w:2)
+	fn reject_join() -> Weight {
+		// Not benchmarked yet, the time is that of `claim_payout`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(64_950_000 as u64)
+			.saturating_add(RocksDbWeight::get().reads(4 as u64))
+			.saturating_add(RocksDbWeight::get().writes(3 as u64))
+	}
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: NominationPools PoolIdByAlias (r:2 w:2)
+	// Storage: NominationPools PoolAliases (r:1 w:1)
+	// Storage: System Account (r:3 w:3)
+	fn set_alias() -> Weight {
+		// Not benchmarked yet, the time is that of `claim_payout`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(64_950_000 as u64)
+			.saturating_add(RocksDbWeight::get().reads(7 as u64))
+			.saturating_add(RocksDbWeight::get().writes(6 as u64))
+	}
+	// Storage: NominationPools BondedPools (r:1 w:0)
+	// Storage: NominationPools PoolAliases (r:1 w:1)
+	// Storage: NominationPools PoolIdByAlias (r:0 w:1)
+	// Storage: System Account (r:2 w:2)
+	fn clear_alias() -> Weight {
+		// Not benchmarked yet, the time is that of `claim_payout`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(64_950_000 as u64)
+			.saturating_add(RocksDbWeight::get().reads(4 as u64))
+			.saturating_add(RocksDbWeight::get().writes(4 as u64))
+	}
+	// Storage: NominationPools PoolMembers (r:1 w:1)
+	// Storage: NominationPools BondedPools (r:1 w:1)
+	// Storage: NominationPools RewardPools (r:1 w:1)
+	// Storage: NominationPools LiquidatedPools (r:1 w:0)
+	// Storage: Staking Bonded (r:1 w:0)
+	// Storage: Staking Ledger (r:1 w:1)
+	// Storage: System Account (r:2 w:1)
+	// Storage: Staking CurrentEra (r:1 w:0)
+	// Storage: Staking Nominators (r:1 w:0)
+	// Storage: Staking MinNominatorBond (r:1 w:0)
+	// Storage: Balances Locks (r:1 w:1)
+	// Storage: VoterList ListNodes (r:3 w:3)
+	// Storage: VoterList ListBags (r:2 w:2)
+	// Storage: NominationPools SubPoolsStorage (r:1 w:1)
+	// Storage: NominationPools CounterForSubPoolsStorage (r:1 w:1)
+	fn unbond_balance() -> Weight {
+		// Not benchmarked yet, the time is that of `unbond`; regenerate with `benchmark pallet`.
+		Weight::from_ref_time(162_991_000 as u64)
+			.saturating_add(RocksDbWeight::get().reads(19 as u64))
+			.saturating_add(RocksDbWeight::get().writes(13 as u64))
+	}
 }
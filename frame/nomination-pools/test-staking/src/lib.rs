@@ -303,7 +303,12 @@ fn pool_slash_e2e() {
 				// 30 has been slashed to 15 (15 slash)
 				PoolsEvent::UnbondingPoolSlashed { pool_id: 1, era: 5, balance: 15 },
 				// 30 has been slashed to 15 (15 slash)
-				PoolsEvent::PoolSlashed { pool_id: 1, balance: 15 }
+				PoolsEvent::PoolSlashed { pool_id: 1, balance: 15 },
+				PoolsEvent::SlashApplied {
+					pool_id: 1,
+					bonded: 15,
+					unbonding: [(5, 15)].into_iter().collect()
+				}
 			]
 		);
 
@@ -523,7 +528,12 @@ fn pool_slash_proportional() {
 				// slashed, and 12 is all the remaining slash
 				PoolsEvent::UnbondingPoolSlashed { pool_id: 1, era: 129, balance: 8 },
 				// Bonded pool got slashed for 25, remaining 15 in it.
-				PoolsEvent::PoolSlashed { pool_id: 1, balance: 15 }
+				PoolsEvent::PoolSlashed { pool_id: 1, balance: 15 },
+				PoolsEvent::SlashApplied {
+					pool_id: 1,
+					bonded: 25,
+					unbonding: [(128, 13), (129, 12)].into_iter().collect()
+				}
 			]
 		);
 	});
@@ -603,7 +613,10 @@ fn pool_slash_non_proportional_only_bonded_pool() {
 		);
 		assert_eq!(
 			pool_events_since_last_call(),
-			vec![PoolsEvent::PoolSlashed { pool_id: 1, balance: 10 }]
+			vec![
+				PoolsEvent::PoolSlashed { pool_id: 1, balance: 10 },
+				PoolsEvent::SlashApplied { pool_id: 1, bonded: 30, unbonding: Default::default() }
+			]
 		);
 	});
 }
@@ -686,7 +699,12 @@ fn pool_slash_non_proportional_bonded_pool_and_chunks() {
 				// out of 20, 10 was taken.
 				PoolsEvent::UnbondingPoolSlashed { pool_id: 1, era: 127, balance: 10 },
 				// out of 40, all was taken.
-				PoolsEvent::PoolSlashed { pool_id: 1, balance: 0 }
+				PoolsEvent::PoolSlashed { pool_id: 1, balance: 0 },
+				PoolsEvent::SlashApplied {
+					pool_id: 1,
+					bonded: 40,
+					unbonding: [(127, 10)].into_iter().collect()
+				}
 			]
 		);
 	});
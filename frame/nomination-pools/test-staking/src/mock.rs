@@ -178,6 +178,12 @@ impl pallet_nomination_pools::Config for Runtime {
 	type PostUnbondingPoolsWindow = PostUnbondingPoolsWindow;
 	type MaxMetadataLen = ConstU32<256>;
 	type MaxUnbonding = ConstU32<8>;
+	type MaxWithdrawBatch = ConstU32<32>;
+	type MinPayout = frame_support::traits::ConstU128<0>;
+	type JoinRequestTimeout = frame_support::traits::ConstU64<10>;
+	type MaxJoinRequests = frame_support::traits::ConstU32<8>;
+	type MaxAliasLen = ConstU32<32>;
+	type AliasDeposit = frame_support::traits::ConstU128<10>;
 	type MaxPointsToBalance = ConstU8<10>;
 	type PalletId = PoolsPalletId;
 }
@@ -235,6 +241,14 @@ pub fn new_test_ext() -> sp_io::TestExternalities {
 			pallet_staking::ConfigOp::Noop,
 			pallet_staking::ConfigOp::Noop,
 		));
+
+		// register some validators for the pools to nominate.
+		for validator in 1..=3 {
+			pallet_staking::Validators::<Runtime>::insert(
+				validator,
+				pallet_staking::ValidatorPrefs::default(),
+			);
+		}
 	});
 
 	ext
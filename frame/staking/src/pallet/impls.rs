@@ -1597,12 +1597,51 @@ impl<T: Config> StakingInterface for Pallet<T> {
 		)
 	}
 
+	fn payee(who: &Self::AccountId) -> Option<Self::AccountId> {
+		let controller = Self::bonded(who)?;
+		match Payee::<T>::get(who) {
+			RewardDestination::Account(payee) => Some(payee),
+			RewardDestination::Stash => Some(who.clone()),
+			RewardDestination::Controller => Some(controller),
+			RewardDestination::Staked | RewardDestination::None => None,
+		}
+	}
+
+	fn set_payee(who: &Self::AccountId, payee: &Self::AccountId) -> DispatchResult {
+		let ctrl = Self::bonded(who).ok_or(Error::<T>::NotStash)?;
+		Self::set_payee(RawOrigin::Signed(ctrl).into(), RewardDestination::Account(payee.clone()))
+	}
+
+	fn max_nominations() -> u32 {
+		T::MaxNominations::get()
+	}
+
+	fn is_validator(who: &Self::AccountId) -> bool {
+		Validators::<T>::contains_key(who)
+	}
+
 	fn nominate(who: &Self::AccountId, targets: Vec<Self::AccountId>) -> DispatchResult {
 		let ctrl = Self::bonded(who).ok_or(Error::<T>::NotStash)?;
 		let targets = targets.into_iter().map(T::Lookup::unlookup).collect::<Vec<_>>();
 		Self::nominate(RawOrigin::Signed(ctrl).into(), targets)
 	}
 
+	fn nomination_targets(who: &Self::AccountId) -> Option<(Vec<(T::AccountId, bool)>, EraIndex)> {
+		Nominators::<T>::get(who).map(|n| {
+			let targets = n
+				.targets
+				.into_iter()
+				.map(|target| {
+					// same as in `get_npos_voters`.
+					let stale = Self::slashing_spans(&target)
+						.map_or(false, |spans| n.submitted_in < spans.last_nonzero_slash());
+					(target, stale)
+				})
+				.collect();
+			(targets, n.submitted_in)
+		})
+	}
+
 	#[cfg(feature = "runtime-benchmarks")]
 	fn nominations(who: Self::AccountId) -> Option<Vec<T::AccountId>> {
 		Nominators::<T>::get(who).map(|n| n.targets.into_inner())
@@ -3225,6 +3225,10 @@ fn slash_kicks_validators_not_nominators_and_disables_nominator_for_kicked_valid
 		// re-registers.
 		let last_slash = <Staking as Store>::SlashingSpans::get(&11).unwrap().last_nonzero_slash();
 		assert!(nominations.submitted_in < last_slash);
+		assert_eq!(
+			<Staking as sp_staking::StakingInterface>::nomination_targets(&101),
+			Some((vec![(11, true), (21, false)], nominations.submitted_in))
+		);
 
 		// actually re-bond the slashed validator
 		assert_ok!(Staking::validate(RuntimeOrigin::signed(10), Default::default()));
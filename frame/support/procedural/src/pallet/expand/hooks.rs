@@ -82,6 +82,15 @@ pub fn expand_hooks(def: &mut Def) -> proc_macro2::TokenStream {
 		proc_macro2::TokenStream::new()
 	};
 
+	// report the weight of a hook within its span, e.g. for the hook timings of try-runtime.
+	let trace_hook_weight = quote::quote!(
+		#frame_support::sp_tracing::trace!(
+			target: "runtime::hook_weight",
+			ref_time = weight.ref_time(),
+			proof_size = weight.proof_size(),
+		);
+	);
+
 	quote::quote_spanned!(span =>
 		#hooks_impl
 
@@ -109,11 +118,16 @@ pub fn expand_hooks(def: &mut Def) -> proc_macro2::TokenStream {
 				n: <T as #frame_system::Config>::BlockNumber,
 				remaining_weight: #frame_support::weights::Weight
 			) -> #frame_support::weights::Weight {
-				<
+				#frame_support::sp_tracing::enter_span!(
+					#frame_support::sp_tracing::trace_span!("on_idle")
+				);
+				let weight = <
 					Self as #frame_support::traits::Hooks<
 						<T as #frame_system::Config>::BlockNumber
 					>
-				>::on_idle(n, remaining_weight)
+				>::on_idle(n, remaining_weight);
+				#trace_hook_weight
+				weight
 			}
 		}
 
@@ -127,11 +141,13 @@ pub fn expand_hooks(def: &mut Def) -> proc_macro2::TokenStream {
 				#frame_support::sp_tracing::enter_span!(
 					#frame_support::sp_tracing::trace_span!("on_initialize")
 				);
-				<
+				let weight = <
 					Self as #frame_support::traits::Hooks<
 						<T as #frame_system::Config>::BlockNumber
 					>
-				>::on_initialize(n)
+				>::on_initialize(n);
+				#trace_hook_weight
+				weight
 			}
 		}
 
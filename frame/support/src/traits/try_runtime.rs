@@ -17,6 +17,7 @@
 
 //! Try-runtime specific traits and types.
 
+use crate::weights::Weight;
 use impl_trait_for_tuples::impl_for_tuples;
 use sp_arithmetic::traits::AtLeast32BitUnsigned;
 use sp_std::prelude::*;
@@ -136,3 +137,224 @@ impl<BlockNumber: Clone + sp_std::fmt::Debug + AtLeast32BitUnsigned> TryState<Bl
 		}
 	}
 }
+
+/// Execute the state checks of each pallet of a runtime on its own, see [`TryState`].
+///
+/// Unlike with [`TryState`], a failing check does not hide the outcome of the checks of the other
+/// pallets, which thus can all be reported.
+pub trait TryStateOfEach<BlockNumber> {
+	/// Execute the state checks of each pallet that `select` selects, and return the name of
+	/// each checked pallet along with the outcome of its checks.
+	///
+	/// Pallet names are obtained from [`super::PalletInfoAccess`]. Fails without executing any
+	/// checks if `select` is [`Select::Only`] and one of its names is not the name of a pallet.
+	fn try_state_of_each(
+		n: BlockNumber,
+		select: Select,
+	) -> Result<Vec<(&'static str, Result<(), &'static str>)>, &'static str>;
+}
+
+#[cfg_attr(all(not(feature = "tuples-96"), not(feature = "tuples-128")), impl_for_tuples(64))]
+#[cfg_attr(all(feature = "tuples-96", not(feature = "tuples-128")), impl_for_tuples(96))]
+#[cfg_attr(all(feature = "tuples-128"), impl_for_tuples(128))]
+impl<BlockNumber: Clone + sp_std::fmt::Debug + AtLeast32BitUnsigned> TryStateOfEach<BlockNumber>
+	for Tuple
+{
+	for_tuples!( where #( Tuple: crate::traits::PalletInfoAccess + TryState<BlockNumber> )* );
+	fn try_state_of_each(
+		n: BlockNumber,
+		select: Select,
+	) -> Result<Vec<(&'static str, Result<(), &'static str>)>, &'static str> {
+		let try_state_fns: &[(
+			&'static str,
+			fn(BlockNumber, Select) -> Result<(), &'static str>,
+		)] = &[for_tuples!(
+			#( (<Tuple as crate::traits::PalletInfoAccess>::name(), Tuple::try_state) ),*
+		)];
+		let selected: Vec<_> = match select {
+			Select::None => Vec::new(),
+			Select::All => try_state_fns.iter().collect(),
+			Select::RoundRobin(_) if try_state_fns.is_empty() => Vec::new(),
+			Select::RoundRobin(len) => {
+				// the same pallets as those that `TryState` checks.
+				let skip = n.clone() % (try_state_fns.len() as u32).into();
+				let skip: u32 =
+					skip.try_into().unwrap_or_else(|_| sp_runtime::traits::Bounded::max_value());
+				try_state_fns.iter().cycle().skip(skip as usize).take(len as usize).collect()
+			},
+			Select::Only(ref pallet_names) => {
+				let unknown = pallet_names.iter().find(|p| {
+					!try_state_fns.iter().any(|(name, _)| name.as_bytes() == p.as_slice())
+				});
+				if let Some(unknown) = unknown {
+					crate::log::error!(
+						target: "runtime::try-runtime",
+						"no pallet is named {:?}",
+						sp_std::str::from_utf8(unknown).unwrap_or("<invalid?>"),
+					);
+					return Err("no pallet has one of the given names")
+				}
+				try_state_fns
+					.iter()
+					.filter(|(name, _)| pallet_names.iter().any(|n| n == name.as_bytes()))
+					.collect()
+			},
+		};
+		Ok(selected
+			.into_iter()
+			.map(|(name, try_state_fn)| (*name, try_state_fn(n.clone(), select.clone())))
+			.collect())
+	}
+}
+
+/// Execute the runtime upgrade of only some of the pallets of a runtime, see
+/// [`super::OnRuntimeUpgrade`].
+///
+/// This allows iterating on the migration of a single pallet without executing those of all
+/// other pallets in the runtime.
+pub trait TryOnRuntimeUpgradeOf {
+	/// Execute the runtime upgrade of each pallet whose name is in `pallets`, along with its pre
+	/// and post upgrade checks, and return the consumed weight.
+	///
+	/// Pallet names are obtained from [`super::PalletInfoAccess`]. Fails without executing any
+	/// upgrade if one of `pallets` is not the name of a pallet, and otherwise as soon as any of
+	/// the checks fails.
+	fn try_on_runtime_upgrade_of(pallets: &[Vec<u8>]) -> Result<Weight, &'static str>;
+}
+
+#[cfg_attr(all(not(feature = "tuples-96"), not(feature = "tuples-128")), impl_for_tuples(64))]
+#[cfg_attr(all(feature = "tuples-96", not(feature = "tuples-128")), impl_for_tuples(96))]
+#[cfg_attr(all(feature = "tuples-128"), impl_for_tuples(128))]
+impl TryOnRuntimeUpgradeOf for Tuple {
+	for_tuples!( where #( Tuple: crate::traits::PalletInfoAccess + crate::traits::OnRuntimeUpgrade )* );
+	fn try_on_runtime_upgrade_of(pallets: &[Vec<u8>]) -> Result<Weight, &'static str> {
+		let names: &[&'static str] =
+			&[for_tuples!( #( <Tuple as crate::traits::PalletInfoAccess>::name() ),* )];
+		if let Some(unknown) =
+			pallets.iter().find(|p| !names.iter().any(|n| n.as_bytes() == p.as_slice()))
+		{
+			crate::log::error!(
+				target: "runtime::try-runtime",
+				"no pallet is named {:?}",
+				sp_std::str::from_utf8(unknown).unwrap_or("<invalid?>"),
+			);
+			return Err("no pallet has one of the given names")
+		}
+
+		let mut weight = Weight::zero();
+		for_tuples!( #(
+			let name = <Tuple as crate::traits::PalletInfoAccess>::name();
+			if pallets.iter().any(|p| p == name.as_bytes()) {
+				let _guard = crate::StorageNoopGuard::default();
+				let state = Tuple::pre_upgrade()?;
+				drop(_guard);
+
+				weight = weight.saturating_add(Tuple::on_runtime_upgrade());
+
+				let _guard = crate::StorageNoopGuard::default();
+				Tuple::post_upgrade(state)?;
+				drop(_guard);
+			}
+		)* );
+		Ok(weight)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::traits::{CrateVersion, OnRuntimeUpgrade, PalletInfoAccess};
+	use sp_io::TestExternalities;
+
+	macro_rules! test_pallet {
+		($pallet:ident, $weight:expr, $try_state:expr) => {
+			struct $pallet;
+			impl PalletInfoAccess for $pallet {
+				fn index() -> usize {
+					0
+				}
+				fn name() -> &'static str {
+					stringify!($pallet)
+				}
+				fn module_name() -> &'static str {
+					stringify!($pallet)
+				}
+				fn crate_version() -> CrateVersion {
+					CrateVersion::new(1, 0, 0)
+				}
+			}
+			impl OnRuntimeUpgrade for $pallet {
+				fn on_runtime_upgrade() -> Weight {
+					Weight::from_ref_time($weight)
+				}
+			}
+			impl TryState<u32> for $pallet {
+				fn try_state(_: u32, _: Select) -> Result<(), &'static str> {
+					$try_state
+				}
+			}
+		};
+	}
+
+	test_pallet!(Foo, 10, Ok(()));
+	test_pallet!(Bar, 20, Err("Bar is broken"));
+	type Pallets = (Foo, Bar);
+
+	fn names(pallets: &[&str]) -> Vec<Vec<u8>> {
+		pallets.iter().map(|p| p.as_bytes().to_vec()).collect()
+	}
+
+	#[test]
+	fn try_on_runtime_upgrade_of_only_upgrades_the_given_pallets() {
+		TestExternalities::default().execute_with(|| {
+			assert_eq!(
+				Pallets::try_on_runtime_upgrade_of(&names(&["Bar"])),
+				Ok(Weight::from_ref_time(20))
+			);
+			assert_eq!(
+				Pallets::try_on_runtime_upgrade_of(&names(&["Foo", "Bar"])),
+				Ok(Weight::from_ref_time(30))
+			);
+			assert_eq!(Pallets::try_on_runtime_upgrade_of(&[]), Ok(Weight::zero()));
+		});
+	}
+
+	#[test]
+	fn try_on_runtime_upgrade_of_fails_for_unknown_pallets() {
+		TestExternalities::default().execute_with(|| {
+			assert_eq!(
+				Pallets::try_on_runtime_upgrade_of(&names(&["Foo", "Baz"])),
+				Err("no pallet has one of the given names")
+			);
+			assert_eq!(
+				Pallets::try_on_runtime_upgrade_of(&names(&["foo"])),
+				Err("no pallet has one of the given names")
+			);
+		});
+	}
+
+	#[test]
+	fn try_state_of_each_reports_each_selected_pallet() {
+		assert_eq!(
+			Pallets::try_state_of_each(0u32, Select::All),
+			Ok(vec![("Foo", Ok(())), ("Bar", Err("Bar is broken"))])
+		);
+		assert_eq!(
+			Pallets::try_state_of_each(0u32, Select::Only(names(&["Bar"]))),
+			Ok(vec![("Bar", Err("Bar is broken"))])
+		);
+		assert_eq!(
+			Pallets::try_state_of_each(1u32, Select::RoundRobin(1)),
+			Ok(vec![("Bar", Err("Bar is broken"))])
+		);
+		assert_eq!(Pallets::try_state_of_each(0u32, Select::None), Ok(vec![]));
+	}
+
+	#[test]
+	fn try_state_of_each_fails_for_unknown_pallets() {
+		assert_eq!(
+			Pallets::try_state_of_each(0u32, Select::Only(names(&["Foo", "Baz"]))),
+			Err("no pallet has one of the given names")
+		);
+	}
+}
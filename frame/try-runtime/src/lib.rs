@@ -22,6 +22,8 @@
 
 pub use frame_support::traits::TryStateSelect;
 use frame_support::weights::Weight;
+use sp_runtime::DispatchResult;
+use sp_std::prelude::*;
 
 sp_api::decl_runtime_apis! {
 	/// Runtime api for testing the execution of a runtime upgrade.
@@ -35,10 +37,40 @@ sp_api::decl_runtime_apis! {
 		/// the total allowed block weight of the runtime.
 		fn on_runtime_upgrade() -> (Weight, Weight);
 
+		/// Same as [`Self::on_runtime_upgrade`], but only for the pallets whose name is in
+		/// `pallets`.
+		///
+		/// The migrations of the runtime that belong to no pallet are not executed, and none is
+		/// if one of `pallets` is not the name of a pallet.
+		#[api_version(2)]
+		fn on_runtime_upgrade_of(pallets: Vec<Vec<u8>>) -> (Weight, Weight);
+
 		/// Execute the given block, but don't check that its state root matches that of yours.
 		///
 		/// This is only sensible where the incoming block is from a different network, yet it has
 		/// the same block format as the runtime implementing this API.
 		fn execute_block(block: Block, state_root_check: bool, try_state: TryStateSelect) -> Weight;
+
+		/// Execute the try-state checks of each pallet that `select` selects on top of the current
+		/// state.
+		///
+		/// Returns the name of each checked pallet, along with the error of its checks, if any, or
+		/// an error if `select` names a pallet that does not exist.
+		#[api_version(2)]
+		fn try_state(select: TryStateSelect) -> Result<Vec<(Vec<u8>, Option<Vec<u8>>)>, Vec<u8>>;
+
+		/// Dispatch the given SCALE encoded call on top of the current state, as signed by the
+		/// SCALE encoded account `signer`, or as root if `None`.
+		///
+		/// None of the checks and payments of a real extrinsic are performed.
+		///
+		/// Returns the weight that the call declares in its dispatch info, the actual weight that
+		/// it reported after dispatch, and the outcome of the dispatch, or the utf8 encoded reason
+		/// for which `call` or `signer` could not be decoded.
+		#[api_version(2)]
+		fn dispatch_call(
+			call: Vec<u8>,
+			signer: Option<Vec<u8>>,
+		) -> Result<(Weight, Weight, DispatchResult), Vec<u8>>;
 	}
 }
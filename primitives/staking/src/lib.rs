@@ -55,6 +55,19 @@ impl<AccountId, Balance> OnStakerSlash<AccountId, Balance> for () {
 	}
 }
 
+/// Trait describing something that can credit rewards to pools of stakers, from a source other
+/// than the staking system (e.g. an incentive program).
+pub trait PoolRewardCredit<AccountId, Balance> {
+	/// The identifier of a pool.
+	type PoolId;
+
+	/// Transfer `amount` from `source` to the rewards of pool `pool_id`.
+	///
+	/// The reward is accounted for right away, i.e. it is shared among the members of the pool as
+	/// of now, without relying on the pool to detect the transfer later on.
+	fn credit_reward(source: &AccountId, pool_id: Self::PoolId, amount: Balance) -> DispatchResult;
+}
+
 /// A struct that reflects stake that an account has in the staking system. Provides a set of
 /// methods to operate on it's properties. Aimed at making `StakingInterface` more concise.
 pub struct Stake<T: StakingInterface + ?Sized> {
@@ -134,9 +147,33 @@ pub trait StakingInterface {
 	fn bond(who: &Self::AccountId, value: Self::Balance, payee: &Self::AccountId)
 		-> DispatchResult;
 
+	/// The account that the rewards of `who` are forwarded to.
+	///
+	/// `None` if `who` is not bonded, or if their rewards are not forwarded to an account (e.g.
+	/// because they are re-staked).
+	fn payee(who: &Self::AccountId) -> Option<Self::AccountId>;
+
+	/// Forward the rewards of `who` to `payee` from now on.
+	fn set_payee(who: &Self::AccountId, payee: &Self::AccountId) -> DispatchResult;
+
+	/// The maximum number of targets a nominator can have.
+	fn max_nominations() -> u32;
+
+	/// Whether `who` is currently registered as a validator.
+	fn is_validator(who: &Self::AccountId) -> bool;
+
 	/// Have `who` nominate `validators`.
 	fn nominate(who: &Self::AccountId, validators: Vec<Self::AccountId>) -> DispatchResult;
 
+	/// The targets that `who` nominates, each along with whether the nomination of it is stale,
+	/// and the era in which the nomination was submitted, if `who` is a nominator.
+	///
+	/// The staking system disregards a target of a nomination that was submitted before some
+	/// later change to the target, e.g. a slash, in which case the nomination of it is stale.
+	fn nomination_targets(
+		who: &Self::AccountId,
+	) -> Option<(Vec<(Self::AccountId, bool)>, EraIndex)>;
+
 	/// Chill `who`.
 	fn chill(who: &Self::AccountId) -> DispatchResult;
 
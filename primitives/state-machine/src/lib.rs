@@ -167,6 +167,7 @@ mod execution {
 	};
 	use sp_externalities::Extensions;
 	use std::{
+		any::TypeId,
 		collections::{HashMap, HashSet},
 		fmt,
 	};
@@ -325,7 +326,10 @@ mod execution {
 			runtime_code: &'a RuntimeCode,
 			spawn_handle: impl SpawnNamed + Send + 'static,
 		) -> Self {
-			extensions.register(ReadRuntimeVersionExt::new(exec.clone()));
+			// a `ReadRuntimeVersionExt` given by the caller, e.g. to mock the versions, wins.
+			if extensions.get_mut(TypeId::of::<ReadRuntimeVersionExt>()).is_none() {
+				extensions.register(ReadRuntimeVersionExt::new(exec.clone()));
+			}
 			extensions.register(sp_core::traits::TaskExecutorExt::new(spawn_handle));
 
 			Self {
@@ -1391,6 +1395,69 @@ mod tests {
 		assert_eq!(state_machine.execute(ExecutionStrategy::NativeWhenPossible).unwrap(), vec![66]);
 	}
 
+	#[test]
+	fn given_read_runtime_version_ext_is_kept() {
+		use sp_core::traits::ReadRuntimeVersionExt;
+		use sp_externalities::{Extensions, ExternalitiesExt};
+
+		#[derive(Clone)]
+		struct VersionReader(Vec<u8>);
+
+		impl CodeExecutor for VersionReader {
+			type Error = u8;
+
+			fn call(
+				&self,
+				ext: &mut dyn Externalities,
+				_: &RuntimeCode,
+				_method: &str,
+				_data: &[u8],
+				use_native: bool,
+			) -> (CallResult<Self::Error>, bool) {
+				let version = ext
+					.extension::<ReadRuntimeVersionExt>()
+					.unwrap()
+					.read_runtime_version(&[], &mut BasicExternalities::default());
+				(version.map_err(|_| 0), use_native)
+			}
+		}
+
+		impl sp_core::traits::ReadRuntimeVersion for VersionReader {
+			fn read_runtime_version(
+				&self,
+				_: &[u8],
+				_: &mut dyn Externalities,
+			) -> std::result::Result<Vec<u8>, String> {
+				Ok(self.0.clone())
+			}
+		}
+
+		let backend = trie_backend::tests::test_trie(StateVersion::V1, None, None);
+		let wasm_code = RuntimeCode::empty();
+		let execute = |extensions| {
+			StateMachine::new(
+				&backend,
+				&mut Default::default(),
+				&VersionReader(vec![1]),
+				"test",
+				&[],
+				extensions,
+				&wasm_code,
+				TaskExecutor::new(),
+			)
+			.execute(ExecutionStrategy::NativeWhenPossible)
+			.unwrap()
+		};
+
+		// by default, the executor reads the version.
+		assert_eq!(execute(Default::default()), vec![1]);
+
+		// unless the caller provides its own way of reading it.
+		let mut extensions = Extensions::default();
+		extensions.register(ReadRuntimeVersionExt::new(VersionReader(vec![2])));
+		assert_eq!(execute(extensions), vec![2]);
+	}
+
 	#[test]
 	fn execute_works_with_native_else_wasm() {
 		execute_works_with_native_else_wasm_inner(StateVersion::V0);
@@ -21,25 +21,41 @@
 //! based chain, or a local state snapshot file.
 
 use codec::{Decode, Encode};
+use frame_metadata::{RuntimeMetadata, RuntimeMetadataPrefixed};
+use futures::future::try_join_all;
 
 use log::*;
 use serde::de::DeserializeOwned;
 use sp_core::{
-	hashing::twox_128,
+	hashing::{blake2_256, twox_128},
 	hexdisplay::HexDisplay,
 	storage::{
 		well_known_keys::{is_default_child_storage_key, DEFAULT_CHILD_STORAGE_KEY_PREFIX},
 		ChildInfo, ChildType, PrefixedStorageKey, StorageData, StorageKey,
 	},
+	Hasher,
 };
 pub use sp_io::TestExternalities;
-use sp_runtime::{traits::Block as BlockT, StateVersion};
+use sp_runtime::{
+	traits::{Block as BlockT, Header as HeaderT, UniqueSaturatedInto},
+	StateVersion,
+};
+use sp_state_machine::Backend;
 use std::{
+	collections::{BTreeMap, BTreeSet},
 	fs,
+	num::NonZeroUsize,
 	path::{Path, PathBuf},
-	sync::Arc,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+	time::{SystemTime, UNIX_EPOCH},
+};
+use substrate_rpc_client::{
+	batch_child_storage, batch_storage, rpc_client_with, rpc_params, ChainApi, ClientConfig,
+	ClientT, RpcClient, StateApi,
 };
-use substrate_rpc_client::{rpc_params, ws_client, ChainApi, ClientT, StateApi, WsClient};
 
 type KeyValue = (StorageKey, StorageData);
 type TopKeyValues = Vec<KeyValue>;
@@ -50,6 +66,11 @@ const DEFAULT_TARGET: &str = "wss://rpc.polkadot.io:443";
 const BATCH_SIZE: usize = 1000;
 const PAGE: u32 = 1000;
 
+/// The bytes that every state snapshot file starts with.
+const SNAPSHOT_MAGIC: [u8; 8] = *b"rext-snp";
+/// The current version of the format of state snapshot files.
+const SNAPSHOT_VERSION: u16 = 1;
+
 /// The execution mode.
 #[derive(Clone)]
 pub enum Mode<B: BlockT> {
@@ -79,31 +100,32 @@ pub struct OfflineConfig {
 /// Description of the transport protocol (for online execution).
 #[derive(Debug, Clone)]
 pub enum Transport {
-	/// Use the `URI` to open a new WebSocket connection.
+	/// Use the `URI` to open a new connection, over HTTP if it starts with `http://` or
+	/// `https://`, and over WebSocket otherwise.
 	Uri(String),
-	/// Use existing WebSocket connection.
-	RemoteClient(Arc<WsClient>),
+	/// Use existing connection.
+	RemoteClient(Arc<RpcClient>),
 }
 
 impl Transport {
-	fn as_client(&self) -> Option<&WsClient> {
+	fn as_client(&self) -> Option<&RpcClient> {
 		match self {
 			Self::RemoteClient(client) => Some(client),
 			_ => None,
 		}
 	}
 
-	// Open a new WebSocket connection if it's not connected.
-	async fn map_uri(&mut self) -> Result<(), &'static str> {
+	// Open a new connection with `config` if it's not connected.
+	async fn map_uri(&mut self, config: ClientConfig) -> Result<(), &'static str> {
 		if let Self::Uri(uri) = self {
 			log::debug!(target: LOG_TARGET, "initializing remote client to {:?}", uri);
 
-			let ws_client = ws_client(uri).await.map_err(|e| {
+			let client = rpc_client_with(uri, config).await.map_err(|e| {
 				log::error!(target: LOG_TARGET, "error: {:?}", e);
-				"failed to build ws client"
+				"failed to build rpc client"
 			})?;
 
-			*self = Self::RemoteClient(Arc::new(ws_client))
+			*self = Self::RemoteClient(Arc::new(client))
 		}
 
 		Ok(())
@@ -116,8 +138,8 @@ impl From<String> for Transport {
 	}
 }
 
-impl From<Arc<WsClient>> for Transport {
-	fn from(client: Arc<WsClient>) -> Self {
+impl From<Arc<RpcClient>> for Transport {
+	fn from(client: Arc<RpcClient>) -> Self {
 		Transport::RemoteClient(client)
 	}
 }
@@ -132,20 +154,45 @@ pub struct OnlineConfig<B: BlockT> {
 	pub at: Option<B::Hash>,
 	/// An optional state snapshot file to WRITE to, not for reading. Not written if set to `None`.
 	pub state_snapshot: Option<SnapshotConfig>,
+	/// An optional state snapshot file to READ, and bring up to date with [`Self::at`], instead
+	/// of scraping the entire state.
+	///
+	/// The snapshot must be of an ancestor of [`Self::at`], and have been scraped with the same
+	/// pallets and keys. Then, only the keys are listed anew, and the changes of the values of
+	/// the keys of the snapshot are queried through `state_queryStorage`. Only the values of new
+	/// keys, and the child trees whose root changed, are fetched. It may be the same file as
+	/// [`Self::state_snapshot`]. Only used in [`Mode::Online`].
+	pub update_snapshot: Option<SnapshotConfig>,
 	/// The pallets to scrape. If empty, entire chain state will be scraped.
+	///
+	/// `*` stands for all the pallets that declare storage in the metadata of the chain at
+	/// [`Self::at`].
 	pub pallets: Vec<String>,
+	/// The pallets to exclude from [`Self::pallets`], or from all the pallets that declare storage
+	/// in the metadata of the chain at [`Self::at`] if that is empty.
+	pub exclude_pallets: Vec<String>,
 	/// Transport config.
 	pub transport: Transport,
 	/// Lookout for child-keys, and scrape them as well if set to true.
+	///
+	/// Only the child trees whose root is among the scraped top keys are scraped, see
+	/// [`Builder::inject_child_tree`].
 	pub scrape_children: bool,
+	/// The number of connections over which the key-values of the top trie are fetched
+	/// concurrently.
+	///
+	/// If more than one, the keys of every prefix are split into ranges by their next byte, which
+	/// are then evenly distributed over the connections. If [`Self::transport`] is an existing
+	/// connection, it is shared instead.
+	pub threads: NonZeroUsize,
 }
 
 impl<B: BlockT> OnlineConfig<B> {
-	/// Return rpc (ws) client.
-	fn rpc_client(&self) -> &WsClient {
+	/// Return rpc client.
+	fn rpc_client(&self) -> &RpcClient {
 		self.transport
 			.as_client()
-			.expect("ws client must have been initialized by now; qed.")
+			.expect("rpc client must have been initialized by now; qed.")
 	}
 }
 
@@ -155,8 +202,11 @@ impl<B: BlockT> Default for OnlineConfig<B> {
 			transport: Transport::Uri(DEFAULT_TARGET.to_owned()),
 			at: None,
 			state_snapshot: None,
+			update_snapshot: None,
 			pallets: vec![],
+			exclude_pallets: vec![],
 			scrape_children: true,
+			threads: NonZeroUsize::new(1).expect("1 is not zero; qed"),
 		}
 	}
 }
@@ -167,6 +217,116 @@ impl<B: BlockT> From<String> for OnlineConfig<B> {
 	}
 }
 
+/// The header of a state snapshot file, which follows [`SNAPSHOT_MAGIC`] and precedes the encoded
+/// key-values.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct SnapshotHeader {
+	/// The version of the format of the file.
+	pub version: u16,
+	/// The name of the chain that the state was scraped from, if it was.
+	pub chain: Option<String>,
+	/// The hash and number of the block whose state was scraped, if it was.
+	pub block: Option<(Vec<u8>, u64)>,
+	/// The state version of the state, as `u8`.
+	pub state_version: u8,
+	/// The time of creation, in seconds since the unix epoch.
+	pub created_at: u64,
+	/// The blake2-256 hash of the encoded key-values.
+	pub checksum: [u8; 32],
+}
+
+/// Where the state of a state snapshot was scraped from, see [`SnapshotHeader`].
+type SnapshotOrigin = (Option<String>, Option<(Vec<u8>, u64)>);
+
+/// Prefix `payload` with [`SNAPSHOT_MAGIC`] and its [`SnapshotHeader`].
+fn encode_snapshot(
+	payload: Vec<u8>,
+	state_version: StateVersion,
+	(chain, block): SnapshotOrigin,
+) -> Vec<u8> {
+	let header = SnapshotHeader {
+		version: SNAPSHOT_VERSION,
+		chain,
+		block,
+		state_version: state_version as u8,
+		created_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+		checksum: blake2_256(&payload),
+	};
+	let mut encoded = SNAPSHOT_MAGIC.to_vec();
+	header.encode_to(&mut encoded);
+	encoded.extend(payload);
+	encoded
+}
+
+/// Validate the [`SnapshotHeader`] of the state snapshot file `bytes`, and return it along with
+/// the encoded key-values.
+///
+/// The file must be of the current version, match its checksum, and contain a state of
+/// `state_version`. A file without a header, as created by older versions, is taken to consist of
+/// the encoded key-values only, with a warning, since none of this can be validated.
+fn decode_snapshot<'a>(
+	bytes: &'a [u8],
+	path: &Path,
+	state_version: StateVersion,
+) -> Result<(Option<SnapshotHeader>, &'a [u8]), &'static str> {
+	let mut input = match bytes.strip_prefix(&SNAPSHOT_MAGIC[..]) {
+		Some(input) => input,
+		None => {
+			warn!(
+				target: LOG_TARGET,
+				"{:?} has no header, presumably it was created by an older version; its integrity \
+				and state version are not validated",
+				path,
+			);
+			return Ok((None, bytes))
+		},
+	};
+	let header = SnapshotHeader::decode(&mut input).map_err(|e| {
+		error!(target: LOG_TARGET, "failed to decode the header of {:?}: {:?}", path, e);
+		"failed to decode the snapshot header."
+	})?;
+
+	if header.version != SNAPSHOT_VERSION {
+		error!(
+			target: LOG_TARGET,
+			"{:?} is of version {} of the snapshot format, only version {} is supported",
+			path,
+			header.version,
+			SNAPSHOT_VERSION,
+		);
+		return Err("unsupported snapshot version.")
+	}
+	if header.checksum != blake2_256(input) {
+		error!(target: LOG_TARGET, "the checksum of {:?} does not match, it is corrupted", path);
+		return Err("snapshot checksum mismatch.")
+	}
+	if header.state_version != state_version as u8 {
+		error!(
+			target: LOG_TARGET,
+			"{:?} contains a state of state version {}, but state version {} is used; use the \
+			state version of the snapshot instead",
+			path,
+			header.state_version,
+			state_version as u8,
+		);
+		return Err("snapshot state version mismatch.")
+	}
+
+	info!(
+		target: LOG_TARGET,
+		"snapshot {:?} of chain {}, block {}, created at {} (unix time)",
+		path,
+		header.chain.as_deref().unwrap_or("(unknown)"),
+		header.block.as_ref().map_or("(unknown)".into(), |(hash, number)| format!(
+			"#{} (0x{})",
+			number,
+			HexDisplay::from(hash)
+		)),
+		header.created_at,
+	);
+	Ok((Some(header), input))
+}
+
 /// Configuration of the state snapshot.
 #[derive(Clone)]
 pub struct SnapshotConfig {
@@ -197,6 +357,9 @@ pub struct Builder<B: BlockT> {
 	/// Custom key-pairs to be injected into the externalities. The *hashed* keys and values must
 	/// be given.
 	hashed_key_values: Vec<KeyValue>,
+	/// Custom key-pairs to be injected into child trees of the externalities. The *hashed* keys
+	/// and values must be given.
+	child_key_values: ChildKeyValues,
 	/// Storage entry key prefixes to be injected into the externalities. The *hashed* prefix must
 	/// be given.
 	hashed_prefixes: Vec<Vec<u8>>,
@@ -208,6 +371,16 @@ pub struct Builder<B: BlockT> {
 	mode: Mode<B>,
 	/// The state version being used.
 	state_version: StateVersion,
+	/// The maximum approximate memory, in bytes, that the downloaded state may consume.
+	max_memory: Option<usize>,
+	/// The connections, besides the one of the online config, opened for
+	/// [`OnlineConfig::threads`].
+	parallel_transports: Vec<Transport>,
+	/// The settings of the RPC clients, including the number of times that a failed request is
+	/// retried.
+	rpc_config: ClientConfig,
+	/// The chain and block that the state is scraped from, once known.
+	origin: SnapshotOrigin,
 }
 
 // NOTE: ideally we would use `DefaultNoBound` here, but not worth bringing in frame-support for
@@ -217,10 +390,15 @@ impl<B: BlockT> Default for Builder<B> {
 		Self {
 			mode: Default::default(),
 			hashed_key_values: Default::default(),
+			child_key_values: Default::default(),
 			hashed_prefixes: Default::default(),
 			hashed_keys: Default::default(),
 			hashed_blacklist: Default::default(),
 			state_version: StateVersion::V1,
+			max_memory: None,
+			parallel_transports: Default::default(),
+			rpc_config: Default::default(),
+			origin: (None, None),
 		}
 	}
 }
@@ -242,6 +420,48 @@ impl<B: BlockT> Builder<B> {
 			_ => panic!("Unexpected mode: Online"),
 		}
 	}
+
+	/// All the rpc clients to fetch key-values over, see [`OnlineConfig::threads`].
+	fn rpc_clients(&self) -> Vec<&RpcClient> {
+		std::iter::once(self.as_online().rpc_client())
+			.chain(self.parallel_transports.iter().map(|transport| {
+				transport
+					.as_client()
+					.expect("rpc clients must have been initialized by now; qed.")
+			}))
+			.collect()
+	}
+}
+
+/// The approximate memory, in bytes, consumed by `kv`.
+fn kv_size(kv: &[KeyValue]) -> usize {
+	kv.iter().map(|(k, v)| k.0.len() + v.0.len()).sum()
+}
+
+/// Convert `bytes` to MiB, for display.
+fn as_mib(bytes: usize) -> f64 {
+	bytes as f64 / (1024.0 * 1024.0)
+}
+
+// Memory methods
+impl<B: BlockT> Builder<B> {
+	/// Ensure that `used` bytes, consumed by `what`, are within the configured memory limit.
+	fn ensure_memory_limit(&self, used: usize, what: &str) -> Result<(), &'static str> {
+		match self.max_memory {
+			Some(max) if used > max => {
+				log::error!(
+					target: LOG_TARGET,
+					"{} consumes ~{:.2} MiB, exceeding the memory limit of {:.2} MiB. Consider \
+					scraping fewer pallets, or raising the limit.",
+					what,
+					as_mib(used),
+					as_mib(max),
+				);
+				Err("memory limit exceeded")
+			},
+			_ => Ok(()),
+		}
+	}
 }
 
 // RPC methods
@@ -279,18 +499,96 @@ where
 			})
 	}
 
-	/// Get all the keys at `prefix` at `hash` using the paged, safe RPC methods.
+	/// Ensure that the remote node still has the state of block `at`, to which all queries are
+	/// pinned.
+	///
+	/// Unless it is an archive node, the remote node can prune the state of `at`, even while it is
+	/// being scraped.
+	async fn ensure_state_available(&self, at: B::Hash) -> Result<(), &'static str> {
+		trace!(target: LOG_TARGET, "rpc: runtime_version");
+		match StateApi::<B::Hash>::runtime_version(self.as_online().rpc_client(), Some(at)).await {
+			Ok(_) => Ok(()),
+			Err(e) => {
+				error!(
+					target: LOG_TARGET,
+					"the state of block {:?} is not available on the remote node, it might have \
+					been pruned; use an archive node or a more recent block. Error = {:?}",
+					at,
+					e
+				);
+				Err("the state of the pinned block is not available.")
+			},
+		}
+	}
+
+	/// Get the names and storage prefixes of all the pallets in the metadata of the chain at `at`.
+	async fn rpc_get_pallets(
+		&self,
+		at: B::Hash,
+	) -> Result<Vec<(String, Option<String>)>, &'static str> {
+		trace!(target: LOG_TARGET, "rpc: metadata");
+
+		let bytes = StateApi::<B::Hash>::metadata(self.as_online().rpc_client(), Some(at))
+			.await
+			.map_err(|e| {
+				error!(target: LOG_TARGET, "Error = {:?}", e);
+				"rpc metadata failed."
+			})?;
+		match RuntimeMetadataPrefixed::decode(&mut &*bytes.0).map(|m| m.1) {
+			Ok(RuntimeMetadata::V14(metadata)) => Ok(metadata
+				.pallets
+				.into_iter()
+				.map(|p| (p.name, p.storage.map(|s| s.prefix)))
+				.collect()),
+			Ok(_) => Err("unsupported metadata version, only V14 is supported"),
+			Err(_) => Err("failed to decode metadata"),
+		}
+	}
+
+	/// Get the storage prefixes of the pallets to scrape, as per [`OnlineConfig::pallets`] and
+	/// [`OnlineConfig::exclude_pallets`].
+	///
+	/// If either contains a wildcard or an exclusion, the pallets are resolved from the metadata of
+	/// the chain at `at`, and pallets that cannot be found there are an error.
+	async fn pallet_prefixes(&self, at: B::Hash) -> Result<Vec<String>, &'static str> {
+		let config = self.as_online();
+		let all = config.pallets.is_empty() || config.pallets.iter().any(|p| p == "*");
+		if !config.pallets.iter().any(|p| p == "*") && config.exclude_pallets.is_empty() {
+			return Ok(config.pallets.clone())
+		}
+
+		let pallets = self.rpc_get_pallets(at).await?;
+		for name in config.pallets.iter().chain(config.exclude_pallets.iter()) {
+			if name != "*" && !pallets.iter().any(|(n, _)| n == name) {
+				error!(target: LOG_TARGET, "pallet {} not found in the metadata at {:?}", name, at);
+				return Err("unknown pallet name.")
+			}
+		}
+
+		let prefixes = pallets
+			.into_iter()
+			.filter(|(name, _)| all || config.pallets.contains(name))
+			.filter(|(name, _)| !config.exclude_pallets.contains(name))
+			.filter_map(|(_, prefix)| prefix)
+			.collect::<Vec<_>>();
+		// an empty list would otherwise mean scraping the entire state.
+		if prefixes.is_empty() {
+			return Err("no pallets left to scrape.")
+		}
+		Ok(prefixes)
+	}
+
+	/// Get all the keys at `prefix` at `hash` through `client` using the paged, safe RPC methods.
 	async fn rpc_get_keys_paged(
 		&self,
+		client: &RpcClient,
 		prefix: StorageKey,
 		at: B::Hash,
 	) -> Result<Vec<StorageKey>, &'static str> {
 		let mut last_key: Option<StorageKey> = None;
 		let mut all_keys: Vec<StorageKey> = vec![];
 		let keys = loop {
-			let page = self
-				.as_online()
-				.rpc_client()
+			let page = client
 				.storage_keys_paged(Some(prefix.clone()), PAGE, last_key.clone(), Some(at))
 				.await
 				.map_err(|e| {
@@ -299,6 +597,23 @@ where
 				})?;
 			let page_len = page.len();
 
+			// every page must continue the previous one, as it would if it is from the same state.
+			let mut previous = last_key.as_ref();
+			for key in &page {
+				if !key.0.starts_with(&prefix.0) || previous.map_or(false, |p| key.0 <= p.0) {
+					error!(
+						target: LOG_TARGET,
+						"inconsistent page of keys at {:?}: {} does not follow {:?} with prefix {}",
+						at,
+						HexDisplay::from(key),
+						previous.map(HexDisplay::from),
+						HexDisplay::from(&prefix),
+					);
+					return Err("inconsistent page of keys.")
+				}
+				previous = Some(key);
+			}
+
 			all_keys.extend(page);
 
 			if page_len < PAGE as usize {
@@ -323,26 +638,95 @@ where
 	/// Synonym of `rpc_get_pairs_unsafe` that uses paged queries to first get the keys, and then
 	/// map them to values one by one.
 	///
-	/// This can work with public nodes. But, expect it to be darn slow.
+	/// This can work with public nodes. But, expect it to be darn slow, unless the keys are
+	/// fetched over multiple connections, see [`OnlineConfig::threads`].
 	pub(crate) async fn rpc_get_pairs_paged(
 		&self,
 		prefix: StorageKey,
 		at: B::Hash,
 	) -> Result<Vec<KeyValue>, &'static str> {
-		let keys = self.rpc_get_keys_paged(prefix, at).await?;
+		let clients = self.rpc_clients();
+		// the size of the key-values of `prefix` downloaded so far, over all connections.
+		let downloaded = AtomicUsize::new(0);
+		if clients.len() == 1 {
+			let keys = self.rpc_get_keys_paged(clients[0], prefix, at).await?;
+			return self.rpc_get_values_paged(clients[0], keys, at, &downloaded).await
+		}
+
+		// the key `prefix` itself is not covered by any of the ranges.
+		let mut key_values = vec![];
+		if !prefix.0.is_empty() {
+			let value = clients[0].storage(prefix.clone(), Some(at)).await.map_err(|e| {
+				error!(target: LOG_TARGET, "Error = {:?}", e);
+				"rpc get_storage failed."
+			})?;
+			if let Some(value) = value {
+				key_values.push((prefix.clone(), value));
+			}
+		}
+
+		let ranges = (0..=u8::MAX)
+			.map(|next| StorageKey(prefix.0.iter().copied().chain(Some(next)).collect()))
+			.collect::<Vec<_>>();
+		let ranges_count = ranges.len();
+		let ranges_per_client = (ranges_count + clients.len() - 1) / clients.len();
+		let done = AtomicUsize::new(0);
+		log::debug!(
+			target: LOG_TARGET,
+			"fetching {} ranges of prefix {} over {} connections",
+			ranges_count,
+			HexDisplay::from(&prefix),
+			clients.len(),
+		);
+
+		let fetches =
+			clients.iter().zip(ranges.chunks(ranges_per_client)).map(|(client, ranges)| {
+				let (prefix, downloaded, done) = (&prefix, &downloaded, &done);
+				async move {
+					let mut key_values = vec![];
+					for range in ranges {
+						let keys = self.rpc_get_keys_paged(client, range.clone(), at).await?;
+						key_values
+							.extend(self.rpc_get_values_paged(client, keys, at, downloaded).await?);
+						let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+						if done % 16 == 0 {
+							log::info!(
+								target: LOG_TARGET,
+								"progress of prefix {} = {:.2} [{} / {} ranges, ~{:.2} MiB]",
+								HexDisplay::from(prefix),
+								done as f64 / ranges_count as f64,
+								done,
+								ranges_count,
+								as_mib(downloaded.load(Ordering::Relaxed)),
+							);
+						}
+					}
+					Ok::<_, &'static str>(key_values)
+				}
+			});
+		// the ranges, and thus the chunks of them, are in order.
+		key_values.extend(try_join_all(fetches).await?.into_iter().flatten());
+
+		Ok(key_values)
+	}
+
+	/// Get the values of `keys` at `at` through `client`, in batches.
+	///
+	/// The size of the fetched key-values is added to `downloaded`, which is checked against the
+	/// memory limit.
+	async fn rpc_get_values_paged(
+		&self,
+		client: &RpcClient,
+		keys: Vec<StorageKey>,
+		at: B::Hash,
+		downloaded: &AtomicUsize,
+	) -> Result<Vec<KeyValue>, &'static str> {
 		let keys_count = keys.len();
 		log::debug!(target: LOG_TARGET, "Querying a total of {} keys", keys.len());
 
 		let mut key_values: Vec<KeyValue> = vec![];
-		let client = self.as_online().rpc_client();
 		for chunk_keys in keys.chunks(BATCH_SIZE) {
-			let batch = chunk_keys
-				.iter()
-				.cloned()
-				.map(|key| ("state_getStorage", rpc_params![key, at]))
-				.collect::<Vec<_>>();
-
-			let values = client.batch_request::<Option<StorageData>>(batch).await.map_err(|e| {
+			let values = batch_storage(client, chunk_keys, Some(at)).await.map_err(|e| {
 				log::error!(
 					target: LOG_TARGET,
 					"failed to execute batch: {:?}. Error: {:?}",
@@ -354,12 +738,14 @@ where
 
 			assert_eq!(chunk_keys.len(), values.len());
 
+			let mut size = 0;
 			for (idx, key) in chunk_keys.iter().enumerate() {
 				let maybe_value = values[idx].clone();
 				let value = maybe_value.unwrap_or_else(|| {
 					log::warn!(target: LOG_TARGET, "key {:?} had none corresponding value.", &key);
 					StorageData(vec![])
 				});
+				size += key.0.len() + value.0.len();
 				key_values.push((key.clone(), value));
 				if key_values.len() % (10 * BATCH_SIZE) == 0 {
 					let ratio: f64 = key_values.len() as f64 / keys_count as f64;
@@ -372,11 +758,48 @@ where
 					);
 				}
 			}
+			let size = downloaded.fetch_add(size, Ordering::Relaxed) + size;
+			self.ensure_memory_limit(size, "the downloaded state")?;
 		}
 
 		Ok(key_values)
 	}
 
+	/// Get the last changes of the values of `keys` in the blocks after `from`, up to and including
+	/// `to`, or `None` for the keys that were removed.
+	///
+	/// `state_queryStorage` reports the values at `from` as well, which are skipped. Queried in
+	/// batches of keys, as the node iterates over all the blocks for each key.
+	async fn rpc_query_changes(
+		&self,
+		keys: &[StorageKey],
+		from: B::Hash,
+		to: B::Hash,
+	) -> Result<BTreeMap<StorageKey, Option<StorageData>>, &'static str> {
+		let client = self.as_online().rpc_client();
+		let mut changes = BTreeMap::new();
+		for chunk_keys in keys.chunks(BATCH_SIZE) {
+			let change_sets =
+				StateApi::<B::Hash>::query_storage(client, chunk_keys.to_vec(), from, Some(to))
+					.await
+					.map_err(|e| {
+						error!(target: LOG_TARGET, "Error = {:?}", e);
+						"rpc query_storage failed."
+					})?;
+			// the change sets are in the order of the blocks, hence the last change wins.
+			for change_set in change_sets.into_iter().filter(|c| c.block != from) {
+				changes.extend(change_set.changes);
+			}
+			log::debug!(
+				target: LOG_TARGET,
+				"queried the changes of {} keys, {} changed so far",
+				chunk_keys.len(),
+				changes.len(),
+			);
+		}
+		Ok(changes)
+	}
+
 	/// Get the values corresponding to `child_keys` at the given `prefixed_top_key`.
 	pub(crate) async fn rpc_child_get_storage_paged(
 		&self,
@@ -385,36 +808,24 @@ where
 		at: B::Hash,
 	) -> Result<Vec<KeyValue>, &'static str> {
 		let mut child_kv_inner = vec![];
+		let child_storage_key = PrefixedStorageKey::new(prefixed_top_key.as_ref().to_vec());
 		for batch_child_key in child_keys.chunks(BATCH_SIZE) {
-			let batch_request = batch_child_key
-				.iter()
-				.cloned()
-				.map(|key| {
-					(
-						"childstate_getStorage",
-						rpc_params![
-							PrefixedStorageKey::new(prefixed_top_key.as_ref().to_vec()),
-							key,
-							at
-						],
-					)
-				})
-				.collect::<Vec<_>>();
-
-			let batch_response = self
-				.as_online()
-				.rpc_client()
-				.batch_request::<Option<StorageData>>(batch_request)
-				.await
-				.map_err(|e| {
-					log::error!(
-						target: LOG_TARGET,
-						"failed to execute batch: {:?}. Error: {:?}",
-						batch_child_key,
-						e
-					);
-					"batch failed."
-				})?;
+			let batch_response = batch_child_storage(
+				self.as_online().rpc_client(),
+				&child_storage_key,
+				batch_child_key,
+				Some(at),
+			)
+			.await
+			.map_err(|e| {
+				log::error!(
+					target: LOG_TARGET,
+					"failed to execute batch: {:?}. Error: {:?}",
+					batch_child_key,
+					e
+				);
+				"batch failed."
+			})?;
 
 			assert_eq!(batch_child_key.len(), batch_response.len());
 
@@ -462,77 +873,309 @@ where
 	}
 }
 
+/// Save the given data to the top keys snapshot.
+fn save_top_snapshot(
+	data: &[KeyValue],
+	path: &PathBuf,
+	state_version: StateVersion,
+	origin: SnapshotOrigin,
+) -> Result<(), &'static str> {
+	let mut path = path.clone();
+	let encoded = encode_snapshot(data.encode(), state_version, origin);
+	path.set_extension("top");
+	debug!(target: LOG_TARGET, "writing {} bytes to state snapshot file {:?}", encoded.len(), path);
+	fs::write(path, encoded).map_err(|_| "fs::write failed.")?;
+	Ok(())
+}
+
+/// Save the given data to the child keys snapshot.
+fn save_child_snapshot(
+	data: &ChildKeyValues,
+	path: &PathBuf,
+	state_version: StateVersion,
+	origin: SnapshotOrigin,
+) -> Result<(), &'static str> {
+	let mut path = path.clone();
+	path.set_extension("child");
+	let encoded = encode_snapshot(data.encode(), state_version, origin);
+	debug!(target: LOG_TARGET, "writing {} bytes to state snapshot file {:?}", encoded.len(), path);
+	fs::write(path, encoded).map_err(|_| "fs::write failed.")?;
+	Ok(())
+}
+
+/// Save the entire state of `backend`, including all of its default child trees, as a state
+/// snapshot to `path`.
+///
+/// The snapshot can be loaded again with [`Mode::Offline`], and `state_version`. As the state of
+/// `backend` is not necessarily that of a block, the header of the snapshot names no chain and
+/// block.
+pub fn save_snapshot<H: Hasher, S: Backend<H>>(
+	backend: &S,
+	path: &PathBuf,
+	state_version: StateVersion,
+) -> Result<(), &'static str> {
+	let top_kv = backend
+		.pairs()
+		.into_iter()
+		.map(|(k, v)| (StorageKey(k), StorageData(v)))
+		.collect::<Vec<_>>();
+
+	let mut child_kv = vec![];
+	for (prefixed_key, _) in &top_kv {
+		if let Some(storage_key) = prefixed_key.0.strip_prefix(DEFAULT_CHILD_STORAGE_KEY_PREFIX) {
+			let info = ChildInfo::new_default(storage_key);
+			let mut kv = vec![];
+			for key in backend.child_keys(&info, &[]) {
+				let value = backend
+					.child_storage(&info, &key)
+					.map_err(|_| "failed to read child storage")?
+					.ok_or("child key without value")?;
+				kv.push((StorageKey(key), StorageData(value)));
+			}
+			child_kv.push((info, kv));
+		}
+	}
+
+	info!(
+		target: LOG_TARGET,
+		"saving {} top keys and {} child trees to state snapshot {:?}",
+		top_kv.len(),
+		child_kv.len(),
+		path
+	);
+	save_top_snapshot(&top_kv, path, state_version, (None, None))?;
+	save_child_snapshot(&child_kv, path, state_version, (None, None))
+}
+
+/// Bring `base`, the key-values of a state snapshot, up to date with `keys`, all the keys of the
+/// newer state.
+///
+/// `changes` are those of the values of the keys of `base` since, and `fresh` the key-values of
+/// the keys that are not in `base`. The keys of `base` that are not among `keys` were removed.
+fn update_key_values(
+	base: TopKeyValues,
+	keys: Vec<StorageKey>,
+	mut changes: BTreeMap<StorageKey, Option<StorageData>>,
+	fresh: TopKeyValues,
+) -> TopKeyValues {
+	let mut values = base.into_iter().chain(fresh).collect::<BTreeMap<_, _>>();
+	keys.into_iter()
+		.filter_map(|key| {
+			let value = values.remove(&key);
+			changes.remove(&key).unwrap_or(value).map(|value| (key, value))
+		})
+		.collect()
+}
+
 // Internal methods
 impl<B: BlockT> Builder<B>
 where
 	B::Hash: DeserializeOwned,
 	B::Header: DeserializeOwned,
 {
-	/// Save the given data to the top keys snapshot.
-	fn save_top_snapshot(&self, data: &[KeyValue], path: &PathBuf) -> Result<(), &'static str> {
-		let mut path = path.clone();
-		let encoded = data.encode();
-		path.set_extension("top");
-		debug!(
-			target: LOG_TARGET,
-			"writing {} bytes to state snapshot file {:?}",
-			encoded.len(),
-			path
-		);
-		fs::write(path, encoded).map_err(|_| "fs::write failed.")?;
-		Ok(())
+	fn load_top_snapshot(&self, path: &PathBuf) -> Result<TopKeyValues, &'static str> {
+		self.load_top_snapshot_with_header(path).map(|(_, kv)| kv)
 	}
 
-	/// Save the given data to the child keys snapshot.
-	fn save_child_snapshot(
+	fn load_top_snapshot_with_header(
 		&self,
-		data: &ChildKeyValues,
 		path: &PathBuf,
-	) -> Result<(), &'static str> {
-		let mut path = path.clone();
-		path.set_extension("child");
-		let encoded = data.encode();
-		debug!(
-			target: LOG_TARGET,
-			"writing {} bytes to state snapshot file {:?}",
-			encoded.len(),
-			path
-		);
-		fs::write(path, encoded).map_err(|_| "fs::write failed.")?;
-		Ok(())
-	}
-
-	fn load_top_snapshot(&self, path: &PathBuf) -> Result<TopKeyValues, &'static str> {
+	) -> Result<(Option<SnapshotHeader>, TopKeyValues), &'static str> {
 		let mut path = path.clone();
 		path.set_extension("top");
 		info!(target: LOG_TARGET, "loading top key-pairs from snapshot {:?}", path);
-		let bytes = fs::read(path).map_err(|_| "fs::read failed.")?;
-		Decode::decode(&mut &*bytes).map_err(|e| {
+		let bytes = fs::read(&path).map_err(|_| "fs::read failed.")?;
+		let (header, mut payload) = decode_snapshot(&bytes, &path, self.state_version)?;
+		let kv = Decode::decode(&mut payload).map_err(|e| {
 			log::error!(target: LOG_TARGET, "{:?}", e);
 			"decode failed"
-		})
+		})?;
+		Ok((header, kv))
 	}
 
 	fn load_child_snapshot(&self, path: &PathBuf) -> Result<ChildKeyValues, &'static str> {
 		let mut path = path.clone();
 		path.set_extension("child");
 		info!(target: LOG_TARGET, "loading child key-pairs from snapshot {:?}", path);
-		let bytes = fs::read(path).map_err(|_| "fs::read failed.")?;
-		Decode::decode(&mut &*bytes).map_err(|e| {
+		let bytes = fs::read(&path).map_err(|_| "fs::read failed.")?;
+		let (_, mut payload) = decode_snapshot(&bytes, &path, self.state_version)?;
+		Decode::decode(&mut payload).map_err(|e| {
 			log::error!(target: LOG_TARGET, "{:?}", e);
 			"decode failed"
 		})
 	}
 
+	/// Replace `error`, which occurred while scraping, with a clearer one if the state of the
+	/// pinned block is no longer available.
+	async fn scrape_error(&self, error: &'static str) -> &'static str {
+		let at = self.as_online().at.expect("at must be initialized in online mode.");
+		self.ensure_state_available(at).await.err().unwrap_or(error)
+	}
+
 	/// Load all the `top` keys from the remote config, and maybe write then to cache.
 	async fn load_top_remote_and_maybe_save(&self) -> Result<TopKeyValues, &'static str> {
-		let top_kv = self.load_top_remote().await?;
+		let top_kv = match self.load_top_remote().await {
+			Ok(top_kv) => top_kv,
+			Err(e) => return Err(self.scrape_error(e).await),
+		};
 		if let Some(c) = &self.as_online().state_snapshot {
-			self.save_top_snapshot(&top_kv, &c.path)?;
+			save_top_snapshot(&top_kv, &c.path, self.state_version, self.origin.clone())?;
 		}
 		Ok(top_kv)
 	}
 
+	/// Bring the state snapshot at `path` up to date with the pinned block, see
+	/// [`OnlineConfig::update_snapshot`], and maybe write it to cache.
+	async fn update_remote_and_maybe_save(
+		&self,
+		path: &PathBuf,
+	) -> Result<(TopKeyValues, ChildKeyValues), &'static str> {
+		let (top_kv, child_kv) = match self.update_remote(path).await {
+			Ok(kv) => kv,
+			Err(e) => return Err(self.scrape_error(e).await),
+		};
+		if let Some(c) = &self.as_online().state_snapshot {
+			save_top_snapshot(&top_kv, &c.path, self.state_version, self.origin.clone())?;
+			save_child_snapshot(&child_kv, &c.path, self.state_version, self.origin.clone())?;
+		}
+		Ok((top_kv, child_kv))
+	}
+
+	/// Bring the state snapshot at `path` up to date with the pinned block, see
+	/// [`OnlineConfig::update_snapshot`].
+	async fn update_remote(
+		&self,
+		path: &PathBuf,
+	) -> Result<(TopKeyValues, ChildKeyValues), &'static str> {
+		let (header, base_top_kv) = self.load_top_snapshot_with_header(path)?;
+		let header = header.ok_or_else(|| {
+			error!(target: LOG_TARGET, "{:?} has no header, hence it cannot be updated", path);
+			"snapshot without a header."
+		})?;
+		let (base_hash, base_number) = header.block.ok_or_else(|| {
+			error!(
+				target: LOG_TARGET,
+				"the header of {:?} names no block, hence it cannot be updated",
+				path,
+			);
+			"snapshot of an unknown block."
+		})?;
+		let base_hash = B::Hash::decode(&mut &*base_hash)
+			.map_err(|_| "invalid block hash in the snapshot header.")?;
+		if let (Some(snapshot_chain), Some(chain)) = (&header.chain, &self.origin.0) {
+			if snapshot_chain != chain {
+				error!(
+					target: LOG_TARGET,
+					"{:?} is a snapshot of chain {}, but the remote node is of chain {}",
+					path,
+					snapshot_chain,
+					chain,
+				);
+				return Err("snapshot of another chain.")
+			}
+		}
+		if let Some((_, number)) = &self.origin.1 {
+			if *number < base_number {
+				error!(
+					target: LOG_TARGET,
+					"{:?} is a snapshot of block #{}, which is newer than block #{}",
+					path,
+					base_number,
+					number,
+				);
+				return Err("snapshot of a newer block.")
+			}
+		}
+
+		let at = self.as_online().at.expect("at must be initialized in online mode.");
+		info!(
+			target: LOG_TARGET,
+			"updating the snapshot of block #{} ({:?}) to {:?}",
+			base_number,
+			base_hash,
+			at,
+		);
+
+		// list the keys anew, the same ones as `load_top_remote` scrapes.
+		let client = self.as_online().rpc_client();
+		let mut prefixes = self
+			.pallet_prefixes(at)
+			.await?
+			.iter()
+			.map(|p| StorageKey(twox_128(p.as_bytes()).to_vec()))
+			.collect::<Vec<_>>();
+		if prefixes.is_empty() {
+			prefixes.push(StorageKey(vec![]));
+		}
+		prefixes.extend(self.hashed_prefixes.iter().cloned().map(StorageKey));
+		let mut keys = vec![];
+		for prefix in prefixes {
+			keys.extend(self.rpc_get_keys_paged(client, prefix, at).await?);
+		}
+		keys.extend(self.hashed_keys.iter().cloned().map(StorageKey));
+
+		let base_keys = base_top_kv.iter().map(|(k, _)| k).collect::<BTreeSet<_>>();
+		let (known, fresh): (Vec<_>, Vec<_>) =
+			keys.iter().cloned().partition(|k| base_keys.contains(k));
+		let removed = base_keys.len().saturating_sub(known.len());
+
+		let changes = self.rpc_query_changes(&known, base_hash, at).await?;
+		let fresh = self.rpc_get_values_paged(client, fresh, at, &AtomicUsize::new(0)).await?;
+		info!(
+			target: LOG_TARGET,
+			"{} of the {} keys of the snapshot changed, {} were removed and {} were added",
+			changes.len(),
+			base_top_kv.len(),
+			removed,
+			fresh.len(),
+		);
+
+		// the values of the child roots are needed to tell which child trees changed.
+		let base_roots = base_top_kv
+			.iter()
+			.filter(|(k, _)| is_default_child_storage_key(k.as_ref()))
+			.cloned()
+			.collect::<BTreeMap<_, _>>();
+		let top_kv = update_key_values(base_top_kv, keys, changes, fresh);
+		self.ensure_memory_limit(kv_size(&top_kv), "the downloaded state")?;
+
+		if !self.as_online().scrape_children {
+			info!(target: LOG_TARGET, "not scraping child-tree data");
+			return Ok((top_kv, vec![]))
+		}
+
+		// the child trees whose root did not change are kept, the others are scraped anew.
+		let mut base_child_kv = self
+			.load_child_snapshot(path)
+			.map_err(
+				|why| log::warn!(target: LOG_TARGET, "failed to load child-key file due to {:?}.", why),
+			)
+			.unwrap_or_default()
+			.into_iter()
+			.map(|(info, kv)| (info.prefixed_storage_key().into_inner(), (info, kv)))
+			.collect::<BTreeMap<_, _>>();
+		let mut child_kv = vec![];
+		let mut stale_roots = vec![];
+		for (key, value) in top_kv.iter().filter(|(k, _)| is_default_child_storage_key(k.as_ref()))
+		{
+			match base_child_kv.remove(&key.0) {
+				Some(child) if base_roots.get(key) == Some(value) => child_kv.push(child),
+				_ => stale_roots.push((key.clone(), value.clone())),
+			}
+		}
+		info!(
+			target: LOG_TARGET,
+			"{} child trees are unchanged, scraping the {} others",
+			child_kv.len(),
+			stale_roots.len(),
+		);
+		child_kv.extend(self.load_child_remote(&stale_roots).await?);
+
+		let size = kv_size(&top_kv) + child_kv.iter().map(|(_, kv)| kv_size(kv)).sum::<usize>();
+		self.ensure_memory_limit(size, "the downloaded state")?;
+		Ok((top_kv, child_kv))
+	}
+
 	/// Load all of the child keys from the remote config, given the already scraped list of top key
 	/// pairs.
 	///
@@ -541,9 +1184,17 @@ where
 		&self,
 		top_kv: &[KeyValue],
 	) -> Result<ChildKeyValues, &'static str> {
-		let child_kv = self.load_child_remote(top_kv).await?;
+		let child_kv = if !self.as_online().scrape_children {
+			info!(target: LOG_TARGET, "not scraping child-tree data");
+			vec![]
+		} else {
+			match self.load_child_remote(top_kv).await {
+				Ok(child_kv) => child_kv,
+				Err(e) => return Err(self.scrape_error(e).await),
+			}
+		};
 		if let Some(c) = &self.as_online().state_snapshot {
-			self.save_child_snapshot(&child_kv, &c.path)?;
+			save_child_snapshot(&child_kv, &c.path, self.state_version, self.origin.clone())?;
 		}
 		Ok(child_kv)
 	}
@@ -566,6 +1217,7 @@ where
 		);
 
 		let mut child_kv = vec![];
+		let mut size = kv_size(top_kv);
 		for prefixed_top_key in child_roots {
 			let at = self.as_online().at.expect("at must be initialized in online mode.");
 			let child_keys =
@@ -582,6 +1234,8 @@ where
 				},
 			};
 
+			size += kv_size(&child_kv_inner);
+			self.ensure_memory_limit(size, "the downloaded state")?;
 			child_kv.push((ChildInfo::new_default(un_prefixed), child_kv_inner));
 		}
 
@@ -590,16 +1244,16 @@ where
 
 	/// Build `Self` from a network node denoted by `uri`.
 	async fn load_top_remote(&self) -> Result<TopKeyValues, &'static str> {
-		let config = self.as_online();
 		let at = self
 			.as_online()
 			.at
 			.expect("online config must be initialized by this point; qed.");
 		log::info!(target: LOG_TARGET, "scraping key-pairs from remote @ {:?}", at);
 
-		let mut keys_and_values = if config.pallets.len() > 0 {
+		let pallets = self.pallet_prefixes(at).await?;
+		let mut keys_and_values = if pallets.len() > 0 {
 			let mut filtered_kv = vec![];
-			for p in config.pallets.iter() {
+			for p in pallets.iter() {
 				let hashed_prefix = StorageKey(twox_128(p.as_bytes()).to_vec());
 				let pallet_kv = self.rpc_get_pairs_paged(hashed_prefix.clone(), at).await?;
 				log::info!(
@@ -610,6 +1264,7 @@ where
 					HexDisplay::from(&hashed_prefix),
 				);
 				filtered_kv.extend(pallet_kv);
+				self.ensure_memory_limit(kv_size(&filtered_kv), "the downloaded state")?;
 			}
 			filtered_kv
 		} else {
@@ -643,8 +1298,14 @@ where
 	}
 
 	pub(crate) async fn init_remote_client(&mut self) -> Result<(), &'static str> {
-		// First, initialize the ws client.
-		self.as_online_mut().transport.map_uri().await?;
+		// First, initialize the rpc client, and the parallel ones, if any.
+		for _ in 1..self.as_online().threads.get() {
+			let mut transport = self.as_online().transport.clone();
+			transport.map_uri(self.rpc_config).await?;
+			self.parallel_transports.push(transport);
+		}
+		let rpc_config = self.rpc_config;
+		self.as_online_mut().transport.map_uri(rpc_config).await?;
 
 		// Then, if `at` is not set, set it.
 		if self.as_online().at.is_none() {
@@ -652,17 +1313,54 @@ where
 			self.as_online_mut().at = Some(at);
 		}
 
+		// All queries are pinned to `at`, which must thus be known to the remote node.
+		let at = self.as_online().at.expect("at was just set; qed");
+		let header =
+			ChainApi::<(), _, B::Header, ()>::header(self.as_online().rpc_client(), Some(at))
+				.await
+				.map_err(|e| {
+					error!(target: LOG_TARGET, "Error = {:?}", e);
+					"rpc header failed."
+				})?
+				.ok_or_else(|| {
+					error!(target: LOG_TARGET, "block {:?} not found on the remote node", at);
+					"block not found."
+				})?;
+		self.ensure_state_available(at).await?;
+		info!(target: LOG_TARGET, "pinned all queries to block #{} ({:?})", header.number(), at);
+
+		// only recorded in the header of a state snapshot, hence not worth failing over.
+		let chain = self
+			.as_online()
+			.rpc_client()
+			.request::<String>("system_chain", rpc_params![])
+			.await
+			.map_err(|e| warn!(target: LOG_TARGET, "failed to fetch the chain name: {:?}", e))
+			.ok();
+		let number: u64 = (*header.number()).unique_saturated_into();
+		self.origin = (chain, Some((at.as_ref().to_vec(), number)));
+
 		Ok(())
 	}
 
 	pub(crate) async fn pre_build(
 		mut self,
 	) -> Result<(TopKeyValues, ChildKeyValues), &'static str> {
+		// the child trees of an updated snapshot, which are updated along with its top keys.
+		let mut updated_child_kv = None;
 		let mut top_kv = match self.mode.clone() {
 			Mode::Offline(config) => self.load_top_snapshot(&config.state_snapshot.path)?,
-			Mode::Online(_) => {
+			Mode::Online(config) => {
 				self.init_remote_client().await?;
-				self.load_top_remote_and_maybe_save().await?
+				match config.update_snapshot {
+					Some(update) => {
+						let (top_kv, child_kv) =
+							self.update_remote_and_maybe_save(&update.path).await?;
+						updated_child_kv = Some(child_kv);
+						top_kv
+					},
+					None => self.load_top_remote_and_maybe_save().await?,
+				}
 			},
 			Mode::OfflineOrElseOnline(offline_config, _) => {
 				if let Ok(kv) = self.load_top_snapshot(&offline_config.state_snapshot.path) {
@@ -694,8 +1392,11 @@ where
 			top_kv.retain(|(k, _)| !self.hashed_blacklist.contains(&k.0))
 		}
 
-		let child_kv = match self.mode.clone() {
-			Mode::Online(_) => self.load_child_remote_and_maybe_save(&top_kv).await?,
+		let mut child_kv = match self.mode.clone() {
+			Mode::Online(_) => match updated_child_kv {
+				Some(child_kv) => child_kv,
+				None => self.load_child_remote_and_maybe_save(&top_kv).await?,
+			},
 			Mode::OfflineOrElseOnline(offline_config, _) => {
 				if let Ok(kv) = self.load_child_snapshot(&offline_config.state_snapshot.path) {
 					kv
@@ -715,6 +1416,24 @@ where
 				.unwrap_or_default(),
 		};
 
+		// inject manual child key values.
+		if !self.child_key_values.is_empty() {
+			log::info!(
+				target: LOG_TARGET,
+				"extending externalities with {} manually injected child trees",
+				self.child_key_values.len()
+			);
+			for (info, key_values) in &self.child_key_values {
+				match child_kv.iter_mut().find(|(i, _)| i.storage_key() == info.storage_key()) {
+					Some((_, kv)) => kv.extend(key_values.iter().cloned()),
+					None => child_kv.push((info.clone(), key_values.clone())),
+				}
+			}
+		}
+
+		let size = kv_size(&top_kv) + child_kv.iter().map(|(_, kv)| kv_size(kv)).sum::<usize>();
+		self.ensure_memory_limit(size, "the state")?;
+
 		Ok((top_kv, child_kv))
 	}
 }
@@ -734,6 +1453,18 @@ impl<B: BlockT> Builder<B> {
 		self
 	}
 
+	/// Inject a manual list of key and values to the default child tree of `child_info`.
+	///
+	/// The key-values are added to those of the child tree, if it is scraped or loaded as well.
+	pub fn inject_child_key_value(
+		mut self,
+		child_info: ChildInfo,
+		injections: &[KeyValue],
+	) -> Self {
+		self.child_key_values.push((child_info, injections.to_vec()));
+		self
+	}
+
 	/// Inject a hashed prefix. This is treated as-is, and should be pre-hashed.
 	///
 	/// This should be used to inject a "PREFIX", like a storage (double) map.
@@ -758,6 +1489,15 @@ impl<B: BlockT> Builder<B> {
 		self.inject_hashed_prefix(DEFAULT_CHILD_STORAGE_KEY_PREFIX)
 	}
 
+	/// Scrape the default child tree with the (unprefixed) `storage_key`, e.g. the one of a
+	/// crowdloan fund, regardless of the pallets that are scraped.
+	///
+	/// This injects the root of the child tree into [`Self::inject_hashed_key`].
+	pub fn inject_child_tree(self, storage_key: &[u8]) -> Self {
+		let prefixed = ChildInfo::new_default(storage_key).prefixed_storage_key();
+		self.inject_hashed_key(&prefixed)
+	}
+
 	/// Inject a hashed key to scrape. This is treated as-is, and should be pre-hashed.
 	///
 	/// This should be used to inject a "KEY", like a storage value.
@@ -785,6 +1525,36 @@ impl<B: BlockT> Builder<B> {
 		self
 	}
 
+	/// Limit the approximate memory, in bytes, that the state may consume, or remove the limit if
+	/// `None`.
+	///
+	/// Building fails as soon as the limit is exceeded, instead of (potentially hours later)
+	/// running out of memory.
+	pub fn max_memory(mut self, max_memory: Option<usize>) -> Self {
+		self.max_memory = max_memory;
+		self
+	}
+
+	/// Retry each RPC request that fails because of the connection up to `retries` times, with
+	/// an exponential backoff, and reconnecting in between. Defaults to no retries.
+	///
+	/// Each page of keys and batch of values is retried on its own, hence a scrape resumes from
+	/// the last fetched key. Only has an effect if [`Transport::Uri`] is used.
+	pub fn rpc_retries(mut self, retries: u32) -> Self {
+		self.rpc_config.retries = retries;
+		self
+	}
+
+	/// The settings of the RPC clients, e.g. their timeouts, see [`ClientConfig`]. Defaults to
+	/// [`ClientConfig::default`].
+	///
+	/// This includes the retries of [`Self::rpc_retries`], hence it overrides them if called
+	/// afterwards. Only has an effect if [`Transport::Uri`] is used.
+	pub fn rpc_config(mut self, config: ClientConfig) -> Self {
+		self.rpc_config = config;
+		self
+	}
+
 	/// overwrite the `at` value, if `mode` is set to [`Mode::Online`].
 	///
 	/// noop if `mode` is [`Mode::Offline`]
@@ -806,6 +1576,7 @@ where
 	pub async fn build(self) -> Result<TestExternalities, &'static str> {
 		let state_version = self.state_version;
 		let (top_kv, child_kv) = self.pre_build().await?;
+		let size = kv_size(&top_kv) + child_kv.iter().map(|(_, kv)| kv_size(kv)).sum::<usize>();
 		let mut ext = TestExternalities::new_with_code_and_state(
 			Default::default(),
 			Default::default(),
@@ -836,8 +1607,9 @@ where
 		ext.commit_all().unwrap();
 		info!(
 			target: LOG_TARGET,
-			"initialized state externalities with storage root {:?}",
-			ext.as_backend().root()
+			"initialized state externalities with storage root {:?}, consuming ~{:.2} MiB",
+			ext.as_backend().root(),
+			as_mib(size),
 		);
 
 		Ok(ext)
@@ -906,6 +1678,123 @@ mod tests {
 			.expect("Can't read state snapshot file")
 			.execute_with(|| assert!(sp_io::storage::get(&some_key).is_none()));
 	}
+
+	#[tokio::test]
+	async fn memory_limit_is_enforced() {
+		init_logger();
+		let builder = || {
+			Builder::<Block>::new().mode(Mode::Offline(OfflineConfig {
+				state_snapshot: SnapshotConfig::new("test_data/proxy_test"),
+			}))
+		};
+
+		assert!(builder().max_memory(Some(1)).build().await.is_err());
+		assert!(builder().max_memory(Some(usize::MAX)).build().await.is_ok());
+	}
+
+	#[tokio::test]
+	async fn snapshot_header_is_validated() {
+		init_logger();
+		let path = PathBuf::from("snapshot_header_is_validated_data");
+		let ext = Builder::<Block>::new()
+			.mode(Mode::Offline(OfflineConfig {
+				state_snapshot: SnapshotConfig::new("test_data/proxy_test"),
+			}))
+			.build()
+			.await
+			.expect("Can't read state snapshot file");
+		save_snapshot(&ext.backend, &path, StateVersion::V1).unwrap();
+
+		let builder = |state_version| {
+			Builder::<Block>::new()
+				.mode(Mode::Offline(OfflineConfig { state_snapshot: SnapshotConfig::new(&path) }))
+				.state_version(state_version)
+		};
+		assert!(builder(StateVersion::V1).build().await.is_ok());
+		assert_eq!(
+			builder(StateVersion::V0).build().await.err(),
+			Some("snapshot state version mismatch.")
+		);
+
+		// corrupt the last byte of the key-values.
+		let top = path.with_extension("top");
+		let original = fs::read(&top).unwrap();
+		let mut bytes = original.clone();
+		*bytes.last_mut().unwrap() ^= 1;
+		fs::write(&top, &bytes).unwrap();
+		assert_eq!(
+			builder(StateVersion::V1).build().await.err(),
+			Some("snapshot checksum mismatch.")
+		);
+
+		// a snapshot without a header, as created by older versions, is still loaded, whatever
+		// the state version.
+		let (_, payload) = decode_snapshot(&original, &top, StateVersion::V1).unwrap();
+		fs::write(&top, payload).unwrap();
+		let child = path.with_extension("child");
+		let child_bytes = fs::read(&child).unwrap();
+		fs::write(&child, decode_snapshot(&child_bytes, &child, StateVersion::V1).unwrap().1)
+			.unwrap();
+		assert!(builder(StateVersion::V1).build().await.is_ok());
+		assert!(builder(StateVersion::V0).build().await.is_ok());
+
+		// but it must still decode.
+		fs::write(&top, &payload[..payload.len() - 1]).unwrap();
+		assert_eq!(builder(StateVersion::V1).build().await.err(), Some("decode failed"));
+
+		fs::remove_file(&top).unwrap();
+		fs::remove_file(&child).unwrap();
+	}
+
+	#[tokio::test]
+	async fn child_key_values_are_injected_and_saved() {
+		init_logger();
+		let path = PathBuf::from("child_key_values_are_injected_and_saved_data");
+		let child_info = ChildInfo::new_default(b"some_child");
+		let key_value = (StorageKey(b"key".to_vec()), StorageData(b"value".to_vec()));
+		let ext = Builder::<Block>::new()
+			.mode(Mode::Offline(OfflineConfig {
+				state_snapshot: SnapshotConfig::new("test_data/proxy_test"),
+			}))
+			.inject_child_key_value(child_info.clone(), &[key_value])
+			.build()
+			.await
+			.expect("Can't read state snapshot file");
+		save_snapshot(&ext.backend, &path, StateVersion::V1).unwrap();
+
+		Builder::<Block>::new()
+			.mode(Mode::Offline(OfflineConfig { state_snapshot: SnapshotConfig::new(&path) }))
+			.build()
+			.await
+			.expect("Can't read state snapshot file")
+			.execute_with(|| {
+				assert_eq!(
+					sp_io::default_child_storage::get(child_info.storage_key(), b"key"),
+					Some(b"value".to_vec())
+				);
+			});
+
+		fs::remove_file(path.with_extension("top")).unwrap();
+		fs::remove_file(path.with_extension("child")).unwrap();
+	}
+
+	#[test]
+	fn key_values_are_updated() {
+		let kv = |k: &[u8], v: &[u8]| (StorageKey(k.to_vec()), StorageData(v.to_vec()));
+		let key = |k: &[u8]| StorageKey(k.to_vec());
+		let base = vec![kv(b"a", b"1"), kv(b"b", b"2"), kv(b"c", b"3"), kv(b"d", b"4")];
+
+		// `b` changed, `c` was removed in a later block, `d` was removed from the keys, and `e`
+		// is new.
+		let changes = vec![(key(b"b"), Some(StorageData(b"5".to_vec()))), (key(b"c"), None)]
+			.into_iter()
+			.collect();
+		let keys = vec![key(b"a"), key(b"b"), key(b"c"), key(b"e")];
+		assert_eq!(
+			update_key_values(base, keys, changes, vec![kv(b"e", b"6")]),
+			vec![kv(b"a", b"1"), kv(b"b", b"5"), kv(b"e", b"6")],
+		);
+	}
 }
 
 #[cfg(all(test, feature = "remote-test"))]
@@ -967,6 +1856,43 @@ mod remote_tests {
 		}
 	}
 
+	#[tokio::test]
+	async fn unknown_block_is_an_error() {
+		init_logger();
+		assert!(Builder::<Block>::new()
+			.mode(Mode::Online(OnlineConfig {
+				at: Some(Hash::repeat_byte(0xff)),
+				pallets: vec!["Proxy".to_owned()],
+				..Default::default()
+			}))
+			.build()
+			.await
+			.is_err());
+	}
+
+	#[tokio::test]
+	async fn parallel_fetch_matches_sequential() {
+		init_logger();
+		let config = |threads| OnlineConfig {
+			pallets: vec!["Proxy".to_owned()],
+			threads: NonZeroUsize::new(threads).unwrap(),
+			..Default::default()
+		};
+
+		let mut builder = Builder::<Block>::new().mode(Mode::Online(config(1)));
+		builder.init_remote_client().await.expect(REMOTE_INACCESSIBLE);
+		let at = builder.as_online().at;
+		let (sequential, _) = builder.pre_build().await.expect(REMOTE_INACCESSIBLE);
+
+		let (parallel, _) = Builder::<Block>::new()
+			.mode(Mode::Online(OnlineConfig { at, ..config(4) }))
+			.pre_build()
+			.await
+			.expect(REMOTE_INACCESSIBLE);
+		assert!(!sequential.is_empty());
+		assert_eq!(sequential, parallel);
+	}
+
 	#[tokio::test]
 	#[ignore = "too slow"]
 	async fn can_build_one_big_pallet() {
@@ -1008,6 +1934,47 @@ mod remote_tests {
 			.execute_with(|| {});
 	}
 
+	#[tokio::test]
+	async fn can_build_over_http() {
+		init_logger();
+		Builder::<Block>::new()
+			.mode(Mode::Online(OnlineConfig {
+				transport: "https://rpc.polkadot.io:443".to_owned().into(),
+				pallets: vec!["Council".to_owned()],
+				..Default::default()
+			}))
+			.build()
+			.await
+			.expect(REMOTE_INACCESSIBLE)
+			.execute_with(|| {});
+	}
+
+	#[tokio::test]
+	async fn can_resolve_pallets_from_metadata() {
+		init_logger();
+		Builder::<Block>::new()
+			.mode(Mode::Online(OnlineConfig {
+				transport: "wss://kusama-rpc.polkadot.io:443".to_owned().into(),
+				pallets: vec!["Council".to_owned(), "TechnicalCommittee".to_owned()],
+				exclude_pallets: vec!["TechnicalCommittee".to_owned()],
+				..Default::default()
+			}))
+			.build()
+			.await
+			.expect(REMOTE_INACCESSIBLE)
+			.execute_with(|| {});
+
+		assert!(Builder::<Block>::new()
+			.mode(Mode::Online(OnlineConfig {
+				transport: "wss://kusama-rpc.polkadot.io:443".to_owned().into(),
+				exclude_pallets: vec!["NotAPallet".to_owned()],
+				..Default::default()
+			}))
+			.build()
+			.await
+			.is_err());
+	}
+
 	#[tokio::test]
 	async fn can_build_few_pallet() {
 		init_logger();
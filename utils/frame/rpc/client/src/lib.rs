@@ -20,6 +20,9 @@
 //! It exposes a `WebSocket JSON-RPC` client that implements the RPC interface in [`sc-rpc-api`]
 //! along with some abstractions.
 //!
+//! [`rpc_client`] connects over either `WebSocket` or `HTTP`, depending on the scheme of the uri.
+//! Subscriptions are only supported over `WebSocket`.
+//!
 //! ## Usage
 //!
 //! ```no_run
@@ -36,15 +39,42 @@
 //!     StateApi::<H256>::storage(&client, StorageKey(vec![]), None).await.unwrap();
 //! }
 //! ```
+//!
+//! Many requests of the same kind are best sent as a single JSON-RPC batch, which costs one
+//! round-trip rather than one per request:
+//!
+//! ```no_run
+//! # use substrate_rpc_client::{batch_storage, ws_client};
+//! # use sp_core::{H256, storage::StorageKey};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = ws_client("ws://127.0.0.1:9944").await.unwrap();
+//!     let keys = vec![StorageKey(vec![1]), StorageKey(vec![2])];
+//!     let values = batch_storage(&client, &keys, None::<H256>).await.unwrap();
+//!     assert_eq!(values.len(), keys.len());
+//! }
+//! ```
 
 use async_trait::async_trait;
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Serialize};
+use sp_core::storage::{PrefixedStorageKey, StorageData, StorageKey};
 use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
-use std::collections::VecDeque;
+use std::{
+	collections::VecDeque,
+	future::Future,
+	sync::{Arc, RwLock},
+	time::Duration,
+};
 
 pub use jsonrpsee::{
-	core::client::{ClientT, Subscription, SubscriptionClientT},
+	core::{
+		client::{ClientT, Subscription, SubscriptionClientT},
+		Error,
+	},
+	http_client::{HttpClient, HttpClientBuilder},
 	rpc_params,
+	types::ParamsSer,
 	ws_client::{WsClient, WsClientBuilder},
 };
 pub use sc_rpc_api::{
@@ -54,18 +84,374 @@ pub use sc_rpc_api::{
 	system::SystemApiClient as SystemApi,
 };
 
+/// The settings of the clients, see [`rpc_client_with`].
+///
+/// The default settings are those of [`ws_client`], [`http_client`] and [`rpc_client`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientConfig {
+	/// Ping the node over `WebSocket` connections with this interval, which keeps idle
+	/// connections alive and detects dropped ones. `None` to never ping.
+	pub ping_interval: Option<Duration>,
+	/// The timeout of opening a `WebSocket` connection.
+	pub connection_timeout: Duration,
+	/// The timeout of a request.
+	pub request_timeout: Duration,
+	/// The maximum size of a message, in bytes.
+	pub max_message_size: u32,
+	/// The number of times that a request is retried, see [`RpcClient::with_retries`]. Only
+	/// applies to [`RpcClient`].
+	pub retries: u32,
+}
+
+impl Default for ClientConfig {
+	fn default() -> Self {
+		Self {
+			ping_interval: None,
+			connection_timeout: Duration::from_secs(60),
+			request_timeout: Duration::from_secs(60 * 10),
+			max_message_size: u32::MAX,
+			retries: 0,
+		}
+	}
+}
+
 /// Create a new `WebSocket` connection with shared settings.
 pub async fn ws_client(uri: impl AsRef<str>) -> Result<WsClient, String> {
-	WsClientBuilder::default()
-		.max_request_body_size(u32::MAX)
-		.request_timeout(std::time::Duration::from_secs(60 * 10))
-		.connection_timeout(std::time::Duration::from_secs(60))
-		.max_notifs_per_subscription(1024)
+	ws_client_with(uri, &Default::default()).await
+}
+
+/// Same as [`ws_client`], with the settings of `config`.
+pub async fn ws_client_with(
+	uri: impl AsRef<str>,
+	config: &ClientConfig,
+) -> Result<WsClient, String> {
+	let builder = WsClientBuilder::default()
+		.max_request_body_size(config.max_message_size)
+		.request_timeout(config.request_timeout)
+		.connection_timeout(config.connection_timeout)
+		.max_notifs_per_subscription(1024);
+	let builder = match config.ping_interval {
+		Some(interval) => builder.ping_interval(interval),
+		None => builder,
+	};
+	builder
 		.build(uri)
 		.await
 		.map_err(|e| format!("`WsClientBuilder` failed to build: {:?}", e))
 }
 
+/// Create a new `HTTP` client with shared settings.
+pub fn http_client(uri: impl AsRef<str>) -> Result<HttpClient, String> {
+	http_client_with(uri, &Default::default())
+}
+
+/// Same as [`http_client`], with the settings of `config`.
+pub fn http_client_with(uri: impl AsRef<str>, config: &ClientConfig) -> Result<HttpClient, String> {
+	HttpClientBuilder::default()
+		.max_request_body_size(config.max_message_size)
+		.request_timeout(config.request_timeout)
+		.build(uri)
+		.map_err(|e| format!("`HttpClientBuilder` failed to build: {:?}", e))
+}
+
+/// Create a new client with shared settings, over `HTTP` if `uri` starts with `http://` or
+/// `https://`, and over `WebSocket` otherwise.
+///
+/// Failed requests are not retried, see [`RpcClient::with_retries`].
+pub async fn rpc_client(uri: impl AsRef<str>) -> Result<RpcClient, String> {
+	rpc_client_with(uri, Default::default()).await
+}
+
+/// Same as [`rpc_client`], with the settings of `config`, which also apply whenever the client
+/// reconnects.
+pub async fn rpc_client_with(
+	uri: impl AsRef<str>,
+	config: ClientConfig,
+) -> Result<RpcClient, String> {
+	let uri = uri.as_ref().to_owned();
+	let connection = Connection::open(&uri, &config).await?;
+	Ok(RpcClient { uri, config, connection: RwLock::new(Arc::new(connection)) })
+}
+
+/// The delay before the first retry of a request, which doubles with every retry.
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The maximum delay between two retries of a request.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A connection over either of the transports.
+#[derive(Debug)]
+enum Connection {
+	Ws(WsClient),
+	Http(HttpClient),
+}
+
+impl Connection {
+	async fn open(uri: &str, config: &ClientConfig) -> Result<Self, String> {
+		if uri.starts_with("http://") || uri.starts_with("https://") {
+			http_client_with(uri, config).map(Self::Http)
+		} else {
+			ws_client_with(uri, config).await.map(Self::Ws)
+		}
+	}
+}
+
+/// A client over either of the transports, see [`rpc_client`].
+///
+/// Implements the RPC interface in [`sc-rpc-api`] like the clients it wraps. Subscribing over
+/// `HTTP` always fails.
+#[derive(Debug)]
+pub struct RpcClient {
+	uri: String,
+	config: ClientConfig,
+	connection: RwLock<Arc<Connection>>,
+}
+
+impl RpcClient {
+	/// Retry each request that fails because of the connection, e.g. because it dropped or timed
+	/// out, up to `retries` times.
+	///
+	/// Before each retry, the client waits for an exponentially growing delay, and reconnects.
+	/// Subscriptions and notifications are never retried.
+	pub fn with_retries(mut self, retries: u32) -> Self {
+		self.config.retries = retries;
+		self
+	}
+
+	/// The current connection.
+	fn connection(&self) -> Arc<Connection> {
+		self.connection.read().expect("the lock is never poisoned; qed").clone()
+	}
+
+	/// Execute `request` on the current connection, retrying it as per [`Self::with_retries`].
+	async fn retry<T, F, Fut>(&self, method: &str, request: F) -> Result<T, Error>
+	where
+		F: Fn(Arc<Connection>) -> Fut + Send + Sync,
+		Fut: Future<Output = Result<T, Error>> + Send,
+		T: Send,
+	{
+		let mut attempt = 0;
+		loop {
+			let error = match request(self.connection()).await {
+				Err(
+					error @ (Error::Transport(_) | Error::RestartNeeded(_) | Error::RequestTimeout),
+				) if attempt < self.config.retries => error,
+				result => return result,
+			};
+
+			let backoff = RETRY_BACKOFF.saturating_mul(1 << attempt.min(16)).min(MAX_RETRY_BACKOFF);
+			attempt += 1;
+			log::warn!(
+				"`{}` failed: {:?}, retrying in {:?} ({}/{})",
+				method,
+				error,
+				backoff,
+				attempt,
+				self.config.retries,
+			);
+			tokio::time::sleep(backoff).await;
+
+			match Connection::open(&self.uri, &self.config).await {
+				Ok(connection) =>
+					*self.connection.write().expect("the lock is never poisoned; qed") =
+						Arc::new(connection),
+				Err(why) => log::warn!("failed to reconnect to {}: {}", self.uri, why),
+			}
+		}
+	}
+}
+
+#[async_trait]
+impl ClientT for Connection {
+	async fn notification<'a>(
+		&self,
+		method: &'a str,
+		params: Option<ParamsSer<'a>>,
+	) -> Result<(), Error> {
+		match self {
+			Self::Ws(client) => client.notification(method, params).await,
+			Self::Http(client) => client.notification(method, params).await,
+		}
+	}
+
+	async fn request<'a, R>(
+		&self,
+		method: &'a str,
+		params: Option<ParamsSer<'a>>,
+	) -> Result<R, Error>
+	where
+		R: DeserializeOwned,
+	{
+		match self {
+			Self::Ws(client) => client.request(method, params).await,
+			Self::Http(client) => client.request(method, params).await,
+		}
+	}
+
+	async fn batch_request<'a, R>(
+		&self,
+		batch: Vec<(&'a str, Option<ParamsSer<'a>>)>,
+	) -> Result<Vec<R>, Error>
+	where
+		R: DeserializeOwned + Default + Clone,
+	{
+		match self {
+			Self::Ws(client) => client.batch_request(batch).await,
+			Self::Http(client) => client.batch_request(batch).await,
+		}
+	}
+}
+
+#[async_trait]
+impl SubscriptionClientT for Connection {
+	async fn subscribe<'a, Notif>(
+		&self,
+		subscribe_method: &'a str,
+		params: Option<ParamsSer<'a>>,
+		unsubscribe_method: &'a str,
+	) -> Result<Subscription<Notif>, Error>
+	where
+		Notif: DeserializeOwned,
+	{
+		match self {
+			Self::Ws(client) =>
+				client.subscribe(subscribe_method, params, unsubscribe_method).await,
+			Self::Http(client) =>
+				client.subscribe(subscribe_method, params, unsubscribe_method).await,
+		}
+	}
+
+	async fn subscribe_to_method<'a, Notif>(
+		&self,
+		method: &'a str,
+	) -> Result<Subscription<Notif>, Error>
+	where
+		Notif: DeserializeOwned,
+	{
+		match self {
+			Self::Ws(client) => client.subscribe_to_method(method).await,
+			Self::Http(client) => client.subscribe_to_method(method).await,
+		}
+	}
+}
+
+#[async_trait]
+impl ClientT for RpcClient {
+	async fn notification<'a>(
+		&self,
+		method: &'a str,
+		params: Option<ParamsSer<'a>>,
+	) -> Result<(), Error> {
+		self.connection().notification(method, params).await
+	}
+
+	async fn request<'a, R>(
+		&self,
+		method: &'a str,
+		params: Option<ParamsSer<'a>>,
+	) -> Result<R, Error>
+	where
+		R: DeserializeOwned,
+	{
+		self.retry(method, |connection| {
+			let params = params.clone();
+			async move { connection.request(method, params).await }
+		})
+		.await
+	}
+
+	async fn batch_request<'a, R>(
+		&self,
+		batch: Vec<(&'a str, Option<ParamsSer<'a>>)>,
+	) -> Result<Vec<R>, Error>
+	where
+		R: DeserializeOwned + Default + Clone,
+	{
+		self.retry("batch", |connection| {
+			let batch = batch.clone();
+			async move { connection.batch_request(batch).await }
+		})
+		.await
+	}
+}
+
+#[async_trait]
+impl SubscriptionClientT for RpcClient {
+	async fn subscribe<'a, Notif>(
+		&self,
+		subscribe_method: &'a str,
+		params: Option<ParamsSer<'a>>,
+		unsubscribe_method: &'a str,
+	) -> Result<Subscription<Notif>, Error>
+	where
+		Notif: DeserializeOwned,
+	{
+		self.connection().subscribe(subscribe_method, params, unsubscribe_method).await
+	}
+
+	async fn subscribe_to_method<'a, Notif>(
+		&self,
+		method: &'a str,
+	) -> Result<Subscription<Notif>, Error>
+	where
+		Notif: DeserializeOwned,
+	{
+		self.connection().subscribe_to_method(method).await
+	}
+}
+
+/// Get the headers of the blocks with `hashes` through `client`, in a single JSON-RPC batch.
+///
+/// The headers are in the order of `hashes`, and `None` for the blocks that are not known.
+pub async fn batch_headers<C, Hash, Header>(
+	client: &C,
+	hashes: &[Hash],
+) -> Result<Vec<Option<Header>>, Error>
+where
+	C: ClientT + Sync,
+	Hash: Serialize,
+	Header: DeserializeOwned + Clone,
+{
+	let batch = hashes.iter().map(|hash| ("chain_getHeader", rpc_params![hash])).collect();
+	client.batch_request(batch).await
+}
+
+/// Get the values of `keys` at the block `at`, or else at the best block, through `client`, in a
+/// single JSON-RPC batch.
+///
+/// The values are in the order of `keys`, and `None` for the keys that have no value.
+pub async fn batch_storage<C, Hash>(
+	client: &C,
+	keys: &[StorageKey],
+	at: Option<Hash>,
+) -> Result<Vec<Option<StorageData>>, Error>
+where
+	C: ClientT + Sync,
+	Hash: Serialize,
+{
+	let batch = keys.iter().map(|key| ("state_getStorage", rpc_params![key, &at])).collect();
+	client.batch_request(batch).await
+}
+
+/// Like [`batch_storage`], for the `keys` of the child tree with the prefixed storage key
+/// `child_storage_key`.
+pub async fn batch_child_storage<C, Hash>(
+	client: &C,
+	child_storage_key: &PrefixedStorageKey,
+	keys: &[StorageKey],
+	at: Option<Hash>,
+) -> Result<Vec<Option<StorageData>>, Error>
+where
+	C: ClientT + Sync,
+	Hash: Serialize,
+{
+	let batch = keys
+		.iter()
+		.map(|key| ("childstate_getStorage", rpc_params![child_storage_key, key, &at]))
+		.collect();
+	client.batch_request(batch).await
+}
+
 /// Abstraction over RPC calling for headers.
 #[async_trait]
 pub trait HeaderProvider<Block: BlockT>
@@ -194,8 +580,12 @@ where
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use jsonrpsee::{
+		http_server::{HttpServerBuilder, HttpServerHandle},
+		RpcModule,
+	};
 	use sp_runtime::testing::{Block as TBlock, ExtrinsicWrapper, Header, H256};
-	use std::sync::Arc;
+	use std::{net::SocketAddr, sync::Arc, time::Instant};
 	use tokio::sync::Mutex;
 
 	type Block = TBlock<ExtrinsicWrapper<()>>;
@@ -262,4 +652,104 @@ mod tests {
 		}
 		assert_eq!(None, headers.next().await);
 	}
+
+	/// A free local address, which nothing listens at.
+	fn free_addr() -> SocketAddr {
+		std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+	}
+
+	/// Serve `module` over `HTTP` at `addr`.
+	async fn serve(module: RpcModule<()>, addr: SocketAddr) -> HttpServerHandle {
+		HttpServerBuilder::default().build(addr).await.unwrap().start(module).unwrap()
+	}
+
+	fn system_name_module() -> RpcModule<()> {
+		let mut module = RpcModule::new(());
+		module.register_method("system_name", |_, _| Ok("node")).unwrap();
+		module
+	}
+
+	#[tokio::test]
+	async fn transport_errors_are_retried() {
+		let addr = free_addr();
+		let config = ClientConfig { retries: 1, ..Default::default() };
+		let client = rpc_client_with(format!("http://{}", addr), config).await.unwrap();
+
+		// the node only starts listening once the first attempt failed, during the backoff.
+		let (name, _handle) = tokio::join!(client.request::<String>("system_name", None), async {
+			tokio::time::sleep(RETRY_BACKOFF / 2).await;
+			serve(system_name_module(), addr).await
+		});
+		assert_eq!(name.unwrap(), "node");
+	}
+
+	#[tokio::test]
+	async fn errors_are_returned_as_is_without_retries() {
+		let client = rpc_client(format!("http://{}", free_addr())).await.unwrap();
+
+		let started = Instant::now();
+		let result = client.request::<String>("system_name", None).await;
+		assert!(matches!(result, Err(Error::Transport(_))), "{:?}", result);
+		assert!(started.elapsed() < RETRY_BACKOFF);
+	}
+
+	#[tokio::test]
+	async fn batch_results_are_in_key_order() {
+		let headers = headers();
+		let known = headers.clone();
+		let mut module = RpcModule::new(());
+		module
+			.register_method("chain_getHeader", move |params, _| {
+				let hash = params.one::<Hash>()?;
+				Ok(known.iter().find(|header| header.hash() == hash).cloned())
+			})
+			.unwrap();
+		// the value of a key is the key itself, prefixed by the child storage key if any, and odd
+		// keys have no value.
+		module
+			.register_method("state_getStorage", |params, _| {
+				let (key, _) = params.parse::<(StorageKey, Option<Hash>)>()?;
+				Ok((key.0[0] % 2 == 0).then(|| StorageData(key.0)))
+			})
+			.unwrap();
+		module
+			.register_method("childstate_getStorage", |params, _| {
+				let (child_storage_key, key, _) =
+					params.parse::<(PrefixedStorageKey, StorageKey, Option<Hash>)>()?;
+				Ok((key.0[0] % 2 == 0)
+					.then(|| StorageData([child_storage_key.into_inner(), key.0].concat())))
+			})
+			.unwrap();
+		let addr = free_addr();
+		let _handle = serve(module, addr).await;
+		let client = rpc_client(format!("http://{}", addr)).await.unwrap();
+
+		let hashes =
+			vec![headers[3].hash(), H256::repeat_byte(0xff), headers[1].hash(), headers[7].hash()];
+		assert_eq!(
+			batch_headers::<_, _, Header>(&client, &hashes).await.unwrap(),
+			vec![
+				Some(headers[3].clone()),
+				None,
+				Some(headers[1].clone()),
+				Some(headers[7].clone())
+			],
+		);
+
+		let keys = (0..10u8).rev().map(|k| StorageKey(vec![k])).collect::<Vec<_>>();
+		let values = |prefix: &[u8]| {
+			keys.iter()
+				.map(|key| (key.0[0] % 2 == 0).then(|| StorageData([prefix, &key.0[..]].concat())))
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(batch_storage(&client, &keys, None::<Hash>).await.unwrap(), values(&[]));
+
+		let child_storage_key = PrefixedStorageKey::new(b":child_storage:default:child".to_vec());
+		assert_eq!(
+			batch_child_storage(&client, &child_storage_key, &keys, None::<Hash>)
+				.await
+				.unwrap(),
+			values(&child_storage_key),
+		);
+	}
 }
@@ -0,0 +1,34 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{env, process::Command};
+
+/// Expose the version of the compiler as `TRY_RUNTIME_RUSTC_VERSION`, for the provenance of the
+/// json summaries.
+fn main() {
+	let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+	let version = Command::new(rustc)
+		.arg("--version")
+		.output()
+		.ok()
+		.and_then(|output| String::from_utf8(output.stdout).ok())
+		.map(|version| version.trim().to_string())
+		.unwrap_or_else(|| "unknown".into());
+
+	println!("cargo:rustc-env=TRY_RUNTIME_RUSTC_VERSION={}", version);
+	println!("cargo:rerun-if-env-changed=RUSTC");
+}
@@ -0,0 +1,541 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Assertions on the storage, evaluated once a command has executed.
+//!
+//! See [`crate::SharedParams::assertions`] and [`crate::SharedParams::assert_exists`], as well as
+//! [`crate::SharedParams::check_issuance`] for values that must be conserved.
+
+use crate::{runtime_metadata, SharedParams, LOG_TARGET};
+use frame_metadata::{RuntimeMetadataV14, StorageEntryModifier, StorageEntryType};
+use parity_scale_codec::{Compact, Decode};
+use remote_externalities::TestExternalities;
+use sc_executor::NativeElseWasmExecutor;
+use sc_service::NativeExecutionDispatch;
+use scale_info::{PortableRegistry, TypeDef, TypeDefPrimitive};
+use sp_core::{bytes::to_hex, hashing::twox_128};
+use sp_runtime::traits::Block as BlockT;
+use sp_state_machine::{Backend, OverlayedChanges};
+use std::{cmp::Ordering, fmt};
+
+/// A plain storage item, as `Pallet::Item`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageItem {
+	pallet: String,
+	item: String,
+}
+
+impl fmt::Display for StorageItem {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}::{}", self.pallet, self.item)
+	}
+}
+
+/// A comparison operator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Op {
+	Eq,
+	Ne,
+	Lt,
+	Le,
+	Gt,
+	Ge,
+}
+
+impl Op {
+	/// All operators, along with their symbol.
+	const ALL: [(&'static str, Op); 6] = [
+		("==", Op::Eq),
+		("!=", Op::Ne),
+		("<=", Op::Le),
+		(">=", Op::Ge),
+		("<", Op::Lt),
+		(">", Op::Gt),
+	];
+
+	fn symbol(&self) -> &'static str {
+		Op::ALL
+			.iter()
+			.find(|(_, op)| op == self)
+			.map(|(symbol, _)| *symbol)
+			.unwrap_or_default()
+	}
+
+	fn holds(&self, ordering: Ordering) -> bool {
+		match self {
+			Op::Eq => ordering == Ordering::Equal,
+			Op::Ne => ordering != Ordering::Equal,
+			Op::Lt => ordering == Ordering::Less,
+			Op::Le => ordering != Ordering::Greater,
+			Op::Gt => ordering == Ordering::Greater,
+			Op::Ge => ordering != Ordering::Less,
+		}
+	}
+}
+
+/// The value that a storage item is compared to.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+	/// The SCALE encoded value, compared as-is.
+	Bytes(Vec<u8>),
+	/// A number, compared to the decoded (unsigned integer) value.
+	Number(u128),
+	/// A boolean, compared to the decoded value.
+	Bool(bool),
+}
+
+impl fmt::Display for Value {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Value::Bytes(bytes) => write!(f, "{}", to_hex(bytes, false)),
+			Value::Number(number) => write!(f, "{}", number),
+			Value::Bool(boolean) => write!(f, "{}", boolean),
+		}
+	}
+}
+
+/// An assertion on the storage, e.g. `System::Number == 12345`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assertion {
+	item: StorageItem,
+	op: Op,
+	value: Value,
+}
+
+impl fmt::Display for Assertion {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} {} {}", self.item, self.op.symbol(), self.value)
+	}
+}
+
+/// Parse a [`StorageItem`], as given to `--assert-exists`.
+pub(crate) fn parse_storage_item(s: &str) -> Result<StorageItem, String> {
+	match s.trim().split_once("::") {
+		Some((pallet, item)) if !pallet.is_empty() && !item.is_empty() && !item.contains("::") =>
+			Ok(StorageItem { pallet: pallet.into(), item: item.into() }),
+		_ => Err(format!("expected a storage item as `Pallet::Item`, found `{}`", s)),
+	}
+}
+
+/// Parse an [`Assertion`], as given to `--assert`.
+pub(crate) fn parse_assertion(s: &str) -> Result<Assertion, String> {
+	let (index, symbol, op) = Op::ALL
+		.iter()
+		.filter_map(|(symbol, op)| s.find(symbol).map(|index| (index, *symbol, *op)))
+		// the first operator in the string, preferring the longest one at the same position.
+		.min_by_key(|(index, symbol, _)| (*index, usize::MAX - symbol.len()))
+		.ok_or_else(|| format!("expected `Pallet::Item <op> <value>`, found `{}`", s))?;
+
+	let item = parse_storage_item(&s[..index])?;
+	let value =
+		match s[index + symbol.len()..].trim() {
+			"true" => Value::Bool(true),
+			"false" => Value::Bool(false),
+			hex if hex.starts_with("0x") => Value::Bytes(
+				sp_core::bytes::from_hex(hex).map_err(|e| format!("invalid hex {}: {}", hex, e))?,
+			),
+			number =>
+				Value::Number(number.replace('_', "").parse().map_err(|_| {
+					format!("expected a number, a boolean or hex, found `{}`", number)
+				})?),
+		};
+
+	if !matches!(value, Value::Number(_)) && !matches!(op, Op::Eq | Op::Ne) {
+		return Err(format!("only numbers can be compared with `{}`", symbol))
+	}
+
+	Ok(Assertion { item, op, value })
+}
+
+/// Decode an unsigned integer of type `ty` from `bytes`.
+fn decode_number(types: &PortableRegistry, ty: u32, bytes: &mut &[u8]) -> Result<u128, String> {
+	let ty = types
+		.resolve(ty)
+		.ok_or_else(|| format!("type {} not found in the metadata", ty))?;
+	let decode_err = |e: parity_scale_codec::Error| format!("failed to decode number: {}", e);
+	match ty.type_def() {
+		TypeDef::Primitive(primitive) => match primitive {
+			TypeDefPrimitive::U8 => u8::decode(bytes).map(Into::into).map_err(decode_err),
+			TypeDefPrimitive::U16 => u16::decode(bytes).map(Into::into).map_err(decode_err),
+			TypeDefPrimitive::U32 => u32::decode(bytes).map(Into::into).map_err(decode_err),
+			TypeDefPrimitive::U64 => u64::decode(bytes).map(Into::into).map_err(decode_err),
+			TypeDefPrimitive::U128 => u128::decode(bytes).map_err(decode_err),
+			other => Err(format!("{:?} cannot be compared to a number", other)),
+		},
+		TypeDef::Compact(_) => Compact::<u128>::decode(bytes).map(|c| c.0).map_err(decode_err),
+		// e.g. `Perbill(u32)`.
+		TypeDef::Composite(composite) if composite.fields().len() == 1 =>
+			decode_number(types, composite.fields()[0].ty().id(), bytes),
+		_ => Err(format!("{:?} cannot be compared to a number", ty.path())),
+	}
+}
+
+/// Find the storage key, the value type and the default value of `item` in `metadata`.
+///
+/// Only plain storage items (i.e. storage values) are supported.
+fn resolve(
+	metadata: &RuntimeMetadataV14,
+	item: &StorageItem,
+) -> Result<(Vec<u8>, u32, Option<Vec<u8>>), String> {
+	let storage = metadata
+		.pallets
+		.iter()
+		.find(|p| p.name == item.pallet)
+		.ok_or_else(|| format!("pallet {} not found in the metadata", item.pallet))?
+		.storage
+		.as_ref()
+		.ok_or_else(|| format!("pallet {} has no storage", item.pallet))?;
+	let entry = storage
+		.entries
+		.iter()
+		.find(|e| e.name == item.item)
+		.ok_or_else(|| format!("storage item {} not found in the metadata", item))?;
+
+	let ty = match &entry.ty {
+		StorageEntryType::Plain(ty) => ty.id(),
+		StorageEntryType::Map { .. } => return Err(format!("{} is a map, not a value", item)),
+	};
+	let default = match entry.modifier {
+		StorageEntryModifier::Default => Some(entry.default.clone()),
+		StorageEntryModifier::Optional => None,
+	};
+	let key = [twox_128(storage.prefix.as_bytes()), twox_128(entry.name.as_bytes())].concat();
+	Ok((key, ty, default))
+}
+
+/// Evaluate `assertion`, returning a message describing why it failed, if it did.
+fn evaluate(
+	types: &PortableRegistry,
+	assertion: &Assertion,
+	ty: u32,
+	value: &[u8],
+) -> Result<(), String> {
+	let ordering = match &assertion.value {
+		Value::Bytes(expected) => value.cmp(expected),
+		Value::Number(expected) => decode_number(types, ty, &mut &*value)?.cmp(expected),
+		Value::Bool(expected) => match types.resolve(ty).map(|t| t.type_def()) {
+			Some(TypeDef::Primitive(TypeDefPrimitive::Bool)) => bool::decode(&mut &*value)
+				.map_err(|e| format!("failed to decode bool: {}", e))?
+				.cmp(expected),
+			_ => return Err(format!("{} is not a bool", assertion.item)),
+		},
+	};
+
+	if assertion.op.holds(ordering) {
+		Ok(())
+	} else {
+		Err(format!("found {}", to_hex(value, false)))
+	}
+}
+
+/// Read `key` from the state of `ext`, with `changes` applied on top of it.
+fn read(
+	ext: &TestExternalities,
+	changes: &OverlayedChanges,
+	key: &[u8],
+) -> sc_cli::Result<Option<Vec<u8>>> {
+	match changes.storage(key) {
+		Some(value) => Ok(value.map(|v| v.to_vec())),
+		None => ext
+			.backend
+			.storage(key)
+			.map_err(|e| format!("failed to read storage: {}", e).into()),
+	}
+}
+
+/// Check all of the [`SharedParams::assertions`] and [`SharedParams::assert_exists`] against the
+/// state of `ext`, with `changes` applied on top of it.
+pub(crate) fn check_assertions<Block: BlockT, D: NativeExecutionDispatch + 'static>(
+	shared: &SharedParams,
+	ext: &TestExternalities,
+	executor: &NativeElseWasmExecutor<D>,
+	changes: &OverlayedChanges,
+) -> sc_cli::Result<()> {
+	if shared.assertions.is_empty() && shared.assert_exists.is_empty() {
+		return Ok(())
+	}
+
+	let metadata = runtime_metadata::<Block, D>(ext, executor)?;
+	let get = |key: &[u8]| read(ext, changes, key);
+
+	let mut failed = 0;
+	for item in &shared.assert_exists {
+		let (key, _, _) = resolve(&metadata, item)?;
+		if get(&key)?.is_some() {
+			log::info!(target: LOG_TARGET, "✅ {} exists", item);
+		} else {
+			log::error!(target: LOG_TARGET, "❌ {} does not exist", item);
+			failed += 1;
+		}
+	}
+
+	for assertion in &shared.assertions {
+		let (key, ty, default) = resolve(&metadata, &assertion.item)?;
+		let outcome = match get(&key)?.or(default) {
+			Some(value) => evaluate(&metadata.types, assertion, ty, &value),
+			None => Err("the item does not exist".into()),
+		};
+		match outcome {
+			Ok(()) => log::info!(target: LOG_TARGET, "✅ {}", assertion),
+			Err(why) => {
+				log::error!(target: LOG_TARGET, "❌ {}: {}", assertion, why);
+				failed += 1;
+			},
+		}
+	}
+
+	let total = shared.assertions.len() + shared.assert_exists.len();
+	if failed > 0 {
+		return Err(format!("{} out of {} assertions failed", failed, total).into())
+	}
+	log::info!(target: LOG_TARGET, "all {} assertions hold", total);
+	Ok(())
+}
+
+/// The items that must be conserved, as per [`SharedParams::check_issuance`] and
+/// [`SharedParams::conserve`].
+fn conserved_items(shared: &SharedParams) -> Vec<StorageItem> {
+	let total_issuance = StorageItem { pallet: "Balances".into(), item: "TotalIssuance".into() };
+	let mut items = shared.conserve.clone();
+	if shared.check_issuance && !items.contains(&total_issuance) {
+		items.insert(0, total_issuance);
+	}
+	items
+}
+
+/// Check that the numeric value of every conserved item changed by at most
+/// [`SharedParams::conservation_tolerance`] from the state of `ext` to the state with `changes`
+/// applied on top of it.
+///
+/// A change beyond the tolerance is an error, unless [`SharedParams::allow_issuance_change`].
+pub(crate) fn check_conservation<Block: BlockT, D: NativeExecutionDispatch + 'static>(
+	shared: &SharedParams,
+	ext: &TestExternalities,
+	executor: &NativeElseWasmExecutor<D>,
+	changes: &OverlayedChanges,
+) -> sc_cli::Result<()> {
+	let items = conserved_items(shared);
+	if items.is_empty() {
+		return Ok(())
+	}
+
+	let metadata = runtime_metadata::<Block, D>(ext, executor)?;
+	let read_number = |value: Option<Vec<u8>>, ty: u32| -> sc_cli::Result<Option<u128>> {
+		value
+			.map(|v| decode_number(&metadata.types, ty, &mut &*v))
+			.transpose()
+			.map_err(Into::into)
+	};
+
+	let mut changed = 0;
+	for item in &items {
+		let (key, ty, default) = resolve(&metadata, item)?;
+		let before = ext
+			.backend
+			.storage(&key)
+			.map_err(|e| format!("failed to read storage: {}", e))?
+			.or_else(|| default.clone());
+		let after = read(ext, changes, &key)?.or(default);
+		// an item that is created or removed is considered to change from or to zero.
+		let (before, after) = match (read_number(before, ty)?, read_number(after, ty)?) {
+			(None, None) => {
+				log::warn!(target: LOG_TARGET, "⚠️ {} does not exist, nothing to conserve", item);
+				continue
+			},
+			(before, after) => (before.unwrap_or_default(), after.unwrap_or_default()),
+		};
+
+		let difference = before.abs_diff(after);
+		if difference <= shared.conservation_tolerance {
+			log::info!(target: LOG_TARGET, "✅ {} is conserved: {} -> {}", item, before, after);
+		} else if shared.allow_issuance_change {
+			log::warn!(
+				target: LOG_TARGET,
+				"⚠️ {} changed by {}: {} -> {}",
+				item,
+				difference,
+				before,
+				after,
+			);
+		} else {
+			log::error!(
+				target: LOG_TARGET,
+				"❌ {} changed by {}: {} -> {}",
+				item,
+				difference,
+				before,
+				after,
+			);
+			changed += 1;
+		}
+	}
+
+	if changed > 0 {
+		return Err(format!(
+			"{} out of {} conserved items changed beyond the tolerance of {}, pass \
+			`--allow-issuance-change` if this is expected",
+			changed,
+			items.len(),
+			shared.conservation_tolerance,
+		)
+		.into())
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use parity_scale_codec::Encode;
+	use scale_info::{meta_type, Registry, TypeInfo};
+	use sp_runtime::Perbill;
+
+	/// The registry of the type `T`, along with the id of `T` in it.
+	fn registry_of<T: TypeInfo + 'static>() -> (PortableRegistry, u32) {
+		let mut registry = Registry::new();
+		let id = registry.register_type(&meta_type::<T>()).id();
+		(registry.into(), id)
+	}
+
+	fn number_of<T: TypeInfo + Encode + 'static>(value: T) -> Result<u128, String> {
+		let (types, ty) = registry_of::<T>();
+		decode_number(&types, ty, &mut &*value.encode())
+	}
+
+	fn item(pallet: &str, item: &str) -> StorageItem {
+		StorageItem { pallet: pallet.into(), item: item.into() }
+	}
+
+	#[test]
+	fn parse_storage_item_works() {
+		assert_eq!(parse_storage_item(" System::Number "), Ok(item("System", "Number")));
+		assert!(parse_storage_item("System").is_err());
+		assert!(parse_storage_item("System::").is_err());
+		assert!(parse_storage_item("::Number").is_err());
+		assert!(parse_storage_item("System::Number::Foo").is_err());
+	}
+
+	#[test]
+	fn parse_assertion_works() {
+		assert_eq!(
+			parse_assertion("System::Number == 12_345"),
+			Ok(Assertion {
+				item: item("System", "Number"),
+				op: Op::Eq,
+				value: Value::Number(12345)
+			}),
+		);
+		assert_eq!(
+			parse_assertion("Balances::TotalIssuance!=0"),
+			Ok(Assertion {
+				item: item("Balances", "TotalIssuance"),
+				op: Op::Ne,
+				value: Value::Number(0)
+			}),
+		);
+		assert_eq!(
+			parse_assertion("Staking::ForceEra == 0x02"),
+			Ok(Assertion {
+				item: item("Staking", "ForceEra"),
+				op: Op::Eq,
+				value: Value::Bytes(vec![2])
+			}),
+		);
+		assert_eq!(
+			parse_assertion("Pallet::Flag != false"),
+			Ok(Assertion { item: item("Pallet", "Flag"), op: Op::Ne, value: Value::Bool(false) }),
+		);
+	}
+
+	#[test]
+	fn parse_assertion_prefers_the_longest_operator() {
+		for (symbol, op) in Op::ALL {
+			let assertion = parse_assertion(&format!("System::Number {} 10", symbol)).unwrap();
+			assert_eq!(assertion.op, op, "{}", symbol);
+			assert_eq!(assertion.to_string(), format!("System::Number {} 10", symbol));
+		}
+	}
+
+	#[test]
+	fn parse_assertion_fails() {
+		// no operator.
+		assert!(parse_assertion("System::Number 10").is_err());
+		// no storage item.
+		assert!(parse_assertion("System == 10").is_err());
+		// not a value.
+		assert!(parse_assertion("System::Number == ten").is_err());
+		assert!(parse_assertion("System::Number == 0xzz").is_err());
+		// only numbers can be ordered.
+		assert!(parse_assertion("Pallet::Flag < true").is_err());
+		assert!(parse_assertion("Staking::ForceEra >= 0x02").is_err());
+	}
+
+	#[test]
+	fn decode_number_works() {
+		assert_eq!(number_of(7u8), Ok(7));
+		assert_eq!(number_of(7u16), Ok(7));
+		assert_eq!(number_of(7u32), Ok(7));
+		assert_eq!(number_of(u64::MAX), Ok(u64::MAX as u128));
+		assert_eq!(number_of(u128::MAX), Ok(u128::MAX));
+		assert_eq!(number_of(Compact(1u64 << 40)), Ok(1 << 40));
+		assert_eq!(number_of(Perbill::from_parts(500)), Ok(500));
+
+		assert!(number_of(true).is_err());
+		assert!(number_of(7i32).is_err());
+		assert!(number_of((1u32, 2u32)).is_err());
+
+		// not enough bytes.
+		let (types, ty) = registry_of::<u64>();
+		assert!(decode_number(&types, ty, &mut &[1u8, 2][..]).is_err());
+		// unknown type.
+		assert!(decode_number(&types, ty + 1, &mut &[1u8][..]).is_err());
+	}
+
+	#[test]
+	fn evaluate_numbers_works() {
+		let (types, ty) = registry_of::<u32>();
+		let value = 10u32.encode();
+		let holds = |s: &str| evaluate(&types, &parse_assertion(s).unwrap(), ty, &value).is_ok();
+
+		assert!(holds("System::Number == 10"));
+		assert!(!holds("System::Number != 10"));
+		assert!(holds("System::Number <= 10"));
+		assert!(!holds("System::Number < 10"));
+		assert!(holds("System::Number < 11"));
+		assert!(holds("System::Number >= 10"));
+		assert!(!holds("System::Number > 10"));
+		assert!(holds("System::Number > 9"));
+		// the encoded value is compared as-is.
+		assert!(holds("System::Number == 0x0a000000"));
+		assert!(!holds("System::Number == 0x0a"));
+		// not a bool.
+		assert!(!holds("System::Number == true"));
+	}
+
+	#[test]
+	fn evaluate_bools_works() {
+		let (types, ty) = registry_of::<bool>();
+		let value = true.encode();
+		let holds = |s: &str| evaluate(&types, &parse_assertion(s).unwrap(), ty, &value).is_ok();
+
+		assert!(holds("Pallet::Flag == true"));
+		assert!(!holds("Pallet::Flag == false"));
+		assert!(holds("Pallet::Flag != false"));
+		// not a number.
+		assert!(!holds("Pallet::Flag == 1"));
+	}
+}
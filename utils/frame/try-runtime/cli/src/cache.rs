@@ -0,0 +1,230 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An on-disk cache of the data that is downloaded from a node for a given block hash, and thus
+//! never changes, see [`crate::SharedParams::cache_dir`].
+
+use crate::{hash_of, SharedParams, State, LOG_TARGET};
+use parity_scale_codec::{Decode, Encode};
+use remote_externalities::Builder;
+use sp_core::{
+	bytes::to_hex,
+	storage::{well_known_keys, StorageData, StorageKey},
+};
+use sp_runtime::{traits::Block as BlockT, DeserializeOwned};
+use std::{fmt::Debug, fs, path::PathBuf, str::FromStr};
+use substrate_rpc_client::{rpc_client_with, ChainApi, ClientConfig, StateApi};
+
+/// The name of the directory of the cache, in the temporary directory of the system, if no
+/// [`SharedParams::cache_dir`] is given.
+const DEFAULT_CACHE_DIR: &str = "try-runtime-cache";
+
+/// The cache, keyed by block hash.
+///
+/// Failing to read from or write to the cache is only logged, and the data is then downloaded.
+pub(crate) struct Cache {
+	/// The directory of the cache, or `None` if it is disabled.
+	dir: Option<PathBuf>,
+	/// The settings of the RPC clients, see [`SharedParams::rpc_config`].
+	rpc_config: ClientConfig,
+}
+
+impl Cache {
+	pub(crate) fn new(shared: &SharedParams) -> Self {
+		let dir = (!shared.no_cache).then(|| {
+			shared
+				.cache_dir
+				.clone()
+				.unwrap_or_else(|| std::env::temp_dir().join(DEFAULT_CACHE_DIR))
+		});
+		Self { dir, rpc_config: shared.rpc_config() }
+	}
+
+	/// The path of the `kind` of data of the block with `hash`, if the cache is enabled.
+	fn path(&self, kind: &str, hash: &[u8]) -> Option<PathBuf> {
+		self.dir.as_ref().map(|dir| dir.join(kind).join(to_hex(hash, false)))
+	}
+
+	fn read<T: Decode>(&self, kind: &str, hash: &[u8]) -> Option<T> {
+		let path = self.path(kind, hash)?;
+		let bytes = fs::read(&path).ok()?;
+		match T::decode(&mut &*bytes) {
+			Ok(value) => {
+				log::info!(target: LOG_TARGET, "loaded the {} from the cache {:?}", kind, path);
+				Some(value)
+			},
+			Err(e) => {
+				log::warn!(target: LOG_TARGET, "ignoring the corrupt cache {:?}: {:?}", path, e);
+				None
+			},
+		}
+	}
+
+	fn write<T: Encode>(&self, kind: &str, hash: &[u8], value: &T) {
+		let path = match self.path(kind, hash) {
+			Some(path) => path,
+			None => return,
+		};
+		let written = path
+			.parent()
+			.map_or(Ok(()), fs::create_dir_all)
+			.and_then(|_| fs::write(&path, value.encode()));
+		if let Err(e) = written {
+			log::warn!(target: LOG_TARGET, "failed to write the cache {:?}: {}", path, e);
+		}
+	}
+
+	/// Get the header of the block with `hash`, from the cache or else from the node at `uri`.
+	pub(crate) async fn header<Block: BlockT>(
+		&self,
+		uri: &str,
+		hash: Block::Hash,
+	) -> sc_cli::Result<Block::Header>
+	where
+		Block::Header: DeserializeOwned,
+	{
+		if let Some(header) = self.read("header", hash.as_ref()) {
+			return Ok(header)
+		}
+		let rpc = rpc_client_with(uri, self.rpc_config).await?;
+		let header = ChainApi::<(), Block::Hash, Block::Header, ()>::header(&rpc, Some(hash))
+			.await
+			.map_err(|e| format!("failed to fetch the header of {:?}: {:?}", hash, e))?
+			.ok_or_else(|| format!("header of {:?} not found", hash))?;
+		self.write("header", hash.as_ref(), &header);
+		Ok(header)
+	}
+
+	/// Get the block with `hash`, from the cache or else from the node at `uri`.
+	pub(crate) async fn block<Block: BlockT + DeserializeOwned>(
+		&self,
+		uri: &str,
+		hash: Block::Hash,
+	) -> sc_cli::Result<Block>
+	where
+		Block::Header: DeserializeOwned,
+	{
+		if let Some(block) = self.read("block", hash.as_ref()) {
+			return Ok(block)
+		}
+		let rpc = rpc_client_with(uri, self.rpc_config).await?;
+		let block: Block = ChainApi::<(), Block::Hash, Block::Header, _>::block(&rpc, Some(hash))
+			.await
+			.map_err(|e| format!("failed to fetch the block {:?}: {:?}", hash, e))?
+			.ok_or_else(|| format!("block {:?} not found", hash))?;
+		self.write("block", hash.as_ref(), &block);
+		Ok(block)
+	}
+
+	/// Have `builder` inject the `:code:` of the live `state`, at `at` if given or else at the
+	/// block of `state`.
+	///
+	/// The code is taken from the cache, or else downloaded and cached, if the block is known
+	/// upfront. It is scraped along with the rest of the state otherwise, and for states that
+	/// are stored as a snapshot, which must contain the code.
+	pub(crate) async fn inject_code<Block: BlockT + DeserializeOwned>(
+		&self,
+		builder: Builder<Block>,
+		state: &State,
+		at: Option<Block::Hash>,
+	) -> sc_cli::Result<Builder<Block>>
+	where
+		Block::Hash: FromStr,
+		<Block::Hash as FromStr>::Err: Debug,
+	{
+		let (uri, at) = match state {
+			State::Live { uri, snapshot_path: None, at: state_at, .. } if self.dir.is_some() =>
+				match at.map(Ok).or_else(|| state_at.as_ref().map(|at| hash_of::<Block>(at))) {
+					Some(at) => (uri, at?),
+					None => return Ok(builder.inject_hashed_key(well_known_keys::CODE)),
+				},
+			_ => return Ok(builder.inject_hashed_key(well_known_keys::CODE)),
+		};
+
+		let code = match self.read::<Vec<u8>>("code", at.as_ref()) {
+			Some(code) => code,
+			None => {
+				let rpc = rpc_client_with(uri, self.rpc_config).await?;
+				let code = StateApi::<Block::Hash>::storage(
+					&rpc,
+					StorageKey(well_known_keys::CODE.to_vec()),
+					Some(at),
+				)
+				.await
+				.map_err(|e| format!("failed to fetch the code at {:?}: {:?}", at, e))?
+				.ok_or_else(|| format!("no code at {:?}", at))?
+				.0;
+				self.write("code", at.as_ref(), &code);
+				code
+			},
+		};
+		Ok(builder.inject_hashed_key_value(&[(
+			StorageKey(well_known_keys::CODE.to_vec()),
+			StorageData(code),
+		)]))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn cache_in(dir: &tempfile::TempDir) -> Cache {
+		Cache { dir: Some(dir.path().to_path_buf()), rpc_config: Default::default() }
+	}
+
+	#[test]
+	fn read_returns_what_was_written() {
+		let dir = tempfile::tempdir().unwrap();
+		let cache = cache_in(&dir);
+
+		assert_eq!(cache.read::<Vec<u8>>("code", &[1, 2]), None);
+		cache.write("code", &[1, 2], &vec![3u8, 4]);
+		assert_eq!(cache.read::<Vec<u8>>("code", &[1, 2]), Some(vec![3, 4]));
+		assert!(cache.path("code", &[1, 2]).unwrap().starts_with(dir.path().join("code")));
+
+		// neither other kinds nor other hashes are affected.
+		assert_eq!(cache.read::<Vec<u8>>("block", &[1, 2]), None);
+		assert_eq!(cache.read::<Vec<u8>>("code", &[1, 3]), None);
+
+		// and entries can be overwritten.
+		cache.write("code", &[1, 2], &vec![5u8]);
+		assert_eq!(cache.read::<Vec<u8>>("code", &[1, 2]), Some(vec![5]));
+	}
+
+	#[test]
+	fn corrupt_entries_are_ignored() {
+		let dir = tempfile::tempdir().unwrap();
+		let cache = cache_in(&dir);
+
+		let path = cache.path("code", &[1, 2]).unwrap();
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		// the length prefix of a vector, without the vector.
+		fs::write(&path, [0xff]).unwrap();
+		assert_eq!(cache.read::<Vec<u8>>("code", &[1, 2]), None);
+
+		cache.write("code", &[1, 2], &vec![3u8, 4]);
+		assert_eq!(cache.read::<Vec<u8>>("code", &[1, 2]), Some(vec![3, 4]));
+	}
+
+	#[test]
+	fn disabled_cache_is_always_empty() {
+		let cache = Cache { dir: None, rpc_config: Default::default() };
+		cache.write("code", &[1, 2], &vec![3u8, 4]);
+		assert_eq!(cache.read::<Vec<u8>>("code", &[1, 2]), None);
+	}
+}
@@ -0,0 +1,133 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+	build_executor, cache::Cache, ensure_matching_spec, ensure_try_runtime_api, extract_code,
+	full_extensions, local_version, mock_version::mock_runtime_version, state_machine_call,
+	SharedParams, State, LOG_TARGET,
+};
+use parity_scale_codec::{Decode, Encode};
+use sc_executor::NativeExecutionDispatch;
+use sc_service::Configuration;
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+use std::{fmt::Debug, str::FromStr};
+
+/// Configurations of the [`Command::CheckState`].
+#[derive(Debug, Clone, clap::Parser)]
+pub struct CheckStateCmd {
+	/// Which try-state checks to execute.
+	///
+	/// Expected values:
+	/// - `all`
+	/// - `none`
+	/// - A comma separated list of pallets, as per pallet names in `construct_runtime!()` (e.g.
+	///   `Staking, System`).
+	/// - `rr-[x]` where `[x]` is a number. Then, the given number of pallets are checked in a
+	///   round-robin fashion.
+	#[arg(long, default_value = "all")]
+	try_state: frame_try_runtime::TryStateSelect,
+
+	/// Overwrite the wasm code in state or not.
+	#[arg(long)]
+	overwrite_wasm_code: bool,
+
+	/// The state type to use.
+	#[command(subcommand)]
+	pub(crate) state: State,
+}
+
+pub(crate) async fn check_state<Block, ExecDispatch>(
+	shared: SharedParams,
+	command: CheckStateCmd,
+	config: Configuration,
+) -> sc_cli::Result<()>
+where
+	Block: BlockT + serde::de::DeserializeOwned,
+	Block::Hash: FromStr,
+	<Block::Hash as FromStr>::Err: Debug,
+	Block::Header: serde::de::DeserializeOwned,
+	NumberFor<Block>: FromStr,
+	<NumberFor<Block> as FromStr>::Err: Debug,
+	ExecDispatch: NativeExecutionDispatch + 'static,
+{
+	let executor = build_executor::<ExecDispatch>(&shared, &config);
+
+	let ext = {
+		let builder = command
+			.state
+			.builder::<Block>()?
+			.state_version(shared.state_version)
+			.max_memory(shared.max_memory_bytes())
+			.rpc_config(shared.rpc_config());
+		let builder = if command.overwrite_wasm_code {
+			log::info!(
+				target: LOG_TARGET,
+				"replacing the in-storage :code: with the local code from {}'s chain_spec (your local repo)",
+				config.chain_spec.name(),
+			);
+			let (code_key, code) = extract_code(&config.chain_spec)?;
+			builder.inject_hashed_key_value(&[(code_key, code)])
+		} else {
+			Cache::new(&shared).inject_code(builder, &command.state, None).await?
+		};
+		builder.build().await?
+	};
+
+	let version = local_version::<Block, ExecDispatch>(&ext, &executor);
+	ensure_try_runtime_api(&version, 2, "TryRuntime_try_state")?;
+	if let Some(uri) = command.state.live_uri() {
+		ensure_matching_spec::<Block>(uri, &version, &shared).await?;
+	}
+
+	log::info!(target: LOG_TARGET, "executing the try-state checks {:?}", command.try_state);
+	let (_, encoded_result) = state_machine_call::<Block, ExecDispatch>(
+		&ext,
+		&executor,
+		shared.execution,
+		"TryRuntime_try_state",
+		&command.try_state.encode(),
+		mock_runtime_version(full_extensions(), &shared, &executor),
+	)?;
+	let outcomes =
+		<Result<Vec<(Vec<u8>, Option<Vec<u8>>)>, Vec<u8>> as Decode>::decode(&mut &*encoded_result)
+			.map_err(|e| format!("failed to decode output: {:?}", e))?
+			.map_err(|e| format!("the try-state checks failed: {}", String::from_utf8_lossy(&e)))?;
+
+	let mut failed = Vec::new();
+	for (pallet, error) in &outcomes {
+		let pallet = String::from_utf8_lossy(pallet);
+		match error {
+			None => log::info!(target: LOG_TARGET, "{}: ok", pallet),
+			Some(error) => {
+				log::error!(target: LOG_TARGET, "{}: {}", pallet, String::from_utf8_lossy(error));
+				failed.push(pallet.into_owned());
+			},
+		}
+	}
+
+	if !failed.is_empty() {
+		return Err(format!(
+			"the try-state checks of {} out of {} pallets failed: {}",
+			failed.len(),
+			outcomes.len(),
+			failed.join(", "),
+		)
+		.into())
+	}
+	log::info!(target: LOG_TARGET, "the try-state checks of {} pallets passed", outcomes.len());
+	Ok(())
+}
@@ -0,0 +1,154 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{build_executor, runtime_metadata, SharedParams, State, LOG_TARGET};
+use parity_scale_codec::Decode;
+use sc_executor::NativeExecutionDispatch;
+use sc_service::Configuration;
+use sp_core::{
+	storage::{well_known_keys, StorageData, StorageKey},
+	twox_128,
+};
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+use sp_state_machine::Backend;
+use std::{fmt::Debug, str::FromStr};
+
+/// The postfix of the key of the storage version of a pallet, see
+/// `frame_support::traits::STORAGE_VERSION_STORAGE_KEY_POSTFIX`.
+const STORAGE_VERSION_STORAGE_KEY_POSTFIX: &[u8] = b":__STORAGE_VERSION__:";
+
+/// Configurations of the [`Command::CheckStorageVersions`].
+#[derive(Debug, Clone, clap::Parser)]
+pub struct CheckStorageVersionsCmd {
+	/// The state type to use.
+	///
+	/// The storage versions are part of the storage of each pallet, hence the state must contain
+	/// all pallets.
+	#[command(subcommand)]
+	pub(crate) state: State,
+}
+
+/// Decode a storage version, which is a `u16`.
+fn decode_version(pallet: &str, value: &[u8]) -> sc_cli::Result<u16> {
+	u16::decode(&mut &*value)
+		.map_err(|e| format!("failed to decode the storage version of {}: {}", pallet, e).into())
+}
+
+pub(crate) async fn check_storage_versions<Block, ExecDispatch>(
+	shared: SharedParams,
+	command: CheckStorageVersionsCmd,
+	config: Configuration,
+) -> sc_cli::Result<()>
+where
+	Block: BlockT + serde::de::DeserializeOwned,
+	Block::Hash: FromStr,
+	<Block::Hash as FromStr>::Err: Debug,
+	Block::Header: serde::de::DeserializeOwned,
+	NumberFor<Block>: FromStr,
+	<NumberFor<Block> as FromStr>::Err: Debug,
+	ExecDispatch: NativeExecutionDispatch + 'static,
+{
+	let executor = build_executor::<ExecDispatch>(&shared, &config);
+
+	// each pallet writes the storage version that it declares at genesis, which is thus where the
+	// versions of the local runtime are found.
+	let genesis = config.chain_spec.build_storage()?;
+	let code = genesis
+		.top
+		.get(well_known_keys::CODE)
+		.ok_or("code key must exist in genesis storage")?
+		.clone();
+
+	let ext = command
+		.state
+		.builder::<Block>()?
+		.state_version(shared.state_version)
+		.max_memory(shared.max_memory_bytes())
+		.rpc_config(shared.rpc_config())
+		.inject_hashed_key_value(&[(StorageKey(well_known_keys::CODE.to_vec()), StorageData(code))])
+		.build()
+		.await?;
+	let metadata = runtime_metadata::<Block, ExecDispatch>(&ext, &executor)?;
+
+	let (mut matching, mut behind, mut ahead, mut missing) = (0, 0, 0, 0);
+	for pallet in &metadata.pallets {
+		let key = [twox_128(pallet.name.as_bytes()), twox_128(STORAGE_VERSION_STORAGE_KEY_POSTFIX)]
+			.concat();
+		let declared = match genesis.top.get(&key) {
+			Some(value) => decode_version(&pallet.name, value)?,
+			None => {
+				log::debug!(target: LOG_TARGET, "{} declares no storage version", pallet.name);
+				continue
+			},
+		};
+		let on_chain = ext
+			.backend
+			.storage(&key)
+			.map_err(|e| format!("failed to read storage: {}", e))?
+			.map(|value| decode_version(&pallet.name, &value))
+			.transpose()?;
+
+		match on_chain {
+			None => {
+				missing += 1;
+				log::warn!(
+					target: LOG_TARGET,
+					"{}: no storage version on chain, the local runtime declares {}",
+					pallet.name,
+					declared,
+				);
+			},
+			Some(on_chain) if on_chain < declared => {
+				behind += 1;
+				log::warn!(
+					target: LOG_TARGET,
+					"{}: storage version {} on chain, the local runtime declares {}, a migration \
+					is required",
+					pallet.name,
+					on_chain,
+					declared,
+				);
+			},
+			Some(on_chain) if on_chain > declared => {
+				ahead += 1;
+				log::warn!(
+					target: LOG_TARGET,
+					"{}: storage version {} on chain is ahead of the {} that the local runtime \
+					declares",
+					pallet.name,
+					on_chain,
+					declared,
+				);
+			},
+			Some(on_chain) => {
+				matching += 1;
+				log::debug!(target: LOG_TARGET, "{}: storage version {}", pallet.name, on_chain);
+			},
+		}
+	}
+
+	log::info!(
+		target: LOG_TARGET,
+		"{} pallets match their on chain storage version, {} require a migration, {} are ahead and \
+		{} have no storage version on chain",
+		matching,
+		behind,
+		ahead,
+		missing,
+	);
+	Ok(())
+}
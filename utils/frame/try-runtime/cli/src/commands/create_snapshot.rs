@@ -0,0 +1,129 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{parse, resolve_block_number, SharedParams, State, LOG_TARGET};
+use sp_core::{bytes::to_hex, storage::well_known_keys};
+use sp_runtime::{
+	traits::{Block as BlockT, NumberFor},
+	DeserializeOwned,
+};
+use std::{fmt::Debug, num::NonZeroUsize, path::PathBuf, str::FromStr};
+use substrate_rpc_client::{rpc_client_with, ChainApi};
+
+/// Configurations of the [`Command::CreateSnapshot`].
+#[derive(Debug, Clone, clap::Parser)]
+pub struct CreateSnapshotCmd {
+	/// The url of the node to scrape the state from.
+	#[arg(short, long, value_parser = parse::url)]
+	uri: String,
+
+	/// The block hash or number at which to scrape the state.
+	///
+	/// If omitted, the latest finalized head is used.
+	#[arg(short, long, value_parser = parse::hash_or_number)]
+	at: Option<String>,
+
+	/// A pallet to scrape. Can be provided multiple times. If empty, entire chain state will be
+	/// scraped.
+	///
+	/// `*` stands for all the pallets in the metadata of the chain at the block being scraped.
+	#[arg(short, long, num_args = 1..)]
+	pallet: Vec<String>,
+
+	/// A pallet not to scrape. Can be provided multiple times, see [`State::Live`].
+	#[arg(long)]
+	exclude_pallet: Vec<String>,
+
+	/// Fetch the child-keys as well, see [`State::Live`].
+	#[arg(long)]
+	child_tree: bool,
+
+	/// The storage key of a default child tree to scrape as well, see [`State::Live`].
+	#[arg(long, value_parser = parse::hex_bytes)]
+	child_tree_key: Vec<String>,
+
+	/// The number of connections to the node over which the state is scraped concurrently.
+	#[arg(long, default_value = "1")]
+	threads: NonZeroUsize,
+
+	/// An existing snapshot to bring up to date with `--at`, instead of scraping the entire
+	/// state, see [`State::Live`]. May be the same as the path to write to.
+	#[arg(long)]
+	update_snapshot: Option<PathBuf>,
+
+	/// The path to write the snapshot to.
+	snapshot_path: PathBuf,
+}
+
+pub(crate) async fn create_snapshot<Block>(
+	shared: SharedParams,
+	command: CreateSnapshotCmd,
+) -> sc_cli::Result<()>
+where
+	Block: BlockT + DeserializeOwned,
+	Block::Hash: FromStr + DeserializeOwned,
+	<Block::Hash as FromStr>::Err: Debug,
+	Block::Header: DeserializeOwned,
+	NumberFor<Block>: FromStr,
+	<NumberFor<Block> as FromStr>::Err: Debug,
+{
+	// resolve the block upfront, such that it can be reported.
+	let at = match command.at {
+		Some(at) => resolve_block_number::<Block>(&command.uri, shared.rpc_config(), &at).await?,
+		None => {
+			let rpc = rpc_client_with(&command.uri, shared.rpc_config()).await?;
+			let head = ChainApi::<(), Block::Hash, Block::Header, ()>::finalized_head(&rpc)
+				.await
+				.map_err(|e| format!("failed to fetch the finalized head: {}", e))?;
+			log::info!(target: LOG_TARGET, "no --at provided, using the finalized head {:?}", head);
+			to_hex(head.as_ref(), false)
+		},
+	};
+
+	let state = State::Live {
+		uri: command.uri,
+		at: Some(at.clone()),
+		snapshot_path: Some(command.snapshot_path.clone()),
+		update_snapshot: command.update_snapshot,
+		pallet: command.pallet,
+		exclude_pallet: command.exclude_pallet,
+		child_tree: command.child_tree,
+		child_tree_key: command.child_tree_key,
+		include_key: Default::default(),
+		include_prefix: Default::default(),
+		threads: command.threads,
+	};
+
+	// the code is needed by every command that is executed on top of the snapshot.
+	let _ = state
+		.builder::<Block>()?
+		.state_version(shared.state_version)
+		.max_memory(shared.max_memory_bytes())
+		.rpc_config(shared.rpc_config())
+		.inject_hashed_key(well_known_keys::CODE)
+		.build()
+		.await?;
+
+	log::info!(
+		target: LOG_TARGET,
+		"wrote snapshot {:?} of block {} with state version {:?}",
+		command.snapshot_path,
+		at,
+		shared.state_version,
+	);
+	Ok(())
+}
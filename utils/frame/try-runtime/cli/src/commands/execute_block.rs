@@ -16,30 +16,73 @@
 // limitations under the License.
 
 use crate::{
-	build_executor, ensure_matching_spec, extract_code, full_extensions, hash_of, local_spec,
-	state_machine_call_with_proof, SharedParams, State, LOG_TARGET,
+	apply_changes,
+	assertions::{check_assertions, check_conservation},
+	build_executor,
+	cache::Cache,
+	changes_size, ensure_matching_spec, ensure_memory_limit, extract_code, full_extensions,
+	hash_of, hook_timing, local_version, maybe_store_snapshot,
+	mock_version::mock_runtime_version,
+	output::{Spec, Summary},
+	pallet_storage_prefixes, parse, report_divergence_from_chain, report_unknown_storage_writes,
+	state_machine_call, state_machine_call_with_proof, state_size, SharedParams, State, LOG_TARGET,
 };
-use parity_scale_codec::Encode;
+use parity_scale_codec::{Decode, Encode};
+use remote_externalities::TestExternalities;
 use sc_service::{Configuration, NativeExecutionDispatch};
-use sp_core::storage::well_known_keys;
-use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor};
+use sp_core::hashing::blake2_64;
+use sp_runtime::{
+	traits::{Block as BlockT, Hash as HashT, Header as HeaderT, NumberFor},
+	ConsensusEngineId, DigestItem,
+};
+use sp_version::RuntimeVersion;
+use sp_weights::Weight;
 use std::{fmt::Debug, str::FromStr};
-use substrate_rpc_client::{ws_client, ChainApi};
+use substrate_rpc_client::{rpc_client_with, rpc_params, ChainApi, ClientConfig, ClientT};
+
+/// A block, given by its hash or by its number.
+#[derive(Debug, Clone)]
+pub(crate) enum BlockRef {
+	/// A block hash, without the `0x` prefix.
+	Hash(String),
+	/// The number of the block of the canonical chain.
+	Number(u64),
+}
 
 /// Configurations of the [`Command::ExecuteBlock`].
 ///
 /// This will always call into `TryRuntime_execute_block`, which can optionally skip the state-root
 /// check (useful for trying a unreleased runtime), and can execute runtime sanity checks as well.
+///
+/// Before the block is executed, its digest is checked against the consensus engines implemented
+/// by the local runtime, and a warning is logged if the block seems to be produced under another
+/// consensus configuration.
 #[derive(Debug, Clone, clap::Parser)]
 pub struct ExecuteBlockCmd {
 	/// Overwrite the wasm code in state or not.
 	#[arg(long)]
 	overwrite_wasm_code: bool,
 
-	/// If set the state root check is disabled.
+	/// If set the state root check is disabled, both in the runtime and, when replaying with the
+	/// on-chain code, on the host.
 	#[arg(long)]
 	no_state_root_check: bool,
 
+	/// If set, report the time spent in, and the weight reported by, the `on_initialize`,
+	/// `on_idle` and `on_finalize` hooks of each pallet.
+	///
+	/// When executing wasm, this requires the runtime to be built with the `with-tracing` feature.
+	#[arg(long)]
+	hook_timings: bool,
+
+	/// If set, compare the storage written by the replay against the real state of the chain
+	/// after the block, and report every key that diverges.
+	///
+	/// This is most useful together with `--overwrite-wasm-code`, to pinpoint exactly which
+	/// storage items a logic change altered relative to the chain.
+	#[arg(long)]
+	diff_with_chain: bool,
+
 	/// Which try-state targets to execute when running this command.
 	///
 	/// Expected values:
@@ -52,6 +95,25 @@ pub struct ExecuteBlockCmd {
 	#[arg(long, default_value = "none")]
 	try_state: frame_try_runtime::TryStateSelect,
 
+	/// Replace the extrinsic at the given index of the block before executing it, as
+	/// `<index>=<hex>`, where `<hex>` is the SCALE encoded extrinsic. Can be given multiple times.
+	///
+	/// This is mostly useful for parachain runtimes, whose blocks start with the
+	/// `set_validation_data` inherent of the parachain system pallet: replaying such a block
+	/// against an altered state or runtime might require stubbing the validation data that the
+	/// relay chain provided. The replacement is not built here: this tree knows nothing of the
+	/// parachain system types, so it has to be encoded by the user, e.g. with a relay chain state
+	/// proof that matches the altered state.
+	///
+	/// The extrinsics root of the header is recomputed accordingly. Since the block then no longer
+	/// matches the chain, its state root is not checked.
+	#[arg(
+		long = "replace-extrinsic",
+		value_name = "INDEX=HEX",
+		value_parser = parse::indexed_hex_bytes
+	)]
+	replace_extrinsics: Vec<(usize, Vec<u8>)>,
+
 	/// The block hash at which to fetch the block.
 	///
 	/// If the `live` state type is being used, then this can be omitted, and is equal to whatever
@@ -62,7 +124,7 @@ pub struct ExecuteBlockCmd {
 	)]
 	block_at: Option<String>,
 
-	/// The ws uri from which to fetch the block.
+	/// The ws or http uri from which to fetch the block.
 	///
 	/// If the `live` state type is being used, then this can be omitted, and is equal to whatever
 	/// the `state::uri` is. Only use this (with care) when combined with a snapshot.
@@ -72,24 +134,45 @@ pub struct ExecuteBlockCmd {
 	)]
 	block_ws_uri: Option<String>,
 
+	/// Replay all blocks from this one up to `--to`, given by hash or by number.
+	///
+	/// The blocks are executed one after the other, each on top of the state that the previous one
+	/// left behind, starting with the state of the parent of this block, and the command stops at
+	/// the first block which fails or whose state root does not match its header. The latter is
+	/// not checked with `--overwrite-wasm-code`, for the code itself is part of the state, nor
+	/// with `--no-state-root-check`.
+	#[arg(
+		long,
+		value_parser = parse::block_ref,
+		requires = "to",
+		conflicts_with_all = ["block_at", "replace_extrinsics", "hook_timings", "diff_with_chain"]
+	)]
+	from: Option<BlockRef>,
+
+	/// The last block to replay, given by hash or by number, see `--from`.
+	#[arg(long, value_parser = parse::block_ref, requires = "from")]
+	to: Option<BlockRef>,
+
 	/// The state type to use.
 	///
 	/// For this command only, if the `live` is used, then state of the parent block is fetched.
 	///
 	/// If `block_at` is provided, then the [`State::Live::at`] is being ignored.
 	#[command(subcommand)]
-	state: State,
+	pub(crate) state: State,
 }
 
 impl ExecuteBlockCmd {
-	async fn block_at<Block: BlockT>(&self, ws_uri: String) -> sc_cli::Result<Block::Hash>
+	async fn block_at<Block: BlockT>(
+		&self,
+		ws_uri: String,
+		rpc_config: ClientConfig,
+	) -> sc_cli::Result<Block::Hash>
 	where
 		Block::Hash: FromStr + serde::de::DeserializeOwned,
 		<Block::Hash as FromStr>::Err: Debug,
 		Block::Header: serde::de::DeserializeOwned,
 	{
-		let rpc = ws_client(&ws_uri).await?;
-
 		match (&self.block_at, &self.state) {
 			(Some(block_at), State::Snap { .. }) => hash_of::<Block>(block_at),
 			(Some(block_at), State::Live { .. }) => {
@@ -101,40 +184,174 @@ impl ExecuteBlockCmd {
 					target: LOG_TARGET,
 					"No --block-at or --at provided, using the latest finalized block instead"
 				);
+				let rpc = rpc_client_with(&ws_uri, rpc_config).await?;
 				ChainApi::<(), Block::Hash, Block::Header, ()>::finalized_head(&rpc)
 					.await
 					.map_err(|e| e.to_string().into())
 			},
 			(None, State::Live { at: Some(at), .. }) => hash_of::<Block>(at),
-			_ => {
-				panic!("either `--block-at` must be provided, or state must be `live with a proper `--at``");
-			},
+			_ => Err("either `--block-at` must be provided, or state must be `live` with a proper `--at`"
+				.into()),
 		}
 	}
 
-	fn block_ws_uri<Block: BlockT>(&self) -> String
+	fn block_ws_uri<Block: BlockT>(&self) -> sc_cli::Result<String>
 	where
 		Block::Hash: FromStr,
 		<Block::Hash as FromStr>::Err: Debug,
 	{
 		match (&self.block_ws_uri, &self.state) {
-			(Some(block_ws_uri), State::Snap { .. }) => block_ws_uri.to_owned(),
+			(Some(block_ws_uri), State::Snap { .. }) => Ok(block_ws_uri.to_owned()),
 			(Some(block_ws_uri), State::Live { .. }) => {
 				log::error!(target: LOG_TARGET, "--block-uri is provided while state type is live, Are you sure you know what you are doing?");
-				block_ws_uri.to_owned()
-			},
-			(None, State::Live { uri, .. }) => uri.clone(),
-			(None, State::Snap { .. }) => {
-				panic!("either `--block-uri` must be provided, or state must be `live`");
+				Ok(block_ws_uri.to_owned())
 			},
+			(None, State::Live { uri, .. }) => Ok(uri.clone()),
+			(None, State::Snap { .. }) =>
+				Err("either `--block-uri` must be provided, or state must be `live`".into()),
+		}
+	}
+
+	/// Build the state of the parent block, with hash `parent_hash`, of the block to execute.
+	async fn parent_state<Block, ExecDispatch>(
+		&self,
+		shared: &SharedParams,
+		config: &Configuration,
+		cache: &Cache,
+		parent_hash: Block::Hash,
+	) -> sc_cli::Result<TestExternalities>
+	where
+		Block: BlockT + serde::de::DeserializeOwned,
+		Block::Hash: FromStr,
+		<Block::Hash as FromStr>::Err: Debug,
+		ExecDispatch: NativeExecutionDispatch + 'static,
+	{
+		let builder = self
+			.state
+			.builder::<Block>()?
+			// make sure the state is being build with the parent hash, if it is online.
+			.overwrite_online_at(parent_hash)
+			.state_version(shared.state_version)
+			.max_memory(shared.max_memory_bytes())
+			.rpc_config(shared.rpc_config());
+
+		let builder = if self.overwrite_wasm_code {
+			log::info!(
+				target: LOG_TARGET,
+				"replacing the in-storage :code: with the local code from {}'s chain_spec (your local repo)",
+				config.chain_spec.name(),
+			);
+			let (code_key, code) = extract_code(&config.chain_spec)?;
+			builder.inject_hashed_key_value(&[(code_key, code)])
+		} else {
+			cache.inject_code(builder, &self.state, Some(parent_hash)).await?
+		};
+
+		builder.build().await.map_err(Into::into)
+	}
+}
+
+/// Get the hash of the block `block` from the node at `uri`.
+pub(crate) async fn hash_of_ref<Block: BlockT>(
+	uri: &str,
+	rpc_config: ClientConfig,
+	block: &BlockRef,
+) -> sc_cli::Result<Block::Hash>
+where
+	Block::Hash: FromStr + serde::de::DeserializeOwned,
+	<Block::Hash as FromStr>::Err: Debug,
+{
+	match block {
+		BlockRef::Hash(hash) => hash_of::<Block>(hash),
+		BlockRef::Number(number) => {
+			let rpc = rpc_client_with(uri, rpc_config).await?;
+			rpc.request::<Option<Block::Hash>>("chain_getBlockHash", rpc_params![number])
+				.await
+				.map_err(|e| format!("failed to fetch the hash of block #{}: {:?}", number, e))?
+				.ok_or_else(|| format!("block #{} not found", number).into())
+		},
+	}
+}
+
+/// Well-known consensus engines, along with the runtime api that a runtime using them implements,
+/// and whether the author of a block announces itself in a pre-runtime digest of the engine.
+const KNOWN_ENGINES: [(ConsensusEngineId, &str, bool); 4] = [
+	(*b"BABE", "BabeApi", true),
+	(*b"aura", "AuraApi", true),
+	(*b"FRNK", "GrandpaApi", false),
+	(*b"BEEF", "BeefyApi", false),
+];
+
+/// Check the digest of `header` against the consensus engines that the local runtime, of
+/// `version`, implements, and warn about everything that suggests that the block was produced
+/// with another consensus configuration.
+///
+/// Returns whether the last digest item is a seal.
+fn check_digest<Block: BlockT>(header: &Block::Header, version: &RuntimeVersion) -> bool {
+	let engine = |id: &ConsensusEngineId| String::from_utf8_lossy(id).into_owned();
+	let logs = header.digest().logs();
+
+	let sealed = matches!(logs.last(), Some(DigestItem::Seal(_, _)));
+	if !sealed {
+		log::warn!(
+			target: LOG_TARGET,
+			"block #{:?} is not sealed, executing it with its digest as-is",
+			header.number(),
+		);
+	}
+
+	for item in logs {
+		let (kind, id) = match item {
+			DigestItem::PreRuntime(id, _) => ("pre-runtime", id),
+			DigestItem::Consensus(id, _) => ("consensus", id),
+			DigestItem::Seal(id, _) => ("seal", id),
+			_ => continue,
+		};
+		match KNOWN_ENGINES.iter().find(|(known, _, _)| known == id) {
+			Some((_, api, _)) if !version.has_api_with(&blake2_64(api.as_bytes()), |_| true) =>
+				log::warn!(
+					target: LOG_TARGET,
+					"the block has a {} digest of the {} consensus engine, but the local runtime \
+					does not implement {}: it was probably produced under a different consensus \
+					configuration",
+					kind,
+					engine(id),
+					api,
+				),
+			Some(_) => (),
+			None => log::debug!(
+				target: LOG_TARGET,
+				"the block has a {} digest of the unknown consensus engine {:?}",
+				kind,
+				engine(id),
+			),
 		}
 	}
+
+	for (id, api, _) in KNOWN_ENGINES.iter().filter(|(_, _, announces_author)| *announces_author) {
+		let implemented = version.has_api_with(&blake2_64(api.as_bytes()), |_| true);
+		let announced =
+			logs.iter().any(|item| matches!(item, DigestItem::PreRuntime(i, _) if i == id));
+		if implemented && !announced {
+			log::warn!(
+				target: LOG_TARGET,
+				"the local runtime implements {}, but the block has no pre-runtime digest of the {} \
+				consensus engine: it was probably produced under a different consensus \
+				configuration",
+				api,
+				engine(id),
+			);
+		}
+	}
+
+	sealed
 }
 
 pub(crate) async fn execute_block<Block, ExecDispatch>(
 	shared: SharedParams,
 	command: ExecuteBlockCmd,
 	config: Configuration,
+	summary: &mut Summary,
 ) -> sc_cli::Result<()>
 where
 	Block: BlockT + serde::de::DeserializeOwned,
@@ -146,16 +363,17 @@ where
 	<NumberFor<Block> as FromStr>::Err: Debug,
 	ExecDispatch: NativeExecutionDispatch + 'static,
 {
+	if command.from.is_some() {
+		return execute_block_range::<Block, ExecDispatch>(shared, command, config, summary).await
+	}
+
 	let executor = build_executor::<ExecDispatch>(&shared, &config);
 	let execution = shared.execution;
 
-	let block_ws_uri = command.block_ws_uri::<Block>();
-	let block_at = command.block_at::<Block>(block_ws_uri.clone()).await?;
-	let rpc = ws_client(&block_ws_uri).await?;
-	let block: Block = ChainApi::<(), Block::Hash, Block::Header, _>::block(&rpc, Some(block_at))
-		.await
-		.unwrap()
-		.unwrap();
+	let block_ws_uri = command.block_ws_uri::<Block>()?;
+	let block_at = command.block_at::<Block>(block_ws_uri.clone(), shared.rpc_config()).await?;
+	let cache = Cache::new(&shared);
+	let block = cache.block::<Block>(&block_ws_uri, block_at).await?;
 	let parent_hash = block.header().parent_hash();
 	log::info!(
 		target: LOG_TARGET,
@@ -165,56 +383,307 @@ where
 		parent_hash
 	);
 
-	let ext = {
-		let builder = command
-			.state
-			.builder::<Block>()?
-			// make sure the state is being build with the parent hash, if it is online.
-			.overwrite_online_at(parent_hash.to_owned())
-			.state_version(shared.state_version);
+	let ext = command
+		.parent_state::<Block, ExecDispatch>(&shared, &config, &cache, *parent_hash)
+		.await?;
 
-		let builder = if command.overwrite_wasm_code {
-			log::info!(
+	let expected_state_root = *block.header().state_root();
+	let version = local_version::<Block, ExecDispatch>(&ext, &executor);
+	let spec_state_version = version.state_version();
+
+	// The seal is added by the author after the runtime has processed the block, so we need to
+	// pop it to be consistent with what the runtime saw when the block was produced.
+	let sealed = check_digest::<Block>(block.header(), &version);
+	let (mut header, mut extrinsics) = block.deconstruct();
+	if sealed {
+		header.digest_mut().pop();
+	}
+
+	let patched = !command.replace_extrinsics.is_empty();
+	for (index, encoded) in &command.replace_extrinsics {
+		let extrinsic = extrinsics
+			.get_mut(*index)
+			.ok_or_else(|| format!("the block has no extrinsic at index {}", index))?;
+		*extrinsic = <Block::Extrinsic as Decode>::decode(&mut &**encoded)
+			.map_err(|e| format!("failed to decode extrinsic {}: {:?}", index, e))?;
+		log::info!(target: LOG_TARGET, "replaced the extrinsic at index {}", index);
+	}
+	if patched {
+		let extrinsics_root = <<Block::Header as HeaderT>::Hashing as HashT>::ordered_trie_root(
+			extrinsics.iter().map(Encode::encode).collect(),
+			spec_state_version,
+		);
+		header.set_extrinsics_root(extrinsics_root);
+		if !command.no_state_root_check {
+			log::warn!(
 				target: LOG_TARGET,
-				"replacing the in-storage :code: with the local code from {}'s chain_spec (your local repo)",
-				config.chain_spec.name(),
+				"the block has been patched, its state root is not going to be checked"
 			);
-			let (code_key, code) = extract_code(&config.chain_spec)?;
-			builder.inject_hashed_key_value(&[(code_key, code)])
+		}
+	}
+
+	let block = Block::new(header, extrinsics);
+	let state_root_check = !command.no_state_root_check && !patched;
+	let payload = (block.clone(), state_root_check, command.try_state).encode();
+
+	summary.local_spec =
+		Some(Spec { name: version.spec_name.to_string(), version: version.spec_version });
+	summary.provenance.record_code(&ext);
+	summary.provenance.endpoint = Some(block_ws_uri.clone());
+	summary.remote_spec =
+		ensure_matching_spec::<Block>(block_ws_uri.clone(), &version, &shared).await?;
+
+	let call = || {
+		state_machine_call_with_proof::<Block, ExecDispatch>(
+			&ext,
+			&executor,
+			execution,
+			"TryRuntime_execute_block",
+			&payload,
+			mock_runtime_version(full_extensions(), &shared, &executor),
+			shared.record_proof,
+		)
+	};
+	let result = if command.hook_timings {
+		let (result, timings) = hook_timing::measure(call);
+		timings.log();
+		result
+	} else {
+		call()
+	};
+	maybe_store_snapshot(&shared, &ext, &result);
+	let (changes, encoded_result) = result?;
+
+	log::info!(target: LOG_TARGET, "Core_execute_block executed without errors.");
+	let weight = <Weight as Decode>::decode(&mut &*encoded_result)
+		.map_err(|e| format!("failed to decode weight: {:?}", e))?;
+	summary.storage_roots(
+		ext.backend.root(),
+		changes.storage_root(&ext.backend, &mut Default::default(), spec_state_version),
+	);
+	summary.weight = Some(weight.into());
+	ensure_memory_limit(&shared, state_size(&ext), changes_size(&changes))?;
+	check_assertions::<Block, ExecDispatch>(&shared, &ext, &executor, &changes)?;
+	check_conservation::<Block, ExecDispatch>(&shared, &ext, &executor, &changes)?;
+
+	let known_prefixes = pallet_storage_prefixes::<Block, ExecDispatch>(&ext, &executor)?;
+	report_unknown_storage_writes(&changes, &known_prefixes);
+
+	if command.diff_with_chain {
+		let rpc = rpc_client_with(&block_ws_uri, shared.rpc_config()).await?;
+		report_divergence_from_chain::<Block>(&rpc, block_at, &changes, &known_prefixes).await?;
+	}
+
+	// with the on-chain code, the host must arrive at exactly the same state root as the chain.
+	if state_root_check && !command.overwrite_wasm_code {
+		let state_root =
+			changes.storage_root(&ext.backend, &mut Default::default(), spec_state_version);
+		if state_root.as_ref() != expected_state_root.as_ref() {
+			return Err(format!(
+				"state root mismatch while replaying with the on-chain code: computed {:?}, \
+				block header has {:?}",
+				state_root, expected_state_root,
+			)
+			.into())
+		}
+		log::info!(target: LOG_TARGET, "post-state root {:?} matches the chain.", state_root);
+	}
+
+	Ok(())
+}
+
+/// Execute the blocks from [`ExecuteBlockCmd::from`] up to [`ExecuteBlockCmd::to`], one after the
+/// other, and fail at the first one that diverges.
+///
+/// The storage roots of the `summary` are the ones before the first and after the last block, and
+/// its weight is the sum of the weights of all blocks.
+async fn execute_block_range<Block, ExecDispatch>(
+	shared: SharedParams,
+	command: ExecuteBlockCmd,
+	config: Configuration,
+	summary: &mut Summary,
+) -> sc_cli::Result<()>
+where
+	Block: BlockT + serde::de::DeserializeOwned,
+	Block::Hash: FromStr + serde::de::DeserializeOwned,
+	<Block::Hash as FromStr>::Err: Debug,
+	Block::Header: serde::de::DeserializeOwned,
+	ExecDispatch: NativeExecutionDispatch + 'static,
+{
+	let executor = build_executor::<ExecDispatch>(&shared, &config);
+	let execution = shared.execution;
+	let cache = Cache::new(&shared);
+
+	let block_ws_uri = command.block_ws_uri::<Block>()?;
+	let (from, to) = match (&command.from, &command.to) {
+		(Some(from), Some(to)) => (from, to),
+		_ => return Err("both `--from` and `--to` must be provided".into()),
+	};
+	let from = hash_of_ref::<Block>(&block_ws_uri, shared.rpc_config(), from).await?;
+	let to = hash_of_ref::<Block>(&block_ws_uri, shared.rpc_config(), to).await?;
+
+	// walk back from `to`, so that the range also works on forks.
+	let first = cache.header::<Block>(&block_ws_uri, from).await?;
+	let mut headers = vec![cache.header::<Block>(&block_ws_uri, to).await?];
+	loop {
+		let header = headers.last().expect("never empty; qed");
+		if header.hash() == from {
+			break
+		}
+		if header.number() <= first.number() {
+			return Err(format!("block {:?} is not an ancestor of block {:?}", from, to).into())
+		}
+		let parent_hash = *header.parent_hash();
+		headers.push(cache.header::<Block>(&block_ws_uri, parent_hash).await?);
+	}
+	headers.reverse();
+	log::info!(
+		target: LOG_TARGET,
+		"replaying {} blocks, from #{:?} to #{:?}",
+		headers.len(),
+		first.number(),
+		headers.last().expect("never empty; qed").number(),
+	);
+
+	let mut ext = command
+		.parent_state::<Block, ExecDispatch>(&shared, &config, &cache, *first.parent_hash())
+		.await?;
+
+	let version = local_version::<Block, ExecDispatch>(&ext, &executor);
+	summary.local_spec =
+		Some(Spec { name: version.spec_name.to_string(), version: version.spec_version });
+	summary.provenance.record_code(&ext);
+	summary.provenance.endpoint = Some(block_ws_uri.clone());
+	summary.remote_spec =
+		ensure_matching_spec::<Block>(block_ws_uri.clone(), &version, &shared).await?;
+
+	let root_before = *ext.backend.root();
+	let mut total_weight = Weight::zero();
+	// the storage prefixes of the pallets, along with the spec version they were read in, since a
+	// block of the range might upgrade the runtime.
+	let mut known_prefixes: Option<(u32, Vec<(String, [u8; 16])>)> = None;
+
+	for header in &headers {
+		let hash = header.hash();
+		let block = cache.block::<Block>(&block_ws_uri, hash).await?;
+		let version = local_version::<Block, ExecDispatch>(&ext, &executor);
+		let state_version = version.state_version();
+
+		let sealed = check_digest::<Block>(block.header(), &version);
+		let (mut header, extrinsics) = block.deconstruct();
+		if sealed {
+			header.digest_mut().pop();
+		}
+		let number = *header.number();
+		let expected_state_root = *header.state_root();
+
+		// the state root is checked here rather than by the runtime, to report it along with the
+		// computed one.
+		let payload = (Block::new(header, extrinsics), false, command.try_state.clone()).encode();
+		let extensions = mock_runtime_version(full_extensions(), &shared, &executor);
+		let result = if shared.record_proof {
+			state_machine_call_with_proof::<Block, ExecDispatch>(
+				&ext,
+				&executor,
+				execution,
+				"TryRuntime_execute_block",
+				&payload,
+				extensions,
+				true,
+			)
 		} else {
-			builder.inject_hashed_key(well_known_keys::CODE)
+			state_machine_call::<Block, ExecDispatch>(
+				&ext,
+				&executor,
+				execution,
+				"TryRuntime_execute_block",
+				&payload,
+				extensions,
+			)
 		};
+		maybe_store_snapshot(&shared, &ext, &result);
+		let (changes, encoded_result) =
+			result.map_err(|e| format!("block #{:?} ({:?}) diverges: {}", number, hash, e))?;
+		let weight = <Weight as Decode>::decode(&mut &*encoded_result)
+			.map_err(|e| format!("failed to decode weight: {:?}", e))?;
+		total_weight.saturating_accrue(weight);
 
-		builder.build().await?
+		check_conservation::<Block, ExecDispatch>(&shared, &ext, &executor, &changes)
+			.map_err(|e| format!("block #{:?} ({:?}): {}", number, hash, e))?;
+		if known_prefixes.as_ref().map_or(true, |(v, _)| *v != version.spec_version) {
+			let prefixes = pallet_storage_prefixes::<Block, ExecDispatch>(&ext, &executor)?;
+			known_prefixes = Some((version.spec_version, prefixes));
+		}
+		let (_, prefixes) = known_prefixes.as_ref().expect("set above; qed");
+		report_unknown_storage_writes(&changes, prefixes);
+
+		if !command.no_state_root_check && !command.overwrite_wasm_code {
+			let state_root =
+				changes.storage_root(&ext.backend, &mut Default::default(), state_version);
+			if state_root.as_ref() != expected_state_root.as_ref() {
+				return Err(format!(
+					"block #{:?} ({:?}) diverges: computed state root {:?}, block header has {:?}",
+					number, hash, state_root, expected_state_root,
+				)
+				.into())
+			}
+		}
+		apply_changes(&mut ext, changes, state_version)?;
+		log::info!(target: LOG_TARGET, "block #{:?} ({:?}) executed without errors.", number, hash);
+	}
+
+	log::info!(target: LOG_TARGET, "replayed {} blocks without divergence.", headers.len());
+	summary.storage_roots(root_before, ext.backend.root());
+	summary.weight = Some(total_weight.into());
+	check_assertions::<Block, ExecDispatch>(&shared, &ext, &executor, &Default::default())?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_runtime::{
+		testing::{Block as RawBlock, ExtrinsicWrapper, Header, H256},
+		Digest,
 	};
 
-	// A digest item gets added when the runtime is processing the block, so we need to pop
-	// the last one to be consistent with what a gossiped block would contain.
-	let (mut header, extrinsics) = block.deconstruct();
-	header.digest_mut().pop();
-	let block = Block::new(header, extrinsics);
-	let payload = (block.clone(), !command.no_state_root_check, command.try_state).encode();
-
-	let (expected_spec_name, expected_spec_version, _) =
-		local_spec::<Block, ExecDispatch>(&ext, &executor);
-	ensure_matching_spec::<Block>(
-		block_ws_uri.clone(),
-		expected_spec_name,
-		expected_spec_version,
-		shared.no_spec_check_panic,
-	)
-	.await;
-
-	let _ = state_machine_call_with_proof::<Block, ExecDispatch>(
-		&ext,
-		&executor,
-		execution,
-		"TryRuntime_execute_block",
-		&payload,
-		full_extensions(),
-	)?;
+	type Block = RawBlock<ExtrinsicWrapper<H256>>;
 
-	log::info!(target: LOG_TARGET, "Core_execute_block executed without errors.");
+	fn header(logs: Vec<DigestItem>) -> Header {
+		Header::new(1, Default::default(), Default::default(), Default::default(), Digest { logs })
+	}
 
-	Ok(())
+	fn version(apis: &[&str]) -> RuntimeVersion {
+		RuntimeVersion {
+			apis: apis.iter().map(|api| (blake2_64(api.as_bytes()), 1)).collect::<Vec<_>>().into(),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn check_digest_detects_the_seal() {
+		let babe = version(&["BabeApi"]);
+		let pre_runtime = DigestItem::PreRuntime(*b"BABE", vec![]);
+		let seal = DigestItem::Seal(*b"BABE", vec![]);
+
+		assert!(check_digest::<Block>(&header(vec![pre_runtime.clone(), seal.clone()]), &babe));
+		assert!(!check_digest::<Block>(&header(vec![pre_runtime.clone()]), &babe));
+		assert!(!check_digest::<Block>(&header(vec![seal, pre_runtime]), &babe));
+		assert!(!check_digest::<Block>(&header(vec![]), &babe));
+	}
+
+	#[test]
+	fn check_digest_accepts_foreign_engines() {
+		// e.g. a block of another consensus configuration, or of an unknown engine: this is only
+		// warned about.
+		let aura = version(&["AuraApi"]);
+		let logs = vec![
+			DigestItem::PreRuntime(*b"BABE", vec![]),
+			DigestItem::Consensus(*b"FRNK", vec![]),
+			DigestItem::Other(vec![1]),
+			DigestItem::Seal(*b"pow_", vec![]),
+		];
+		assert!(check_digest::<Block>(&header(logs), &aura));
+		assert!(check_digest::<Block>(&header(vec![DigestItem::Seal(*b"aura", vec![])]), &aura));
+	}
 }
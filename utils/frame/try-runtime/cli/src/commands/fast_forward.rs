@@ -0,0 +1,303 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+	apply_changes, assertions::check_assertions, build_executor, cache::Cache,
+	ensure_matching_spec, extract_code, full_extensions, local_version,
+	mock_version::mock_runtime_version, parse, state_machine_call, SharedParams, State, LOG_TARGET,
+};
+use parity_scale_codec::{Decode, Encode, Output};
+use remote_externalities::TestExternalities;
+use sc_executor::{NativeElseWasmExecutor, NativeExecutionDispatch};
+use sc_service::Configuration;
+use sp_core::{hashing::blake2_64, twox_128};
+use sp_inherents::{InherentData, InherentIdentifier};
+use sp_runtime::{
+	traits::{Block as BlockT, Header as HeaderT, NumberFor, One},
+	ApplyExtrinsicResult, Digest, DigestItem,
+};
+use sp_state_machine::Backend;
+use sp_version::RuntimeVersion;
+use std::{fmt::Debug, str::FromStr};
+
+/// The identifier of the inherent of the timestamp pallet.
+const TIMESTAMP_INHERENT_IDENTIFIER: InherentIdentifier = *b"timstap0";
+
+/// Configurations of the [`Command::FastForward`].
+#[derive(Debug, Clone, clap::Parser)]
+pub struct FastForwardCmd {
+	/// The number of blocks to produce.
+	#[arg(long)]
+	n_blocks: u32,
+
+	/// The time between two blocks, in milliseconds.
+	///
+	/// Each block is timestamped this much later than its parent, starting from `Timestamp::Now`.
+	/// This assumes that the timestamp pallet is named `Timestamp` in `construct_runtime!()`. For
+	/// runtimes that use BABE or Aura, this must be the slot duration.
+	#[arg(long, default_value = "6000")]
+	blocktime: u64,
+
+	/// Only execute `on_initialize` and `on_finalize` of each block, without any inherents.
+	///
+	/// Note that most runtimes expect at least the timestamp inherent in every block.
+	#[arg(long)]
+	no_inherents: bool,
+
+	/// Additional inherent data to create the inherents of every block from, as
+	/// `<identifier>=<hex>`, where `<identifier>` is the 8 character identifier of the inherent
+	/// and `<hex>` its SCALE encoded data. Can be given multiple times.
+	///
+	/// This is mostly useful for parachain runtimes, whose parachain system pallet creates its
+	/// `set_validation_data` inherent from the `ParachainInherentData` under `sysi1337`. That data
+	/// is provided by the relay chain and can not be made up here, hence it has to be given, and
+	/// the same data is used for every block.
+	#[arg(
+		long = "inherent-data",
+		value_name = "IDENTIFIER=HEX",
+		value_parser = parse::inherent_data,
+		conflicts_with = "no_inherents"
+	)]
+	inherent_data: Vec<(InherentIdentifier, Vec<u8>)>,
+
+	/// Overwrite the wasm code in state or not.
+	#[arg(long)]
+	overwrite_wasm_code: bool,
+
+	/// The state type to use.
+	#[command(subcommand)]
+	pub(crate) state: State,
+}
+
+/// The digest that claims `slot` for the first authority, if the runtime of `version` uses BABE
+/// or Aura.
+fn slot_digest(version: &RuntimeVersion, slot: u64) -> Digest {
+	let implements = |api: &str| version.has_api_with(&blake2_64(api.as_bytes()), |_| true);
+	let mut digest = Digest::default();
+	if implements("BabeApi") {
+		// a `PreDigest::SecondaryPlain`, which does not require a vrf output.
+		digest.push(DigestItem::PreRuntime(*b"BABE", (2u8, 0u32, slot).encode()));
+	} else if implements("AuraApi") {
+		digest.push(DigestItem::PreRuntime(*b"aura", slot.encode()));
+	}
+	digest
+}
+
+/// Inherent data that is already SCALE encoded.
+struct Encoded<'a>(&'a [u8]);
+
+impl Encode for Encoded<'_> {
+	fn size_hint(&self) -> usize {
+		self.0.len()
+	}
+
+	fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+		dest.write(self.0)
+	}
+}
+
+/// Read and decode the storage value `pallet::item` from the state of `ext`.
+fn read_value<T: Decode>(
+	ext: &TestExternalities,
+	pallet: &str,
+	item: &str,
+) -> sc_cli::Result<Option<T>> {
+	let key = [twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat();
+	ext.backend
+		.storage(&key)
+		.map_err(|e| format!("failed to read {}::{}: {}", pallet, item, e))?
+		.map(|value| {
+			T::decode(&mut &*value)
+				.map_err(|e| format!("failed to decode {}::{}: {}", pallet, item, e))
+		})
+		.transpose()
+		.map_err(Into::into)
+}
+
+/// Produce and execute `n_blocks` blocks on top of the state of `ext`, each timestamped
+/// `blocktime` milliseconds after its parent, see [`Command::FastForward`].
+///
+/// If `inherents` is given, each block contains the inherents that the runtime creates from the
+/// timestamp and the given additional inherent data. Otherwise, blocks have no extrinsics.
+///
+/// Returns the number of the last block.
+pub(crate) fn produce_blocks<Block, ExecDispatch>(
+	shared: &SharedParams,
+	executor: &NativeElseWasmExecutor<ExecDispatch>,
+	ext: &mut TestExternalities,
+	n_blocks: u32,
+	blocktime: u64,
+	inherents: Option<&[(InherentIdentifier, Vec<u8>)]>,
+) -> sc_cli::Result<NumberFor<Block>>
+where
+	Block: BlockT,
+	ExecDispatch: NativeExecutionDispatch + 'static,
+{
+	let version = local_version::<Block, ExecDispatch>(ext, executor);
+	let state_version = version.state_version();
+
+	let mut number = read_value::<NumberFor<Block>>(ext, "System", "Number")?
+		.ok_or("no block number found in `System::Number`")?;
+	let mut now = read_value::<u64>(ext, "Timestamp", "Now")?.unwrap_or_else(|| {
+		log::warn!(target: LOG_TARGET, "no timestamp found in `Timestamp::Now`, starting at zero");
+		0
+	});
+	// the hash of the block of the state is not part of the state, hence the first block has a
+	// made up parent.
+	let mut parent_hash = Block::Hash::default();
+
+	let call = |ext: &TestExternalities, method: &'static str, data: &[u8]| {
+		state_machine_call::<Block, ExecDispatch>(
+			ext,
+			executor,
+			shared.execution,
+			method,
+			data,
+			mock_runtime_version(full_extensions(), shared, executor),
+		)
+	};
+
+	for _ in 0..n_blocks {
+		number += One::one();
+		now = now.saturating_add(blocktime);
+		let header = <Block::Header as HeaderT>::new(
+			number,
+			Default::default(),
+			Default::default(),
+			parent_hash,
+			slot_digest(&version, now / blocktime.max(1)),
+		);
+
+		let (changes, _) = call(ext, "Core_initialize_block", &header.encode())?;
+		apply_changes(ext, changes, state_version)?;
+
+		let mut applied = 0;
+		if let Some(additional) = inherents {
+			let mut inherent_data = InherentData::new();
+			inherent_data
+				.put_data(TIMESTAMP_INHERENT_IDENTIFIER, &now)
+				.map_err(|e| format!("failed to create the timestamp inherent: {:?}", e))?;
+			for (identifier, data) in additional {
+				inherent_data.put_data(*identifier, &Encoded(data)).map_err(|e| {
+					format!(
+						"failed to add the inherent data {}: {:?}",
+						String::from_utf8_lossy(identifier),
+						e
+					)
+				})?;
+			}
+			let (_, encoded) =
+				call(ext, "BlockBuilder_inherent_extrinsics", &inherent_data.encode())?;
+			let extrinsics = <Vec<Block::Extrinsic> as Decode>::decode(&mut &*encoded)
+				.map_err(|e| format!("failed to decode inherents: {:?}", e))?;
+
+			for extrinsic in extrinsics {
+				let (changes, encoded) =
+					call(ext, "BlockBuilder_apply_extrinsic", &extrinsic.encode())?;
+				match <ApplyExtrinsicResult as Decode>::decode(&mut &*encoded)
+					.map_err(|e| format!("failed to decode output: {:?}", e))?
+				{
+					Ok(Ok(())) => (),
+					Ok(Err(e)) =>
+						return Err(
+							format!("block #{:?}: an inherent failed: {:?}", number, e).into()
+						),
+					Err(e) =>
+						return Err(
+							format!("block #{:?}: an inherent is invalid: {:?}", number, e).into()
+						),
+				}
+				apply_changes(ext, changes, state_version)?;
+				applied += 1;
+			}
+		}
+
+		let (changes, encoded) = call(ext, "BlockBuilder_finalize_block", &[])?;
+		apply_changes(ext, changes, state_version)?;
+		let header = <Block::Header as Decode>::decode(&mut &*encoded)
+			.map_err(|e| format!("failed to decode header: {:?}", e))?;
+		parent_hash = header.hash();
+
+		log::info!(
+			target: LOG_TARGET,
+			"produced block #{:?} at timestamp {} with {} inherents, state root {:?}",
+			number,
+			now,
+			applied,
+			header.state_root(),
+		);
+	}
+
+	Ok(number)
+}
+
+pub(crate) async fn fast_forward<Block, ExecDispatch>(
+	shared: SharedParams,
+	command: FastForwardCmd,
+	config: Configuration,
+) -> sc_cli::Result<()>
+where
+	Block: BlockT + serde::de::DeserializeOwned,
+	Block::Hash: FromStr,
+	<Block::Hash as FromStr>::Err: Debug,
+	Block::Header: serde::de::DeserializeOwned,
+	NumberFor<Block>: FromStr,
+	<NumberFor<Block> as FromStr>::Err: Debug,
+	ExecDispatch: NativeExecutionDispatch + 'static,
+{
+	let executor = build_executor::<ExecDispatch>(&shared, &config);
+
+	let mut ext = {
+		let builder = command
+			.state
+			.builder::<Block>()?
+			.state_version(shared.state_version)
+			.max_memory(shared.max_memory_bytes())
+			.rpc_config(shared.rpc_config());
+
+		let builder = if command.overwrite_wasm_code {
+			log::info!(
+				target: LOG_TARGET,
+				"replacing the in-storage :code: with the local code from {}'s chain_spec (your local repo)",
+				config.chain_spec.name(),
+			);
+			let (code_key, code) = extract_code(&config.chain_spec)?;
+			builder.inject_hashed_key_value(&[(code_key, code)])
+		} else {
+			Cache::new(&shared).inject_code(builder, &command.state, None).await?
+		};
+
+		builder.build().await?
+	};
+
+	let version = local_version::<Block, ExecDispatch>(&ext, &executor);
+	if let Some(uri) = command.state.live_uri() {
+		ensure_matching_spec::<Block>(uri, &version, &shared).await?;
+	}
+	let number = produce_blocks::<Block, ExecDispatch>(
+		&shared,
+		&executor,
+		&mut ext,
+		command.n_blocks,
+		command.blocktime,
+		(!command.no_inherents).then_some(&command.inherent_data[..]),
+	)?;
+
+	log::info!(target: LOG_TARGET, "fast-forwarded {} blocks to #{:?}", command.n_blocks, number);
+	check_assertions::<Block, ExecDispatch>(&shared, &ext, &executor, &Default::default())?;
+	Ok(())
+}
@@ -16,8 +16,11 @@
 // limitations under the License.
 
 use crate::{
-	build_executor, ensure_matching_spec, extract_code, full_extensions, local_spec, parse,
-	state_machine_call_with_proof, SharedParams, LOG_TARGET,
+	build_executor, changes_size, ensure_matching_spec, ensure_memory_limit, extract_code,
+	full_extensions, hook_timing, local_version, maybe_store_snapshot,
+	mock_version::mock_runtime_version, pallet_storage_prefixes, parse,
+	report_unknown_storage_writes, state_machine_call_with_proof, state_size, SharedParams,
+	LOG_TARGET,
 };
 use parity_scale_codec::{Decode, Encode};
 use remote_externalities::{Builder, Mode, OnlineConfig};
@@ -27,7 +30,9 @@ use serde::{de::DeserializeOwned, Serialize};
 use sp_core::H256;
 use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor};
 use std::{fmt::Debug, str::FromStr};
-use substrate_rpc_client::{ws_client, ChainApi, FinalizedHeaders, Subscription, WsClient};
+use substrate_rpc_client::{
+	ws_client_with, ChainApi, ClientConfig, FinalizedHeaders, Subscription, WsClient,
+};
 
 const SUB: &str = "chain_subscribeFinalizedHeads";
 const UN_SUB: &str = "chain_unsubscribeFinalizedHeads";
@@ -36,7 +41,9 @@ const UN_SUB: &str = "chain_unsubscribeFinalizedHeads";
 #[derive(Debug, Clone, clap::Parser)]
 pub struct FollowChainCmd {
 	/// The url to connect to.
-	#[arg(short, long, value_parser = parse::url)]
+	///
+	/// Must be a WS(S) url, as the finalized heads are subscribed to.
+	#[arg(short, long, value_parser = parse::ws_url)]
 	uri: String,
 
 	/// If set, then the state root check is enabled.
@@ -58,6 +65,13 @@ pub struct FollowChainCmd {
 	/// If present, a single connection to a node will be kept and reused for fetching blocks.
 	#[arg(long)]
 	keep_connection: bool,
+
+	/// If set, report the time spent in, and the weight reported by, the `on_initialize`,
+	/// `on_idle` and `on_finalize` hooks of each pallet, for every executed block.
+	///
+	/// When executing wasm, this requires the runtime to be built with the `with-tracing` feature.
+	#[arg(long)]
+	hook_timings: bool,
 }
 
 /// Start listening for with `SUB` at `url`.
@@ -66,8 +80,11 @@ pub struct FollowChainCmd {
 /// relies on the related alive `client`.
 async fn start_subscribing<Header: DeserializeOwned + Serialize + Send + Sync + 'static>(
 	url: &str,
+	config: &ClientConfig,
 ) -> sc_cli::Result<(WsClient, Subscription<Header>)> {
-	let client = ws_client(url).await.map_err(|e| sc_cli::Error::Application(e.into()))?;
+	let client = ws_client_with(url, config)
+		.await
+		.map_err(|e| sc_cli::Error::Application(e.into()))?;
 
 	log::info!(target: LOG_TARGET, "subscribing to {:?} / {:?}", SUB, UN_SUB);
 
@@ -92,7 +109,8 @@ where
 	ExecDispatch: NativeExecutionDispatch + 'static,
 {
 	let mut maybe_state_ext = None;
-	let (rpc, subscription) = start_subscribing::<Block::Header>(&command.uri).await?;
+	let (rpc, subscription) =
+		start_subscribing::<Block::Header>(&command.uri, &shared.rpc_config()).await?;
 
 	let (code_key, code) = extract_code(&config.chain_spec)?;
 	let executor = build_executor::<ExecDispatch>(&shared, &config);
@@ -126,7 +144,9 @@ where
 					at: Some(*header.parent_hash()),
 					..Default::default()
 				}))
-				.state_version(shared.state_version);
+				.state_version(shared.state_version)
+				.max_memory(shared.max_memory_bytes())
+				.rpc_config(shared.rpc_config());
 
 			let new_ext = builder
 				.inject_hashed_key_value(&[(code_key.clone(), code.clone())])
@@ -139,34 +159,51 @@ where
 				new_ext.as_backend().root()
 			);
 
-			let (expected_spec_name, expected_spec_version, spec_state_version) =
-				local_spec::<Block, ExecDispatch>(&new_ext, &executor);
-			ensure_matching_spec::<Block>(
-				command.uri.clone(),
-				expected_spec_name,
-				expected_spec_version,
-				shared.no_spec_check_panic,
-			)
-			.await;
+			let version = local_version::<Block, ExecDispatch>(&new_ext, &executor);
+			let spec_state_version = version.state_version();
+			ensure_matching_spec::<Block>(command.uri.clone(), &version, &shared).await?;
 
-			maybe_state_ext = Some((new_ext, spec_state_version));
+			let known_prefixes =
+				pallet_storage_prefixes::<Block, ExecDispatch>(&new_ext, &executor)?;
+			let size = state_size(&new_ext);
+			maybe_state_ext = Some((new_ext, spec_state_version, known_prefixes, size));
 		}
 
-		let (state_ext, spec_state_version) =
+		// NOTE: the size of the state is only computed once, and then grown by the size of the
+		// changes of each block, thus it is an upper bound.
+		let (state_ext, spec_state_version, known_prefixes, size) =
 			maybe_state_ext.as_mut().expect("state_ext either existed or was just created");
 
-		let (mut changes, encoded_result) = state_machine_call_with_proof::<Block, ExecDispatch>(
-			state_ext,
-			&executor,
-			execution,
-			"TryRuntime_execute_block",
-			(block, command.state_root_check, command.try_state.clone()).encode().as_ref(),
-			full_extensions(),
-		)?;
+		let payload = (block, command.state_root_check, command.try_state.clone()).encode();
+		let call = || {
+			state_machine_call_with_proof::<Block, ExecDispatch>(
+				state_ext,
+				&executor,
+				execution,
+				"TryRuntime_execute_block",
+				&payload,
+				mock_runtime_version(full_extensions(), &shared, &executor),
+				shared.record_proof,
+			)
+		};
+		let result = if command.hook_timings {
+			let (result, timings) = hook_timing::measure(call);
+			timings.log();
+			result
+		} else {
+			call()
+		};
+		maybe_store_snapshot(&shared, state_ext, &result);
+		let (mut changes, encoded_result) = result?;
 
 		let consumed_weight = <sp_weights::Weight as Decode>::decode(&mut &*encoded_result)
 			.map_err(|e| format!("failed to decode weight: {:?}", e))?;
 
+		report_unknown_storage_writes(&changes, known_prefixes);
+		let block_changes_size = changes_size(&changes);
+		ensure_memory_limit(&shared, *size, block_changes_size)?;
+		*size += block_changes_size;
+
 		let storage_changes = changes
 			.drain_storage_changes(
 				&state_ext.backend,
@@ -0,0 +1,98 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::TryRuntimeCmd;
+use clap::{Arg, CommandFactory};
+use serde_json::{json, Value};
+
+/// Configurations of the [`Command::ListCommands`].
+#[derive(Debug, Clone, clap::Parser)]
+pub struct ListCommandsCmd {
+	/// Print the listing as JSON instead of plain text.
+	#[arg(long)]
+	json: bool,
+}
+
+pub(crate) fn list_commands(command: ListCommandsCmd) -> sc_cli::Result<()> {
+	let mut cli = TryRuntimeCmd::command();
+	cli.build();
+
+	if command.json {
+		let listing = serde_json::to_string_pretty(&describe(&cli))
+			.map_err(|e| format!("failed to serialize the command listing: {:?}", e))?;
+		println!("{}", listing);
+	} else {
+		print(&cli, 0);
+	}
+
+	Ok(())
+}
+
+/// The visible arguments of `cmd`, excluding the auto-generated `--help` and `--version`.
+fn visible_args(cmd: &clap::Command) -> impl Iterator<Item = &Arg> {
+	cmd.get_arguments()
+		.filter(|arg| !arg.is_hide_set())
+		.filter(|arg| !matches!(arg.get_id().as_str(), "help" | "version"))
+}
+
+/// Describe `cmd` and, recursively, all of its subcommands.
+fn describe(cmd: &clap::Command) -> Value {
+	let args = visible_args(cmd)
+		.map(|arg| {
+			json!({
+				"id": arg.get_id().as_str(),
+				"long": arg.get_long(),
+				"short": arg.get_short(),
+				"required": arg.is_required_set(),
+				"takes_value": arg.get_num_args().map_or(false, |n| n.takes_values()),
+				"default_values": arg
+					.get_default_values()
+					.iter()
+					.map(|v| v.to_string_lossy())
+					.collect::<Vec<_>>(),
+				"possible_values": arg
+					.get_possible_values()
+					.iter()
+					.map(|v| v.get_name().to_string())
+					.collect::<Vec<_>>(),
+				"help": arg.get_help().map(|h| h.to_string()),
+			})
+		})
+		.collect::<Vec<_>>();
+
+	json!({
+		"name": cmd.get_name(),
+		"about": cmd.get_about().map(|a| a.to_string()),
+		"args": args,
+		"subcommands": cmd.get_subcommands().map(describe).collect::<Vec<_>>(),
+	})
+}
+
+/// Print `cmd` and, recursively, all of its subcommands as an indented tree.
+fn print(cmd: &clap::Command, depth: usize) {
+	let indent = "  ".repeat(depth);
+	match cmd.get_about() {
+		Some(about) => println!("{}{}: {}", indent, cmd.get_name(), about),
+		None => println!("{}{}", indent, cmd.get_name()),
+	}
+	for arg in visible_args(cmd).filter_map(|arg| arg.get_long()) {
+		println!("{}    --{}", indent, arg);
+	}
+	for subcommand in cmd.get_subcommands() {
+		print(subcommand, depth + 1);
+	}
+}
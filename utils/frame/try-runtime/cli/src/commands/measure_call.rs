@@ -0,0 +1,230 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+	build_executor, cache::Cache, ensure_matching_spec, ensure_try_runtime_api, extract_code,
+	full_extensions, local_version, mock_version::mock_runtime_version, parse,
+	report_execution_times, runtime_metadata, state_machine_call,
+	state_machine_call_recording_proof, SharedParams, State, LOG_TARGET,
+};
+use frame_metadata::RuntimeMetadataV14;
+use parity_scale_codec::{Decode, Encode};
+use sc_executor::NativeExecutionDispatch;
+use sc_service::Configuration;
+use scale_info::TypeDef;
+use sp_core::bytes::from_hex;
+use sp_runtime::{
+	traits::{Block as BlockT, NumberFor},
+	DispatchResult,
+};
+use sp_weights::Weight;
+use std::{fmt::Debug, str::FromStr, time::Instant};
+
+/// Configurations of the [`Command::MeasureCall`].
+#[derive(Debug, Clone, clap::Parser)]
+pub struct MeasureCallCmd {
+	/// The name of the pallet, as per pallet names in `construct_runtime!()` (e.g. `Staking`).
+	#[arg(long)]
+	pallet: String,
+
+	/// The name of the call in the given pallet (e.g. `bond`).
+	#[arg(long)]
+	call: String,
+
+	/// The SCALE encoded arguments of the call, in hex.
+	#[arg(long, default_value = "0x", value_parser = parse::hex_bytes)]
+	args: String,
+
+	/// The SCALE encoded account, in hex, as which the call is dispatched.
+	///
+	/// If omitted, the call is dispatched as root.
+	#[arg(long, value_parser = parse::hex_bytes)]
+	signer: Option<String>,
+
+	/// Dispatch the call this many times, each time on a fresh copy of the state, to measure its
+	/// execution time.
+	#[arg(long, default_value = "20")]
+	repeat: u32,
+
+	/// Overwrite the wasm code in state or not.
+	#[arg(long)]
+	overwrite_wasm_code: bool,
+
+	/// The state type to use.
+	#[command(subcommand)]
+	pub(crate) state: State,
+}
+
+/// Find the `(pallet index, call index)` of the given `call` of the given `pallet` in `metadata`.
+pub(crate) fn find_call_index(
+	metadata: &RuntimeMetadataV14,
+	pallet: &str,
+	call: &str,
+) -> sc_cli::Result<(u8, u8)> {
+	let pallet_metadata = metadata
+		.pallets
+		.iter()
+		.find(|p| p.name == pallet)
+		.ok_or_else(|| format!("pallet {} not found in the metadata", pallet))?;
+	let calls = pallet_metadata
+		.calls
+		.as_ref()
+		.and_then(|c| metadata.types.resolve(c.ty.id()))
+		.ok_or_else(|| format!("pallet {} has no calls", pallet))?;
+
+	match calls.type_def() {
+		TypeDef::Variant(variants) => variants
+			.variants()
+			.iter()
+			.find(|v| v.name() == call)
+			.map(|v| (pallet_metadata.index, v.index()))
+			.ok_or_else(|| format!("call {} not found in pallet {}", call, pallet).into()),
+		_ => Err(format!("calls of pallet {} are not an enum", pallet).into()),
+	}
+}
+
+/// Decode the output of `TryRuntime_dispatch_call` into the declared weight, the actual weight and
+/// the outcome of the dispatch, or an error if the runtime could not decode its inputs.
+pub(crate) fn decode_dispatch_output(
+	encoded: &[u8],
+) -> sc_cli::Result<(Weight, Weight, DispatchResult)> {
+	<Result<(Weight, Weight, DispatchResult), Vec<u8>> as Decode>::decode(&mut &*encoded)
+		.map_err(|e| format!("failed to decode output: {:?}", e))?
+		.map_err(|e| {
+			format!("the runtime rejected the call: {}", String::from_utf8_lossy(&e)).into()
+		})
+}
+
+pub(crate) async fn measure_call<Block, ExecDispatch>(
+	shared: SharedParams,
+	command: MeasureCallCmd,
+	config: Configuration,
+) -> sc_cli::Result<()>
+where
+	Block: BlockT + serde::de::DeserializeOwned,
+	Block::Hash: FromStr,
+	<Block::Hash as FromStr>::Err: Debug,
+	Block::Header: serde::de::DeserializeOwned,
+	NumberFor<Block>: FromStr,
+	<NumberFor<Block> as FromStr>::Err: Debug,
+	ExecDispatch: NativeExecutionDispatch + 'static,
+{
+	let executor = build_executor::<ExecDispatch>(&shared, &config);
+	let execution = shared.execution;
+
+	let ext = {
+		let builder = command
+			.state
+			.builder::<Block>()?
+			.state_version(shared.state_version)
+			.max_memory(shared.max_memory_bytes())
+			.rpc_config(shared.rpc_config());
+		let builder = if command.overwrite_wasm_code {
+			log::info!(
+				target: LOG_TARGET,
+				"replacing the in-storage :code: with the local code from {}'s chain_spec (your local repo)",
+				config.chain_spec.name(),
+			);
+			let (code_key, code) = extract_code(&config.chain_spec)?;
+			builder.inject_hashed_key_value(&[(code_key, code)])
+		} else {
+			Cache::new(&shared).inject_code(builder, &command.state, None).await?
+		};
+		builder.build().await?
+	};
+
+	let version = local_version::<Block, ExecDispatch>(&ext, &executor);
+	ensure_try_runtime_api(&version, 2, "TryRuntime_dispatch_call")?;
+	if let Some(uri) = command.state.live_uri() {
+		ensure_matching_spec::<Block>(uri, &version, &shared).await?;
+	}
+
+	let metadata = runtime_metadata::<Block, ExecDispatch>(&ext, &executor)?;
+	let (pallet_index, call_index) = find_call_index(&metadata, &command.pallet, &command.call)?;
+	let mut call = vec![pallet_index, call_index];
+	call.extend(from_hex(&command.args).map_err(|e| format!("invalid args: {:?}", e))?);
+	let signer = command
+		.signer
+		.as_ref()
+		.map(|s| from_hex(s))
+		.transpose()
+		.map_err(|e| format!("invalid signer: {:?}", e))?;
+	let payload = (call, signer).encode();
+
+	let (_, encoded_result, proof) = state_machine_call_recording_proof::<Block, ExecDispatch>(
+		&ext,
+		&executor,
+		execution,
+		"TryRuntime_dispatch_call",
+		&payload,
+		mock_runtime_version(full_extensions(), &shared, &executor),
+	)?;
+	let (declared, actual, result) = decode_dispatch_output(&encoded_result)?;
+
+	log::info!(
+		target: LOG_TARGET,
+		"{}::{} dispatched with result {:?}. Declared weight = ({} ps, {} byte), actual weight = ({} ps, {} byte).",
+		command.pallet,
+		command.call,
+		result,
+		declared.ref_time(),
+		declared.proof_size(),
+		actual.ref_time(),
+		actual.proof_size(),
+	);
+	if result.is_err() {
+		log::warn!(
+			target: LOG_TARGET,
+			"the call failed, the measurements below are those of the failing code path",
+		);
+	}
+
+	let proof_size = proof.encoded_size() as u64;
+	log::info!(
+		target: LOG_TARGET,
+		"storage proof size = {} byte; declared = {} byte",
+		proof_size,
+		declared.proof_size(),
+	);
+	if proof_size > declared.proof_size() {
+		log::warn!(
+			target: LOG_TARGET,
+			"storage proof size ({} byte) exceeds the declared weight ({} byte)",
+			proof_size,
+			declared.proof_size(),
+		);
+	}
+
+	// the state machine never mutates `ext`, thus every execution starts from the same state.
+	let samples = (0..command.repeat)
+		.map(|_| {
+			let start = Instant::now();
+			state_machine_call::<Block, ExecDispatch>(
+				&ext,
+				&executor,
+				execution,
+				"TryRuntime_dispatch_call",
+				&payload,
+				mock_runtime_version(full_extensions(), &shared, &executor),
+			)
+			.map(|_| start.elapsed())
+		})
+		.collect::<sc_cli::Result<Vec<_>>>()?;
+	report_execution_times(samples, declared);
+
+	Ok(())
+}
@@ -15,7 +15,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub(crate) mod check_state;
+pub(crate) mod check_storage_versions;
+pub(crate) mod create_snapshot;
 pub(crate) mod execute_block;
+pub(crate) mod fast_forward;
 pub(crate) mod follow_chain;
+pub(crate) mod list_commands;
+pub(crate) mod measure_call;
 pub(crate) mod offchain_worker;
 pub(crate) mod on_runtime_upgrade;
+pub(crate) mod scenario;
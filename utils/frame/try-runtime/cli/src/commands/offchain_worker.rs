@@ -16,16 +16,40 @@
 // limitations under the License.
 
 use crate::{
-	build_executor, ensure_matching_spec, extract_code, full_extensions, hash_of, local_spec,
-	parse, state_machine_call, SharedParams, State, LOG_TARGET,
+	assertions::check_assertions,
+	build_executor,
+	cache::Cache,
+	changes_size,
+	commands::execute_block::{hash_of_ref, BlockRef},
+	ensure_matching_spec, ensure_memory_limit, extensions_with_offchain,
+	extensions_with_offchain_timestamp, extract_code, extract_code_from_path, full_extensions,
+	hash_of, local_version, maybe_store_snapshot,
+	mock_version::mock_runtime_version,
+	offchain::OffchainExt,
+	output::{Spec, Summary},
+	pallet_storage_prefixes, parse, report_unknown_storage_writes, resolve_block_number,
+	runtime_metadata, state_machine_call, state_machine_call_with_proof, state_size,
+	storage_diff::{decode_extrinsic, print_storage_diff},
+	storage_trace::write_storage_trace,
+	SharedParams, State, LOG_TARGET,
 };
-use parity_scale_codec::Encode;
-use sc_executor::NativeExecutionDispatch;
+use parity_scale_codec::{Decode, Encode};
+use remote_externalities::TestExternalities;
+use sc_executor::{NativeElseWasmExecutor, NativeExecutionDispatch};
 use sc_service::Configuration;
-use sp_core::storage::well_known_keys;
-use sp_runtime::traits::{Block as BlockT, Header, NumberFor};
-use std::{fmt::Debug, str::FromStr};
-use substrate_rpc_client::{ws_client, ChainApi};
+use sp_core::{
+	hexdisplay::HexDisplay,
+	offchain::{testing::TestTransactionPoolExt, Timestamp},
+	twox_128,
+};
+use sp_runtime::{
+	traits::{Block as BlockT, Header, NumberFor, UniqueSaturatedInto},
+	transaction_validity::{TransactionSource, TransactionValidity},
+	ApplyExtrinsicResult,
+};
+use sp_state_machine::Backend;
+use std::{fmt::Debug, path::PathBuf, str::FromStr};
+use substrate_rpc_client::ClientConfig;
 
 /// Configurations of the [`Command::OffchainWorker`].
 #[derive(Debug, Clone, clap::Parser)]
@@ -34,32 +58,202 @@ pub struct OffchainWorkerCmd {
 	#[arg(long)]
 	overwrite_wasm_code: bool,
 
-	/// The block hash at which to fetch the header.
+	/// Overwrite the wasm code in state with the wasm blob at the given path, e.g. an unreleased
+	/// runtime artifact.
+	#[arg(long, value_name = "PATH", conflicts_with = "overwrite_wasm_code")]
+	wasm_path: Option<PathBuf>,
+
+	/// The block hash or number at which to fetch the header.
 	///
 	/// If the `live` state type is being used, then this can be omitted, and is equal to whatever
 	/// the `state::at` is. Only use this (with care) when combined with a snapshot.
 	#[arg(
 		long,
-		value_parser = parse::hash
+		value_parser = parse::hash_or_number
 	)]
 	header_at: Option<String>,
 
-	/// The ws uri from which to fetch the header.
+	/// The ws or http uri from which to fetch the header.
 	///
 	/// If the `live` state type is being used, then this can be omitted, and is equal to whatever
-	/// the `state::uri` is. Only use this (with care) when combined with a snapshot.
+	/// the `state::uri` is. Only use this (with care) when combined with a snapshot, and along
+	/// with `--header-at`.
 	#[arg(
 		long,
-		value_parser = parse::url
+		value_parser = parse::url,
+		requires = "header_at"
 	)]
 	header_ws_uri: Option<String>,
 
+	/// The timestamp that the offchain worker observes as the current time.
+	///
+	/// Either a unix timestamp in milliseconds, or `block` to use the timestamp of the block, as
+	/// stored in `Timestamp::Now`. This assumes that the timestamp pallet is named `Timestamp` in
+	/// `construct_runtime!()`. If omitted, the offchain worker observes a timestamp of zero.
+	#[arg(
+		long,
+		value_parser = parse::mock_timestamp
+	)]
+	mock_timestamp: Option<MockTimestamp>,
+
+	/// The state that the offchain worker is executed on top of: `self` for the state after the
+	/// block of the header, as nodes do by default once they imported the block, or `parent` for
+	/// the state of its parent block.
+	///
+	/// With `parent`, a `live` state is fetched at the parent of the block of the header, while a
+	/// snapshot is assumed to be of the parent block already.
+	#[arg(long, default_value = "self", value_parser = parse::state_at)]
+	state_at: StateAt,
+
+	/// Keep the offchain local storage, both persistent and local, in this file between runs.
+	///
+	/// The storage is loaded from the file if it exists, and written back once the offchain
+	/// worker has been executed successfully.
+	#[arg(long, value_name = "PATH")]
+	offchain_db: Option<PathBuf>,
+
+	/// Write all HTTP requests of the offchain worker to this file, as json: their method, uri,
+	/// headers, body and the status of their response.
+	#[arg(long, value_name = "PATH")]
+	http_report: Option<PathBuf>,
+
+	/// Answer the HTTP requests of the offchain worker with the canned responses in this yaml (or
+	/// json) file, a list of:
+	///
+	///   - { method: "GET", uri: "https://..", status: 200, headers: [["..", ".."]], body: ".." }
+	///
+	/// The first response whose `uri` and, if given, `method` match a request answers it. All
+	/// fields but `uri` are optional. Requests that no response matches fail with an IO error.
+	#[arg(long, value_name = "PATH")]
+	http_fixtures: Option<PathBuf>,
+
+	/// Dry-run the transactions that the offchain worker submits, each on its own on top of the
+	/// state: validate it as the transaction pool would, and apply it.
+	#[arg(long)]
+	dry_run_transactions: bool,
+
+	/// The number of consecutive blocks to execute the offchain worker for, starting at the block
+	/// of `--header-at`.
+	///
+	/// The offchain storage is carried forward from one block to the next, as is the state of
+	/// `live` states, which is fetched anew for each block.
+	#[arg(long, default_value = "1")]
+	blocks: u32,
+
 	/// The state type to use.
 	#[command(subcommand)]
 	pub state: State,
 }
 
+/// The timestamp reported to the offchain worker, see `OffchainWorkerCmd::mock_timestamp`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum MockTimestamp {
+	/// A fixed unix timestamp, in milliseconds.
+	Fixed(u64),
+	/// The timestamp of the block that the offchain worker is executed for.
+	Block,
+}
+
+/// The block of the state of the offchain worker, see `OffchainWorkerCmd::state_at`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StateAt {
+	/// The block of the header.
+	Header,
+	/// The parent block of the header.
+	Parent,
+}
+
+/// Print the `transactions` that the offchain worker submitted, decoded as per the metadata of the
+/// runtime in `ext`, and dry-run each of them on top of `ext` at the block `at` if `dry_run`.
+fn report_transactions<Block: BlockT, D: NativeExecutionDispatch + 'static>(
+	shared: &SharedParams,
+	ext: &TestExternalities,
+	executor: &NativeElseWasmExecutor<D>,
+	at: Block::Hash,
+	transactions: &[Vec<u8>],
+	dry_run: bool,
+) -> sc_cli::Result<()> {
+	if transactions.is_empty() {
+		log::info!(target: LOG_TARGET, "the offchain worker submitted no transactions.");
+		return Ok(())
+	}
+
+	let metadata = runtime_metadata::<Block, D>(ext, executor)?;
+	log::info!(
+		target: LOG_TARGET,
+		"the offchain worker submitted {} transactions:",
+		transactions.len()
+	);
+	for (index, transaction) in transactions.iter().enumerate() {
+		let decoded = decode_extrinsic(&metadata, transaction).unwrap_or_else(|e| {
+			format!("0x{} (failed to decode: {})", HexDisplay::from(transaction), e)
+		});
+		log::info!(target: LOG_TARGET, "#{}: {}", index, decoded);
+		if !dry_run {
+			continue
+		}
+
+		let extrinsic = <Block::Extrinsic as Decode>::decode(&mut &**transaction)
+			.map_err(|e| format!("failed to decode transaction #{}: {:?}", index, e))?;
+		let call = |method: &'static str, data: &[u8]| {
+			state_machine_call::<Block, D>(
+				ext,
+				executor,
+				shared.execution,
+				method,
+				data,
+				mock_runtime_version(full_extensions(), shared, executor),
+			)
+			.map(|(_, encoded)| encoded)
+		};
+
+		let encoded = call(
+			"TaggedTransactionQueue_validate_transaction",
+			&(TransactionSource::Local, &extrinsic, at).encode(),
+		)?;
+		let validity = <TransactionValidity as Decode>::decode(&mut &*encoded)
+			.map_err(|e| format!("failed to decode the validity: {:?}", e))?;
+		let encoded = call("BlockBuilder_apply_extrinsic", &extrinsic.encode())?;
+		let applied = <ApplyExtrinsicResult as Decode>::decode(&mut &*encoded)
+			.map_err(|e| format!("failed to decode the outcome: {:?}", e))?;
+		match (&validity, &applied) {
+			(Ok(_), Ok(Ok(()))) => log::info!(
+				target: LOG_TARGET,
+				"#{}: valid with {:?}, applied successfully",
+				index,
+				validity,
+			),
+			_ => log::warn!(
+				target: LOG_TARGET,
+				"#{}: validity {:?}, applied with {:?}",
+				index,
+				validity,
+				applied,
+			),
+		}
+	}
+	Ok(())
+}
+
 impl OffchainWorkerCmd {
+	/// Resolve [`Self::header_at`] and the block of the state to block hashes, if they are
+	/// block numbers.
+	pub(crate) async fn resolve_block_numbers<Block: BlockT>(
+		&mut self,
+		rpc_config: ClientConfig,
+	) -> sc_cli::Result<()>
+	where
+		Block::Hash: FromStr + serde::de::DeserializeOwned,
+		<Block::Hash as FromStr>::Err: Debug,
+	{
+		if let Some(header_at) = self.header_at.clone() {
+			let uri = self.header_ws_uri::<Block>()?;
+			self.header_at =
+				Some(resolve_block_number::<Block>(&uri, rpc_config, &header_at).await?);
+		}
+		self.state.resolve_at::<Block>(rpc_config).await
+	}
+
 	fn header_at<Block: BlockT>(&self) -> sc_cli::Result<Block::Hash>
 	where
 		Block::Hash: FromStr,
@@ -72,27 +266,25 @@ impl OffchainWorkerCmd {
 				hash_of::<Block>(header_at)
 			},
 			(None, State::Live { at: Some(at), .. }) => hash_of::<Block>(at),
-			_ => {
-				panic!("either `--header-at` must be provided, or state must be `live` with a proper `--at`");
-			},
+			_ => Err("either `--header-at` must be provided, or state must be `live` with a proper `--at`"
+				.into()),
 		}
 	}
 
-	fn header_ws_uri<Block: BlockT>(&self) -> String
+	fn header_ws_uri<Block: BlockT>(&self) -> sc_cli::Result<String>
 	where
 		Block::Hash: FromStr,
 		<Block::Hash as FromStr>::Err: Debug,
 	{
 		match (&self.header_ws_uri, &self.state) {
-			(Some(header_ws_uri), State::Snap { .. }) => header_ws_uri.to_owned(),
+			(Some(header_ws_uri), State::Snap { .. }) => Ok(header_ws_uri.to_owned()),
 			(Some(header_ws_uri), State::Live { .. }) => {
-				log::error!(target: LOG_TARGET, "--header-uri is provided while state type is live, this will most likely lead to a nonsensical result.");
-				header_ws_uri.to_owned()
-			},
-			(None, State::Live { uri, .. }) => uri.clone(),
-			(None, State::Snap { .. }) => {
-				panic!("either `--header-uri` must be provided, or state must be `live`");
+				log::error!(target: LOG_TARGET, "--header-ws-uri is provided while state type is live, this will most likely lead to a nonsensical result.");
+				Ok(header_ws_uri.to_owned())
 			},
+			(None, State::Live { uri, .. }) => Ok(uri.clone()),
+			(None, State::Snap { .. }) =>
+				Err("either `--header-ws-uri` must be provided, or state must be `live`".into()),
 		}
 	}
 }
@@ -101,10 +293,11 @@ pub(crate) async fn offchain_worker<Block, ExecDispatch>(
 	shared: SharedParams,
 	command: OffchainWorkerCmd,
 	config: Configuration,
+	summary: &mut Summary,
 ) -> sc_cli::Result<()>
 where
 	Block: BlockT + serde::de::DeserializeOwned,
-	Block::Hash: FromStr,
+	Block::Hash: FromStr + serde::de::DeserializeOwned,
 	Block::Header: serde::de::DeserializeOwned,
 	<Block::Hash as FromStr>::Err: Debug,
 	NumberFor<Block>: FromStr,
@@ -112,27 +305,127 @@ where
 	ExecDispatch: NativeExecutionDispatch + 'static,
 {
 	let executor = build_executor(&shared, &config);
-	let execution = shared.execution;
 
 	let header_at = command.header_at::<Block>()?;
-	let header_ws_uri = command.header_ws_uri::<Block>();
+	let header_ws_uri = command.header_ws_uri::<Block>()?;
+	if command.blocks > 1 && matches!(command.state, State::Snap { .. }) {
+		log::warn!(
+			target: LOG_TARGET,
+			"the snapshot is the state of all {} blocks, it cannot follow the chain",
+			command.blocks,
+		);
+	}
 
-	let rpc = ws_client(&header_ws_uri).await?;
-	let header = ChainApi::<(), Block::Hash, Block::Header, ()>::header(&rpc, Some(header_at))
-		.await
-		.unwrap()
-		.unwrap();
-	log::info!(
-		target: LOG_TARGET,
-		"fetched header from {:?}, block number: {:?}",
-		header_ws_uri,
-		header.number()
-	);
+	let cache = Cache::new(&shared);
+	let offchain = OffchainExt::new(
+		Timestamp::default(),
+		command.offchain_db.as_deref(),
+		command.http_fixtures.as_deref(),
+	)?;
+
+	let mut header = cache.header::<Block>(&header_ws_uri, header_at).await?;
+	for n in 0..command.blocks {
+		if n > 0 {
+			let number = UniqueSaturatedInto::<u64>::unique_saturated_into(*header.number()) + 1;
+			let hash = hash_of_ref::<Block>(
+				&header_ws_uri,
+				shared.rpc_config(),
+				&BlockRef::Number(number),
+			)
+			.await?;
+			header = cache.header::<Block>(&header_ws_uri, hash).await?;
+		}
+		log::info!(
+			target: LOG_TARGET,
+			"fetched header from {:?}, block number: {:?}",
+			header_ws_uri,
+			header.number()
+		);
+
+		let result = execute_offchain_worker::<Block, ExecDispatch>(
+			&shared,
+			&command,
+			&config,
+			&executor,
+			&cache,
+			&header_ws_uri,
+			&header,
+			// the state of the first block is the one given.
+			n > 0,
+			&offchain,
+			summary,
+		)
+		.await;
+		if let Some(path) = &command.http_report {
+			offchain.write_report(path)?;
+		}
+		result?;
+		if let Some(path) = &command.offchain_db {
+			offchain.save_db(path)?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Execute the offchain worker for `header`, with `offchain` as its offchain externalities, and
+/// record it in `summary`.
+///
+/// The state is fetched at the block that [`OffchainWorkerCmd::state_at`] selects if
+/// `follow_header`, or else only for [`StateAt::Parent`].
+#[allow(clippy::too_many_arguments)]
+async fn execute_offchain_worker<Block, ExecDispatch>(
+	shared: &SharedParams,
+	command: &OffchainWorkerCmd,
+	config: &Configuration,
+	executor: &NativeElseWasmExecutor<ExecDispatch>,
+	cache: &Cache,
+	header_ws_uri: &str,
+	header: &Block::Header,
+	follow_header: bool,
+	offchain: &OffchainExt,
+	summary: &mut Summary,
+) -> sc_cli::Result<()>
+where
+	Block: BlockT + serde::de::DeserializeOwned,
+	Block::Hash: FromStr,
+	Block::Header: serde::de::DeserializeOwned,
+	<Block::Hash as FromStr>::Err: Debug,
+	ExecDispatch: NativeExecutionDispatch + 'static,
+{
+	let execution = shared.execution;
+
+	let state_at = match command.state_at {
+		StateAt::Header => follow_header.then(|| header.hash()),
+		StateAt::Parent => {
+			if matches!(command.state, State::Snap { .. }) {
+				log::info!(
+					target: LOG_TARGET,
+					"assuming that the snapshot is of the parent block {:?}",
+					header.parent_hash(),
+				);
+			}
+			Some(*header.parent_hash())
+		},
+	};
 
 	let ext = {
-		let builder = command.state.builder::<Block>()?.state_version(shared.state_version);
+		let builder = command
+			.state
+			.builder::<Block>()?
+			.state_version(shared.state_version)
+			.max_memory(shared.max_memory_bytes())
+			.rpc_config(shared.rpc_config());
+		let builder = match state_at {
+			Some(at) => builder.overwrite_online_at(at),
+			None => builder,
+		};
 
-		let builder = if command.overwrite_wasm_code {
+		let builder = if let Some(path) = &command.wasm_path {
+			log::info!(target: LOG_TARGET, "replacing the in-storage :code: with {:?}", path);
+			let (code_key, code) = extract_code_from_path(path, executor)?;
+			builder.inject_hashed_key_value(&[(code_key, code)])
+		} else if command.overwrite_wasm_code {
 			log::info!(
 				target: LOG_TARGET,
 				"replacing the in-storage :code: with the local code from {}'s chain_spec (your local repo)",
@@ -141,32 +434,95 @@ where
 			let (code_key, code) = extract_code(&config.chain_spec)?;
 			builder.inject_hashed_key_value(&[(code_key, code)])
 		} else {
-			builder.inject_hashed_key(well_known_keys::CODE)
+			cache.inject_code(builder, &command.state, state_at).await?
 		};
 
 		builder.build().await?
 	};
 
-	let (expected_spec_name, expected_spec_version, _) =
-		local_spec::<Block, ExecDispatch>(&ext, &executor);
-	ensure_matching_spec::<Block>(
-		header_ws_uri,
-		expected_spec_name,
-		expected_spec_version,
-		shared.no_spec_check_panic,
-	)
-	.await;
-
-	let _ = state_machine_call::<Block, ExecDispatch>(
+	let version = local_version::<Block, ExecDispatch>(&ext, executor);
+	summary.local_spec =
+		Some(Spec { name: version.spec_name.to_string(), version: version.spec_version });
+	summary.provenance.record_code(&ext);
+	summary.provenance.endpoint = Some(header_ws_uri.to_owned());
+	summary.remote_spec =
+		ensure_matching_spec::<Block>(header_ws_uri.to_owned(), &version, shared).await?;
+
+	let timestamp = match command.mock_timestamp {
+		None => Timestamp::default(),
+		Some(MockTimestamp::Fixed(millis)) => Timestamp::from_unix_millis(millis),
+		Some(MockTimestamp::Block) => {
+			let key = [twox_128(b"Timestamp"), twox_128(b"Now")].concat();
+			let now = ext
+				.backend
+				.storage(&key)
+				.map_err(|e| format!("failed to read the block timestamp: {}", e))?
+				.ok_or("no block timestamp found in `Timestamp::Now`")?;
+			let now = <u64 as Decode>::decode(&mut &*now)
+				.map_err(|e| format!("failed to decode the block timestamp: {:?}", e))?;
+			Timestamp::from_unix_millis(now)
+		},
+	};
+	log::info!(target: LOG_TARGET, "offchain worker observes timestamp {:?}", timestamp);
+
+	offchain.set_timestamp(timestamp);
+	let (pool, pool_state) = TestTransactionPoolExt::new();
+	let extensions =
+		mock_runtime_version(extensions_with_offchain(offchain.clone(), pool), shared, executor);
+	let result = if shared.record_proof {
+		state_machine_call_with_proof::<Block, ExecDispatch>(
+			&ext,
+			executor,
+			execution,
+			"OffchainWorkerApi_offchain_worker",
+			header.encode().as_ref(),
+			extensions,
+			true,
+		)
+	} else {
+		state_machine_call::<Block, ExecDispatch>(
+			&ext,
+			executor,
+			execution,
+			"OffchainWorkerApi_offchain_worker",
+			header.encode().as_ref(),
+			extensions,
+		)
+	};
+	maybe_store_snapshot(shared, &ext, &result);
+	let (changes, _) = result?;
+
+	log::info!(target: LOG_TARGET, "OffchainWorkerApi_offchain_worker executed without errors.");
+	summary.storage_roots(
+		ext.backend.root(),
+		changes.storage_root(&ext.backend, &mut Default::default(), shared.state_version),
+	);
+	let transactions = pool_state.read().transactions.clone();
+	report_transactions::<Block, ExecDispatch>(
+		shared,
 		&ext,
-		&executor,
-		execution,
-		"OffchainWorkerApi_offchain_worker",
-		header.encode().as_ref(),
-		full_extensions(),
+		executor,
+		header.hash(),
+		&transactions,
+		command.dry_run_transactions,
 	)?;
+	ensure_memory_limit(shared, state_size(&ext), changes_size(&changes))?;
+	check_assertions::<Block, ExecDispatch>(shared, &ext, executor, &changes)?;
+	print_storage_diff::<Block, ExecDispatch>(shared, &ext, executor, &changes)?;
+	if let Some(path) = &shared.storage_trace {
+		write_storage_trace::<Block, ExecDispatch>(
+			path,
+			&ext,
+			executor,
+			execution,
+			"OffchainWorkerApi_offchain_worker",
+			header.encode().as_ref(),
+			mock_runtime_version(extensions_with_offchain_timestamp(timestamp), shared, executor),
+		)?;
+	}
 
-	log::info!(target: LOG_TARGET, "OffchainWorkerApi_offchain_worker executed without errors.");
+	let known_prefixes = pallet_storage_prefixes::<Block, ExecDispatch>(&ext, executor)?;
+	report_unknown_storage_writes(&changes, &known_prefixes);
 
 	Ok(())
 }
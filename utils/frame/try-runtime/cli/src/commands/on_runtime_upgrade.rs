@@ -15,16 +15,25 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{fmt::Debug, str::FromStr};
+use std::{fmt::Debug, str::FromStr, time::Instant};
 
-use parity_scale_codec::Decode;
+use parity_scale_codec::{Decode, Encode};
 use sc_executor::NativeExecutionDispatch;
 use sc_service::Configuration;
 use sp_runtime::traits::{Block as BlockT, NumberFor};
 use sp_weights::Weight;
 
 use crate::{
-	build_executor, ensure_matching_spec, extract_code, local_spec, state_machine_call_with_proof,
+	assertions::{check_assertions, check_conservation},
+	build_executor, changes_size, ensure_matching_spec, ensure_memory_limit,
+	ensure_try_runtime_api, extract_code, local_version, log_proof_size, maybe_store_snapshot,
+	mock_version::mock_runtime_version,
+	output::{Spec, Summary},
+	pallet_storage_prefixes, remote_max_block_weight, report_execution_times,
+	report_unknown_storage_writes, state_machine_call, state_machine_call_recording_proof,
+	state_size,
+	storage_diff::print_storage_diff,
+	storage_trace::write_storage_trace,
 	SharedParams, State, LOG_TARGET,
 };
 
@@ -34,12 +43,78 @@ pub struct OnRuntimeUpgradeCmd {
 	/// The state type to use.
 	#[command(subcommand)]
 	pub state: State,
+
+	/// Execute the migration this many more times, each time on a fresh copy of the state, and
+	/// report the distribution of the measured execution time.
+	///
+	/// This is a sanity check of the ref-time weight that the migration claims, on the local
+	/// machine. Note that the measured time includes the `pre_upgrade` and `post_upgrade` hooks.
+	#[arg(long, default_value = "0")]
+	pub repeat: u32,
+
+	/// Only execute the `on_runtime_upgrade` of the pallets with these names, as they appear in
+	/// `construct_runtime!()`.
+	///
+	/// Can be given multiple times. Each selected pallet runs its `pre_upgrade`,
+	/// `on_runtime_upgrade` and `post_upgrade` hooks. The migrations of the runtime that belong
+	/// to no pallet are then not executed. Fails if a name does not match any pallet.
+	#[arg(long)]
+	pub pallet: Vec<String>,
+
+	/// Only warn, instead of failing, if the migration does not fit in a block.
+	///
+	/// The consumed weight and the size of the storage proof of the migration are compared
+	/// against `BlockWeights::max_block` of the runtime of the chain if the state is live, and of
+	/// the local runtime otherwise.
+	#[arg(long)]
+	pub allow_overweight: bool,
+}
+
+/// Ensure that a migration that consumed `weight` and has a storage proof of `proof_size` bytes
+/// fits in a block of at most `max_block`, or only warn about it if `allow_overweight`.
+fn ensure_fits_in_block(
+	weight: Weight,
+	proof_size: u64,
+	max_block: Weight,
+	allow_overweight: bool,
+) -> sc_cli::Result<()> {
+	let mut exceeded = vec![];
+	if weight.ref_time() > max_block.ref_time() {
+		exceeded.push(format!(
+			"consumed ref time ({} ps) exceeds the block limit ({} ps)",
+			weight.ref_time(),
+			max_block.ref_time(),
+		));
+	}
+	// the proof size that the migration claims may be less than what it actually records.
+	let proof_size = proof_size.max(weight.proof_size());
+	if proof_size > max_block.proof_size() {
+		exceeded.push(format!(
+			"proof size ({} byte) exceeds the block limit ({} byte)",
+			proof_size,
+			max_block.proof_size(),
+		));
+	}
+
+	if exceeded.is_empty() {
+		log::info!(target: LOG_TARGET, "the migration fits in a block");
+		return Ok(())
+	}
+	for e in &exceeded {
+		log::warn!(target: LOG_TARGET, "the migration does not fit in a block: {}", e);
+	}
+	if allow_overweight {
+		Ok(())
+	} else {
+		Err(format!("the migration does not fit in a block: {}", exceeded.join(", ")).into())
+	}
 }
 
 pub(crate) async fn on_runtime_upgrade<Block, ExecDispatch>(
 	shared: SharedParams,
 	command: OnRuntimeUpgradeCmd,
 	config: Configuration,
+	summary: &mut Summary,
 ) -> sc_cli::Result<()>
 where
 	Block: BlockT + serde::de::DeserializeOwned,
@@ -54,42 +129,158 @@ where
 	let execution = shared.execution;
 
 	let ext = {
-		let builder = command.state.builder::<Block>()?.state_version(shared.state_version);
+		let builder = command
+			.state
+			.builder::<Block>()?
+			.state_version(shared.state_version)
+			.max_memory(shared.max_memory_bytes())
+			.rpc_config(shared.rpc_config());
 		let (code_key, code) = extract_code(&config.chain_spec)?;
 		builder.inject_hashed_key_value(&[(code_key, code)]).build().await?
 	};
+	summary.provenance.record_code(&ext);
 
 	if let Some(uri) = command.state.live_uri() {
-		let (expected_spec_name, expected_spec_version, _) =
-			local_spec::<Block, ExecDispatch>(&ext, &executor);
-		ensure_matching_spec::<Block>(
-			uri,
-			expected_spec_name,
-			expected_spec_version,
-			shared.no_spec_check_panic,
-		)
-		.await;
+		let version = local_version::<Block, ExecDispatch>(&ext, &executor);
+		summary.local_spec =
+			Some(Spec { name: version.spec_name.to_string(), version: version.spec_version });
+		summary.provenance.endpoint = Some(uri.clone());
+		summary.remote_spec = ensure_matching_spec::<Block>(uri, &version, &shared).await?;
 	}
 
-	let (_, encoded_result) = state_machine_call_with_proof::<Block, ExecDispatch>(
+	let (method, data) = if command.pallet.is_empty() {
+		("TryRuntime_on_runtime_upgrade", vec![])
+	} else {
+		log::info!(target: LOG_TARGET, "only upgrading the pallets {:?}", command.pallet);
+		ensure_try_runtime_api(
+			&local_version::<Block, ExecDispatch>(&ext, &executor),
+			2,
+			"TryRuntime_on_runtime_upgrade_of",
+		)?;
+		let pallets = command.pallet.iter().map(|p| p.as_bytes().to_vec()).collect::<Vec<_>>();
+		("TryRuntime_on_runtime_upgrade_of", pallets.encode())
+	};
+
+	let result = state_machine_call_recording_proof::<Block, ExecDispatch>(
 		&ext,
 		&executor,
 		execution,
-		"TryRuntime_on_runtime_upgrade",
-		&[],
-		Default::default(), // we don't really need any extensions here.
-	)?;
+		method,
+		&data,
+		mock_runtime_version(Default::default(), &shared, &executor),
+	)
+	.map(|(changes, encoded_result, proof)| (changes, (encoded_result, proof)));
+	maybe_store_snapshot(&shared, &ext, &result);
+	let (changes, (encoded_result, proof)) = result?;
+	let proof_size = log_proof_size(&ext, method, proof, shared.record_proof)? as u64;
+	ensure_memory_limit(&shared, state_size(&ext), changes_size(&changes))?;
+	check_assertions::<Block, ExecDispatch>(&shared, &ext, &executor, &changes)?;
+	check_conservation::<Block, ExecDispatch>(&shared, &ext, &executor, &changes)?;
+	print_storage_diff::<Block, ExecDispatch>(&shared, &ext, &executor, &changes)?;
 
 	let (weight, total_weight) = <(Weight, Weight) as Decode>::decode(&mut &*encoded_result)
 		.map_err(|e| format!("failed to decode weight: {:?}", e))?;
+	summary.storage_roots(
+		ext.backend.root(),
+		changes.storage_root(&ext.backend, &mut Default::default(), shared.state_version),
+	);
+	summary.weight = Some(weight.into());
+	summary.total_weight = Some(total_weight.into());
 	log::info!(
 		target: LOG_TARGET,
-		"TryRuntime_on_runtime_upgrade executed without errors. Consumed weight = ({} ps, {} byte), total weight = ({} ps, {} byte) ({:.2} %, {:.2} %).",
+		"{} executed without errors. Consumed weight = ({} ps, {} byte), total weight = ({} ps, {} byte) ({:.2} %, {:.2} %).",
+		method,
 		weight.ref_time(), weight.proof_size(),
 		total_weight.ref_time(), total_weight.proof_size(),
 		(weight.ref_time() as f64 / total_weight.ref_time().max(1) as f64) * 100.0,
 		(weight.proof_size() as f64 / total_weight.proof_size().max(1) as f64) * 100.0,
 	);
 
+	let max_block = match command.state.live_uri() {
+		Some(uri) => remote_max_block_weight::<Block>(&uri, shared.rpc_config()).await?,
+		None => {
+			log::info!(
+				target: LOG_TARGET,
+				"state is not live, checking the migration against the block weight limit of the \
+				local runtime",
+			);
+			total_weight
+		},
+	};
+	ensure_fits_in_block(weight, proof_size, max_block, command.allow_overweight)?;
+
+	if let Some(path) = &shared.storage_trace {
+		write_storage_trace::<Block, ExecDispatch>(
+			path,
+			&ext,
+			&executor,
+			execution,
+			method,
+			&data,
+			mock_runtime_version(Default::default(), &shared, &executor),
+		)?;
+	}
+
+	let known_prefixes = pallet_storage_prefixes::<Block, ExecDispatch>(&ext, &executor)?;
+	report_unknown_storage_writes(&changes, &known_prefixes);
+
+	if command.repeat > 0 {
+		log::info!(target: LOG_TARGET, "executing the migration {} more times", command.repeat);
+		// the state machine never mutates `ext`, thus every execution starts from the same state.
+		let samples = (0..command.repeat)
+			.map(|_| {
+				let start = Instant::now();
+				state_machine_call::<Block, ExecDispatch>(
+					&ext,
+					&executor,
+					execution,
+					method,
+					&data,
+					mock_runtime_version(Default::default(), &shared, &executor),
+				)
+				.map(|_| start.elapsed())
+			})
+			.collect::<sc_cli::Result<Vec<_>>>()?;
+		report_execution_times(samples, weight);
+	}
+
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const MAX_BLOCK: Weight = Weight::from_parts(2_000, 500);
+
+	#[test]
+	fn ensure_fits_in_block_works() {
+		assert!(ensure_fits_in_block(Weight::from_parts(1_000, 100), 100, MAX_BLOCK, false).is_ok());
+		// at the limit.
+		assert!(ensure_fits_in_block(MAX_BLOCK, 500, MAX_BLOCK, false).is_ok());
+		assert!(ensure_fits_in_block(Weight::zero(), 0, MAX_BLOCK, false).is_ok());
+	}
+
+	#[test]
+	fn ensure_fits_in_block_fails_if_overweight() {
+		let err = |weight, proof_size| {
+			ensure_fits_in_block(weight, proof_size, MAX_BLOCK, false)
+				.unwrap_err()
+				.to_string()
+		};
+
+		assert!(err(Weight::from_parts(2_001, 0), 0).contains("ref time"));
+		// the recorded proof size counts, even if the migration claims less.
+		assert!(err(Weight::from_parts(0, 100), 501).contains("proof size"));
+		// and so does the claimed proof size, even if less is recorded.
+		assert!(err(Weight::from_parts(0, 501), 100).contains("proof size"));
+
+		let both = err(Weight::from_parts(2_001, 0), 501);
+		assert!(both.contains("ref time") && both.contains("proof size"));
+	}
+
+	#[test]
+	fn ensure_fits_in_block_can_allow_overweight() {
+		assert!(ensure_fits_in_block(Weight::from_parts(2_001, 501), 501, MAX_BLOCK, true).is_ok());
+	}
+}
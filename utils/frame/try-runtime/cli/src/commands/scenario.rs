@@ -0,0 +1,249 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+	apply_changes, build_executor,
+	cache::Cache,
+	commands::{
+		fast_forward::produce_blocks,
+		measure_call::{decode_dispatch_output, find_call_index},
+	},
+	ensure_matching_spec, ensure_try_runtime_api, extract_code, full_extensions, local_spec,
+	local_version,
+	mock_version::mock_runtime_version,
+	runtime_metadata, state_machine_call, state_machine_call_with_proof, SharedParams, State,
+	LOG_TARGET,
+};
+use parity_scale_codec::{Decode, Encode};
+use sc_executor::NativeExecutionDispatch;
+use sc_service::Configuration;
+use sp_core::bytes::{from_hex, to_hex};
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+use sp_state_machine::Backend;
+use sp_weights::Weight;
+use std::{fmt::Debug, path::PathBuf, str::FromStr};
+
+/// Configurations of the [`Command::Scenario`].
+#[derive(Debug, Clone, clap::Parser)]
+pub struct ScenarioCmd {
+	/// The yaml file that describes the scenario.
+	#[arg(long)]
+	file: PathBuf,
+
+	/// The state type to use.
+	#[command(subcommand)]
+	pub(crate) state: State,
+}
+
+/// A scenario, as read from its file.
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scenario {
+	/// The steps to execute, in order.
+	steps: Vec<Step>,
+}
+
+/// A single step of a [`Scenario`].
+///
+/// All keys, values, accounts and arguments are SCALE encoded, in hex.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum Step {
+	/// Set the hashed storage `key` to `value`, or remove it if `value` is omitted.
+	SetStorage { key: String, value: Option<String> },
+	/// Replace the code with the local runtime, and execute its migrations.
+	RuntimeUpgrade,
+	/// Dispatch `call` of `pallet` with `args`, as signed by `signer`, or as root if omitted.
+	///
+	/// A failing call fails the scenario.
+	Dispatch { pallet: String, call: String, args: Option<String>, signer: Option<String> },
+	/// Ensure that the hashed storage `key` has `value`, or does not exist if `value` is omitted.
+	AssertStorage { key: String, value: Option<String> },
+	/// Produce and execute `n` blocks, `blocktime` milliseconds apart, as
+	/// [`Command::FastForward`] does.
+	ProduceBlocks {
+		n: u32,
+		#[serde(default = "default_blocktime")]
+		blocktime: u64,
+	},
+}
+
+/// The default `blocktime` of [`Step::ProduceBlocks`], the same as that of
+/// [`Command::FastForward`].
+fn default_blocktime() -> u64 {
+	6000
+}
+
+/// Decode the given hex string, which is the `what` of step number `step`.
+fn decode_hex(step: usize, what: &str, hex: &str) -> sc_cli::Result<Vec<u8>> {
+	from_hex(hex).map_err(|e| format!("step {}: invalid {} {}: {}", step, what, hex, e).into())
+}
+
+pub(crate) async fn scenario<Block, ExecDispatch>(
+	shared: SharedParams,
+	command: ScenarioCmd,
+	config: Configuration,
+) -> sc_cli::Result<()>
+where
+	Block: BlockT + serde::de::DeserializeOwned,
+	Block::Hash: FromStr,
+	<Block::Hash as FromStr>::Err: Debug,
+	Block::Header: serde::de::DeserializeOwned,
+	NumberFor<Block>: FromStr,
+	<NumberFor<Block> as FromStr>::Err: Debug,
+	ExecDispatch: NativeExecutionDispatch + 'static,
+{
+	let file = std::fs::File::open(&command.file)
+		.map_err(|e| format!("failed to open {:?}: {}", command.file, e))?;
+	let scenario: Scenario = serde_yaml::from_reader(file)
+		.map_err(|e| format!("failed to parse {:?}: {}", command.file, e))?;
+
+	let executor = build_executor::<ExecDispatch>(&shared, &config);
+	let execution = shared.execution;
+
+	let builder = command
+		.state
+		.builder::<Block>()?
+		.state_version(shared.state_version)
+		.max_memory(shared.max_memory_bytes())
+		.rpc_config(shared.rpc_config());
+	let mut ext = Cache::new(&shared)
+		.inject_code(builder, &command.state, None)
+		.await?
+		.build()
+		.await?;
+
+	let (_, _, mut state_version) = local_spec::<Block, ExecDispatch>(&ext, &executor);
+	if let Some(uri) = command.state.live_uri() {
+		let version = local_version::<Block, ExecDispatch>(&ext, &executor);
+		ensure_matching_spec::<Block>(uri, &version, &shared).await?;
+	}
+
+	let steps = scenario.steps.len();
+	for (index, step) in scenario.steps.into_iter().enumerate() {
+		let number = index + 1;
+		log::info!(target: LOG_TARGET, "step {}/{}: {:?}", number, steps, step);
+		match step {
+			Step::SetStorage { key, value } => {
+				let key = decode_hex(number, "key", &key)?;
+				let value = value.map(|v| decode_hex(number, "value", &v)).transpose()?;
+				ext.backend.insert(vec![(None, vec![(key, value)])], state_version);
+			},
+			Step::RuntimeUpgrade => {
+				let (code_key, code) = extract_code(&config.chain_spec)?;
+				ext.backend
+					.insert(vec![(None, vec![(code_key.0, Some(code.0))])], state_version);
+
+				let (changes, encoded_result) = state_machine_call_with_proof::<Block, ExecDispatch>(
+					&ext,
+					&executor,
+					execution,
+					"TryRuntime_on_runtime_upgrade",
+					&[],
+					mock_runtime_version(Default::default(), &shared, &executor),
+					shared.record_proof,
+				)?;
+				let (weight, total_weight) =
+					<(Weight, Weight) as Decode>::decode(&mut &*encoded_result)
+						.map_err(|e| format!("failed to decode weight: {:?}", e))?;
+				log::info!(
+					target: LOG_TARGET,
+					"runtime upgrade consumed weight = ({} ps, {} byte), total weight = ({} ps, {} byte)",
+					weight.ref_time(),
+					weight.proof_size(),
+					total_weight.ref_time(),
+					total_weight.proof_size(),
+				);
+				apply_changes(&mut ext, changes, state_version)?;
+
+				// the new runtime might use another state version.
+				state_version = local_spec::<Block, ExecDispatch>(&ext, &executor).2;
+			},
+			Step::Dispatch { pallet, call, args, signer } => {
+				let metadata = runtime_metadata::<Block, ExecDispatch>(&ext, &executor)?;
+				let (pallet_index, call_index) = find_call_index(&metadata, &pallet, &call)?;
+				let mut encoded_call = vec![pallet_index, call_index];
+				if let Some(args) = args {
+					encoded_call.extend(decode_hex(number, "args", &args)?);
+				}
+				let signer = signer.map(|s| decode_hex(number, "signer", &s)).transpose()?;
+				ensure_try_runtime_api(
+					&local_version::<Block, ExecDispatch>(&ext, &executor),
+					2,
+					"TryRuntime_dispatch_call",
+				)?;
+
+				let (changes, encoded_result) = state_machine_call::<Block, ExecDispatch>(
+					&ext,
+					&executor,
+					execution,
+					"TryRuntime_dispatch_call",
+					&(encoded_call, signer).encode(),
+					mock_runtime_version(full_extensions(), &shared, &executor),
+				)?;
+				let (_, actual, result) = decode_dispatch_output(&encoded_result)
+					.map_err(|e| format!("step {}: {}::{}: {}", number, pallet, call, e))?;
+				result.map_err(|e| {
+					format!("step {}: {}::{} failed: {:?}", number, pallet, call, e)
+				})?;
+				log::info!(
+					target: LOG_TARGET,
+					"{}::{} consumed weight = ({} ps, {} byte)",
+					pallet,
+					call,
+					actual.ref_time(),
+					actual.proof_size(),
+				);
+				apply_changes(&mut ext, changes, state_version)?;
+			},
+			Step::AssertStorage { key, value } => {
+				let key = decode_hex(number, "key", &key)?;
+				let expected = value.map(|v| decode_hex(number, "value", &v)).transpose()?;
+				let actual = ext
+					.backend
+					.storage(&key)
+					.map_err(|e| format!("failed to read storage: {}", e))?;
+				if actual != expected {
+					return Err(format!(
+						"step {}: storage assertion failed: expected {:?}, found {:?}",
+						number,
+						expected.map(|v| to_hex(&v, false)),
+						actual.map(|v| to_hex(&v, false)),
+					)
+					.into())
+				}
+			},
+			Step::ProduceBlocks { n, blocktime } => {
+				let last = produce_blocks::<Block, ExecDispatch>(
+					&shared,
+					&executor,
+					&mut ext,
+					n,
+					blocktime,
+					Some(&[]),
+				)
+				.map_err(|e| format!("step {}: {}", number, e))?;
+				log::info!(target: LOG_TARGET, "produced {} blocks, up to #{:?}", n, last);
+				// a block might have upgraded the runtime, which might use another state version.
+				state_version = local_spec::<Block, ExecDispatch>(&ext, &executor).2;
+			},
+		}
+	}
+
+	log::info!(target: LOG_TARGET, "all {} steps of the scenario executed successfully.", steps);
+	Ok(())
+}
@@ -0,0 +1,416 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Host side timing of the `on_initialize`, `on_idle` and `on_finalize` hooks of each pallet.
+//!
+//! The pallet macro wraps every hook in a tracing span named after the hook, with the pallet's
+//! module as target. Within the span of `on_initialize` and `on_idle`, it also emits an event with
+//! target [`HOOK_WEIGHT_TARGET`] that carries the weight returned by the hook.
+//!
+//! For the duration of a runtime call, a [`HookTimer`] is installed as the thread-local
+//! dispatcher. It forwards everything to the dispatcher that was in place before (so logging keeps
+//! working), and additionally measures the time spent in each of these spans and adds up the
+//! weight reported within them. The timer hands out its own span ids, since the dispatcher it
+//! wraps is free to reuse ids (`NoSubscriber`, for one, gives every span the same id).
+//!
+//! Spans of a wasm runtime only reach the host if the runtime was compiled with the `with-tracing`
+//! feature of `sp-io`. The native runtime always emits them.
+
+use crate::LOG_TARGET;
+use sp_tracing::{WASM_NAME_KEY, WASM_TARGET_KEY, WASM_TRACE_IDENTIFIER};
+use sp_weights::Weight;
+use std::{
+	collections::{BTreeMap, HashMap},
+	fmt::Debug,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex,
+	},
+	time::{Duration, Instant},
+};
+use tracing::{
+	field::{Field, Visit},
+	span,
+	subscriber::Interest,
+	Dispatch, Event, Metadata, Subscriber,
+};
+
+/// The hooks that are being measured.
+const HOOKS: [&str; 3] = ["on_initialize", "on_idle", "on_finalize"];
+
+/// The target of the events through which the pallet macro reports the weight of a hook.
+const HOOK_WEIGHT_TARGET: &str = "runtime::hook_weight";
+
+/// What has been measured of a single `(target, hook)`.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct HookTiming {
+	/// The time spent in the hook.
+	elapsed: Duration,
+	/// The number of times the hook was called.
+	calls: u32,
+	/// The weight that the hook reported, if any.
+	weight: Weight,
+}
+
+/// Accumulated [`HookTiming`] of each `(target, hook)`.
+#[derive(Debug, Default)]
+pub(crate) struct HookTimings(BTreeMap<(String, String), HookTiming>);
+
+impl HookTimings {
+	/// Log all the timings, slowest first.
+	pub(crate) fn log(&self) {
+		if self.0.is_empty() {
+			log::warn!(
+				target: LOG_TARGET,
+				"no hook spans recorded; make sure the wasm runtime is built with `with-tracing`"
+			);
+			return
+		}
+
+		let mut timings = self.0.iter().collect::<Vec<_>>();
+		timings.sort_by(|(_, a), (_, b)| b.elapsed.cmp(&a.elapsed));
+		let total = timings.iter().map(|(_, t)| t.elapsed).sum::<Duration>();
+		log::info!(target: LOG_TARGET, "hook execution time breakdown, total {:?}:", total);
+		for ((target, hook), timing) in timings {
+			log::info!(
+				target: LOG_TARGET,
+				"  {}::{}: {:?} ({:.2} %, {} calls, weight {}, proof size {})",
+				target,
+				hook,
+				timing.elapsed,
+				timing.elapsed.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON) * 100.0,
+				timing.calls,
+				timing.weight.ref_time(),
+				timing.weight.proof_size(),
+			);
+		}
+	}
+}
+
+/// Run `f` while measuring the time spent in each pallet's hooks.
+pub(crate) fn measure<R>(f: impl FnOnce() -> R) -> (R, HookTimings) {
+	let timer = HookTimer {
+		inner: tracing::dispatcher::get_default(|d| d.clone()),
+		next_id: AtomicU64::new(1),
+		spans: Default::default(),
+		stack: Default::default(),
+		timings: Default::default(),
+	};
+	let timings = timer.timings.clone();
+	let result = tracing::dispatcher::with_default(&Dispatch::new(timer), f);
+
+	let timings = std::mem::take(&mut *timings.lock().expect("lock is never poisoned; qed"));
+	(result, HookTimings(timings))
+}
+
+/// A hook span that is being measured.
+struct ActiveSpan {
+	key: (String, String),
+	entered_at: Option<Instant>,
+}
+
+/// A span handed out by the [`HookTimer`].
+struct SpanState {
+	/// The id of the span in the wrapped dispatcher.
+	inner: span::Id,
+	metadata: &'static Metadata<'static>,
+	/// The number of handles to this span that are still open.
+	refs: usize,
+	/// Set if this is the span of a hook.
+	hook: Option<ActiveSpan>,
+}
+
+/// A subscriber that forwards everything to `inner`, and measures the hook spans along the way.
+struct HookTimer {
+	inner: Dispatch,
+	next_id: AtomicU64,
+	spans: Mutex<HashMap<u64, SpanState>>,
+	/// The spans that are currently entered, innermost last.
+	stack: Mutex<Vec<u64>>,
+	timings: Arc<Mutex<BTreeMap<(String, String), HookTiming>>>,
+}
+
+/// Whether this callsite can be a pallet hook span or a hook weight event, either native or
+/// originating from wasm.
+fn is_candidate(metadata: &Metadata<'_>) -> bool {
+	metadata.name() == WASM_TRACE_IDENTIFIER ||
+		(metadata.is_span() && HOOKS.contains(&metadata.name())) ||
+		(metadata.is_event() && metadata.target() == HOOK_WEIGHT_TARGET)
+}
+
+/// Collects the real name and target of a span or event that originates from wasm, and the
+/// fields of a hook weight event.
+#[derive(Default)]
+struct WasmVisitor {
+	name: Option<String>,
+	target: Option<String>,
+	params: Option<String>,
+	ref_time: Option<u64>,
+	proof_size: Option<u64>,
+}
+
+impl Visit for WasmVisitor {
+	fn record_u64(&mut self, field: &Field, value: u64) {
+		match field.name() {
+			"ref_time" => self.ref_time = Some(value),
+			"proof_size" => self.proof_size = Some(value),
+			_ => {},
+		}
+	}
+
+	fn record_str(&mut self, field: &Field, value: &str) {
+		match field.name() {
+			n if n == WASM_NAME_KEY => self.name = Some(value.to_string()),
+			n if n == WASM_TARGET_KEY => self.target = Some(value.to_string()),
+			_ => {},
+		}
+	}
+
+	fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+		if field.name() == "params" {
+			self.params = Some(format!("{:?}", value));
+		}
+	}
+}
+
+/// Find the `u64` value of `field` in the debug representation of the fields of a wasm event,
+/// e.g. `{ ref_time: 10_u64, proof_size: 0_u64 }`.
+fn wasm_u64_field(params: &str, field: &str) -> Option<u64> {
+	let start = params.find(&format!(" {}: ", field))? + field.len() + 3;
+	let value = &params[start..];
+	value[..value.find("_u64")?].parse().ok()
+}
+
+/// The weight carried by `event`, if it is a hook weight event.
+fn hook_weight(event: &Event<'_>) -> Option<Weight> {
+	let metadata = event.metadata();
+	let mut visitor = WasmVisitor::default();
+	if metadata.target() == HOOK_WEIGHT_TARGET {
+		event.record(&mut visitor);
+		return Some(Weight::from_parts(visitor.ref_time?, visitor.proof_size.unwrap_or_default()))
+	}
+
+	if metadata.name() != WASM_TRACE_IDENTIFIER {
+		return None
+	}
+	event.record(&mut visitor);
+	if visitor.target.as_deref() != Some(HOOK_WEIGHT_TARGET) {
+		return None
+	}
+	let params = visitor.params?;
+	Some(Weight::from_parts(
+		wasm_u64_field(&params, "ref_time")?,
+		wasm_u64_field(&params, "proof_size").unwrap_or_default(),
+	))
+}
+
+impl HookTimer {
+	/// The id of `span` in the wrapped dispatcher.
+	fn inner_id(&self, span: &span::Id) -> Option<span::Id> {
+		self.spans
+			.lock()
+			.expect("lock is never poisoned; qed")
+			.get(&span.into_u64())
+			.map(|s| s.inner.clone())
+	}
+}
+
+impl Subscriber for HookTimer {
+	fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+		if is_candidate(metadata) {
+			Interest::always()
+		} else {
+			self.inner.register_callsite(metadata)
+		}
+	}
+
+	fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+		is_candidate(metadata) || self.inner.enabled(metadata)
+	}
+
+	fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+		let metadata = attrs.metadata();
+		let inner = match attrs.parent().and_then(|p| self.inner_id(p)) {
+			Some(parent) =>
+				self.inner
+					.new_span(&span::Attributes::child_of(parent, metadata, attrs.values())),
+			None if attrs.is_root() || attrs.parent().is_some() =>
+				self.inner.new_span(&span::Attributes::new_root(metadata, attrs.values())),
+			None => self.inner.new_span(attrs),
+		};
+
+		let hook = is_candidate(metadata)
+			.then(|| {
+				if metadata.name() == WASM_TRACE_IDENTIFIER {
+					let mut visitor = WasmVisitor::default();
+					attrs.record(&mut visitor);
+					(visitor.target.unwrap_or_default(), visitor.name.unwrap_or_default())
+				} else {
+					(metadata.target().to_string(), metadata.name().to_string())
+				}
+			})
+			.filter(|(_, name)| HOOKS.contains(&name.as_str()))
+			.map(|key| ActiveSpan { key, entered_at: None });
+
+		let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+		self.spans
+			.lock()
+			.expect("lock is never poisoned; qed")
+			.insert(id, SpanState { inner, metadata, refs: 1, hook });
+		span::Id::from_u64(id)
+	}
+
+	fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+		if let Some(inner) = self.inner_id(span) {
+			self.inner.record(&inner, values)
+		}
+	}
+
+	fn record_follows_from(&self, span: &span::Id, follows: &span::Id) {
+		if let (Some(span), Some(follows)) = (self.inner_id(span), self.inner_id(follows)) {
+			self.inner.record_follows_from(&span, &follows)
+		}
+	}
+
+	fn event(&self, event: &Event<'_>) {
+		if let Some(weight) = hook_weight(event) {
+			// wasm events always arrive without a parent, hence attribute the weight to the
+			// innermost hook that is currently entered.
+			let stack = self.stack.lock().expect("lock is never poisoned; qed");
+			let spans = self.spans.lock().expect("lock is never poisoned; qed");
+			if let Some(hook) =
+				stack.iter().rev().find_map(|id| spans.get(id).and_then(|s| s.hook.as_ref()))
+			{
+				let mut timings = self.timings.lock().expect("lock is never poisoned; qed");
+				timings.entry(hook.key.clone()).or_default().weight += weight;
+			}
+		}
+
+		// NOTE: an event with an explicit parent is forwarded with the parent id of this timer,
+		// since `tracing` offers no way to rebuild an event. The pallet hooks never emit those.
+		if self.inner.enabled(event.metadata()) {
+			self.inner.event(event)
+		}
+	}
+
+	fn enter(&self, span: &span::Id) {
+		let inner = match self
+			.spans
+			.lock()
+			.expect("lock is never poisoned; qed")
+			.get_mut(&span.into_u64())
+		{
+			Some(state) => {
+				if let Some(hook) = state.hook.as_mut() {
+					hook.entered_at = Some(Instant::now());
+				}
+				state.inner.clone()
+			},
+			None => return,
+		};
+		self.stack.lock().expect("lock is never poisoned; qed").push(span.into_u64());
+		self.inner.enter(&inner)
+	}
+
+	fn exit(&self, span: &span::Id) {
+		{
+			let mut stack = self.stack.lock().expect("lock is never poisoned; qed");
+			if let Some(position) = stack.iter().rposition(|id| *id == span.into_u64()) {
+				stack.remove(position);
+			}
+		}
+
+		let inner = match self
+			.spans
+			.lock()
+			.expect("lock is never poisoned; qed")
+			.get_mut(&span.into_u64())
+		{
+			Some(state) => {
+				if let Some(ActiveSpan { key, entered_at }) = state.hook.as_mut() {
+					if let Some(entered_at) = entered_at.take() {
+						let mut timings = self.timings.lock().expect("lock is never poisoned; qed");
+						let timing = timings.entry(key.clone()).or_default();
+						timing.elapsed += entered_at.elapsed();
+						timing.calls += 1;
+					}
+				}
+				state.inner.clone()
+			},
+			None => return,
+		};
+		self.inner.exit(&inner)
+	}
+
+	fn clone_span(&self, span: &span::Id) -> span::Id {
+		let inner = self
+			.spans
+			.lock()
+			.expect("lock is never poisoned; qed")
+			.get_mut(&span.into_u64())
+			.map(|state| {
+				state.refs += 1;
+				state.inner.clone()
+			});
+		if let Some(inner) = inner {
+			self.inner.clone_span(&inner);
+		}
+		span.clone()
+	}
+
+	fn try_close(&self, span: span::Id) -> bool {
+		let (inner, closed) = {
+			let mut spans = self.spans.lock().expect("lock is never poisoned; qed");
+			let (inner, refs) = match spans.get_mut(&span.into_u64()) {
+				Some(state) => {
+					state.refs -= 1;
+					(state.inner.clone(), state.refs)
+				},
+				None => return false,
+			};
+			if refs == 0 {
+				spans.remove(&span.into_u64());
+			}
+			(inner, refs == 0)
+		};
+		self.inner.try_close(inner);
+		closed
+	}
+
+	fn current_span(&self) -> span::Current {
+		let stack = self.stack.lock().expect("lock is never poisoned; qed");
+		let spans = self.spans.lock().expect("lock is never poisoned; qed");
+		match stack.last().and_then(|id| spans.get(id).map(|s| (id, s.metadata))) {
+			Some((id, metadata)) => span::Current::new(span::Id::from_u64(*id), metadata),
+			None => span::Current::none(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn wasm_u64_field_works() {
+		let params = " { ref_time: 1234_u64, proof_size: 0_u64 }";
+		assert_eq!(wasm_u64_field(params, "ref_time"), Some(1234));
+		assert_eq!(wasm_u64_field(params, "proof_size"), Some(0));
+		assert_eq!(wasm_u64_field(params, "size"), None);
+		assert_eq!(wasm_u64_field(" { ref_time: 12_u32 }", "ref_time"), None);
+	}
+}
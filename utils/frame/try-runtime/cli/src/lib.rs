@@ -95,17 +95,25 @@
 //!
 //! Furthermore, other relevant log targets are: `try-runtime::cli`, `remote-ext`, and `runtime`.
 //!
-//! ## Spec name check
+//! ## Spec check
 //!
 //! A common pitfall is that you might be running some test on top of the state of chain `x`, with
-//! the runtime of chain `y`. To avoid this all commands do a spec-name check before executing
-//! anything by default. This will check the spec name of the remote node your are connected to,
-//! with the spec name of your local runtime and ensure that they match.
+//! the runtime of chain `y`. To avoid this all commands compare the runtime version of the remote
+//! node your are connected to with the one of your local runtime before executing anything.
 //!
-//! Should you need to disable this on certain occasions, a top level flag of `--no-spec-name-check`
-//! can be used.
+//! A mismatching spec name only emits a warning. A local spec version lower than the remote one is
+//! an error, since the chain would not accept such code as an upgrade. Should you need to run
+//! anyway on certain occasions, a top level flag of `--force` can be used.
 //!
-//! The spec version is also always inspected, but if it is a mismatch, it will only emit a warning.
+//! Failing to fetch the remote version is an error as well. The top level flag of
+//! `--no-spec-check-panic` turns both errors into warnings.
+//!
+//! ## Unknown storage writes
+//!
+//! After executing the runtime, all commands inspect the storage keys that have been written and
+//! compare them against the storage prefixes of all pallets, as reported by the runtime metadata.
+//! Any key that belongs to no pallet and is not a well-known key (i.e. starting with `:`) is
+//! reported as a warning. Such writes usually hint at raw `sp_io::storage` misuse.
 //!
 //! ## Note nodes that operate with `try-runtime`
 //!
@@ -183,7 +191,7 @@
 //!     cargo run try-runtime \
 //!     --execution Native \
 //!     --chain dev \
-//!     --no-spec-name-check \ # mind this one!
+//!     --force \ # mind this one!
 //!     on-runtime-upgrade \
 //!     live \
 //!     --uri wss://rpc.polkadot.io
@@ -197,7 +205,7 @@
 //!     cargo run try-runtime \
 //!     --execution Native \
 //!     --chain dev \
-//!     --no-spec-name-check \ # mind this one! on-runtime-upgrade \
+//!     --force \ # mind this one!
 //!     on-runtime-upgrade \
 //!     live \
 //!     --uri wss://rpc.polkadot.io \
@@ -264,9 +272,88 @@
 //!     snap \
 //!     -s snap \
 //! ```
+//!
+//! To bisect a divergence from the chain, a range of blocks can be replayed one after the other,
+//! each on top of the state that the previous one left behind, starting at the state of the parent
+//! of the first one. The command stops at the first block that fails or whose state root differs
+//! from the one in its header:
+//!
+//! ```sh
+//! cargo run try-runtime \
+//!     --execution Native \
+//!     --chain polkadot-dev \
+//!     execute-block \
+//!     --from 12_000_000 \
+//!     --to <block-hash> \
+//!     live \
+//!     --uri wss://rpc.polkadot.io
+//! ```
+//!
+//! Such a snapshot can be created upfront, without executing anything, with the `create-snapshot`
+//! command, e.g. of the state of the staking pallet only:
+//!
+//! ```sh
+//! cargo run try-runtime \
+//!     create-snapshot \
+//!     --uri wss://rpc.polkadot.io \
+//!     --at <parent-of-x> \
+//!     --pallet Staking \
+//!     snap
+//! ```
+//!
+//! Later on, the same snapshot can be brought up to date with a newer block, fetching only the
+//! values that changed since, instead of scraping the state again:
+//!
+//! ```sh
+//! cargo run try-runtime \
+//!     create-snapshot \
+//!     --uri wss://rpc.polkadot.io \
+//!     --at <newer-block> \
+//!     --pallet Staking \
+//!     --update-snapshot snap \
+//!     snap
+//! ```
+//!
+//! A snapshot of the entire state can then be used to see what happens a few blocks into the
+//! future, e.g. whether the next era starts in time:
+//!
+//! ```sh
+//! cargo run try-runtime \
+//!     --execution Native \
+//!     --chain polkadot-dev \
+//!     --assert 'Staking::CurrentEra > <era>' \
+//!     fast-forward \
+//!     --n-blocks 600 \
+//!     snap \
+//!     -s snap
+//! ```
+//!
+//! Before an upgrade, the same snapshot shows which pallets of the local runtime declare a
+//! storage version that differs from the one on chain, i.e. which pallets need a migration:
+//!
+//! ```sh
+//! cargo run try-runtime \
+//!     --chain polkadot-dev \
+//!     check-storage-versions \
+//!     snap \
+//!     -s snap
+//! ```
+//!
+//! The invariants of all pallets, as checked by their `try_state` hooks, can be checked on top of
+//! the same snapshot, or of the live state of any block, reporting every pallet whose checks fail:
+//!
+//! ```sh
+//! cargo run try-runtime \
+//!     --chain polkadot-dev \
+//!     check-state \
+//!     live \
+//!     --uri ws://localhost:9944 \
+//!     --at <block-hash>
+//! ```
 
 #![cfg(feature = "try-runtime")]
 
+use frame_metadata::{RuntimeMetadata, RuntimeMetadataPrefixed, RuntimeMetadataV14};
 use parity_scale_codec::Decode;
 use remote_externalities::{
 	Builder, Mode, OfflineConfig, OnlineConfig, SnapshotConfig, TestExternalities,
@@ -280,13 +367,17 @@ use sc_cli::{
 use sc_executor::NativeElseWasmExecutor;
 use sc_service::{Configuration, NativeExecutionDispatch};
 use sp_core::{
+	bytes::{from_hex, to_hex},
+	hashing::blake2_64,
+	hexdisplay::HexDisplay,
 	offchain::{
 		testing::{TestOffchainExt, TestTransactionPoolExt},
-		OffchainDbExt, OffchainWorkerExt, TransactionPoolExt,
+		DbExternalities, Externalities, OffchainDbExt, OffchainWorkerExt, Timestamp,
+		TransactionPoolExt,
 	},
 	storage::{well_known_keys, StorageData, StorageKey},
 	testing::TaskExecutor,
-	traits::TaskExecutorExt,
+	traits::{ReadRuntimeVersion, TaskExecutorExt},
 	twox_128, H256,
 };
 use sp_externalities::Extensions;
@@ -295,13 +386,31 @@ use sp_runtime::{
 	traits::{Block as BlockT, NumberFor},
 	DeserializeOwned,
 };
-use sp_state_machine::{OverlayedChanges, StateMachine, TrieBackendBuilder};
-use sp_version::StateVersion;
-use std::{fmt::Debug, path::PathBuf, str::FromStr};
-use substrate_rpc_client::{ws_client, StateApi};
+use sp_state_machine::{
+	Backend, BasicExternalities, OverlayedChanges, StateMachine, StorageProof, TrieBackendBuilder,
+};
+use sp_version::{RuntimeVersion, StateVersion};
+use sp_weights::Weight;
+use std::{
+	cmp::Ordering,
+	fmt::Debug,
+	num::NonZeroUsize,
+	path::{Path, PathBuf},
+	str::FromStr,
+	time::Duration,
+};
+use substrate_rpc_client::{rpc_client_with, ClientConfig, RpcClient, StateApi};
 
+mod assertions;
+mod cache;
 mod commands;
+mod hook_timing;
+mod mock_version;
+mod offchain;
+mod output;
 pub(crate) mod parse;
+mod storage_diff;
+mod storage_trace;
 pub(crate) const LOG_TARGET: &str = "try-runtime::cli";
 
 /// Possible commands of `try-runtime`.
@@ -309,7 +418,8 @@ pub(crate) const LOG_TARGET: &str = "try-runtime::cli";
 pub enum Command {
 	/// Execute the migrations of the "local runtime".
 	///
-	/// This uses a custom runtime api call, namely "TryRuntime_on_runtime_upgrade".
+	/// This uses a custom runtime api call, namely "TryRuntime_on_runtime_upgrade", or
+	/// "TryRuntime_on_runtime_upgrade_of" if only some pallets are selected with `--pallet`.
 	///
 	/// This always overwrites the wasm code with the local runtime (specified by `--chain`), to
 	/// ensure the new migrations are being executed. Re-executing already existing migrations is
@@ -346,6 +456,17 @@ pub enum Command {
 	/// use [`State::Live`], and the parent hash of the target block is used to scrape the state.
 	/// If [`State::Snap`] is being used, then this needs to be manually taken into consideration.
 	///
+	/// If the wasm code is not overwritten, the block is replayed with the exact runtime that
+	/// executed it on chain. In that case, the post-state root is also computed on the host side
+	/// and compared against the state root in the header of the block. A mismatch is reported as
+	/// an error, as it indicates a divergence between this executor (or its host functions) and
+	/// the one that originally authored the block.
+	///
+	/// Blocks of parachain runtimes can be replayed as well. If their relay chain provided
+	/// inherent needs to be stubbed, it can be replaced through `--replace-extrinsic`, see
+	/// `ExecuteBlockCmd::replace_extrinsics`. The replacement has to be provided in full, since
+	/// the validation data of the relay chain can not be made up on the host.
+	///
 	/// This does not execute the same runtime api as normal block import do, namely
 	/// `Core_execute_block`. Instead, it uses `TryRuntime_execute_block`, which can optionally
 	/// skip state-root check (useful for trying a unreleased runtime), and can execute runtime
@@ -383,6 +504,92 @@ pub enum Command {
 	/// initializes the state from the remote node, and starts applying that block, plus all the
 	/// blocks that follow, to the same growing state.
 	FollowChain(commands::follow_chain::FollowChainCmd),
+
+	/// Dispatch a single call of a pallet against some state, repeatedly, and compare its
+	/// measured execution time and storage proof size against the weight that it declares.
+	///
+	/// This uses a custom runtime api call, namely "TryRuntime_dispatch_call". The call is
+	/// dispatched directly, without any of the checks and payments of a real extrinsic, as signed
+	/// by `--signer`, or as root.
+	///
+	/// This is a sanity check of the weight of a call on real data, on the local machine. It is
+	/// not a substitute for benchmarking, and the measured time includes the overhead of the
+	/// runtime api call itself.
+	MeasureCall(commands::measure_call::MeasureCallCmd),
+
+	/// Execute a scenario, as described by a yaml file, against some state.
+	///
+	/// A scenario is a sequence of steps, each of which builds on the state left by the previous
+	/// one, such that complex rehearsals (e.g. of a runtime upgrade) can be version controlled:
+	///
+	/// ```yaml
+	/// steps:
+	///   - set_storage: { key: "0x..", value: "0x.." }
+	///   - dispatch: { pallet: Balances, call: transfer, args: "0x..", signer: "0x.." }
+	///   - runtime_upgrade
+	///   - produce_blocks: { n: 10, blocktime: 6000 }
+	///   - assert_storage: { key: "0x..", value: "0x.." }
+	/// ```
+	///
+	/// `set_storage` and `assert_storage` operate on hashed keys. Omitting their `value` removes
+	/// the key, or asserts that it does not exist, respectively. `runtime_upgrade` replaces the
+	/// code with the local runtime and executes its migrations, using
+	/// "TryRuntime_on_runtime_upgrade". `dispatch` uses "TryRuntime_dispatch_call", as
+	/// [`Command::MeasureCall`] does, and fails the scenario if the call fails. `produce_blocks`
+	/// produces and executes `n` blocks with their timestamp inherents, as
+	/// [`Command::FastForward`] does, `blocktime` milliseconds apart (6000 if omitted).
+	///
+	/// Until the `runtime_upgrade` step, the code of the state is used, hence it must be built
+	/// with the `try-runtime` feature for `dispatch` steps to work.
+	Scenario(commands::scenario::ScenarioCmd),
+
+	/// Scrape the state of a live chain into a snapshot file, without executing anything on top
+	/// of it.
+	///
+	/// The snapshot, which always contains the code of the chain, can then be reused by all other
+	/// commands through [`State::Snap`].
+	CreateSnapshot(commands::create_snapshot::CreateSnapshotCmd),
+
+	/// Produce and execute a number of empty blocks on top of some state, to inspect what happens
+	/// in the future, e.g. whether scheduled tasks, elections and era transitions behave.
+	///
+	/// Each block is built with the same runtime apis as block authorship, namely
+	/// `Core_initialize_block` and `BlockBuilder_finalize_block`, which execute the
+	/// `on_initialize` and `on_finalize` hooks. By default, the inherents that the runtime creates
+	/// from a timestamp are applied as well, through `BlockBuilder_inherent_extrinsics` and
+	/// `BlockBuilder_apply_extrinsic`. Runtimes that use BABE or Aura are given a pre-runtime
+	/// digest that claims the slot of the block. The inherents of e.g. a parachain need more
+	/// inherent data, which can be given through `--inherent-data`, see
+	/// `FastForwardCmd::inherent_data`; it is not made up.
+	///
+	/// The blocks are neither sealed nor checked, and the state is persisted between them. Any
+	/// assertions, see [`SharedParams::assertions`], are evaluated on the state after the last
+	/// block.
+	FastForward(commands::fast_forward::FastForwardCmd),
+
+	/// Compare the storage version that each pallet of the local runtime declares against the
+	/// one in some state, to list the pallets that require a migration before an upgrade.
+	///
+	/// The pallets are those in the metadata of the local runtime. As the metadata does not
+	/// contain storage versions, the declared ones are read from the genesis storage of the
+	/// chain spec (specified by `--chain`), to which every pallet writes its storage version.
+	/// Pallets whose storage version is missing on chain, or is ahead of the declared one, are
+	/// reported as well.
+	CheckStorageVersions(commands::check_storage_versions::CheckStorageVersionsCmd),
+
+	/// Execute the try-state checks of the pallets of the local runtime on top of some state,
+	/// which checks the invariants of their storage.
+	///
+	/// The checks of each pallet are executed on their own, hence all failing pallets are
+	/// reported, and not only the first one. The command fails if any of the checks failed.
+	CheckState(commands::check_state::CheckStateCmd),
+
+	/// List all the commands of `try-runtime`, along with their flags.
+	///
+	/// With `--json`, the listing is machine readable, which allows wrapper tooling to validate
+	/// invocations without parsing the output of `--help`. Shell completions of the node binary,
+	/// including all of these commands, are generated by its build script.
+	ListCommands(commands::list_commands::ListCommandsCmd),
 }
 
 /// Shared parameters of the `try-runtime` commands
@@ -395,7 +602,10 @@ pub struct SharedParams {
 	pub shared_params: sc_cli::SharedParams,
 
 	/// The execution strategy that should be used.
-	#[arg(long, value_name = "STRATEGY", value_enum, ignore_case = true, default_value_t = ExecutionStrategy::Wasm)]
+	///
+	/// Like the other settings of the executor, this can also be given after the command, e.g.
+	/// to mirror the executor of the production nodes for a single command.
+	#[arg(long, value_name = "STRATEGY", value_enum, ignore_case = true, default_value_t = ExecutionStrategy::Wasm, global = true)]
 	pub execution: ExecutionStrategy,
 
 	/// Type of wasm execution used.
@@ -405,6 +615,7 @@ pub struct SharedParams {
 		value_enum,
 		ignore_case = true,
 		default_value_t = DEFAULT_WASM_EXECUTION_METHOD,
+		global = true,
 	)]
 	pub wasm_method: WasmExecutionMethod,
 
@@ -416,21 +627,209 @@ pub struct SharedParams {
 		value_name = "STRATEGY",
 		default_value_t = DEFAULT_WASMTIME_INSTANTIATION_STRATEGY,
 		value_enum,
+		global = true,
 	)]
 	pub wasmtime_instantiation_strategy: WasmtimeInstantiationStrategy,
 
 	/// The number of 64KB pages to allocate for Wasm execution. Defaults to
 	/// [`sc_service::Configuration.default_heap_pages`].
-	#[arg(long)]
+	///
+	/// As on a node, this only applies if the state does not set `:heappages`, which takes
+	/// precedence.
+	#[arg(long, global = true)]
 	pub heap_pages: Option<u64>,
 
-	/// When enabled, the spec check will not panic, and instead only show a warning.
+	/// When enabled, the spec check will not fail, and instead only show a warning.
+	///
+	/// This covers both a local spec version lower than the remote one, as [`Self::force`] does,
+	/// and failing to fetch the remote version. A mismatching spec name only ever warns.
 	#[arg(long)]
 	pub no_spec_check_panic: bool,
 
+	/// Run even if the local runtime has a lower spec version than the remote node.
+	#[arg(long)]
+	pub force: bool,
+
 	/// State version that is used by the chain.
 	#[arg(long, default_value_t = StateVersion::V1, value_parser = parse::state_version)]
 	pub state_version: StateVersion,
+
+	/// The format in which the outcome of the command is reported.
+	///
+	/// With `json`, a single json object summarizing the outcome is printed to stdout once the
+	/// command returns, successfully or not: the spec versions that were compared, the storage
+	/// roots before and after, and the consumed weight, as far as they apply to the command, and
+	/// the error, if any. It is accompanied by the provenance of the run, i.e. the versions of the
+	/// node, this crate and rustc, the host, the executor settings, the chain spec, the endpoint
+	/// and the hash of the executed code. The logs keep going to stderr.
+	#[arg(long, value_name = "FORMAT", value_enum, default_value_t = output::OutputFormat::Text)]
+	pub output: output::OutputFormat,
+
+	/// If the runtime call of the command succeeds, store the resulting state as a snapshot at
+	/// this path.
+	///
+	/// For `follow-chain`, the snapshot is rewritten after every block.
+	#[arg(long)]
+	pub store_snapshot_on_success: Option<PathBuf>,
+
+	/// If the runtime call of the command fails, store the state that it failed on as a snapshot
+	/// at this path.
+	///
+	/// The failure can then be reproduced, and debugged, by running the same command again with
+	/// [`State::Snap`].
+	#[arg(long)]
+	pub store_snapshot_on_failure: Option<PathBuf>,
+
+	/// Abort as soon as the state, and the changes made on top of it, consume approximately more
+	/// than this many MiB of memory.
+	#[arg(long, value_name = "MIB")]
+	pub max_memory: Option<usize>,
+
+	/// Retry each RPC request that fails because of the connection, e.g. because it dropped, up to
+	/// this many times.
+	///
+	/// Before each retry, try-runtime waits for an exponentially growing delay, starting at one
+	/// second, and reconnects. A scrape of the state resumes from the last fetched key.
+	#[arg(long, default_value = "0")]
+	pub rpc_retries: u32,
+
+	/// Ping the node every this many seconds over WebSocket connections, which keeps them alive
+	/// over links that drop idle connections, and detects dropped connections.
+	#[arg(long, value_name = "SECS")]
+	pub rpc_ping_interval: Option<u64>,
+
+	/// The timeout of connecting to the node, in seconds.
+	#[arg(long, value_name = "SECS", default_value = "60")]
+	pub rpc_connection_timeout: u64,
+
+	/// The timeout of each RPC request, in seconds.
+	///
+	/// A request that times out is retried as per [`Self::rpc_retries`].
+	#[arg(long, value_name = "SECS", default_value = "600")]
+	pub rpc_request_timeout: u64,
+
+	/// The maximum size of an RPC message, in MiB. Defaults to the maximum that the clients
+	/// support, 4 GiB.
+	#[arg(long, value_name = "MIB")]
+	pub rpc_max_message_size: Option<u32>,
+
+	/// The directory in which the runtime code, headers and blocks downloaded from a node are
+	/// cached, keyed by block hash. Defaults to `try-runtime-cache` in the temporary directory of
+	/// the system.
+	///
+	/// This makes repeated runs against the same block faster, and lets them fetch the header or
+	/// block from the cache without a node, e.g. for `offchain-worker` and `execute-block` on top
+	/// of a snapshot. The code is only cached for live states that are pinned with `--at`, and
+	/// are not stored as a snapshot.
+	#[arg(long, value_name = "PATH")]
+	pub cache_dir: Option<PathBuf>,
+
+	/// Neither read from nor write to the cache, see [`Self::cache_dir`].
+	#[arg(long)]
+	pub no_cache: bool,
+
+	/// Assert that a storage value satisfies a comparison once the command has executed, e.g.
+	/// `System::Number == 12345`.
+	///
+	/// Storage values are resolved through the metadata, and compared to a number, `true` or
+	/// `false`, or SCALE encoded hex (with `==` and `!=` only). Can be given multiple times. Only
+	/// `on-runtime-upgrade`, `execute-block`, `offchain-worker` and `fast-forward` evaluate
+	/// assertions, the other commands fail if given any.
+	#[arg(long = "assert", value_name = "EXPR", value_parser = assertions::parse_assertion)]
+	pub assertions: Vec<assertions::Assertion>,
+
+	/// Assert that a storage value exists once the command has executed, e.g.
+	/// `NominationPools::MaxPools`.
+	///
+	/// Can be given multiple times, see also [`Self::assertions`].
+	#[arg(long, value_name = "PALLET::ITEM", value_parser = assertions::parse_storage_item)]
+	pub assert_exists: Vec<assertions::StorageItem>,
+
+	/// Check that `Balances::TotalIssuance` is conserved, i.e. that it is the same before and
+	/// after executing the migrations or the block.
+	///
+	/// This catches accidental minting or burning, and fails the command unless
+	/// [`Self::allow_issuance_change`]. Only `on-runtime-upgrade` and `execute-block` check
+	/// conservation, the other commands fail if given this or [`Self::conserve`].
+	#[arg(long)]
+	pub check_issuance: bool,
+
+	/// Check that another numeric storage value is conserved as well, see
+	/// [`Self::check_issuance`]. Can be given multiple times.
+	#[arg(long, value_name = "PALLET::ITEM", value_parser = assertions::parse_storage_item)]
+	pub conserve: Vec<assertions::StorageItem>,
+
+	/// The absolute amount by which a conserved value may change, see [`Self::check_issuance`].
+	#[arg(long, value_name = "AMOUNT", default_value = "0")]
+	pub conservation_tolerance: u128,
+
+	/// Only warn about conserved values that changed beyond the tolerance, see
+	/// [`Self::check_issuance`].
+	#[arg(long)]
+	pub allow_issuance_change: bool,
+
+	/// Print every storage key written by the command whose value changed, along with the
+	/// pallet and item it belongs to, and its old and new value.
+	///
+	/// Values are decoded as per the metadata of the runtime where possible, and shown in hex
+	/// otherwise. Only `on-runtime-upgrade` and `offchain-worker` print a diff, the other
+	/// commands fail if given this.
+	#[arg(long)]
+	pub print_storage_diff: bool,
+
+	/// Write the number of storage accesses of each storage entry to this file, as folded stacks
+	/// keyed by `Pallet;Item`.
+	///
+	/// The file can be rendered as a flamegraph, e.g. with `inferno-flamegraph`, to find the hot
+	/// storage paths of a migration or an offchain worker. This executes the command's runtime
+	/// call once more. Only `on-runtime-upgrade` and `offchain-worker` write a trace, the other
+	/// commands fail if given this.
+	#[arg(long, value_name = "PATH")]
+	pub storage_trace: Option<PathBuf>,
+
+	/// Record the storage proof of the runtime call of the command, and report its size: plain,
+	/// compact, and the zstd-compressed compact size that bounds the proof of validity of a
+	/// parachain block.
+	///
+	/// `offchain-worker` and `execute-block` with `--from` only record a proof with this flag.
+	/// The other commands always record one, and only print its size as debug logs otherwise.
+	#[arg(long)]
+	pub record_proof: bool,
+
+	/// Report this `spec_version` to the runtime when it reads the version of a wasm blob, e.g.
+	/// `frame_system` when checking a code upgrade.
+	///
+	/// Allows testing logic that is gated on version comparisons without building another wasm.
+	#[arg(long, value_name = "VERSION")]
+	pub mock_spec_version: Option<u32>,
+
+	/// Same as [`Self::mock_spec_version`], for the `impl_version`.
+	#[arg(long, value_name = "VERSION")]
+	pub mock_impl_version: Option<u32>,
+
+	/// Same as [`Self::mock_spec_version`], for the `transaction_version`.
+	#[arg(long, value_name = "VERSION")]
+	pub mock_transaction_version: Option<u32>,
+}
+
+impl SharedParams {
+	/// [`Self::max_memory`], in bytes.
+	pub(crate) fn max_memory_bytes(&self) -> Option<usize> {
+		self.max_memory.map(|mib| mib.saturating_mul(1024 * 1024))
+	}
+
+	/// The settings of all RPC clients, as per [`Self::rpc_retries`] and the other `rpc_` flags.
+	pub(crate) fn rpc_config(&self) -> ClientConfig {
+		ClientConfig {
+			ping_interval: self.rpc_ping_interval.map(Duration::from_secs),
+			connection_timeout: Duration::from_secs(self.rpc_connection_timeout),
+			request_timeout: Duration::from_secs(self.rpc_request_timeout),
+			max_message_size: self
+				.rpc_max_message_size
+				.map_or(u32::MAX, |mib| mib.saturating_mul(1024 * 1024)),
+			retries: self.rpc_retries,
+		}
+	}
 }
 
 /// Our `try-runtime` command.
@@ -451,6 +850,9 @@ pub enum State {
 	/// Use a state snapshot as the source of runtime state.
 	///
 	/// This can be crated by passing a value to [`State::Live::snapshot_path`].
+	///
+	/// The header of the snapshot is validated on load: its checksum must match, and it must have
+	/// been created with the same `--state-version`.
 	Snap {
 		#[arg(short, long)]
 		snapshot_path: PathBuf,
@@ -458,7 +860,7 @@ pub enum State {
 
 	/// Use a live chain as the source of runtime state.
 	Live {
-		/// The url to connect to.
+		/// The url to connect to, either over WS(S) or HTTP(S).
 		#[arg(
 			short,
 			long,
@@ -466,14 +868,15 @@ pub enum State {
 		)]
 		uri: String,
 
-		/// The block hash at which to fetch the state.
+		/// The block hash or number at which to fetch the state.
 		///
 		/// If non provided, then the latest finalized head is used. This is particularly useful
-		/// for [`Command::OnRuntimeUpgrade`].
+		/// for [`Command::OnRuntimeUpgrade`]. A block number is resolved to the hash of the block
+		/// of the canonical chain.
 		#[arg(
 			short,
 			long,
-			value_parser = parse::hash,
+			value_parser = parse::hash_or_number,
 		)]
 		at: Option<String>,
 
@@ -481,11 +884,30 @@ pub enum State {
 		#[arg(short, long)]
 		snapshot_path: Option<PathBuf>,
 
+		/// An existing state snapshot file to bring up to date with `--at`, instead of scraping
+		/// the entire state. May be the same as `--snapshot-path`.
+		///
+		/// It must be a snapshot of an ancestor of `--at`, scraped with the same pallets and keys.
+		/// Then, only the values that changed since are fetched.
+		#[arg(long)]
+		update_snapshot: Option<PathBuf>,
+
 		/// A pallet to scrape. Can be provided multiple times. If empty, entire chain state will
 		/// be scraped.
+		///
+		/// `*` stands for all the pallets in the metadata of the chain at the block being
+		/// scraped.
 		#[arg(short, long, num_args = 1..)]
 		pallet: Vec<String>,
 
+		/// A pallet not to scrape. Can be provided multiple times.
+		///
+		/// The pallets are then resolved from the metadata of the chain at the block being
+		/// scraped, and all of them but the excluded ones are scraped (or those of `--pallet`
+		/// only, if given). Unknown pallet names are an error.
+		#[arg(long)]
+		exclude_pallet: Vec<String>,
+
 		/// Fetch the child-keys as well.
 		///
 		/// Default is `false`, if specific `--pallets` are specified, `true` otherwise. In other
@@ -493,6 +915,31 @@ pub enum State {
 		/// Otherwise, it must be enabled explicitly using this flag.
 		#[arg(long)]
 		child_tree: bool,
+
+		/// The hex encoded, unprefixed storage key of a default child tree to scrape as well,
+		/// regardless of `--pallet`, e.g. the one of a crowdloan fund. Can be provided multiple
+		/// times.
+		///
+		/// Useful for runtimes that read child storage, without scraping all child trees with
+		/// [`State::Live::child_tree`].
+		#[arg(long, value_parser = parse::hex_bytes)]
+		child_tree_key: Vec<String>,
+
+		/// A hashed storage key to scrape as well, regardless of `--pallet`. Can be provided
+		/// multiple times.
+		///
+		/// Useful for well-known keys such as `:heappages`, or keys that belong to no pallet.
+		#[arg(long, value_parser = parse::hex_bytes)]
+		include_key: Vec<String>,
+
+		/// A hashed storage prefix to scrape as well, regardless of `--pallet`. Can be provided
+		/// multiple times.
+		#[arg(long, value_parser = parse::hex_bytes)]
+		include_prefix: Vec<String>,
+
+		/// The number of connections to the node over which the state is scraped concurrently.
+		#[arg(long, default_value = "1")]
+		threads: NonZeroUsize,
 	},
 }
 
@@ -508,7 +955,19 @@ impl State {
 				Builder::<Block>::new().mode(Mode::Offline(OfflineConfig {
 					state_snapshot: SnapshotConfig::new(snapshot_path),
 				})),
-			State::Live { snapshot_path, pallet, uri, at, child_tree } => {
+			State::Live {
+				snapshot_path,
+				update_snapshot,
+				pallet,
+				exclude_pallet,
+				uri,
+				at,
+				child_tree,
+				child_tree_key,
+				include_key,
+				include_prefix,
+				threads,
+			} => {
 				let at = match at {
 					Some(at_str) => Some(hash_of::<Block>(at_str)?),
 					None => None,
@@ -517,8 +976,11 @@ impl State {
 					.mode(Mode::Online(OnlineConfig {
 						transport: uri.to_owned().into(),
 						state_snapshot: snapshot_path.as_ref().map(SnapshotConfig::new),
+						update_snapshot: update_snapshot.as_ref().map(SnapshotConfig::new),
 						pallets: pallet.clone(),
+						exclude_pallets: exclude_pallet.clone(),
 						scrape_children: true,
+						threads: *threads,
 						at,
 					}))
 					.inject_hashed_key(
@@ -527,11 +989,38 @@ impl State {
 				if *child_tree {
 					builder = builder.inject_default_child_tree_prefix();
 				}
+				// these have already been validated while parsing.
+				let decode =
+					|hex: &String| from_hex(hex).map_err(|e| format!("invalid hex {}: {}", hex, e));
+				for key in include_key {
+					builder = builder.inject_hashed_key(&decode(key)?);
+				}
+				for prefix in include_prefix {
+					builder = builder.inject_hashed_prefix(&decode(prefix)?);
+				}
+				for key in child_tree_key {
+					builder = builder.inject_child_tree(&decode(key)?);
+				}
 				builder
 			},
 		})
 	}
 
+	/// Resolve [`State::Live::at`] to a block hash, if it is a block number.
+	pub(crate) async fn resolve_at<Block: BlockT>(
+		&mut self,
+		rpc_config: ClientConfig,
+	) -> sc_cli::Result<()>
+	where
+		Block::Hash: FromStr + DeserializeOwned,
+		<Block::Hash as FromStr>::Err: Debug,
+	{
+		if let State::Live { uri, at: Some(at), .. } = self {
+			*at = resolve_block_number::<Block>(uri, rpc_config, at).await?;
+		}
+		Ok(())
+	}
+
 	/// Get the uri, if self is `Live`.
 	pub(crate) fn live_uri(&self) -> Option<String> {
 		match self {
@@ -541,6 +1030,35 @@ impl State {
 	}
 }
 
+impl Command {
+	/// Resolve the blocks of `self` that are given by number to their hashes, see
+	/// [`parse::hash_or_number`].
+	async fn resolve_block_numbers<Block: BlockT>(
+		&mut self,
+		rpc_config: ClientConfig,
+	) -> sc_cli::Result<()>
+	where
+		Block::Hash: FromStr + DeserializeOwned,
+		<Block::Hash as FromStr>::Err: Debug,
+	{
+		let state = match self {
+			Command::OnRuntimeUpgrade(cmd) => &mut cmd.state,
+			Command::ExecuteBlock(cmd) => &mut cmd.state,
+			Command::OffchainWorker(cmd) =>
+				return cmd.resolve_block_numbers::<Block>(rpc_config).await,
+			Command::MeasureCall(cmd) => &mut cmd.state,
+			Command::Scenario(cmd) => &mut cmd.state,
+			Command::FastForward(cmd) => &mut cmd.state,
+			Command::CheckStorageVersions(cmd) => &mut cmd.state,
+			Command::CheckState(cmd) => &mut cmd.state,
+			// `create-snapshot` resolves its `--at` along with the finalized head.
+			Command::FollowChain(_) | Command::CreateSnapshot(_) | Command::ListCommands(_) =>
+				return Ok(()),
+		};
+		state.resolve_at::<Block>(rpc_config).await
+	}
+}
+
 impl TryRuntimeCmd {
 	pub async fn run<Block, ExecDispatch>(&self, config: Configuration) -> sc_cli::Result<()>
 	where
@@ -552,35 +1070,154 @@ impl TryRuntimeCmd {
 		<NumberFor<Block> as FromStr>::Err: Debug,
 		ExecDispatch: NativeExecutionDispatch + 'static,
 	{
-		match &self.command {
-			Command::OnRuntimeUpgrade(ref cmd) =>
-				commands::on_runtime_upgrade::on_runtime_upgrade::<Block, ExecDispatch>(
-					self.shared.clone(),
-					cmd.clone(),
-					config,
-				)
-				.await,
-			Command::OffchainWorker(cmd) =>
-				commands::offchain_worker::offchain_worker::<Block, ExecDispatch>(
-					self.shared.clone(),
-					cmd.clone(),
-					config,
-				)
-				.await,
-			Command::ExecuteBlock(cmd) =>
-				commands::execute_block::execute_block::<Block, ExecDispatch>(
-					self.shared.clone(),
-					cmd.clone(),
-					config,
-				)
-				.await,
-			Command::FollowChain(cmd) =>
-				commands::follow_chain::follow_chain::<Block, ExecDispatch>(
-					self.shared.clone(),
-					cmd.clone(),
-					config,
-				)
-				.await,
+		let provenance = output::Provenance::new(&self.shared, &config);
+		let mut summary = output::Summary::new(self.command.name(), provenance);
+		let mut command = self.command.clone();
+		let prepared = match command.ensure_supported(&self.shared) {
+			Ok(()) => command.resolve_block_numbers::<Block>(self.shared.rpc_config()).await,
+			Err(e) => Err(e),
+		};
+		let result = match prepared {
+			Err(e) => Err(e),
+			Ok(()) => match &command {
+				Command::OnRuntimeUpgrade(ref cmd) =>
+					commands::on_runtime_upgrade::on_runtime_upgrade::<Block, ExecDispatch>(
+						self.shared.clone(),
+						cmd.clone(),
+						config,
+						&mut summary,
+					)
+					.await,
+				Command::OffchainWorker(cmd) =>
+					commands::offchain_worker::offchain_worker::<Block, ExecDispatch>(
+						self.shared.clone(),
+						cmd.clone(),
+						config,
+						&mut summary,
+					)
+					.await,
+				Command::ExecuteBlock(cmd) =>
+					commands::execute_block::execute_block::<Block, ExecDispatch>(
+						self.shared.clone(),
+						cmd.clone(),
+						config,
+						&mut summary,
+					)
+					.await,
+				Command::FollowChain(cmd) =>
+					commands::follow_chain::follow_chain::<Block, ExecDispatch>(
+						self.shared.clone(),
+						cmd.clone(),
+						config,
+					)
+					.await,
+				Command::MeasureCall(cmd) =>
+					commands::measure_call::measure_call::<Block, ExecDispatch>(
+						self.shared.clone(),
+						cmd.clone(),
+						config,
+					)
+					.await,
+				Command::Scenario(cmd) =>
+					commands::scenario::scenario::<Block, ExecDispatch>(
+						self.shared.clone(),
+						cmd.clone(),
+						config,
+					)
+					.await,
+				Command::CreateSnapshot(cmd) =>
+					commands::create_snapshot::create_snapshot::<Block>(
+						self.shared.clone(),
+						cmd.clone(),
+					)
+					.await,
+				Command::FastForward(cmd) =>
+					commands::fast_forward::fast_forward::<Block, ExecDispatch>(
+						self.shared.clone(),
+						cmd.clone(),
+						config,
+					)
+					.await,
+				Command::CheckStorageVersions(cmd) =>
+					commands::check_storage_versions::check_storage_versions::<Block, ExecDispatch>(
+						self.shared.clone(),
+						cmd.clone(),
+						config,
+					)
+					.await,
+				Command::CheckState(cmd) =>
+					commands::check_state::check_state::<Block, ExecDispatch>(
+						self.shared.clone(),
+						cmd.clone(),
+						config,
+					)
+					.await,
+				Command::ListCommands(cmd) => commands::list_commands::list_commands(cmd.clone()),
+			},
+		};
+
+		match self.shared.output {
+			output::OutputFormat::Text => result,
+			output::OutputFormat::Json => summary.print(&result).and(result),
+		}
+	}
+}
+
+impl Command {
+	/// The name of the command, as given on the command line.
+	fn name(&self) -> &'static str {
+		match self {
+			Command::OnRuntimeUpgrade(_) => "on-runtime-upgrade",
+			Command::ExecuteBlock(_) => "execute-block",
+			Command::OffchainWorker(_) => "offchain-worker",
+			Command::FollowChain(_) => "follow-chain",
+			Command::MeasureCall(_) => "measure-call",
+			Command::Scenario(_) => "scenario",
+			Command::CreateSnapshot(_) => "create-snapshot",
+			Command::FastForward(_) => "fast-forward",
+			Command::ListCommands(_) => "list-commands",
+			Command::CheckStorageVersions(_) => "check-storage-versions",
+			Command::CheckState(_) => "check-state",
+		}
+	}
+
+	/// Fail if `shared` sets a flag that `self` does not act upon, rather than silently ignoring
+	/// it.
+	fn ensure_supported(&self, shared: &SharedParams) -> sc_cli::Result<()> {
+		// whether the command evaluates assertions, checks conservation, and prints storage diffs
+		// and traces.
+		let (assertions, conservation, storage_diff) = match self {
+			Command::OnRuntimeUpgrade(_) => (true, true, true),
+			Command::ExecuteBlock(_) => (true, true, false),
+			Command::OffchainWorker(_) => (true, false, true),
+			Command::FastForward(_) => (true, false, false),
+			_ => (false, false, false),
+		};
+
+		let mut unsupported = Vec::new();
+		if !assertions && !shared.assertions.is_empty() {
+			unsupported.push("--assert");
+		}
+		if !assertions && !shared.assert_exists.is_empty() {
+			unsupported.push("--assert-exists");
+		}
+		if !conservation && shared.check_issuance {
+			unsupported.push("--check-issuance");
+		}
+		if !conservation && !shared.conserve.is_empty() {
+			unsupported.push("--conserve");
+		}
+		if !storage_diff && shared.print_storage_diff {
+			unsupported.push("--print-storage-diff");
+		}
+		if !storage_diff && shared.storage_trace.is_some() {
+			unsupported.push("--storage-trace");
+		}
+
+		if unsupported.is_empty() {
+			Ok(())
+		} else {
+			Err(format!("{} does not support {}", self.name(), unsupported.join(", ")).into())
 		}
 	}
 }
@@ -614,6 +1251,31 @@ pub(crate) fn extract_code(spec: &Box<dyn ChainSpec>) -> sc_cli::Result<(Storage
 	Ok((code_key, code))
 }
 
+/// Read `:code` from the wasm blob at `path` and return it as `StorageData` along with the
+/// corresponding `StorageKey`.
+///
+/// The blob is validated by reading its runtime version.
+pub(crate) fn extract_code_from_path<D: NativeExecutionDispatch + 'static>(
+	path: &Path,
+	executor: &NativeElseWasmExecutor<D>,
+) -> sc_cli::Result<(StorageKey, StorageData)> {
+	let code = std::fs::read(path).map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+	let encoded = executor
+		.read_runtime_version(&code, &mut BasicExternalities::default())
+		.map_err(|e| format!("{:?} is not a valid runtime: {}", path, e))?;
+	let version = <RuntimeVersion as Decode>::decode(&mut &*encoded)
+		.map_err(|e| format!("failed to decode the runtime version of {:?}: {}", path, e))?;
+	log::info!(
+		target: LOG_TARGET,
+		"loaded runtime {} with spec version {} from {:?}",
+		version.spec_name,
+		version.spec_version,
+		path,
+	);
+
+	Ok((StorageKey(well_known_keys::CODE.to_vec()), StorageData(code)))
+}
+
 /// Get the hash type of the generic `Block` from a `hash_str`.
 pub(crate) fn hash_of<Block: BlockT>(hash_str: &str) -> sc_cli::Result<Block::Hash>
 where
@@ -625,73 +1287,146 @@ where
 		.map_err(|e| format!("Could not parse block hash: {:?}", e).into())
 }
 
-/// Check the spec_name of an `ext`
+/// Resolve `block`, a block hash or number as accepted by [`parse::hash_or_number`], to a block
+/// hash in hex, through the node at `uri` if it is a number.
+pub(crate) async fn resolve_block_number<Block: BlockT>(
+	uri: &str,
+	rpc_config: ClientConfig,
+	block: &str,
+) -> sc_cli::Result<String>
+where
+	Block::Hash: FromStr + DeserializeOwned,
+	<Block::Hash as FromStr>::Err: Debug,
+{
+	let number = match parse::block_number(block) {
+		Some(number) => number,
+		None => return Ok(block.to_owned()),
+	};
+	let hash = commands::execute_block::hash_of_ref::<Block>(
+		uri,
+		rpc_config,
+		&commands::execute_block::BlockRef::Number(number),
+	)
+	.await?;
+	log::info!(target: LOG_TARGET, "resolved block #{} to {:?}", number, hash);
+	Ok(to_hex(hash.as_ref(), false))
+}
+
+/// A table of the fields of the `remote` and the `local` runtime version, for the logs.
+fn version_table(remote: &RuntimeVersion, local: &RuntimeVersion) -> String {
+	let rows = [
+		("spec_name", remote.spec_name.to_string(), local.spec_name.to_string()),
+		("impl_name", remote.impl_name.to_string(), local.impl_name.to_string()),
+		("spec_version", remote.spec_version.to_string(), local.spec_version.to_string()),
+		("impl_version", remote.impl_version.to_string(), local.impl_version.to_string()),
+		(
+			"transaction_version",
+			remote.transaction_version.to_string(),
+			local.transaction_version.to_string(),
+		),
+		("state_version", remote.state_version.to_string(), local.state_version.to_string()),
+	];
+	let mut table = format!("{:<20} {:<24} {:<24}\n", "", "remote", "local");
+	for (field, remote, local) in rows {
+		let marker = if remote == local { "" } else { "  <-" };
+		table.push_str(&format!("{:<20} {:<24} {:<24}{}\n", field, remote, local, marker));
+	}
+	table
+}
+
+/// Compare the `local` runtime version, i.e. of the code that is executed, against the one of
+/// the remote node at `uri`.
 ///
-/// If the spec names don't match, if `relaxed`, then it emits a warning, else it panics.
-/// If the spec versions don't match, it only ever emits a warning.
+/// A mismatching spec name only emits a warning. A local spec version lower than the remote one
+/// is an error, unless [`SharedParams::force`] or [`SharedParams::no_spec_check_panic`], for the
+/// local code would then not be accepted as an upgrade of the chain. Failing to fetch the remote
+/// version is an error, unless [`SharedParams::no_spec_check_panic`].
+///
+/// Returns the spec of the remote runtime, if it could be fetched.
 pub(crate) async fn ensure_matching_spec<Block: BlockT + DeserializeOwned>(
 	uri: String,
-	expected_spec_name: String,
-	expected_spec_version: u32,
-	relaxed: bool,
-) {
-	let rpc = ws_client(&uri).await.unwrap();
-	match StateApi::<Block::Hash>::runtime_version(&rpc, None)
-		.await
-		.map(|version| (String::from(version.spec_name.clone()), version.spec_version))
-		.map(|(spec_name, spec_version)| (spec_name.to_lowercase(), spec_version))
-	{
-		Ok((name, version)) => {
-			// first, deal with spec name
-			if expected_spec_name.to_lowercase() == name {
-				log::info!(target: LOG_TARGET, "found matching spec name: {:?}", name);
-			} else {
-				let msg = format!(
-					"version mismatch: remote spec name: '{}', expected (local chain spec, aka. `--chain`): '{}'",
-					name,
-					expected_spec_name
-				);
-				if relaxed {
-					log::warn!(target: LOG_TARGET, "{}", msg);
-				} else {
-					panic!("{}", msg);
-				}
-			}
-
-			if expected_spec_version == version {
-				log::info!(target: LOG_TARGET, "found matching spec version: {:?}", version);
-			} else {
-				let msg = format!(
-					"spec version mismatch (local {} != remote {}). This could cause some issues.",
-					expected_spec_version, version
-				);
-				if relaxed {
-					log::warn!(target: LOG_TARGET, "{}", msg);
-				} else {
-					panic!("{}", msg);
-				}
+	local: &RuntimeVersion,
+	shared: &SharedParams,
+) -> sc_cli::Result<Option<output::Spec>> {
+	let remote = match rpc_client_with(&uri, shared.rpc_config()).await {
+		Ok(rpc) => StateApi::<Block::Hash>::runtime_version(&rpc, None)
+			.await
+			.map_err(|e| format!("{:?}", e)),
+		Err(e) => Err(e),
+	};
+	let remote = match remote {
+		Ok(remote) => remote,
+		Err(why) => {
+			let msg = format!("failed to fetch runtime version from {}: {}", uri, why);
+			if shared.no_spec_check_panic {
+				log::error!(target: LOG_TARGET, "{}. Skipping the check", msg);
+				return Ok(None)
 			}
+			return Err(msg.into())
 		},
-		Err(why) => {
+	};
+
+	log::info!(target: LOG_TARGET, "runtime versions:\n{}", version_table(&remote, local));
+
+	if remote.spec_name.to_lowercase() == local.spec_name.to_lowercase() {
+		log::info!(target: LOG_TARGET, "found matching spec name: {:?}", local.spec_name);
+	} else {
+		log::warn!(
+			target: LOG_TARGET,
+			"spec name mismatch: remote '{}', local (`--chain` or the overriding code) '{}'",
+			remote.spec_name,
+			local.spec_name,
+		);
+	}
+
+	match local.spec_version.cmp(&remote.spec_version) {
+		Ordering::Equal =>
+			log::info!(target: LOG_TARGET, "found matching spec version: {}", local.spec_version),
+		Ordering::Greater => log::info!(
+			target: LOG_TARGET,
+			"local spec version {} is an upgrade of the remote {}",
+			local.spec_version,
+			remote.spec_version,
+		),
+		Ordering::Less => {
 			let msg = format!(
-				"failed to fetch runtime version from {}: {:?}. Skipping the check",
-				uri, why
+				"the local spec version {} is lower than the remote {}, the chain would reject \
+				such code as an upgrade",
+				local.spec_version, remote.spec_version,
 			);
-			if relaxed {
-				log::error!(target: LOG_TARGET, "{}", msg);
+			if shared.force || shared.no_spec_check_panic {
+				log::warn!(target: LOG_TARGET, "{}", msg);
 			} else {
-				panic!("{}", msg);
+				return Err(format!("{}. Use `--force` to run anyway", msg).into())
 			}
 		},
 	}
+
+	Ok(Some(output::Spec { name: remote.spec_name.to_string(), version: remote.spec_version }))
 }
 
 /// Build all extensions that we typically use.
 pub(crate) fn full_extensions() -> Extensions {
+	extensions_with_offchain_timestamp(Default::default())
+}
+
+/// Same as [`full_extensions`], but the offchain worker extension reports `timestamp` as the
+/// current time.
+pub(crate) fn extensions_with_offchain_timestamp(timestamp: Timestamp) -> Extensions {
+	let (offchain, offchain_state) = TestOffchainExt::new();
+	offchain_state.write().timestamp = timestamp;
+	let (pool, _pool_state) = TestTransactionPoolExt::new();
+	extensions_with_offchain(offchain, pool)
+}
+
+/// Build all extensions that we typically use, with `offchain` as the offchain externalities and
+/// `pool` as the transaction pool.
+pub(crate) fn extensions_with_offchain<O>(offchain: O, pool: TestTransactionPoolExt) -> Extensions
+where
+	O: Externalities + DbExternalities + Clone + 'static,
+{
 	let mut extensions = Extensions::default();
 	extensions.register(TaskExecutorExt::new(TaskExecutor::new()));
-	let (offchain, _offchain_state) = TestOffchainExt::new();
-	let (pool, _pool_state) = TestTransactionPoolExt::new();
 	extensions.register(OffchainDbExt::new(offchain.clone()));
 	extensions.register(OffchainWorkerExt::new(offchain));
 	extensions.register(KeystoreExt(std::sync::Arc::new(KeyStore::new())));
@@ -745,21 +1480,18 @@ pub(crate) fn state_machine_call<Block: BlockT, D: NativeExecutionDispatch + 'st
 	Ok((changes, encoded_results))
 }
 
-/// Same as [`state_machine_call`], but it also computes and prints the storage proof in different
-/// size and formats.
-///
-/// Make sure [`LOG_TARGET`] is enabled in logging.
-pub(crate) fn state_machine_call_with_proof<Block: BlockT, D: NativeExecutionDispatch + 'static>(
+/// Same as [`state_machine_call`], but it also records and returns the storage proof of the call.
+pub(crate) fn state_machine_call_recording_proof<
+	Block: BlockT,
+	D: NativeExecutionDispatch + 'static,
+>(
 	ext: &TestExternalities,
 	executor: &NativeElseWasmExecutor<D>,
 	execution: sc_cli::ExecutionStrategy,
 	method: &'static str,
 	data: &[u8],
 	extensions: Extensions,
-) -> sc_cli::Result<(OverlayedChanges, Vec<u8>)> {
-	use parity_scale_codec::Encode;
-	use sp_core::hexdisplay::HexDisplay;
-
+) -> sc_cli::Result<(OverlayedChanges, Vec<u8>, StorageProof)> {
 	let mut changes = Default::default();
 	let backend = ext.backend.clone();
 	let runtime_code_backend = sp_state_machine::backend::BackendRuntimeCode::new(&backend);
@@ -769,8 +1501,6 @@ pub(crate) fn state_machine_call_with_proof<Block: BlockT, D: NativeExecutionDis
 
 	let runtime_code = runtime_code_backend.runtime_code()?;
 
-	let pre_root = *backend.root();
-
 	let encoded_results = StateMachine::new(
 		&proving_backend,
 		&mut changes,
@@ -788,6 +1518,45 @@ pub(crate) fn state_machine_call_with_proof<Block: BlockT, D: NativeExecutionDis
 	let proof = proving_backend
 		.extract_proof()
 		.expect("A recorder was set and thus, a storage proof can be extracted; qed");
+
+	Ok((changes, encoded_results, proof))
+}
+
+/// Same as [`state_machine_call`], but it also computes and prints the storage proof in different
+/// size and formats, see [`log_proof_size`].
+///
+/// Make sure [`LOG_TARGET`] is enabled in logging.
+pub(crate) fn state_machine_call_with_proof<Block: BlockT, D: NativeExecutionDispatch + 'static>(
+	ext: &TestExternalities,
+	executor: &NativeElseWasmExecutor<D>,
+	execution: sc_cli::ExecutionStrategy,
+	method: &'static str,
+	data: &[u8],
+	extensions: Extensions,
+	report: bool,
+) -> sc_cli::Result<(OverlayedChanges, Vec<u8>)> {
+	let (changes, encoded_results, proof) = state_machine_call_recording_proof::<Block, D>(
+		ext, executor, execution, method, data, extensions,
+	)?;
+	log_proof_size(ext, method, proof, report)?;
+	Ok((changes, encoded_results))
+}
+
+/// Print the size of `proof`, the storage proof of `method` on top of `ext`, in different
+/// formats, and return its plain encoded size.
+///
+/// The sizes are debug logs, unless `report`, as per [`SharedParams::record_proof`].
+///
+/// Make sure [`LOG_TARGET`] is enabled in logging.
+pub(crate) fn log_proof_size(
+	ext: &TestExternalities,
+	method: &'static str,
+	proof: StorageProof,
+	report: bool,
+) -> sc_cli::Result<usize> {
+	use parity_scale_codec::Encode;
+
+	let pre_root = *ext.backend.root();
 	let proof_size = proof.encoded_size();
 	let compact_proof = proof
 		.clone()
@@ -825,14 +1594,334 @@ pub(crate) fn state_machine_call_with_proof<Block: BlockT, D: NativeExecutionDis
 		"zstd-compressed compact proof {}",
 		humanize(compressed_proof.len()),
 	);
-	Ok((changes, encoded_results))
+	if report {
+		log::info!(
+			target: LOG_TARGET,
+			"storage proof of {}: {} plain, {} compact, {} zstd-compressed compact",
+			method,
+			humanize(proof_size),
+			humanize(compact_proof_size),
+			humanize(compressed_proof.len()),
+		);
+	}
+	Ok(proof_size)
 }
 
-/// Get the spec `(name, version)` from the local runtime.
-pub(crate) fn local_spec<Block: BlockT, D: NativeExecutionDispatch + 'static>(
+/// Store a snapshot of the state, as requested by [`SharedParams::store_snapshot_on_success`] and
+/// [`SharedParams::store_snapshot_on_failure`], depending on the `result` of a runtime call that
+/// was executed on top of `ext`.
+///
+/// On success, the snapshot contains `ext` with the changes of the call applied. On failure, it
+/// contains `ext` as-is. Failing to store the snapshot is only logged, such that it never masks
+/// the outcome of the call itself.
+pub(crate) fn maybe_store_snapshot<T>(
+	shared: &SharedParams,
+	ext: &TestExternalities,
+	result: &sc_cli::Result<(OverlayedChanges, T)>,
+) {
+	let (on_success, on_failure) =
+		(&shared.store_snapshot_on_success, &shared.store_snapshot_on_failure);
+	let stored = match (result, on_success, on_failure) {
+		(Ok((changes, _)), Some(path), _) => {
+			let top = changes.changes().map(|(k, v)| (k.clone(), v.value().cloned())).collect();
+			let mut transaction = vec![(None, top)];
+			for (child_changes, child_info) in changes.children() {
+				transaction.push((
+					Some(child_info.clone()),
+					child_changes.map(|(k, v)| (k.clone(), v.value().cloned())).collect(),
+				));
+			}
+			let post_state = ext.backend.update(transaction, shared.state_version);
+			remote_externalities::save_snapshot(&post_state, path, shared.state_version)
+				.map(|_| path)
+		},
+		(Err(_), _, Some(path)) =>
+			remote_externalities::save_snapshot(&ext.backend, path, shared.state_version)
+				.map(|_| path),
+		_ => return,
+	};
+
+	match stored {
+		Ok(path) => log::info!(target: LOG_TARGET, "stored state snapshot at {:?}", path),
+		Err(why) => log::error!(target: LOG_TARGET, "failed to store state snapshot: {}", why),
+	}
+}
+
+/// The approximate memory, in bytes, consumed by the (top) state of `ext`.
+pub(crate) fn state_size(ext: &TestExternalities) -> usize {
+	let mut size = 0;
+	ext.backend.for_key_values_with_prefix(&[], |k, v| size += k.len() + v.len());
+	size
+}
+
+/// The approximate memory, in bytes, consumed by `changes`.
+pub(crate) fn changes_size(changes: &OverlayedChanges) -> usize {
+	let top = changes.changes().map(|(k, v)| k.len() + v.value().map_or(0, Vec::len));
+	let children = changes
+		.children()
+		.flat_map(|(c, _)| c.map(|(k, v)| k.len() + v.value().map_or(0, Vec::len)));
+	top.chain(children).sum()
+}
+
+/// Log the approximate memory consumed by a state of `state_size` bytes, and `changes_size` bytes
+/// of changes on top of it. Fails if this exceeds [`SharedParams::max_memory`].
+pub(crate) fn ensure_memory_limit(
+	shared: &SharedParams,
+	state_size: usize,
+	changes_size: usize,
+) -> sc_cli::Result<()> {
+	let as_mib = |bytes: usize| bytes as f64 / (1024f64 * 1024f64);
+	let total = state_size.saturating_add(changes_size);
+	log::info!(
+		target: LOG_TARGET,
+		"approximate memory usage: state = {:.2} MiB, changes = {:.2} MiB, total = {:.2} MiB",
+		as_mib(state_size),
+		as_mib(changes_size),
+		as_mib(total),
+	);
+
+	match shared.max_memory_bytes() {
+		Some(max) if total > max => Err(format!(
+			"approximate memory usage of {:.2} MiB exceeds --max-memory of {:.2} MiB",
+			as_mib(total),
+			as_mib(max),
+		)
+		.into()),
+		_ => Ok(()),
+	}
+}
+
+/// Get the name and the hashed storage prefix of all pallets that declare storage, as reported by
+/// the metadata of the runtime in `ext`.
+pub(crate) fn pallet_storage_prefixes<Block: BlockT, D: NativeExecutionDispatch + 'static>(
 	ext: &TestExternalities,
 	executor: &NativeElseWasmExecutor<D>,
-) -> (String, u32, sp_core::storage::StateVersion) {
+) -> sc_cli::Result<Vec<(String, [u8; 16])>> {
+	Ok(runtime_metadata::<Block, D>(ext, executor)?
+		.pallets
+		.into_iter()
+		.filter_map(|p| p.storage.map(|s| (p.name, twox_128(s.prefix.as_bytes()))))
+		.collect())
+}
+
+/// Get `BlockWeights::max_block` of the runtime of the chain at `uri`, as declared by the
+/// `System::BlockWeights` constant in its metadata.
+pub(crate) async fn remote_max_block_weight<Block: BlockT + DeserializeOwned>(
+	uri: &str,
+	rpc_config: ClientConfig,
+) -> sc_cli::Result<Weight> {
+	let rpc = rpc_client_with(uri, rpc_config).await?;
+	let opaque = StateApi::<Block::Hash>::metadata(&rpc, None)
+		.await
+		.map_err(|e| format!("failed to fetch the remote metadata: {:?}", e))?;
+	let metadata = match <RuntimeMetadataPrefixed as Decode>::decode(&mut &*opaque.0)
+		.map_err(|e| format!("failed to decode the remote metadata: {:?}", e))?
+		.1
+	{
+		RuntimeMetadata::V14(metadata) => metadata,
+		_ => return Err("unsupported remote metadata version, only V14 is supported".into()),
+	};
+
+	let block_weights = metadata
+		.pallets
+		.iter()
+		.find(|p| p.name == "System")
+		.and_then(|p| p.constants.iter().find(|c| c.name == "BlockWeights"))
+		.ok_or("no `System::BlockWeights` constant in the remote metadata")?;
+	// `base_block` and `max_block` are the first fields of `BlockWeights`.
+	let (_base_block, max_block) = <(Weight, Weight) as Decode>::decode(&mut &*block_weights.value)
+		.map_err(|e| format!("failed to decode the remote `System::BlockWeights`: {:?}", e))?;
+	Ok(max_block)
+}
+
+/// Get the metadata of the runtime in `ext`.
+pub(crate) fn runtime_metadata<Block: BlockT, D: NativeExecutionDispatch + 'static>(
+	ext: &TestExternalities,
+	executor: &NativeElseWasmExecutor<D>,
+) -> sc_cli::Result<RuntimeMetadataV14> {
+	let (_, encoded) = state_machine_call::<Block, D>(
+		ext,
+		executor,
+		sc_cli::ExecutionStrategy::NativeElseWasm,
+		"Metadata_metadata",
+		&[],
+		Default::default(),
+	)?;
+	let opaque = <Vec<u8> as Decode>::decode(&mut &*encoded)
+		.map_err(|e| format!("failed to decode metadata: {:?}", e))?;
+	let metadata = <RuntimeMetadataPrefixed as Decode>::decode(&mut &*opaque)
+		.map_err(|e| format!("failed to decode metadata: {:?}", e))?;
+
+	match metadata.1 {
+		RuntimeMetadata::V14(metadata) => Ok(metadata),
+		_ => Err("unsupported metadata version, only V14 is supported".into()),
+	}
+}
+
+/// Find all the top level keys written in `changes` that neither belong to one of the
+/// `known_prefixes`, nor are a well-known key (i.e. starting with `:`).
+///
+/// Such writes are almost always a bug (e.g. a misuse of `sp_io::storage::set`), and are otherwise
+/// invisible. Each of them is logged as a warning, and they are all returned.
+pub(crate) fn report_unknown_storage_writes(
+	changes: &OverlayedChanges,
+	known_prefixes: &[(String, [u8; 16])],
+) -> Vec<StorageKey> {
+	let unknown = changes
+		.changes()
+		.map(|(key, _)| key)
+		.filter(|key| !known_prefixes.iter().any(|(_, prefix)| key.starts_with(prefix)))
+		.filter(|key| !key.starts_with(b":"))
+		.map(|key| StorageKey(key.clone()))
+		.collect::<Vec<_>>();
+
+	for key in unknown.iter() {
+		log::warn!(
+			target: LOG_TARGET,
+			"storage key 0x{} was written, but it does not belong to any known pallet prefix",
+			HexDisplay::from(&key.0),
+		);
+	}
+	if unknown.is_empty() {
+		log::info!(target: LOG_TARGET, "no storage writes outside of known pallet prefixes found.");
+	} else {
+		log::warn!(
+			target: LOG_TARGET,
+			"found {} storage writes outside of known pallet prefixes",
+			unknown.len()
+		);
+	}
+
+	unknown
+}
+
+/// Compare all the top level keys written in `changes` against their real value in the state of
+/// the chain at block `at`.
+///
+/// Each key whose value diverges is logged as a warning, along with the pallet it belongs to, and
+/// they are all returned. Keys that only the real chain wrote are not detected.
+pub(crate) async fn report_divergence_from_chain<Block: BlockT>(
+	rpc: &RpcClient,
+	at: Block::Hash,
+	changes: &OverlayedChanges,
+	known_prefixes: &[(String, [u8; 16])],
+) -> sc_cli::Result<Vec<StorageKey>> {
+	// keep the individual rpc queries reasonably sized.
+	const BATCH_SIZE: usize = 1000;
+
+	let written = changes
+		.changes()
+		.map(|(key, value)| (StorageKey(key.clone()), value.value().cloned()))
+		.collect::<Vec<_>>();
+
+	let mut diverged = vec![];
+	for batch in written.chunks(BATCH_SIZE) {
+		let keys = batch.iter().map(|(key, _)| key.clone()).collect::<Vec<_>>();
+		let real = StateApi::<Block::Hash>::query_storage_at(rpc, keys, Some(at))
+			.await
+			.map_err(|e| format!("failed to query the state of the chain: {:?}", e))?
+			.into_iter()
+			.flat_map(|change_set| change_set.changes)
+			.map(|(key, value)| (key, value.map(|v| v.0)))
+			.collect::<std::collections::BTreeMap<_, _>>();
+
+		for (key, replayed) in batch {
+			let real = real.get(key).cloned().flatten();
+			if real == *replayed {
+				continue
+			}
+
+			let pallet = known_prefixes
+				.iter()
+				.find(|(_, prefix)| key.0.starts_with(prefix))
+				.map_or("<unknown>", |(name, _)| name.as_str());
+			log::warn!(
+				target: LOG_TARGET,
+				"storage key 0x{} of {} diverges from the chain: replayed {:?}, real {:?}",
+				HexDisplay::from(&key.0),
+				pallet,
+				replayed.as_ref().map(|v| HexDisplay::from(v).to_string()),
+				real.as_ref().map(|v| HexDisplay::from(v).to_string()),
+			);
+			diverged.push(key.clone());
+		}
+	}
+
+	if diverged.is_empty() {
+		log::info!(
+			target: LOG_TARGET,
+			"all {} written storage keys match the state of the chain.",
+			written.len()
+		);
+	} else {
+		log::warn!(
+			target: LOG_TARGET,
+			"{} out of {} written storage keys diverge from the state of the chain",
+			diverged.len(),
+			written.len()
+		);
+	}
+
+	Ok(diverged)
+}
+
+/// The 99th percentile of `sorted`, which must not be empty, by the nearest-rank method.
+fn p99(sorted: &[Duration]) -> Duration {
+	sorted[((sorted.len() * 99 + 99) / 100).min(sorted.len()) - 1]
+}
+
+/// Log the min, average, p99 and max of `samples`, next to the `claimed` weight.
+pub(crate) fn report_execution_times(mut samples: Vec<Duration>, claimed: Weight) {
+	if samples.is_empty() {
+		return
+	}
+	samples.sort();
+
+	let min = samples[0];
+	let max = samples[samples.len() - 1];
+	let avg = samples.iter().sum::<Duration>() / samples.len() as u32;
+	let p99 = p99(&samples);
+	let claimed_time = Duration::from_nanos(claimed.ref_time() / 1_000);
+
+	log::info!(
+		target: LOG_TARGET,
+		"execution time over {} runs: min = {:?}, avg = {:?}, p99 = {:?}, max = {:?}; claimed = {:?}",
+		samples.len(),
+		min,
+		avg,
+		p99,
+		max,
+		claimed_time,
+	);
+	if p99 > claimed_time {
+		log::warn!(
+			target: LOG_TARGET,
+			"p99 execution time ({:?}) exceeds the claimed weight ({:?})",
+			p99,
+			claimed_time,
+		);
+	}
+}
+
+/// Persist `changes` into the backend of `ext`.
+pub(crate) fn apply_changes(
+	ext: &mut TestExternalities,
+	mut changes: OverlayedChanges,
+	state_version: StateVersion,
+) -> sc_cli::Result<()> {
+	let storage_changes = changes
+		.drain_storage_changes(&ext.backend, &mut Default::default(), state_version)
+		.map_err(|e| format!("failed to apply changes: {:?}", e))?;
+	ext.backend
+		.apply_transaction(storage_changes.transaction_storage_root, storage_changes.transaction);
+	Ok(())
+}
+
+/// Get the version of the local runtime.
+pub(crate) fn local_version<Block: BlockT, D: NativeExecutionDispatch + 'static>(
+	ext: &TestExternalities,
+	executor: &NativeElseWasmExecutor<D>,
+) -> RuntimeVersion {
 	let (_, encoded) = state_machine_call::<Block, D>(
 		ext,
 		executor,
@@ -842,11 +1931,58 @@ pub(crate) fn local_spec<Block: BlockT, D: NativeExecutionDispatch + 'static>(
 		Default::default(),
 	)
 	.expect("all runtimes should have version; qed");
-	<sp_version::RuntimeVersion as Decode>::decode(&mut &*encoded)
+	<RuntimeVersion as Decode>::decode(&mut &*encoded)
 		.map_err(|e| format!("failed to decode output: {:?}", e))
-		.map(|v| {
-			let state_version = v.state_version();
-			(v.spec_name.into(), v.spec_version, state_version)
-		})
 		.expect("all runtimes should have version; qed")
 }
+
+/// Ensure that the local runtime, of `version`, implements at least version `required` of the
+/// `TryRuntime` api, which introduced `method`.
+pub(crate) fn ensure_try_runtime_api(
+	version: &RuntimeVersion,
+	required: u32,
+	method: &str,
+) -> sc_cli::Result<()> {
+	match version.api_version(&blake2_64(b"TryRuntime")) {
+		Some(v) if v >= required => Ok(()),
+		Some(v) => Err(format!(
+			"the local runtime implements version {} of the TryRuntime api, but {} requires \
+			version {}: rebuild it against a newer `frame-try-runtime`",
+			v, method, required,
+		)
+		.into()),
+		None => Err("the local runtime does not implement the TryRuntime api, was it built with \
+			the `try-runtime` feature?"
+			.into()),
+	}
+}
+
+/// Get the spec `(name, version)` from the local runtime.
+pub(crate) fn local_spec<Block: BlockT, D: NativeExecutionDispatch + 'static>(
+	ext: &TestExternalities,
+	executor: &NativeElseWasmExecutor<D>,
+) -> (String, u32, sp_core::storage::StateVersion) {
+	let version = local_version::<Block, D>(ext, executor);
+	let state_version = version.state_version();
+	(version.spec_name.into(), version.spec_version, state_version)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn p99_works() {
+		let samples = |n: u64| (1..=n).map(Duration::from_millis).collect::<Vec<_>>();
+		let p99_of = |n: u64| p99(&samples(n)).as_millis();
+
+		assert_eq!(p99_of(1), 1);
+		assert_eq!(p99_of(2), 2);
+		assert_eq!(p99_of(10), 10);
+		assert_eq!(p99_of(99), 99);
+		assert_eq!(p99_of(100), 99);
+		assert_eq!(p99_of(101), 100);
+		assert_eq!(p99_of(200), 198);
+		assert_eq!(p99_of(1000), 990);
+	}
+}
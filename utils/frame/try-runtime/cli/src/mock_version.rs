@@ -0,0 +1,106 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mocking of the runtime version that is reported to the runtime.
+//!
+//! The runtime reads the version of a wasm blob through `sp_io::misc::runtime_version`, e.g. to
+//! check that an upgrade increments the `spec_version`. The state machine answers this through the
+//! [`ReadRuntimeVersionExt`], which [`mock_runtime_version`] replaces with one that overwrites the
+//! versions given in the [`SharedParams`].
+
+use crate::{SharedParams, LOG_TARGET};
+use parity_scale_codec::{Decode, Encode};
+use sc_executor::NativeElseWasmExecutor;
+use sc_service::NativeExecutionDispatch;
+use sp_core::traits::{ReadRuntimeVersion, ReadRuntimeVersionExt};
+use sp_externalities::{Extensions, Externalities};
+use sp_version::RuntimeVersion;
+
+/// The versions to report instead of the real ones, if any.
+#[derive(Debug, Clone, Copy, Default)]
+struct VersionOverrides {
+	spec_version: Option<u32>,
+	impl_version: Option<u32>,
+	transaction_version: Option<u32>,
+}
+
+impl VersionOverrides {
+	fn from_shared(shared: &SharedParams) -> Self {
+		Self {
+			spec_version: shared.mock_spec_version,
+			impl_version: shared.mock_impl_version,
+			transaction_version: shared.mock_transaction_version,
+		}
+	}
+
+	fn is_empty(&self) -> bool {
+		self.spec_version.is_none() &&
+			self.impl_version.is_none() &&
+			self.transaction_version.is_none()
+	}
+
+	fn apply(&self, version: &mut RuntimeVersion) {
+		if let Some(spec_version) = self.spec_version {
+			version.spec_version = spec_version;
+		}
+		if let Some(impl_version) = self.impl_version {
+			version.impl_version = impl_version;
+		}
+		if let Some(transaction_version) = self.transaction_version {
+			version.transaction_version = transaction_version;
+		}
+	}
+}
+
+/// A [`ReadRuntimeVersion`] that reads the version through `inner`, and then applies `overrides`
+/// to it.
+struct MockReadRuntimeVersion<R> {
+	inner: R,
+	overrides: VersionOverrides,
+}
+
+impl<R: ReadRuntimeVersion> ReadRuntimeVersion for MockReadRuntimeVersion<R> {
+	fn read_runtime_version(
+		&self,
+		wasm_code: &[u8],
+		ext: &mut dyn Externalities,
+	) -> Result<Vec<u8>, String> {
+		let encoded = self.inner.read_runtime_version(wasm_code, ext)?;
+		let mut version = RuntimeVersion::decode(&mut &*encoded)
+			.map_err(|e| format!("failed to decode the runtime version: {}", e))?;
+		self.overrides.apply(&mut version);
+		log::debug!(target: LOG_TARGET, "reporting mocked runtime version {:?}", version);
+		Ok(version.encode())
+	}
+}
+
+/// Register a [`ReadRuntimeVersionExt`] that mocks the versions given in `shared` into
+/// `extensions`, if any version is to be mocked.
+pub(crate) fn mock_runtime_version<D: NativeExecutionDispatch + 'static>(
+	mut extensions: Extensions,
+	shared: &SharedParams,
+	executor: &NativeElseWasmExecutor<D>,
+) -> Extensions {
+	let overrides = VersionOverrides::from_shared(shared);
+	if !overrides.is_empty() {
+		extensions.register(ReadRuntimeVersionExt::new(MockReadRuntimeVersion {
+			inner: executor.clone(),
+			overrides,
+		}));
+	}
+	extensions
+}
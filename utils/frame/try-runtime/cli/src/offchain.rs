@@ -0,0 +1,360 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Offchain externalities of the offchain worker whose local storage can be kept on disk between
+//! runs, and which record the HTTP requests of the worker and answer them from fixtures, see
+//! [`crate::commands::offchain_worker::OffchainWorkerCmd`].
+
+use crate::LOG_TARGET;
+use parity_scale_codec::{Decode, Encode};
+use sp_core::{
+	bytes::to_hex,
+	offchain::{
+		DbExternalities, Externalities, HttpError, HttpRequestId, HttpRequestStatus,
+		OpaqueNetworkState, StorageKind, Timestamp,
+	},
+	OpaquePeerId,
+};
+use std::{
+	collections::BTreeMap,
+	fs,
+	path::Path,
+	sync::{Arc, Mutex},
+};
+
+/// A canned response to the HTTP requests of the offchain worker to `uri`, and optionally only
+/// to those with `method`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub(crate) struct Fixture {
+	method: Option<String>,
+	uri: String,
+	#[serde(default = "Fixture::default_status")]
+	status: u16,
+	#[serde(default)]
+	headers: Vec<(String, String)>,
+	#[serde(default)]
+	body: String,
+}
+
+impl Fixture {
+	fn default_status() -> u16 {
+		200
+	}
+
+	fn matches(&self, request: &RecordedRequest) -> bool {
+		self.uri == request.uri &&
+			self.method.as_ref().map_or(true, |m| m.eq_ignore_ascii_case(&request.method))
+	}
+}
+
+/// An HTTP request of the offchain worker, as reported.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub(crate) struct RecordedRequest {
+	method: String,
+	uri: String,
+	headers: Vec<(String, String)>,
+	/// The body, if it is valid utf8, or else its hex encoding.
+	body: String,
+	/// The status of the response, or `None` if no fixture matched the request.
+	status: Option<u16>,
+}
+
+/// An HTTP request that is in flight.
+#[derive(Debug, Default)]
+struct Request {
+	/// The index of the request in [`State::recorded`].
+	index: usize,
+	body: Vec<u8>,
+	/// The fixture that answers the request, once it has been sent.
+	response: Option<Fixture>,
+	read: usize,
+}
+
+#[derive(Debug, Default)]
+struct State {
+	timestamp: Timestamp,
+	persistent: BTreeMap<Vec<u8>, Vec<u8>>,
+	local: BTreeMap<Vec<u8>, Vec<u8>>,
+	requests: BTreeMap<u16, Request>,
+	recorded: Vec<RecordedRequest>,
+	fixtures: Vec<Fixture>,
+}
+
+impl State {
+	fn storage(&mut self, kind: StorageKind) -> &mut BTreeMap<Vec<u8>, Vec<u8>> {
+		match kind {
+			StorageKind::PERSISTENT => &mut self.persistent,
+			StorageKind::LOCAL => &mut self.local,
+		}
+	}
+
+	/// Answer the request `id` from the fixtures, once it is sent.
+	fn respond(&mut self, id: u16) {
+		let request = match self.requests.get_mut(&id) {
+			Some(request) if request.response.is_none() => request,
+			_ => return,
+		};
+		let recorded = &mut self.recorded[request.index];
+		recorded.body = String::from_utf8(request.body.clone())
+			.unwrap_or_else(|_| to_hex(&request.body, false));
+		request.response = self.fixtures.iter().find(|f| f.matches(recorded)).cloned();
+		recorded.status = request.response.as_ref().map(|f| f.status);
+		match &request.response {
+			Some(fixture) => log::info!(
+				target: LOG_TARGET,
+				"offchain worker requested {} {}, answered with {}",
+				recorded.method,
+				recorded.uri,
+				fixture.status,
+			),
+			None => log::warn!(
+				target: LOG_TARGET,
+				"offchain worker requested {} {}, which no fixture matches",
+				recorded.method,
+				recorded.uri,
+			),
+		}
+	}
+}
+
+/// The offchain externalities, shared by all of their clones.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OffchainExt(Arc<Mutex<State>>);
+
+impl OffchainExt {
+	/// The externalities at `timestamp`, with the local storage of `db`, if given and present,
+	/// that answer HTTP requests with the fixtures at `fixtures`, if given.
+	pub(crate) fn new(
+		timestamp: Timestamp,
+		db: Option<&Path>,
+		fixtures: Option<&Path>,
+	) -> sc_cli::Result<Self> {
+		let mut state = State { timestamp, ..Default::default() };
+
+		if let Some(path) = db.filter(|path| path.exists()) {
+			let bytes = fs::read(path)
+				.map_err(|e| format!("failed to read the offchain db {:?}: {}", path, e))?;
+			(state.persistent, state.local) = Decode::decode(&mut &*bytes)
+				.map_err(|e| format!("failed to decode the offchain db {:?}: {:?}", path, e))?;
+			log::info!(
+				target: LOG_TARGET,
+				"loaded {} persistent and {} local offchain storage items from {:?}",
+				state.persistent.len(),
+				state.local.len(),
+				path,
+			);
+		}
+
+		if let Some(path) = fixtures {
+			let file = fs::File::open(path)
+				.map_err(|e| format!("failed to open the HTTP fixtures {:?}: {}", path, e))?;
+			state.fixtures = serde_yaml::from_reader(file)
+				.map_err(|e| format!("failed to parse the HTTP fixtures {:?}: {}", path, e))?;
+		}
+
+		Ok(Self(Arc::new(Mutex::new(state))))
+	}
+
+	fn state(&self) -> std::sync::MutexGuard<'_, State> {
+		self.0.lock().expect("never poisoned; qed")
+	}
+
+	/// Report `timestamp` as the current time from now on.
+	pub(crate) fn set_timestamp(&self, timestamp: Timestamp) {
+		self.state().timestamp = timestamp;
+	}
+
+	/// Write the local storage to `path`, to be loaded by the next run.
+	pub(crate) fn save_db(&self, path: &Path) -> sc_cli::Result<()> {
+		let state = self.state();
+		fs::write(path, (&state.persistent, &state.local).encode())
+			.map_err(|e| format!("failed to write the offchain db {:?}: {}", path, e))?;
+		log::info!(target: LOG_TARGET, "saved the offchain storage to {:?}", path);
+		Ok(())
+	}
+
+	/// Write all HTTP requests of the offchain worker to `path`, as json.
+	pub(crate) fn write_report(&self, path: &Path) -> sc_cli::Result<()> {
+		let state = self.state();
+		let report = serde_json::to_string_pretty(&state.recorded)
+			.map_err(|e| format!("failed to encode the HTTP report: {}", e))?;
+		fs::write(path, report)
+			.map_err(|e| format!("failed to write the HTTP report {:?}: {}", path, e))?;
+		log::info!(
+			target: LOG_TARGET,
+			"wrote {} HTTP requests of the offchain worker to {:?}",
+			state.recorded.len(),
+			path,
+		);
+		Ok(())
+	}
+}
+
+impl Externalities for OffchainExt {
+	fn is_validator(&self) -> bool {
+		true
+	}
+
+	fn network_state(&self) -> Result<OpaqueNetworkState, ()> {
+		Ok(OpaqueNetworkState { peer_id: OpaquePeerId::default(), external_addresses: vec![] })
+	}
+
+	fn timestamp(&mut self) -> Timestamp {
+		self.state().timestamp
+	}
+
+	fn sleep_until(&mut self, deadline: Timestamp) {
+		let mut state = self.state();
+		state.timestamp = state.timestamp.max(deadline);
+	}
+
+	fn random_seed(&mut self) -> [u8; 32] {
+		// deterministic, like everything else.
+		[0; 32]
+	}
+
+	fn http_request_start(
+		&mut self,
+		method: &str,
+		uri: &str,
+		_meta: &[u8],
+	) -> Result<HttpRequestId, ()> {
+		let mut state = self.state();
+		let id = u16::try_from(state.recorded.len()).map_err(|_| ())?;
+		let index = state.recorded.len();
+		state.recorded.push(RecordedRequest {
+			method: method.into(),
+			uri: uri.into(),
+			..Default::default()
+		});
+		state.requests.insert(id, Request { index, ..Default::default() });
+		Ok(HttpRequestId(id))
+	}
+
+	fn http_request_add_header(
+		&mut self,
+		request_id: HttpRequestId,
+		name: &str,
+		value: &str,
+	) -> Result<(), ()> {
+		let mut state = self.state();
+		let index = state.requests.get(&request_id.0).ok_or(())?.index;
+		state.recorded[index].headers.push((name.into(), value.into()));
+		Ok(())
+	}
+
+	fn http_request_write_body(
+		&mut self,
+		request_id: HttpRequestId,
+		chunk: &[u8],
+		_deadline: Option<Timestamp>,
+	) -> Result<(), HttpError> {
+		let mut state = self.state();
+		let request = state.requests.get_mut(&request_id.0).ok_or(HttpError::Invalid)?;
+		if request.response.is_some() {
+			return Err(HttpError::Invalid)
+		}
+		if chunk.is_empty() {
+			state.respond(request_id.0);
+		} else {
+			request.body.extend_from_slice(chunk);
+		}
+		Ok(())
+	}
+
+	fn http_response_wait(
+		&mut self,
+		ids: &[HttpRequestId],
+		_deadline: Option<Timestamp>,
+	) -> Vec<HttpRequestStatus> {
+		let mut state = self.state();
+		ids.iter()
+			.map(|id| {
+				// waiting for a response implies that the request is complete.
+				state.respond(id.0);
+				match state.requests.get(&id.0) {
+					None => HttpRequestStatus::Invalid,
+					Some(Request { response: Some(fixture), .. }) =>
+						HttpRequestStatus::Finished(fixture.status),
+					Some(Request { response: None, .. }) => HttpRequestStatus::IoError,
+				}
+			})
+			.collect()
+	}
+
+	fn http_response_headers(&mut self, request_id: HttpRequestId) -> Vec<(Vec<u8>, Vec<u8>)> {
+		match self.state().requests.get(&request_id.0) {
+			Some(Request { response: Some(fixture), .. }) => fixture
+				.headers
+				.iter()
+				.map(|(name, value)| (name.clone().into_bytes(), value.clone().into_bytes()))
+				.collect(),
+			_ => Default::default(),
+		}
+	}
+
+	fn http_response_read_body(
+		&mut self,
+		request_id: HttpRequestId,
+		buffer: &mut [u8],
+		_deadline: Option<Timestamp>,
+	) -> Result<usize, HttpError> {
+		let mut state = self.state();
+		let request = state.requests.get_mut(&request_id.0).ok_or(HttpError::Invalid)?;
+		let body = request.response.as_ref().ok_or(HttpError::IoError)?.body.as_bytes();
+		let read = buffer.len().min(body.len() - request.read);
+		buffer[..read].copy_from_slice(&body[request.read..request.read + read]);
+		request.read += read;
+		if read == 0 {
+			// the request is done, as per spec.
+			state.requests.remove(&request_id.0);
+		}
+		Ok(read)
+	}
+
+	fn set_authorized_nodes(&mut self, _nodes: Vec<OpaquePeerId>, _authorized_only: bool) {}
+}
+
+impl DbExternalities for OffchainExt {
+	fn local_storage_set(&mut self, kind: StorageKind, key: &[u8], value: &[u8]) {
+		self.state().storage(kind).insert(key.to_vec(), value.to_vec());
+	}
+
+	fn local_storage_clear(&mut self, kind: StorageKind, key: &[u8]) {
+		self.state().storage(kind).remove(key);
+	}
+
+	fn local_storage_compare_and_set(
+		&mut self,
+		kind: StorageKind,
+		key: &[u8],
+		old_value: Option<&[u8]>,
+		new_value: &[u8],
+	) -> bool {
+		let mut state = self.state();
+		let storage = state.storage(kind);
+		if storage.get(key).map(Vec::as_slice) != old_value {
+			return false
+		}
+		storage.insert(key.to_vec(), new_value.to_vec());
+		true
+	}
+
+	fn local_storage_get(&mut self, kind: StorageKind, key: &[u8]) -> Option<Vec<u8>> {
+		self.state().storage(kind).get(key).cloned()
+	}
+}
@@ -0,0 +1,194 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Machine readable summaries of the commands, see [`crate::SharedParams::output`].
+//!
+//! Each command fills the parts of a [`Summary`] that apply to it while it executes. Once it
+//! returns, successfully or not, the summary is printed to stdout as a single json object, whilst
+//! the logs keep going to stderr.
+
+use crate::SharedParams;
+use clap::ValueEnum;
+use remote_externalities::TestExternalities;
+use sc_service::Configuration;
+use serde::Serialize;
+use sp_core::{hashing::blake2_256, storage::well_known_keys};
+use sp_state_machine::Backend;
+use sp_weights::Weight;
+
+/// The format in which the outcome of a command is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+	/// Only log the outcome.
+	Text,
+	/// Additionally print a [`Summary`] as json to stdout.
+	Json,
+}
+
+/// The name and version of a runtime.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct Spec {
+	pub(crate) name: String,
+	pub(crate) version: u32,
+}
+
+/// The weight consumed by a runtime call, in both dimensions.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub(crate) struct WeightSummary {
+	pub(crate) ref_time: u64,
+	pub(crate) proof_size: u64,
+}
+
+impl From<Weight> for WeightSummary {
+	fn from(weight: Weight) -> Self {
+		Self { ref_time: weight.ref_time(), proof_size: weight.proof_size() }
+	}
+}
+
+/// The settings of the executor that the runtime was executed with.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ExecutorSettings {
+	pub(crate) execution: String,
+	pub(crate) wasm_execution: String,
+	pub(crate) wasm_instantiation_strategy: String,
+	/// The heap pages given on the command line, or else the default of the node. The runtime can
+	/// still override these through `:heappages`.
+	pub(crate) heap_pages: Option<u64>,
+	pub(crate) state_version: u8,
+}
+
+/// Where a [`Summary`] comes from, such that results attached to e.g. a release sign-off can be
+/// audited later.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Provenance {
+	/// The name and version of the node binary, including the commit it was built from.
+	pub(crate) node: String,
+	/// The version of `try-runtime-cli`.
+	pub(crate) try_runtime_cli: &'static str,
+	/// The version of rustc that built `try-runtime-cli`.
+	pub(crate) rustc: &'static str,
+	/// The architecture and operating system of the host.
+	pub(crate) host: String,
+	pub(crate) executor: ExecutorSettings,
+	/// The name of the local chain spec, i.e. `--chain`.
+	pub(crate) chain_spec: String,
+	/// The node whose runtime version the local runtime was compared to, if any.
+	pub(crate) endpoint: Option<String>,
+	/// The blake2-256 hash of the code that was executed, if any, be it the one of the state or
+	/// the one that replaced it.
+	pub(crate) code_hash: Option<String>,
+}
+
+impl Provenance {
+	/// The provenance of a command that is executed with `shared` and `config`.
+	pub(crate) fn new(shared: &SharedParams, config: &Configuration) -> Self {
+		Self {
+			node: format!("{} {}", config.impl_name, config.impl_version),
+			try_runtime_cli: env!("CARGO_PKG_VERSION"),
+			rustc: env!("TRY_RUNTIME_RUSTC_VERSION"),
+			host: format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS),
+			executor: ExecutorSettings {
+				execution: cli_name(shared.execution),
+				wasm_execution: cli_name(shared.wasm_method),
+				wasm_instantiation_strategy: cli_name(shared.wasmtime_instantiation_strategy),
+				heap_pages: shared.heap_pages.or(config.default_heap_pages),
+				state_version: shared.state_version.into(),
+			},
+			chain_spec: config.chain_spec.name().to_string(),
+			endpoint: None,
+			code_hash: None,
+		}
+	}
+
+	/// Record the hash of the code in the state of `ext`.
+	pub(crate) fn record_code(&mut self, ext: &TestExternalities) {
+		self.code_hash = ext
+			.backend
+			.storage(well_known_keys::CODE)
+			.ok()
+			.flatten()
+			.map(|code| sp_core::bytes::to_hex(&blake2_256(&code), false));
+	}
+}
+
+/// The name of `value` on the command line.
+fn cli_name(value: impl ValueEnum) -> String {
+	value
+		.to_possible_value()
+		.map_or_else(Default::default, |v| v.get_name().to_string())
+}
+
+/// The outcome of a command.
+///
+/// All fields but `command`, `success`, `error` and `provenance` are only set by the commands
+/// that they apply to, and are `null` otherwise.
+#[derive(Debug, Serialize)]
+pub(crate) struct Summary {
+	/// The name of the command, as given on the command line.
+	pub(crate) command: &'static str,
+	/// Whether the command succeeded.
+	pub(crate) success: bool,
+	/// The error that the command failed with, if any.
+	pub(crate) error: Option<String>,
+	pub(crate) provenance: Provenance,
+	/// The spec of the local runtime, i.e. the one that was executed.
+	pub(crate) local_spec: Option<Spec>,
+	/// The spec of the runtime of the remote node that the local one was compared to.
+	pub(crate) remote_spec: Option<Spec>,
+	/// The storage root of the state that the runtime was executed on.
+	pub(crate) storage_root_before: Option<String>,
+	/// The storage root of that state, once the changes of the runtime were applied.
+	pub(crate) storage_root_after: Option<String>,
+	/// The weight that the runtime reported to have consumed.
+	pub(crate) weight: Option<WeightSummary>,
+	/// The maximum weight of a block, as reported by the runtime along with [`Self::weight`].
+	pub(crate) total_weight: Option<WeightSummary>,
+}
+
+impl Summary {
+	/// An empty summary of `command`, which is executed as per `provenance`.
+	pub(crate) fn new(command: &'static str, provenance: Provenance) -> Self {
+		Self {
+			command,
+			success: false,
+			error: None,
+			provenance,
+			local_spec: None,
+			remote_spec: None,
+			storage_root_before: None,
+			storage_root_after: None,
+			weight: None,
+			total_weight: None,
+		}
+	}
+
+	/// Record the storage roots of a state, before and after a runtime call changed it.
+	pub(crate) fn storage_roots(&mut self, before: impl AsRef<[u8]>, after: impl AsRef<[u8]>) {
+		self.storage_root_before = Some(sp_core::bytes::to_hex(before.as_ref(), false));
+		self.storage_root_after = Some(sp_core::bytes::to_hex(after.as_ref(), false));
+	}
+
+	/// Complete the summary with the `result` of the command, and print it as json.
+	pub(crate) fn print(mut self, result: &sc_cli::Result<()>) -> sc_cli::Result<()> {
+		self.success = result.is_ok();
+		self.error = result.as_ref().err().map(|e| e.to_string());
+		let summary = serde_json::to_string(&self)
+			.map_err(|e| format!("failed to serialize the summary: {:?}", e))?;
+		println!("{}", summary);
+		Ok(())
+	}
+}
@@ -17,6 +17,11 @@
 
 //! Utils for parsing user input
 
+use crate::commands::{
+	execute_block::BlockRef,
+	offchain_worker::{MockTimestamp, StateAt},
+};
+use sp_inherents::InherentIdentifier;
 use sp_version::StateVersion;
 
 pub(crate) fn hash(block_hash: &str) -> Result<String, String> {
@@ -36,7 +41,73 @@ pub(crate) fn hash(block_hash: &str) -> Result<String, String> {
 	}
 }
 
+/// The block number that `s` is, if it is a decimal number rather than a block hash.
+///
+/// Unlike [`block_ref`], hashes need not be prefixed with `0x` here, hence only what is shorter
+/// than a hash is a number.
+pub(crate) fn block_number(s: &str) -> Option<u64> {
+	if s.starts_with("0x") || s.len() >= 64 {
+		return None
+	}
+	s.replace('_', "").parse().ok()
+}
+
+/// A block hash, as per [`hash`], or a block number, as per [`block_number`].
+pub(crate) fn hash_or_number(s: &str) -> Result<String, String> {
+	match block_number(s) {
+		Some(_) => Ok(s.into()),
+		None => hash(s).map_err(|e| format!("{}, or a block number", e)),
+	}
+}
+
+pub(crate) fn block_ref(s: &str) -> Result<BlockRef, String> {
+	if s.starts_with("0x") {
+		hash(s).map(BlockRef::Hash)
+	} else {
+		s.replace('_', "")
+			.parse::<u64>()
+			.map(BlockRef::Number)
+			.map_err(|_| format!("Expected a block hash or number, found {}", s))
+	}
+}
+
+pub(crate) fn hex_bytes(bytes: &str) -> Result<String, String> {
+	sp_core::bytes::from_hex(bytes)
+		.map(|_| bytes.into())
+		.map_err(|e| format!("Expected hex encoded bytes: {}", e))
+}
+
+pub(crate) fn indexed_hex_bytes(s: &str) -> Result<(usize, Vec<u8>), String> {
+	let (index, bytes) = s
+		.split_once('=')
+		.ok_or_else(|| format!("Expected `<index>=<hex>`, found {}", s))?;
+	let index = index.trim().parse().map_err(|_| format!("Invalid index: {}", index))?;
+	let bytes = sp_core::bytes::from_hex(bytes.trim())
+		.map_err(|e| format!("Expected hex encoded bytes: {}", e))?;
+	Ok((index, bytes))
+}
+
+/// Inherent data, as `<identifier>=<hex>`.
+pub(crate) fn inherent_data(s: &str) -> Result<(InherentIdentifier, Vec<u8>), String> {
+	let (identifier, bytes) = s
+		.split_once('=')
+		.ok_or_else(|| format!("Expected `<identifier>=<hex>`, found {}", s))?;
+	let identifier = InherentIdentifier::try_from(identifier.trim().as_bytes())
+		.map_err(|_| format!("Expected an identifier of 8 bytes, found {}", identifier))?;
+	let bytes = sp_core::bytes::from_hex(bytes.trim())
+		.map_err(|e| format!("Expected hex encoded bytes: {}", e))?;
+	Ok((identifier, bytes))
+}
+
 pub(crate) fn url(s: &str) -> Result<String, &'static str> {
+	if ws_url(s).is_ok() || s.starts_with("http://") || s.starts_with("https://") {
+		Ok(s.to_string())
+	} else {
+		Err("not a valid url: must start with 'ws://', 'wss://', 'http://' or 'https://'")
+	}
+}
+
+pub(crate) fn ws_url(s: &str) -> Result<String, &'static str> {
 	if s.starts_with("ws://") || s.starts_with("wss://") {
 		// could use Url crate as well, but lets keep it simple for now.
 		Ok(s.to_string())
@@ -51,3 +122,100 @@ pub(crate) fn state_version(s: &str) -> Result<StateVersion, &'static str> {
 		.and_then(StateVersion::try_from)
 		.map_err(|_| "Invalid state version.")
 }
+
+pub(crate) fn mock_timestamp(s: &str) -> Result<MockTimestamp, &'static str> {
+	if s == "block" {
+		Ok(MockTimestamp::Block)
+	} else {
+		s.parse::<u64>()
+			.map(MockTimestamp::Fixed)
+			.map_err(|_| "Invalid timestamp: expected `block` or unix time in milliseconds.")
+	}
+}
+
+pub(crate) fn state_at(s: &str) -> Result<StateAt, &'static str> {
+	match s {
+		"self" => Ok(StateAt::Header),
+		"parent" => Ok(StateAt::Parent),
+		_ => Err("Invalid state: expected `self` or `parent`."),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn hash_works() {
+		assert_eq!(hash("0x1234abcd"), Ok("1234abcd".into()));
+		assert_eq!(hash("1234abcd"), Ok("1234abcd".into()));
+		assert!(hash("0x12g4").unwrap_err().contains("position: 4"));
+		assert!(hash("12g4").unwrap_err().contains("position: 2"));
+	}
+
+	#[test]
+	fn block_number_works() {
+		assert_eq!(block_number("12_345"), Some(12345));
+		assert_eq!(block_number("0x1234"), None);
+		assert_eq!(block_number(&"1".repeat(64)), None);
+		assert_eq!(block_number("abcd"), None);
+
+		assert_eq!(hash_or_number("12345"), Ok("12345".into()));
+		assert_eq!(hash_or_number("abcd"), Ok("abcd".into()));
+		assert!(hash_or_number("xyz").is_err());
+	}
+
+	#[test]
+	fn block_ref_works() {
+		assert!(matches!(block_ref("0xabcd"), Ok(BlockRef::Hash(h)) if h == "abcd"));
+		assert!(matches!(block_ref("1_000"), Ok(BlockRef::Number(1000))));
+		// hashes must be prefixed.
+		assert!(block_ref("abcd").is_err());
+		assert!(block_ref("0xxyz").is_err());
+	}
+
+	#[test]
+	fn indexed_hex_bytes_works() {
+		assert_eq!(indexed_hex_bytes("1=0x0102"), Ok((1, vec![1, 2])));
+		assert_eq!(indexed_hex_bytes(" 0 = 0102 "), Ok((0, vec![1, 2])));
+		assert!(indexed_hex_bytes("0x0102").is_err());
+		assert!(indexed_hex_bytes("a=0x0102").is_err());
+		assert!(indexed_hex_bytes("1=0x0g").is_err());
+	}
+
+	#[test]
+	fn inherent_data_works() {
+		assert_eq!(inherent_data("sysi1337=0x0102"), Ok((*b"sysi1337", vec![1, 2])));
+		assert!(inherent_data("sysi=0x0102").is_err());
+		assert!(inherent_data("sysi13370=0x0102").is_err());
+		assert!(inherent_data("sysi1337").is_err());
+		assert!(inherent_data("sysi1337=0xzz").is_err());
+	}
+
+	#[test]
+	fn urls_work() {
+		assert!(url("ws://localhost:9944").is_ok());
+		assert!(url("https://rpc.polkadot.io").is_ok());
+		assert!(url("localhost:9944").is_err());
+		assert!(ws_url("wss://rpc.polkadot.io").is_ok());
+		assert!(ws_url("http://localhost:9933").is_err());
+	}
+
+	#[test]
+	fn other_values_work() {
+		assert!(hex_bytes("0x0102").is_ok());
+		assert!(hex_bytes("0x01zz").is_err());
+
+		assert_eq!(state_version("0"), Ok(StateVersion::V0));
+		assert_eq!(state_version("1"), Ok(StateVersion::V1));
+		assert!(state_version("2").is_err());
+
+		assert!(matches!(mock_timestamp("block"), Ok(MockTimestamp::Block)));
+		assert!(matches!(mock_timestamp("1000"), Ok(MockTimestamp::Fixed(1000))));
+		assert!(mock_timestamp("now").is_err());
+
+		assert_eq!(state_at("self"), Ok(StateAt::Header));
+		assert_eq!(state_at("parent"), Ok(StateAt::Parent));
+		assert!(state_at("child").is_err());
+	}
+}
@@ -0,0 +1,311 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A decoded diff of the storage writes made by a command, see
+//! [`crate::SharedParams::print_storage_diff`].
+
+use crate::{runtime_metadata, SharedParams, LOG_TARGET};
+use frame_metadata::{RuntimeMetadataV14, StorageEntryType};
+use parity_scale_codec::{Compact, Decode};
+use remote_externalities::TestExternalities;
+use sc_executor::NativeElseWasmExecutor;
+use sc_service::NativeExecutionDispatch;
+use scale_info::{form::PortableForm, Field, PortableRegistry, TypeDef, TypeDefPrimitive};
+use sp_core::{bytes::to_hex, hashing::twox_128, hexdisplay::HexDisplay};
+use sp_runtime::traits::Block as BlockT;
+use sp_state_machine::{Backend, OverlayedChanges};
+
+/// A storage entry, as declared in the metadata.
+pub(crate) struct Entry {
+	/// The entry, as `Pallet::Item`.
+	pub(crate) name: String,
+	/// The prefix of all keys of the entry.
+	pub(crate) prefix: [u8; 32],
+	/// The type of the values of the entry.
+	ty: u32,
+}
+
+/// All storage entries declared in `metadata`.
+pub(crate) fn entries(metadata: &RuntimeMetadataV14) -> Vec<Entry> {
+	metadata
+		.pallets
+		.iter()
+		.filter_map(|pallet| pallet.storage.as_ref().map(|storage| (&pallet.name, storage)))
+		.flat_map(|(pallet, storage)| {
+			storage.entries.iter().map(move |entry| {
+				let mut prefix = [0u8; 32];
+				prefix[..16].copy_from_slice(&twox_128(storage.prefix.as_bytes()));
+				prefix[16..].copy_from_slice(&twox_128(entry.name.as_bytes()));
+				let ty = match &entry.ty {
+					StorageEntryType::Plain(ty) => ty.id(),
+					StorageEntryType::Map { value, .. } => value.id(),
+				};
+				Entry { name: format!("{}::{}", pallet, entry.name), prefix, ty }
+			})
+		})
+		.collect()
+}
+
+/// Take the next `len` bytes from `input`.
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+	if input.len() < len {
+		return Err(format!("expected {} more bytes, found {}", len, input.len()))
+	}
+	let (taken, rest) = input.split_at(len);
+	*input = rest;
+	Ok(taken)
+}
+
+/// Whether `ty` is `u8`, in which case sequences and arrays of it are shown in hex.
+fn is_u8(types: &PortableRegistry, ty: u32) -> bool {
+	matches!(
+		types.resolve(ty).map(|t| t.type_def()),
+		Some(TypeDef::Primitive(TypeDefPrimitive::U8))
+	)
+}
+
+/// Decode `fields` from `input`, as `{ name: value, .. }` if they are named, or as `(value, ..)`
+/// otherwise.
+fn decode_fields(
+	types: &PortableRegistry,
+	fields: &[Field<PortableForm>],
+	input: &mut &[u8],
+) -> Result<String, String> {
+	if fields.is_empty() {
+		return Ok(String::new())
+	}
+
+	let named = fields.iter().all(|f| f.name().is_some());
+	let values = fields
+		.iter()
+		.map(|f| {
+			let value = decode(types, f.ty().id(), input)?;
+			Ok(match f.name() {
+				Some(name) if named => format!("{}: {}", name, value),
+				_ => value,
+			})
+		})
+		.collect::<Result<Vec<_>, String>>()?;
+
+	if named {
+		Ok(format!(" {{ {} }}", values.join(", ")))
+	} else {
+		Ok(format!("({})", values.join(", ")))
+	}
+}
+
+/// Decode a value of type `ty` from `input`, into a human readable form.
+///
+/// Byte sequences and arrays (e.g. account ids) are shown in hex.
+fn decode(types: &PortableRegistry, ty: u32, input: &mut &[u8]) -> Result<String, String> {
+	let ty = types
+		.resolve(ty)
+		.ok_or_else(|| format!("type {} not found in the metadata", ty))?;
+	let err = |e: parity_scale_codec::Error| e.to_string();
+	let value = match ty.type_def() {
+		TypeDef::Primitive(primitive) => match primitive {
+			TypeDefPrimitive::Bool => bool::decode(input).map_err(err)?.to_string(),
+			TypeDefPrimitive::Char => {
+				let c = u32::decode(input).map_err(err)?;
+				format!("{:?}", char::from_u32(c).ok_or_else(|| format!("invalid char {}", c))?)
+			},
+			TypeDefPrimitive::Str => format!("{:?}", String::decode(input).map_err(err)?),
+			TypeDefPrimitive::U8 => u8::decode(input).map_err(err)?.to_string(),
+			TypeDefPrimitive::U16 => u16::decode(input).map_err(err)?.to_string(),
+			TypeDefPrimitive::U32 => u32::decode(input).map_err(err)?.to_string(),
+			TypeDefPrimitive::U64 => u64::decode(input).map_err(err)?.to_string(),
+			TypeDefPrimitive::U128 => u128::decode(input).map_err(err)?.to_string(),
+			TypeDefPrimitive::I8 => i8::decode(input).map_err(err)?.to_string(),
+			TypeDefPrimitive::I16 => i16::decode(input).map_err(err)?.to_string(),
+			TypeDefPrimitive::I32 => i32::decode(input).map_err(err)?.to_string(),
+			TypeDefPrimitive::I64 => i64::decode(input).map_err(err)?.to_string(),
+			TypeDefPrimitive::I128 => i128::decode(input).map_err(err)?.to_string(),
+			TypeDefPrimitive::U256 | TypeDefPrimitive::I256 => to_hex(take(input, 32)?, false),
+		},
+		TypeDef::Compact(_) => Compact::<u128>::decode(input).map_err(err)?.0.to_string(),
+		TypeDef::Composite(composite) => format!(
+			"{}{}",
+			ty.path().ident().unwrap_or_default(),
+			decode_fields(types, composite.fields(), input)?
+		),
+		TypeDef::Variant(variant) => {
+			let index = u8::decode(input).map_err(err)?;
+			let variant = variant
+				.variants()
+				.iter()
+				.find(|v| v.index() == index)
+				.ok_or_else(|| format!("variant {} not found in {:?}", index, ty.path()))?;
+			format!("{}{}", variant.name(), decode_fields(types, variant.fields(), input)?)
+		},
+		TypeDef::Sequence(sequence) => {
+			let len = Compact::<u32>::decode(input).map_err(err)?.0 as usize;
+			let element = sequence.type_param().id();
+			if is_u8(types, element) {
+				to_hex(take(input, len)?, false)
+			} else {
+				let values = (0..len)
+					.map(|_| decode(types, element, input))
+					.collect::<Result<Vec<_>, _>>()?;
+				format!("[{}]", values.join(", "))
+			}
+		},
+		TypeDef::Array(array) => {
+			let len = array.len() as usize;
+			let element = array.type_param().id();
+			if is_u8(types, element) {
+				to_hex(take(input, len)?, false)
+			} else {
+				let values = (0..len)
+					.map(|_| decode(types, element, input))
+					.collect::<Result<Vec<_>, _>>()?;
+				format!("[{}]", values.join(", "))
+			}
+		},
+		TypeDef::Tuple(tuple) => {
+			let values = tuple
+				.fields()
+				.iter()
+				.map(|t| decode(types, t.id(), input))
+				.collect::<Result<Vec<_>, _>>()?;
+			format!("({})", values.join(", "))
+		},
+		TypeDef::BitSequence(_) => return Err("bit sequences cannot be decoded".into()),
+	};
+	Ok(value)
+}
+
+/// Decode the SCALE encoded `extrinsic`, as `signed by <address>: <call>` or `unsigned: <call>`,
+/// as per the extrinsic format of `metadata`.
+pub(crate) fn decode_extrinsic(
+	metadata: &RuntimeMetadataV14,
+	extrinsic: &[u8],
+) -> Result<String, String> {
+	let types = &metadata.types;
+	let param = |name: &str| {
+		types
+			.resolve(metadata.extrinsic.ty.id())
+			.and_then(|ty| ty.type_params().iter().find(|p| p.name() == name))
+			.and_then(|p| p.ty().map(|ty| ty.id()))
+			.ok_or_else(|| format!("no {} type in the extrinsic metadata", name))
+	};
+	let err = |e: parity_scale_codec::Error| e.to_string();
+
+	let mut input = extrinsic;
+	let len = Compact::<u32>::decode(&mut input).map_err(err)?.0 as usize;
+	if len != input.len() {
+		return Err(format!("expected an extrinsic of {} bytes, found {}", len, input.len()))
+	}
+	let version = u8::decode(&mut input).map_err(err)?;
+	if version & 0b0111_1111 != metadata.extrinsic.version {
+		return Err(format!("unsupported extrinsic version {}", version & 0b0111_1111))
+	}
+	let signer = if version & 0b1000_0000 != 0 {
+		let address = decode(types, param("Address")?, &mut input)?;
+		decode(types, param("Signature")?, &mut input)?;
+		decode(types, param("Extra")?, &mut input)?;
+		Some(address)
+	} else {
+		None
+	};
+	let call = decode(types, param("Call")?, &mut input)?;
+	if !input.is_empty() {
+		return Err(format!("{} bytes left after the call", input.len()))
+	}
+
+	Ok(match signer {
+		Some(signer) => format!("signed by {}: {}", signer, call),
+		None => format!("unsigned: {}", call),
+	})
+}
+
+/// Show `value`, decoded as type `ty` if possible, and in hex otherwise.
+fn show(types: &PortableRegistry, ty: Option<u32>, value: Option<&[u8]>) -> String {
+	let value = match value {
+		Some(value) => value,
+		None => return "(none)".into(),
+	};
+	let mut input = value;
+	match ty.map(|ty| decode(types, ty, &mut input)) {
+		Some(Ok(decoded)) if input.is_empty() => decoded,
+		_ => to_hex(value, false),
+	}
+}
+
+/// Print every top level key written in `changes` whose value differs from the state of `ext`,
+/// along with the storage entry it belongs to, and its old and new value.
+///
+/// Values are decoded as per the metadata of the runtime in `ext` where possible. Only does
+/// anything if [`SharedParams::print_storage_diff`].
+pub(crate) fn print_storage_diff<Block: BlockT, D: NativeExecutionDispatch + 'static>(
+	shared: &SharedParams,
+	ext: &TestExternalities,
+	executor: &NativeElseWasmExecutor<D>,
+	changes: &OverlayedChanges,
+) -> sc_cli::Result<()> {
+	if !shared.print_storage_diff {
+		return Ok(())
+	}
+
+	let metadata = runtime_metadata::<Block, D>(ext, executor)?;
+	let entries = entries(&metadata);
+
+	let mut changed = 0;
+	let mut unchanged = 0;
+	for (key, value) in changes.changes() {
+		let new = value.value();
+		let old = ext.backend.storage(key).map_err(|e| format!("failed to read storage: {}", e))?;
+		if old.as_ref() == new {
+			unchanged += 1;
+			continue
+		}
+		changed += 1;
+
+		let entry = entries.iter().find(|e| key.starts_with(&e.prefix));
+		let name = match entry {
+			Some(entry) => entry.name.clone(),
+			None if key.starts_with(b":") => String::from_utf8_lossy(key).into_owned(),
+			None => "unknown".into(),
+		};
+		// well-known keys, e.g. `:code`, are not worth showing in full.
+		let (old, new) = if key.starts_with(b":") {
+			let size =
+				|v: Option<&[u8]>| v.map_or("(none)".into(), |v| format!("{} bytes", v.len()));
+			(size(old.as_deref()), size(new.map(|v| &v[..])))
+		} else {
+			let ty = entry.map(|e| e.ty);
+			(
+				show(&metadata.types, ty, old.as_deref()),
+				show(&metadata.types, ty, new.map(|v| &v[..])),
+			)
+		};
+
+		log::info!(target: LOG_TARGET, "{} 0x{}", name, HexDisplay::from(key));
+		log::info!(target: LOG_TARGET, "  - {}", old);
+		log::info!(target: LOG_TARGET, "  + {}", new);
+	}
+
+	let child_writes = changes.children().map(|(c, _)| c.count()).sum::<usize>();
+	log::info!(
+		target: LOG_TARGET,
+		"{} keys changed, {} keys were written with their existing value, {} child keys were \
+		written (not shown)",
+		changed,
+		unchanged,
+		child_writes,
+	);
+	Ok(())
+}
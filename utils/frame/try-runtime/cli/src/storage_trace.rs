@@ -0,0 +1,277 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A trace of the storage accesses of a runtime call, in the folded stacks format of flamegraph
+//! tooling, see [`crate::SharedParams::storage_trace`].
+
+use crate::{
+	runtime_metadata,
+	storage_diff::{entries, Entry},
+	LOG_TARGET,
+};
+use remote_externalities::TestExternalities;
+use sc_executor::NativeElseWasmExecutor;
+use sc_service::NativeExecutionDispatch;
+use sp_core::{
+	hexdisplay::HexDisplay,
+	storage::{well_known_keys, ChildInfo, StateVersion},
+	Hasher,
+};
+use sp_externalities::Extensions;
+use sp_runtime::traits::Block as BlockT;
+use sp_state_machine::{
+	backend::BackendRuntimeCode, Backend, OverlayedChanges, StateMachine, StateMachineStats,
+	StorageKey, StorageValue, UsageInfo,
+};
+use std::{collections::BTreeMap, path::Path, sync::Mutex};
+
+/// A [`Backend`] that counts how often each key of the wrapped backend is accessed.
+///
+/// Accesses to a child trie are all counted against the prefixed storage key of the child trie.
+#[derive(Debug)]
+struct TracingBackend<'a, B> {
+	inner: &'a B,
+	accesses: Mutex<BTreeMap<Vec<u8>, u64>>,
+}
+
+impl<'a, B> TracingBackend<'a, B> {
+	fn new(inner: &'a B) -> Self {
+		Self { inner, accesses: Default::default() }
+	}
+
+	fn record(&self, child_info: Option<&ChildInfo>, key: &[u8]) {
+		let key = match child_info {
+			Some(child_info) => child_info.prefixed_storage_key().into_inner(),
+			None => key.to_vec(),
+		};
+		*self.accesses.lock().expect("never poisoned; qed").entry(key).or_default() += 1;
+	}
+
+	fn into_accesses(self) -> BTreeMap<Vec<u8>, u64> {
+		self.accesses.into_inner().expect("never poisoned; qed")
+	}
+}
+
+impl<'a, H: Hasher, B: Backend<H>> Backend<H> for TracingBackend<'a, B> {
+	type Error = B::Error;
+	type Transaction = B::Transaction;
+	type TrieBackendStorage = B::TrieBackendStorage;
+
+	fn storage(&self, key: &[u8]) -> Result<Option<StorageValue>, Self::Error> {
+		self.record(None, key);
+		self.inner.storage(key)
+	}
+
+	fn storage_hash(&self, key: &[u8]) -> Result<Option<H::Out>, Self::Error> {
+		self.record(None, key);
+		self.inner.storage_hash(key)
+	}
+
+	fn child_storage(
+		&self,
+		child_info: &ChildInfo,
+		key: &[u8],
+	) -> Result<Option<StorageValue>, Self::Error> {
+		self.record(Some(child_info), key);
+		self.inner.child_storage(child_info, key)
+	}
+
+	fn child_storage_hash(
+		&self,
+		child_info: &ChildInfo,
+		key: &[u8],
+	) -> Result<Option<H::Out>, Self::Error> {
+		self.record(Some(child_info), key);
+		self.inner.child_storage_hash(child_info, key)
+	}
+
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<StorageKey>, Self::Error> {
+		self.record(None, key);
+		self.inner.next_storage_key(key)
+	}
+
+	fn next_child_storage_key(
+		&self,
+		child_info: &ChildInfo,
+		key: &[u8],
+	) -> Result<Option<StorageKey>, Self::Error> {
+		self.record(Some(child_info), key);
+		self.inner.next_child_storage_key(child_info, key)
+	}
+
+	fn apply_to_key_values_while<F: FnMut(Vec<u8>, Vec<u8>) -> bool>(
+		&self,
+		child_info: Option<&ChildInfo>,
+		prefix: Option<&[u8]>,
+		start_at: Option<&[u8]>,
+		mut f: F,
+		allow_missing: bool,
+	) -> Result<bool, Self::Error> {
+		self.inner.apply_to_key_values_while(
+			child_info,
+			prefix,
+			start_at,
+			|key, value| {
+				self.record(child_info, &key);
+				f(key, value)
+			},
+			allow_missing,
+		)
+	}
+
+	fn apply_to_keys_while<F: FnMut(&[u8]) -> bool>(
+		&self,
+		child_info: Option<&ChildInfo>,
+		prefix: Option<&[u8]>,
+		start_at: Option<&[u8]>,
+		mut f: F,
+	) {
+		self.inner.apply_to_keys_while(child_info, prefix, start_at, |key| {
+			self.record(child_info, key);
+			f(key)
+		})
+	}
+
+	fn for_key_values_with_prefix<F: FnMut(&[u8], &[u8])>(&self, prefix: &[u8], mut f: F) {
+		self.inner.for_key_values_with_prefix(prefix, |key, value| {
+			self.record(None, key);
+			f(key, value)
+		})
+	}
+
+	fn for_child_keys_with_prefix<F: FnMut(&[u8])>(
+		&self,
+		child_info: &ChildInfo,
+		prefix: &[u8],
+		mut f: F,
+	) {
+		self.inner.for_child_keys_with_prefix(child_info, prefix, |key| {
+			self.record(Some(child_info), key);
+			f(key)
+		})
+	}
+
+	fn storage_root<'b>(
+		&self,
+		delta: impl Iterator<Item = (&'b [u8], Option<&'b [u8]>)>,
+		state_version: StateVersion,
+	) -> (H::Out, Self::Transaction)
+	where
+		H::Out: Ord,
+	{
+		self.inner.storage_root(delta, state_version)
+	}
+
+	fn child_storage_root<'b>(
+		&self,
+		child_info: &ChildInfo,
+		delta: impl Iterator<Item = (&'b [u8], Option<&'b [u8]>)>,
+		state_version: StateVersion,
+	) -> (H::Out, bool, Self::Transaction)
+	where
+		H::Out: Ord,
+	{
+		self.inner.child_storage_root(child_info, delta, state_version)
+	}
+
+	fn pairs(&self) -> Vec<(StorageKey, StorageValue)> {
+		self.inner.pairs()
+	}
+
+	fn register_overlay_stats(&self, stats: &StateMachineStats) {
+		self.inner.register_overlay_stats(stats)
+	}
+
+	fn usage_info(&self) -> UsageInfo {
+		self.inner.usage_info()
+	}
+}
+
+/// The frames of the stack of an access to `key`, i.e. `Pallet;Item` for storage entries
+/// declared in the metadata.
+fn frames(entries: &[Entry], key: &[u8]) -> String {
+	if let Some(entry) = entries.iter().find(|e| key.starts_with(&e.prefix)) {
+		entry.name.replace("::", ";")
+	} else if let Some(child) = key.strip_prefix(well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX)
+	{
+		format!("child_storage;0x{}", HexDisplay::from(&child))
+	} else if key.starts_with(b":") {
+		// e.g. `:code`, made safe for the folded format, which separates frames by `;` and the
+		// stack from its count by a space.
+		String::from_utf8_lossy(key)
+			.chars()
+			.map(|c| if c == ';' || c.is_whitespace() || c.is_control() { '_' } else { c })
+			.collect()
+	} else {
+		"unknown".into()
+	}
+}
+
+/// Execute `method` once more on top of `ext`, and write the number of storage accesses of each
+/// storage entry to `path`, as folded stacks, i.e. `method;Pallet;Item count` lines.
+///
+/// Only the accesses that are not served from the changes of the call itself are counted, i.e.
+/// the ones that reach the state. The output can be rendered by e.g. `inferno-flamegraph` or
+/// `flamegraph.pl`.
+pub(crate) fn write_storage_trace<Block: BlockT, D: NativeExecutionDispatch + 'static>(
+	path: &Path,
+	ext: &TestExternalities,
+	executor: &NativeElseWasmExecutor<D>,
+	execution: sc_cli::ExecutionStrategy,
+	method: &'static str,
+	data: &[u8],
+	extensions: Extensions,
+) -> sc_cli::Result<()> {
+	let backend = TracingBackend::new(&ext.backend);
+	let mut changes = OverlayedChanges::default();
+	StateMachine::new(
+		&backend,
+		&mut changes,
+		executor,
+		method,
+		data,
+		extensions,
+		&BackendRuntimeCode::new(&ext.backend).runtime_code()?,
+		sp_core::testing::TaskExecutor::new(),
+	)
+	.execute(execution.into())
+	.map_err(|e| format!("failed to execute '{}' while tracing storage: {}", method, e))?;
+
+	let metadata = runtime_metadata::<Block, D>(ext, executor)?;
+	let entries = entries(&metadata);
+
+	let mut stacks = BTreeMap::<String, u64>::new();
+	for (key, count) in backend.into_accesses() {
+		*stacks.entry(frames(&entries, &key)).or_default() += count;
+	}
+	let total = stacks.values().sum::<u64>();
+	let folded = stacks
+		.iter()
+		.map(|(stack, count)| format!("{};{} {}\n", method, stack, count))
+		.collect::<String>();
+	std::fs::write(path, folded)
+		.map_err(|e| format!("failed to write the storage trace to {:?}: {}", path, e))?;
+
+	log::info!(
+		target: LOG_TARGET,
+		"wrote {} storage accesses of {} storage entries to {:?}",
+		total,
+		stacks.len(),
+		path,
+	);
+	Ok(())
+}